@@ -3,7 +3,10 @@ pub mod token;
 use crate::commands::common::{DataType, InfluxDb3Config, SeparatedKeyValue, parse_key_val};
 use hashbrown::HashMap;
 use humantime::Duration;
+use influxdb3_catalog::log::BatchWindow;
 use influxdb3_catalog::log::ErrorBehavior;
+use influxdb3_catalog::log::RetryFallback;
+use influxdb3_catalog::log::RetryPolicy;
 use influxdb3_catalog::log::TriggerSettings;
 use influxdb3_catalog::log::TriggerSpecificationDefinition;
 use influxdb3_client::Client;
@@ -14,6 +17,7 @@ use secrecy::ExposeSecret;
 use secrecy::Secret;
 use serde_json::json;
 use std::error::Error;
+use std::io::{self, IsTerminal, Read};
 use std::num::NonZeroUsize;
 use std::path::PathBuf;
 use std::str;
@@ -75,6 +79,16 @@ impl Config {
                         ..
                     },
                 ..
+            })
+            | SubCommand::Secret(SecretConfig {
+                ca_cert,
+                influxdb3_config:
+                    InfluxDb3Config {
+                        host_url,
+                        auth_token,
+                        ..
+                    },
+                ..
             }) => (host_url, auth_token, ca_cert),
             SubCommand::Token(create_token_config) => {
                 let host_settings = create_token_config.get_connection_settings()?;
@@ -110,6 +124,8 @@ pub enum SubCommand {
     Token(CreateTokenConfig),
     /// Create a new trigger for the processing engine that executes a plugin on either WAL rows, scheduled tasks, or requests to the serve at `/api/v3/engine/<path>`
     Trigger(TriggerConfig),
+    /// Create a new secret that the plugins of a database's triggers can read
+    Secret(SecretConfig),
 }
 
 #[derive(Debug, clap::Args)]
@@ -246,13 +262,16 @@ pub struct TriggerConfig {
     influxdb3_config: InfluxDb3Config,
     /// Python file name of the file on the server's plugin-dir containing the plugin code. Or
     /// on the [influxdb3_plugins](https://github.com/influxdata/influxdb3_plugins) repo if `gh:` is specified as
-    /// the prefix.
+    /// the prefix. This can also be a directory on the plugin-dir holding a plugin package, with an
+    /// `__init__.py` and a `manifest.json` declaring its entry points. A plugin uploaded through
+    /// the `/api/v3/configure/plugin_files` API can be pinned to one of its versions with
+    /// `<name>@<version>`.
     #[clap(long = "plugin-filename")]
     plugin_filename: String,
     /// When the trigger should fire
     #[clap(long = "trigger-spec",
           value_parser = TriggerSpecificationDefinition::from_string_rep,
-          help = "The plugin file must be for the given trigger type of wal, schedule, or request. Trigger specification format:\nFor wal_rows use: 'table:<TABLE_NAME>' or 'all_tables'\nFor scheduled use: 'cron:<CRON_EXPRESSION>' or 'every:<duration e.g. 10m>'\nFor request use: 'path:<PATH>' e.g. path:foo will be at /api/v3/engine/foo")]
+          help = "The plugin file must be for the given trigger type of wal, schedule, request, persisted, or catalog. Trigger specification format:\nFor wal_rows use: 'table:<TABLE_NAME>', 'table:<TABLE_NAME> where <PREDICATE>' e.g. \"table:cpu where region = 'west' and usage > 90\", or 'all_tables'\nFor scheduled use: 'cron:<CRON_EXPRESSION>' or 'every:<duration e.g. 10m>'\nFor request use: 'path:<PATH>' e.g. path:foo will be at /api/v3/engine/foo\nFor persisted use: 'persisted:<TABLE_NAME>', which fires when a snapshot persists Parquet files for the table\nFor catalog use: 'catalog:<EVENT>', where the event is one of database_deleted, table_created, table_deleted, column_added, cache_created, or cache_deleted")]
     trigger_specification: TriggerSpecificationDefinition,
     /// Comma separated list of key/value pairs to use as trigger arguments. Example: key1=val1,key2=val2
     #[clap(long = "trigger-arguments", value_delimiter = ',')]
@@ -266,6 +285,39 @@ pub struct TriggerConfig {
     /// How you wish the system to respond in the event of an error from the plugin
     #[clap(long, value_enum, default_value_t = ErrorBehavior::Log)]
     error_behavior: ErrorBehavior,
+    /// With `--error-behavior retry`, the maximum number of times the plugin is run for an event
+    #[clap(long, default_value_t = 3)]
+    retry_max_attempts: u32,
+    /// With `--error-behavior retry`, how long to wait before the first retry. This doubles for
+    /// each retry after that. This uses a humantime form: "10s", "1min 30sec", "3 hours"
+    #[clap(long, default_value = "1s")]
+    retry_initial_backoff: Duration,
+    /// With `--error-behavior retry`, the longest time to wait between retries
+    #[clap(long, default_value = "1m")]
+    retry_max_backoff: Duration,
+    /// With `--error-behavior retry`, what to do once all of the attempts have failed. The
+    /// failed event is recorded in the system.processing_engine_dead_letters table either way
+    #[clap(long, value_enum, default_value_t = RetryFallback::Log)]
+    retry_fallback: RetryFallback,
+    /// For WAL triggers, accumulate rows for this long and run the plugin once against the
+    /// combined batch, rather than on every WAL flush. The accumulated rows are persisted, so they
+    /// are kept across restarts. This uses a humantime form: "30s", "5min"
+    #[clap(long)]
+    batch_window: Option<Duration>,
+    /// With `--batch-window`, run the plugin as soon as this many rows have been accumulated
+    #[clap(long, requires = "batch_window")]
+    batch_max_rows: Option<usize>,
+    /// Fail runs of the plugin that take longer than this, with a timeout that is handled like
+    /// any other plugin error. For request triggers with a streamed response, each chunk of the
+    /// body has this long to be produced. The plugin is stopped the next time it runs python code,
+    /// so a plugin that is blocked in a call into C, such as a read from a socket without a
+    /// timeout, can't be stopped until that call returns. This uses a humantime form: "10s",
+    /// "2min"
+    #[clap(long)]
+    execution_timeout: Option<Duration>,
+    /// Fail runs of the plugin that write more than this many rows
+    #[clap(long)]
+    max_rows_written: Option<usize>,
     /// Name for the new trigger
     trigger_name: String,
 
@@ -274,6 +326,22 @@ pub struct TriggerConfig {
     ca_cert: Option<PathBuf>,
 }
 
+#[derive(Debug, clap::Parser)]
+pub struct SecretConfig {
+    #[clap(flatten)]
+    influxdb3_config: InfluxDb3Config,
+    /// The value of the secret. If not given, it is read from stdin, so that it does not end up
+    /// in the shell history
+    #[clap(long = "value", env = "INFLUXDB3_SECRET_VALUE")]
+    value: Option<Secret<String>>,
+    /// Name for the new secret, which plugins read it by with `influxdb3_local.secrets.get()`
+    secret_name: String,
+
+    /// An optional arg to use a custom ca for useful for testing with self signed certs
+    #[clap(long = "tls-ca", env = "INFLUXDB3_TLS_CA")]
+    ca_cert: Option<PathBuf>,
+}
+
 pub async fn command(config: Config) -> Result<(), Box<dyn Error>> {
     let client = config.get_client()?;
     match config.cmd {
@@ -422,6 +490,14 @@ pub async fn command(config: Config) -> Result<(), Box<dyn Error>> {
             disabled,
             run_asynchronous,
             error_behavior,
+            retry_max_attempts,
+            retry_initial_backoff,
+            retry_max_backoff,
+            retry_fallback,
+            batch_window,
+            batch_max_rows,
+            execution_timeout,
+            max_rows_written,
             ..
         }) => {
             let trigger_arguments: Option<HashMap<String, String>> = trigger_arguments.map(|a| {
@@ -433,6 +509,18 @@ pub async fn command(config: Config) -> Result<(), Box<dyn Error>> {
             let trigger_settings = TriggerSettings {
                 run_async: run_asynchronous,
                 error_behavior,
+                retry_policy: RetryPolicy {
+                    max_attempts: retry_max_attempts,
+                    initial_backoff: retry_initial_backoff.into(),
+                    max_backoff: retry_max_backoff.into(),
+                    fallback: retry_fallback,
+                },
+                batch_window: batch_window.map(|max_duration| BatchWindow {
+                    max_duration: max_duration.into(),
+                    max_rows: batch_max_rows,
+                }),
+                execution_timeout: execution_timeout.map(Into::into),
+                max_rows_written,
             };
 
             match client
@@ -454,6 +542,31 @@ pub async fn command(config: Config) -> Result<(), Box<dyn Error>> {
                 Ok(_) => println!("Trigger {} created successfully", trigger_name),
             }
         }
+        SubCommand::Secret(SecretConfig {
+            influxdb3_config: InfluxDb3Config { database_name, .. },
+            value,
+            secret_name,
+            ..
+        }) => {
+            let value = match value {
+                Some(value) => value.expose_secret().clone(),
+                None => {
+                    let stdin = io::stdin();
+                    if stdin.is_terminal() {
+                        return Err(
+                            "no secret value given, pass --value or pipe it to stdin".into()
+                        );
+                    }
+                    let mut value = String::new();
+                    stdin.lock().read_to_string(&mut value)?;
+                    value.trim_end_matches(['\r', '\n']).to_string()
+                }
+            };
+            client
+                .api_v3_configure_secret_create(&database_name, &secret_name, value)
+                .await?;
+            println!("Secret {:?} created successfully", secret_name);
+        }
     }
     Ok(())
 }
@@ -464,7 +577,7 @@ mod tests {
     use std::time::Duration;
 
     use clap::Parser;
-    use influxdb3_catalog::log::{ErrorBehavior, TriggerSpecificationDefinition};
+    use influxdb3_catalog::log::{ErrorBehavior, RetryFallback, TriggerSpecificationDefinition};
 
     #[test]
     fn parse_args_create_last_cache() {
@@ -560,4 +673,101 @@ mod tests {
 
         assert_eq!("/metrics?format=json", query_path.0.1);
     }
+
+    #[test]
+    fn parse_args_create_trigger_retry_policy() {
+        let args = super::Config::parse_from([
+            "create",
+            "trigger",
+            "--trigger-spec",
+            "all_tables",
+            "--plugin-filename",
+            "plugin.py",
+            "--database",
+            "test",
+            "--error-behavior",
+            "retry",
+            "--retry-max-attempts",
+            "5",
+            "--retry-initial-backoff",
+            "500ms",
+            "--retry-fallback",
+            "disable",
+            "test-trigger",
+        ]);
+        let super::SubCommand::Trigger(super::TriggerConfig {
+            error_behavior,
+            retry_max_attempts,
+            retry_initial_backoff,
+            retry_max_backoff,
+            retry_fallback,
+            ..
+        }) = args.cmd
+        else {
+            panic!("Did not parse args correctly: {args:#?}")
+        };
+        assert_eq!(ErrorBehavior::Retry, error_behavior);
+        assert_eq!(5, retry_max_attempts);
+        assert_eq!(Duration::from_millis(500), *retry_initial_backoff);
+        assert_eq!(Duration::from_secs(60), *retry_max_backoff);
+        assert_eq!(RetryFallback::Disable, retry_fallback);
+    }
+
+    #[test]
+    fn parse_args_create_trigger_batch_window() {
+        let args = super::Config::parse_from([
+            "create",
+            "trigger",
+            "--trigger-spec",
+            "table:cpu",
+            "--plugin-filename",
+            "plugin.py",
+            "--database",
+            "test",
+            "--batch-window",
+            "30s",
+            "--batch-max-rows",
+            "10000",
+            "test-trigger",
+        ]);
+        let super::SubCommand::Trigger(super::TriggerConfig {
+            batch_window,
+            batch_max_rows,
+            ..
+        }) = args.cmd
+        else {
+            panic!("Did not parse args correctly: {args:#?}")
+        };
+        assert_eq!(Some(Duration::from_secs(30)), batch_window.map(|d| *d));
+        assert_eq!(Some(10_000), batch_max_rows);
+    }
+
+    #[test]
+    fn parse_args_create_trigger_limits() {
+        let args = super::Config::parse_from([
+            "create",
+            "trigger",
+            "--trigger-spec",
+            "every:1m",
+            "--plugin-filename",
+            "plugin.py",
+            "--database",
+            "test",
+            "--execution-timeout",
+            "10s",
+            "--max-rows-written",
+            "500",
+            "test-trigger",
+        ]);
+        let super::SubCommand::Trigger(super::TriggerConfig {
+            execution_timeout,
+            max_rows_written,
+            ..
+        }) = args.cmd
+        else {
+            panic!("Did not parse args correctly: {args:#?}")
+        };
+        assert_eq!(Some(Duration::from_secs(10)), execution_timeout.map(|d| *d));
+        assert_eq!(Some(500), max_rows_written);
+    }
 }
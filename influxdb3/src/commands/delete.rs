@@ -62,6 +62,16 @@ impl Config {
                     },
                 ..
             })
+            | SubCommand::Secret(SecretConfig {
+                ca_cert,
+                influxdb3_config:
+                    InfluxDb3Config {
+                        host_url,
+                        auth_token,
+                        ..
+                    },
+                ..
+            })
             | SubCommand::Token(TokenConfig {
                 ca_cert,
                 host_url,
@@ -94,6 +104,8 @@ pub enum SubCommand {
     Trigger(TriggerConfig),
     /// Delete a token
     Token(TokenConfig),
+    /// Delete a secret
+    Secret(SecretConfig),
 }
 
 #[derive(Debug, clap::Args)]
@@ -187,6 +199,20 @@ pub struct TriggerConfig {
     ca_cert: Option<PathBuf>,
 }
 
+#[derive(Debug, clap::Parser)]
+pub struct SecretConfig {
+    #[clap(flatten)]
+    influxdb3_config: InfluxDb3Config,
+
+    /// Name of secret to delete
+    #[clap(required = true)]
+    secret_name: String,
+
+    /// An optional arg to use a custom ca for useful for testing with self signed certs
+    #[clap(long = "tls-ca", env = "INFLUXDB3_TLS_CA")]
+    ca_cert: Option<PathBuf>,
+}
+
 #[derive(Debug, clap::Args)]
 pub struct TokenConfig {
     /// The host URL of the running InfluxDB 3 Core server
@@ -292,6 +318,16 @@ pub async fn command(config: Config) -> Result<(), Box<dyn Error>> {
                 .await?;
             println!("Trigger {} deleted successfully", trigger_name);
         }
+        SubCommand::Secret(SecretConfig {
+            influxdb3_config: InfluxDb3Config { database_name, .. },
+            secret_name,
+            ..
+        }) => {
+            client
+                .api_v3_configure_secret_delete(database_name, &secret_name)
+                .await?;
+            println!("Secret {:?} deleted successfully", secret_name);
+        }
         SubCommand::Token(TokenConfig { token_name, .. }) => {
             if token_name == "_admin" {
                 println!(
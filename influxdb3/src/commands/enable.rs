@@ -1,4 +1,6 @@
 use crate::commands::common::InfluxDb3Config;
+use chrono::{DateTime, Utc};
+use humantime::Duration;
 use influxdb3_client::Client;
 use secrecy::ExposeSecret;
 use std::{error::Error, path::PathBuf};
@@ -49,6 +51,20 @@ struct TriggerConfig {
     /// An optional arg to use a custom ca for useful for testing with self signed certs
     #[clap(long = "tls-ca", env = "INFLUXDB3_TLS_CA")]
     pub ca_cert: Option<PathBuf>,
+
+    /// Once the trigger is enabled, run its plugin over the data written from this time, in
+    /// RFC3339 form, e.g. "2025-01-01T00:00:00Z". Only WAL triggers can be backfilled.
+    #[clap(long = "backfill-start")]
+    backfill_start: Option<DateTime<Utc>>,
+
+    /// The end of the backfill, in RFC3339 form. Defaults to the time the trigger is enabled.
+    #[clap(long = "backfill-end", requires = "backfill_start")]
+    backfill_end: Option<DateTime<Utc>>,
+
+    /// How much time the plugin is run over at once during the backfill. This uses a humantime
+    /// form: "30s", "5min". Defaults to 1 minute.
+    #[clap(long = "backfill-window", requires = "backfill_start")]
+    backfill_window: Option<Duration>,
 }
 
 pub async fn command(config: Config) -> Result<(), Box<dyn Error>> {
@@ -57,12 +73,29 @@ pub async fn command(config: Config) -> Result<(), Box<dyn Error>> {
         SubCommand::Trigger(TriggerConfig {
             influxdb3_config: InfluxDb3Config { database_name, .. },
             trigger_name,
+            backfill_start,
+            backfill_end,
+            backfill_window,
             ..
         }) => {
             client
-                .api_v3_configure_processing_engine_trigger_enable(database_name, &trigger_name)
+                .api_v3_configure_processing_engine_trigger_enable(&database_name, &trigger_name)
                 .await?;
             println!("Trigger {} enabled successfully", trigger_name);
+            if let Some(start_time) = backfill_start {
+                let backfill_id = client
+                    .api_v3_configure_processing_engine_trigger_backfill(
+                        database_name,
+                        &trigger_name,
+                        start_time,
+                        backfill_end,
+                        backfill_window.map(|window| window.as_secs()),
+                    )
+                    .await?;
+                println!(
+                    "Backfill {backfill_id} started, follow its progress in system.processing_engine_backfills"
+                );
+            }
         }
     }
     Ok(())
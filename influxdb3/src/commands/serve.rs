@@ -27,10 +27,12 @@ use influxdb3_processing_engine::environment::{
     DisabledManager, PipManager, PythonEnvironmentManager, UVManager,
 };
 use influxdb3_processing_engine::plugins::ProcessingEngineEnvironmentManager;
+use influxdb3_processing_engine::secrets::{SecretsError, SecretsKey};
 use influxdb3_processing_engine::virtualenv::find_python;
 use influxdb3_server::{
     CommonServerState,
     builder::ServerBuilder,
+    query_cache::{QueryResultCache, QueryResultCacheConfig},
     query_executor::{CreateQueryExecutorArgs, QueryExecutorImpl},
     serve,
 };
@@ -127,6 +129,9 @@ pub enum Error {
 
     #[error("tls requires both a cert and a key file to be passed in to work")]
     NoCertOrKeyFile,
+
+    #[error("failed to load secrets key: {0}")]
+    SecretsKey(#[from] SecretsError),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -262,6 +267,29 @@ pub struct Config {
     )]
     pub query_log_size: usize,
 
+    /// The size of the query result cache, in megabytes or percentage of total available mem.
+    ///
+    /// Results of repeated queries made through the HTTP query APIs are served from the cache
+    /// until new data is written to, or persisted for, the tables they query. The cache is
+    /// disabled unless this is set. Clients can skip the cache for individual queries by sending a
+    /// `Cache-Control: no-cache` header.
+    #[clap(
+        long = "query-result-cache-size",
+        env = "INFLUXDB3_QUERY_RESULT_CACHE_SIZE",
+        action
+    )]
+    pub query_result_cache_size: Option<MemorySizeMb>,
+
+    /// The maximum age of an entry in the query result cache, after which the query will be run
+    /// again, expressed as a human-readable time, e.g., "20s", "1m", "1h".
+    #[clap(
+        long = "query-result-cache-ttl",
+        env = "INFLUXDB3_QUERY_RESULT_CACHE_TTL",
+        default_value = "30s",
+        action
+    )]
+    pub query_result_cache_ttl: humantime::Duration,
+
     /// The node idendifier used as a prefix in all object store file paths. This should be unique
     /// for any InfluxDB 3 Core servers that share the same object store configuration, i.e., the
     /// same bucket.
@@ -724,6 +752,17 @@ pub async fn command(config: Config) -> Result<()> {
         );
     }
 
+    let query_result_cache = config.query_result_cache_size.map(|size| {
+        QueryResultCache::new(
+            Arc::clone(&catalog),
+            Arc::clone(&time_provider) as _,
+            QueryResultCacheConfig {
+                max_size_bytes: size.as_num_bytes(),
+                ttl: config.query_result_cache_ttl.into(),
+            },
+        )
+    });
+
     let query_executor = Arc::new(QueryExecutorImpl::new(CreateQueryExecutorArgs {
         catalog: write_buffer.catalog(),
         write_buffer: Arc::clone(&write_buffer),
@@ -735,6 +774,7 @@ pub async fn command(config: Config) -> Result<()> {
         sys_events_store: Arc::clone(&sys_events_store),
         // convert to positive here so that we can avoid double negatives downstream
         started_with_auth: !config.without_auth,
+        query_result_cache: query_result_cache.clone(),
     }));
 
     let listener = TcpListener::bind(*config.http_bind_address)
@@ -742,13 +782,15 @@ pub async fn command(config: Config) -> Result<()> {
         .map_err(Error::BindAddress)?;
 
     let processing_engine = ProcessingEngineManagerImpl::new(
-        setup_processing_engine_env_manager(&config.processing_engine_config),
+        setup_processing_engine_env_manager(&config.processing_engine_config)?,
         write_buffer.catalog(),
         config.node_identifier_prefix,
         Arc::clone(&write_buffer),
         Arc::clone(&query_executor) as _,
         Arc::clone(&time_provider) as _,
         sys_events_store,
+        Arc::clone(&metrics),
+        shutdown_manager.register(),
     )
     .await;
 
@@ -760,6 +802,10 @@ pub async fn command(config: Config) -> Result<()> {
         .persister(persister)
         .tcp_listener(listener)
         .processing_engine(processing_engine);
+    let builder = match query_result_cache {
+        Some(cache) => builder.query_result_cache(cache),
+        None => builder,
+    };
 
     let cert_file = config.cert_file;
     let key_file = config.key_file;
@@ -882,17 +928,25 @@ pub async fn command(config: Config) -> Result<()> {
 
 pub(crate) fn setup_processing_engine_env_manager(
     config: &ProcessingEngineConfig,
-) -> ProcessingEngineEnvironmentManager {
+) -> Result<ProcessingEngineEnvironmentManager> {
     let package_manager: Arc<dyn PythonEnvironmentManager> = match config.package_manager {
         PackageManager::Discover => determine_package_manager(),
         PackageManager::Pip => Arc::new(PipManager),
         PackageManager::UV => Arc::new(UVManager),
     };
-    ProcessingEngineEnvironmentManager {
+    let secrets_key = config
+        .secrets_key_file
+        .as_deref()
+        .map(SecretsKey::from_file)
+        .transpose()?
+        .map(Arc::new);
+    Ok(ProcessingEngineEnvironmentManager {
         plugin_dir: config.plugin_dir.clone(),
         virtual_env_location: config.virtual_env_location.clone(),
         package_manager,
-    }
+        cache_checkpoint_interval: config.plugin_cache_checkpoint_interval.map(Into::into),
+        secrets_key,
+    })
 }
 
 fn determine_package_manager() -> Arc<dyn PythonEnvironmentManager> {
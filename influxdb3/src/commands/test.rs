@@ -2,7 +2,9 @@ use crate::commands::common::{InfluxDb3Config, SeparatedKeyValue};
 use anyhow::Context;
 use hashbrown::HashMap;
 use influxdb3_client::Client;
-use influxdb3_types::http::{SchedulePluginTestRequest, WalPluginTestRequest};
+use influxdb3_types::http::{
+    RequestPluginTestRequest, SchedulePluginTestRequest, WalPluginTestRequest,
+};
 use secrecy::ExposeSecret;
 use std::{error::Error, path::PathBuf};
 
@@ -34,6 +36,16 @@ impl Config {
                         ..
                     },
                 ..
+            })
+            | SubCommand::RequestPlugin(RequestPluginConfig {
+                ca_cert,
+                influxdb3_config:
+                    InfluxDb3Config {
+                        host_url,
+                        auth_token,
+                        ..
+                    },
+                ..
             }) => {
                 let mut client = Client::new(host_url.clone(), ca_cert.clone())?;
                 if let Some(token) = &auth_token {
@@ -53,6 +65,9 @@ pub enum SubCommand {
     /// Test a Cron Plugin
     #[clap(name = "schedule_plugin")]
     SchedulePlugin(SchedulePluginConfig),
+    /// Test a Request Plugin
+    #[clap(name = "request_plugin")]
+    RequestPlugin(RequestPluginConfig),
 }
 
 #[derive(Debug, clap::Parser)]
@@ -100,16 +115,58 @@ pub struct SchedulePluginConfig {
     pub ca_cert: Option<PathBuf>,
 }
 
+#[derive(Debug, clap::Parser)]
+pub struct RequestPluginConfig {
+    #[clap(flatten)]
+    influxdb3_config: InfluxDb3Config,
+    /// If given pass this map of string key/value pairs as input arguments
+    #[clap(long = "input-arguments", value_delimiter = ',')]
+    pub input_arguments: Option<Vec<SeparatedKeyValue<String, String>>>,
+    /// The file name of the plugin, which should exist on the server in `<plugin-dir>/<filename>`.
+    /// The plugin-dir is provided on server startup.
+    #[clap(required = true)]
+    pub filename: String,
+    /// HTTP method of the request. If not given will use GET
+    #[clap(long = "method")]
+    pub method: Option<String>,
+    /// Path of the request below the trigger's path
+    #[clap(long = "path")]
+    pub path: Option<String>,
+    /// If given pass this map of string key/value pairs as the request's query parameters
+    #[clap(long = "query-params", value_delimiter = ',')]
+    pub query_params: Option<Vec<SeparatedKeyValue<String, String>>>,
+    /// If given pass this map of string key/value pairs as the request's headers
+    #[clap(long = "headers", value_delimiter = ',')]
+    pub headers: Option<Vec<SeparatedKeyValue<String, String>>>,
+    /// If given, pass this as the request body
+    #[clap(long = "body", conflicts_with = "body_file")]
+    pub body: Option<String>,
+    /// If given, pass the contents of this file as the request body
+    #[clap(long = "body-file")]
+    pub body_file: Option<PathBuf>,
+    #[clap(long = "cache-name")]
+    pub cache_name: Option<String>,
+    /// An optional arg to use a custom ca for useful for testing with self signed certs
+    #[clap(long = "tls-ca", env = "INFLUXDB3_TLS_CA")]
+    pub ca_cert: Option<PathBuf>,
+}
+
+fn key_values_to_map(
+    key_values: Option<Vec<SeparatedKeyValue<String, String>>>,
+) -> Option<HashMap<String, String>> {
+    key_values.map(|a| {
+        a.into_iter()
+            .map(|SeparatedKeyValue((k, v))| (k, v))
+            .collect::<HashMap<String, String>>()
+    })
+}
+
 pub async fn command(config: Config) -> Result<(), Box<dyn Error>> {
     let client = config.get_client()?;
 
     match config.cmd {
         SubCommand::WalPlugin(plugin_config) => {
-            let input_arguments = plugin_config.input_arguments.map(|a| {
-                a.into_iter()
-                    .map(|SeparatedKeyValue((k, v))| (k, v))
-                    .collect::<HashMap<String, String>>()
-            });
+            let input_arguments = key_values_to_map(plugin_config.input_arguments);
 
             let input_lp = match plugin_config.input_lp {
                 Some(lp) => lp,
@@ -138,11 +195,7 @@ pub async fn command(config: Config) -> Result<(), Box<dyn Error>> {
             );
         }
         SubCommand::SchedulePlugin(plugin_config) => {
-            let input_arguments = plugin_config.input_arguments.map(|a| {
-                a.into_iter()
-                    .map(|SeparatedKeyValue((k, v))| (k, v))
-                    .collect::<HashMap<String, String>>()
-            });
+            let input_arguments = key_values_to_map(plugin_config.input_arguments);
             let cron_plugin_test_request = SchedulePluginTestRequest {
                 filename: plugin_config.filename,
                 database: plugin_config.influxdb3_config.database_name,
@@ -160,6 +213,34 @@ pub async fn command(config: Config) -> Result<(), Box<dyn Error>> {
                     .expect("serialize cron plugin test response as JSON")
             );
         }
+        SubCommand::RequestPlugin(plugin_config) => {
+            let body = match plugin_config.body_file {
+                Some(file_path) => {
+                    Some(std::fs::read_to_string(file_path).context("unable to read body file")?)
+                }
+                None => plugin_config.body,
+            };
+            let request_plugin_test_request = RequestPluginTestRequest {
+                filename: plugin_config.filename,
+                database: plugin_config.influxdb3_config.database_name,
+                method: plugin_config.method,
+                path: plugin_config.path,
+                query_params: key_values_to_map(plugin_config.query_params),
+                headers: key_values_to_map(plugin_config.headers),
+                body,
+                cache_name: plugin_config.cache_name,
+                input_arguments: key_values_to_map(plugin_config.input_arguments),
+            };
+            let response = client
+                .request_plugin_test(request_plugin_test_request)
+                .await?;
+
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&response)
+                    .expect("serialize request plugin test response as JSON")
+            );
+        }
     }
 
     Ok(())
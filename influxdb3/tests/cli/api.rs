@@ -890,6 +890,100 @@ impl TestSchedulePluginQuery<'_> {
         serde_json::from_str(&result).map_err(Into::into)
     }
 }
+// Builder for the 'test request_plugin' command
+pub struct TestRequestPluginQuery<'a> {
+    server: &'a TestServer,
+    db_name: String,
+    plugin_name: String,
+    method: Option<String>,
+    path: Option<String>,
+    query_params: Vec<String>,
+    headers: Vec<String>,
+    body: Option<String>,
+}
+
+impl TestServer {
+    pub fn test_request_plugin(
+        &self,
+        db_name: impl Into<String>,
+        plugin_name: impl Into<String>,
+    ) -> TestRequestPluginQuery {
+        TestRequestPluginQuery {
+            server: self,
+            db_name: db_name.into(),
+            plugin_name: plugin_name.into(),
+            method: None,
+            path: None,
+            query_params: Vec::new(),
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+}
+
+impl TestRequestPluginQuery<'_> {
+    pub fn with_method(mut self, method: impl Into<String>) -> Self {
+        self.method = Some(method.into());
+        self
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn add_query_param(mut self, param: impl Into<String>) -> Self {
+        self.query_params.push(param.into());
+        self
+    }
+
+    pub fn add_header(mut self, header: impl Into<String>) -> Self {
+        self.headers.push(header.into());
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    pub fn run(self) -> Result<Value> {
+        let mut args = vec![
+            "--database",
+            self.db_name.as_str(),
+            "--tls-ca",
+            "../testing-certs/rootCA.pem",
+        ];
+
+        if let Some(method) = &self.method {
+            args.push("--method");
+            args.push(method);
+        }
+        if let Some(path) = &self.path {
+            args.push("--path");
+            args.push(path);
+        }
+        let query_params = self.query_params.join(",");
+        if !self.query_params.is_empty() {
+            args.push("--query-params");
+            args.push(query_params.as_str());
+        }
+        let headers = self.headers.join(",");
+        if !self.headers.is_empty() {
+            args.push("--headers");
+            args.push(headers.as_str());
+        }
+        if let Some(body) = &self.body {
+            args.push("--body");
+            args.push(body);
+        }
+
+        args.push(self.plugin_name.as_str());
+
+        let result = self.server.run(vec!["test", "request_plugin"], &args)?;
+        serde_json::from_str(&result).map_err(Into::into)
+    }
+}
 // Builder for the 'package install' command
 pub struct InstallPackageQuery<'a> {
     server: &'a TestServer,
@@ -1296,6 +1296,57 @@ def process_scheduled_call(influxdb3_local, schedule_time, args=None):
     assert_eq!(result["errors"], expected_result["errors"]);
 }
 
+#[test_log::test(tokio::test)]
+async fn test_request_plugin_test() {
+    use crate::server::ConfigProvider;
+
+    let (temp_dir, plugin_path) = create_plugin_in_temp_dir(
+        r#"
+def process_request(influxdb3_local, query_parameters, request_headers, request_body, args=None, *, method=None, path=None):
+    influxdb3_local.info(f"{method} /{path} with body {request_body.decode()}")
+    influxdb3_local.write(LineBuilder("requests").tag("region", query_parameters["region"]).int64_field("count", 1))
+    return {"agent": request_headers["User-Agent"]}, 202"#,
+    );
+
+    let plugin_dir = temp_dir.path().to_str().unwrap();
+    let plugin_name = plugin_path.file_name().unwrap().to_str().unwrap();
+
+    let server = TestServer::configure()
+        .with_plugin_dir(plugin_dir)
+        .spawn()
+        .await;
+
+    let db_name = "foo";
+    server.create_database(db_name).run().unwrap();
+
+    let result = server
+        .test_request_plugin(db_name, plugin_name)
+        .with_method("POST")
+        .with_path("reports/daily")
+        .add_query_param("region=us-east")
+        .add_header("User-Agent=tester")
+        .with_body("hello")
+        .run()
+        .expect("Failed to run request plugin test");
+
+    debug!(result = ?result, "test request plugin");
+
+    assert_eq!(
+        result,
+        json!({
+            "status": 202,
+            "headers": {"Content-Type": "application/json"},
+            // base64 of `{"agent": "tester"}`
+            "body": "eyJhZ2VudCI6ICJ0ZXN0ZXIifQ==",
+            "log_lines": ["INFO: POST /reports/daily with body hello"],
+            "database_writes": {
+                "foo": ["requests,region=us-east count=1i"]
+            },
+            "errors": []
+        })
+    );
+}
+
 #[test_log::test(tokio::test)]
 async fn test_tag_query_behavior() {
     use crate::server::ConfigProvider;
@@ -2025,6 +2076,70 @@ def process_request(influxdb3_local, query_parameters, request_headers, request_
     let body = response.text().await.unwrap();
     assert_eq!(body, "Line 1\nLine 2\nLine 3\n");
 }
+#[test_log::test(tokio::test)]
+async fn test_request_plugin_method_path_and_bytes_response() {
+    let plugin_code = r#"
+def process_request(influxdb3_local, query_parameters, request_headers, request_body, args=None, *, method=None, path=None, token_id=None):
+    body = f"{method},{path},{token_id}\n".encode()
+    return body, 200, {"Content-Type": "text/csv"}
+"#;
+    let (temp_dir, plugin_path) = create_plugin_in_temp_dir(plugin_code);
+
+    let plugin_dir = temp_dir.path().to_str().unwrap();
+    let plugin_filename = plugin_path.file_name().unwrap().to_str().unwrap();
+
+    let server = TestServer::configure()
+        .with_plugin_dir(plugin_dir)
+        .spawn()
+        .await;
+    let db_name = "request_test_method_path";
+
+    server.create_database(db_name).run().unwrap();
+    server
+        .create_trigger(
+            db_name,
+            "method_path_test",
+            plugin_filename,
+            "request:test_route",
+        )
+        .run()
+        .unwrap();
+
+    // requests below the trigger's path are handled by the trigger, with the rest of the path:
+    let client = server.http_client();
+    let response = client
+        .put(format!(
+            "{}/api/v3/engine/test_route/reports/daily.csv",
+            server.client_addr()
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers().get("content-type").unwrap(), "text/csv");
+    let body = response.bytes().await.unwrap();
+    assert_eq!(&body[..], b"PUT,reports/daily.csv,None\n");
+
+    let response = client
+        .delete(format!("{}/api/v3/engine/test_route", server.client_addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(&response.bytes().await.unwrap()[..], b"DELETE,,None\n");
+
+    // but not requests to a path that only starts with the same characters:
+    let response = client
+        .get(format!(
+            "{}/api/v3/engine/test_routes",
+            server.client_addr()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
 #[test_log::test(tokio::test)]
 async fn test_flask_response_object() {
     let plugin_code = r#"
@@ -297,3 +297,49 @@ fn assert_tablib_not_in_system_python() {
         );
     }
 }
+
+#[test_log::test(tokio::test)]
+#[ignore]
+async fn test_query_arrow() -> Result<()> {
+    let venv_dir = TempDir::new()?;
+    let plugin_code = r#"
+def process_scheduled_call(influxdb3_local, schedule_time, args=None):
+    table = influxdb3_local.query_arrow("SELECT host, usage FROM cpu ORDER BY usage")
+    influxdb3_local.info(str(table.num_rows), str(table.column_names))
+    influxdb3_local.info(str(table.column("usage").to_pylist()))
+
+    empty = influxdb3_local.query_arrow("SELECT host, usage FROM cpu WHERE host = 'nope'")
+    influxdb3_local.info(str(empty.num_rows), str(empty.column_names))
+"#;
+    let mut plugin_file = NamedTempFile::new()?;
+    plugin_file.write_all(plugin_code.as_bytes())?;
+    setup_python_venv(venv_dir.path())?;
+
+    let server = TestServer::configure()
+        .with_plugin_dir(plugin_file.path().parent().unwrap().to_string_lossy())
+        .with_virtual_env(venv_dir.path().to_string_lossy())
+        .spawn()
+        .await;
+
+    server
+        .write_lp_to_db(
+            "foo",
+            "cpu,host=a usage=0.5 1\ncpu,host=b usage=0.25 2",
+            influxdb3_client::Precision::Nanosecond,
+        )
+        .await?;
+    server.install_package().add_package("pyarrow").run()?;
+
+    let json = server
+        .test_schedule_plugin("foo", plugin_file.path().to_str().unwrap(), "* * * * * *")
+        .run()?;
+    assert_eq!(
+        json["log_lines"],
+        serde_json::json!([
+            "INFO: 2 ['host', 'usage']",
+            "INFO: [0.25, 0.5]",
+            "INFO: 0 ['host', 'usage']",
+        ])
+    );
+    Ok(())
+}
@@ -249,6 +249,10 @@ impl DistinctCache {
         Arc::clone(&self.schema)
     }
 
+    pub(crate) fn has_column(&self, column_id: &ColumnId) -> bool {
+        self.column_ids.contains(column_id)
+    }
+
     /// Compare the configuration of a given cache, producing a helpful error message if they differ
     pub(crate) fn compare_config(&self, other: &Self) -> Result<(), CacheError> {
         if self.max_cardinality != other.max_cardinality {
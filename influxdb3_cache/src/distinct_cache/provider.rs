@@ -1,6 +1,7 @@
 use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use arrow::datatypes::SchemaRef;
+use arrow::{array::RecordBatch, datatypes::SchemaRef};
+use indexmap::IndexMap;
 use influxdb3_catalog::{
     catalog::Catalog,
     channel::CatalogUpdateReceiver,
@@ -16,7 +17,7 @@ use parking_lot::RwLock;
 
 use super::{
     CacheError,
-    cache::{CreateDistinctCacheArgs, DistinctCache},
+    cache::{CreateDistinctCacheArgs, DistinctCache, Predicate},
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -25,6 +26,8 @@ pub enum ProviderError {
     Cache(#[from] CacheError),
     #[error("cache not found")]
     CacheNotFound,
+    #[error("column {0} is not in the cache")]
+    ColumnNotInCache(String),
     #[error("unexpected error: {0:#}")]
     Unexpected(#[from] anyhow::Error),
 }
@@ -115,6 +118,55 @@ impl DistinctCacheProvider {
             .map(|cache| cache.arrow_schema())
     }
 
+    /// Read the distinct values from a cache directly, rather than through a query, keeping only
+    /// those where the columns have one of the values given for them in `key_filters`
+    ///
+    /// The cache name can be left out if the table has only one cache.
+    pub fn read_cache(
+        &self,
+        db_id: DbId,
+        table_name: &str,
+        cache_name: Option<&str>,
+        key_filters: &[(String, Vec<String>)],
+    ) -> Result<RecordBatch, ProviderError> {
+        let table_def = self
+            .catalog
+            .db_schema_by_id(&db_id)
+            .and_then(|db| db.table_definition(table_name))
+            .ok_or(ProviderError::CacheNotFound)?;
+        let cache_def = (match cache_name {
+            Some(name) => table_def.distinct_caches.get_by_name(name),
+            None if table_def.distinct_caches.len() == 1 => {
+                table_def.distinct_caches.resource_iter().next().cloned()
+            }
+            None => None,
+        })
+        .ok_or(ProviderError::CacheNotFound)?;
+
+        let read = self.cache_map.read();
+        let cache = read
+            .get(&db_id)
+            .and_then(|db| db.get(&table_def.table_id))
+            .and_then(|table| table.get(&cache_def.cache_id))
+            .ok_or(ProviderError::CacheNotFound)?;
+
+        let mut predicates = IndexMap::new();
+        for (column_name, values) in key_filters {
+            let column_id = table_def
+                .column_name_to_id(column_name)
+                .filter(|id| cache.has_column(id))
+                .ok_or_else(|| ProviderError::ColumnNotInCache(column_name.to_string()))?;
+            predicates.insert(
+                column_id,
+                Predicate::new_in(values.iter().map(String::as_str)),
+            );
+        }
+
+        cache
+            .to_record_batch(cache.arrow_schema(), &predicates, None, None)
+            .map_err(|e| ProviderError::Unexpected(e.into()))
+    }
+
     /// Create a new entry in the distinct cache for a given database and parameters.
     pub fn create_cache(
         &self,
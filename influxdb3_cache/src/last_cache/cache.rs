@@ -674,6 +674,23 @@ impl std::fmt::Display for KeyValue {
     }
 }
 
+impl KeyValue {
+    /// Parse a value, given as a string, for a key column of the given type
+    pub(crate) fn parse(value: &str, column_type: InfluxColumnType) -> Option<Self> {
+        match column_type {
+            InfluxColumnType::Tag | InfluxColumnType::Field(InfluxFieldType::String) => {
+                Some(Self::String(value.to_string()))
+            }
+            InfluxColumnType::Field(InfluxFieldType::Integer) => value.parse().ok().map(Self::Int),
+            InfluxColumnType::Field(InfluxFieldType::UInteger) => {
+                value.parse().ok().map(Self::UInt)
+            }
+            InfluxColumnType::Field(InfluxFieldType::Boolean) => value.parse().ok().map(Self::Bool),
+            InfluxColumnType::Field(InfluxFieldType::Float) | InfluxColumnType::Timestamp => None,
+        }
+    }
+}
+
 #[cfg(test)]
 impl KeyValue {
     pub(crate) fn string(s: impl Into<String>) -> Self {
@@ -33,6 +33,16 @@ pub enum Error {
     ValueColumnDoesNotExist { column_id: ColumnId },
     #[error("requested last cache does not exist")]
     CacheDoesNotExist,
+    #[error(
+        "key column {column_name} can't be filtered on {value:?}, it is a {column_type} column"
+    )]
+    InvalidKeyValue {
+        column_name: String,
+        value: String,
+        column_type: InfluxColumnType,
+    },
+    #[error("failed to read the last cache: {0}")]
+    Read(#[from] arrow::error::ArrowError),
 }
 
 impl Error {
@@ -1641,4 +1651,102 @@ mod tests {
             assert_batches_sorted_eq!(t.expected, &results);
         }
     }
+
+    #[test_log::test(tokio::test(flavor = "multi_thread", worker_threads = 2))]
+    async fn read_cache_with_key_filters() {
+        let writer = TestWriter::new().await;
+        let _ = writer
+            .write_lp_to_write_batch("cpu,region=us-east,host=a usage=99", 0)
+            .await;
+        let provider = LastCacheProvider::new_from_catalog(writer.catalog())
+            .await
+            .unwrap();
+        writer
+            .catalog()
+            .create_last_cache(
+                TestWriter::DB_NAME,
+                "cpu",
+                None,
+                Some(&["region", "host"]),
+                Some(&["usage"]),
+                LastCacheSize::default(),
+                LastCacheTtl::default(),
+            )
+            .await
+            .unwrap();
+
+        // Use a short sleep to allow catalog change to be broadcast:
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let write_batch = writer
+            .write_lp_to_write_batch(
+                "\
+            cpu,region=us-east,host=a usage=1\n\
+            cpu,region=us-east,host=b usage=2\n\
+            cpu,region=us-west,host=c usage=3\n\
+            ",
+                1_000,
+            )
+            .await;
+        let wal_contents = influxdb3_wal::create::wal_contents(
+            (0, 1, 0),
+            [influxdb3_wal::create::write_batch_op(write_batch)],
+        );
+        provider.write_wal_contents_to_cache(&wal_contents);
+
+        let db_id = writer.db_schema().id;
+
+        // no filters returns everything in the cache:
+        let batches = provider.read_cache(db_id, "cpu", None, &[]).unwrap();
+        assert_batches_sorted_eq!(
+            [
+                "+---------+------+-------+-----------------------------+",
+                "| region  | host | usage | time                        |",
+                "+---------+------+-------+-----------------------------+",
+                "| us-east | a    | 1.0   | 1970-01-01T00:00:00.000001Z |",
+                "| us-east | b    | 2.0   | 1970-01-01T00:00:00.000001Z |",
+                "| us-west | c    | 3.0   | 1970-01-01T00:00:00.000001Z |",
+                "+---------+------+-------+-----------------------------+",
+            ],
+            &batches
+        );
+
+        // filter on multiple values for a key column:
+        let batches = provider
+            .read_cache(
+                db_id,
+                "cpu",
+                Some("cpu_region_host_last_cache"),
+                &[("host".to_string(), vec!["a".to_string(), "c".to_string()])],
+            )
+            .unwrap();
+        assert_batches_sorted_eq!(
+            [
+                "+---------+------+-------+-----------------------------+",
+                "| region  | host | usage | time                        |",
+                "+---------+------+-------+-----------------------------+",
+                "| us-east | a    | 1.0   | 1970-01-01T00:00:00.000001Z |",
+                "| us-west | c    | 3.0   | 1970-01-01T00:00:00.000001Z |",
+                "+---------+------+-------+-----------------------------+",
+            ],
+            &batches
+        );
+
+        // filtering on a column that is not a key column is an error:
+        assert!(matches!(
+            provider.read_cache(
+                db_id,
+                "cpu",
+                None,
+                &[("usage".to_string(), vec!["1".to_string()])]
+            ),
+            Err(super::Error::KeyColumnDoesNotExistByName { .. })
+        ));
+
+        // reading a cache that does not exist is an error:
+        assert!(matches!(
+            provider.read_cache(db_id, "cpu", Some("not_a_cache"), &[]),
+            Err(super::Error::CacheDoesNotExist)
+        ));
+    }
 }
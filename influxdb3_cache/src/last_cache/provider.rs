@@ -1,6 +1,7 @@
 use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use arrow::{array::RecordBatch, datatypes::SchemaRef as ArrowSchemaRef, error::ArrowError};
+use indexmap::IndexMap;
 
 use influxdb3_catalog::{
     catalog::Catalog,
@@ -17,7 +18,7 @@ use parking_lot::RwLock;
 
 use super::{
     CreateLastCacheArgs, Error,
-    cache::{LastCache, LastCacheValueColumnsArg},
+    cache::{KeyValue, LastCache, LastCacheValueColumnsArg, Predicate},
     metrics::CacheMetrics,
 };
 
@@ -283,6 +284,70 @@ impl LastCacheProvider {
         });
     }
 
+    /// Read the records from a cache directly, rather than through a query, keeping only those
+    /// whose key columns have one of the values given for them in `key_filters`
+    ///
+    /// The key column values are given as strings, and are parsed according to the column's type.
+    /// The cache name can be left out if the table has only one cache.
+    pub fn read_cache(
+        &self,
+        db_id: DbId,
+        table_name: &str,
+        cache_name: Option<&str>,
+        key_filters: &[(String, Vec<String>)],
+    ) -> Result<Vec<RecordBatch>, Error> {
+        let db_schema = self
+            .catalog
+            .db_schema_by_id(&db_id)
+            .ok_or(Error::CacheDoesNotExist)?;
+        let mut recorder = self
+            .metrics
+            .query_duration_recorder(db_schema.name.to_string());
+        let table_def = db_schema
+            .table_definition(table_name)
+            .ok_or(Error::CacheDoesNotExist)?;
+        let cache_def = (match cache_name {
+            Some(name) => table_def.last_caches.get_by_name(name),
+            None if table_def.last_caches.len() == 1 => {
+                table_def.last_caches.resource_iter().next().cloned()
+            }
+            None => None,
+        })
+        .ok_or(Error::CacheDoesNotExist)?;
+
+        let read = self.cache_map.read();
+        let cache = read
+            .get(&db_id)
+            .and_then(|db| db.get(&table_def.table_id))
+            .and_then(|table| table.get(&cache_def.id))
+            .ok_or(Error::CacheDoesNotExist)?;
+
+        let mut predicates = IndexMap::new();
+        for (column_name, values) in key_filters {
+            let column = table_def
+                .column_definition(column_name)
+                .filter(|column| cache.key_column_ids.contains(&column.id))
+                .ok_or_else(|| Error::KeyColumnDoesNotExistByName {
+                    column_name: column_name.to_string(),
+                })?;
+            let values = values
+                .iter()
+                .map(|value| {
+                    KeyValue::parse(value, column.data_type).ok_or_else(|| Error::InvalidKeyValue {
+                        column_name: column_name.to_string(),
+                        value: value.to_string(),
+                        column_type: column.data_type,
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+            predicates.insert(column.id, Predicate::In(values));
+        }
+
+        let batches = cache.to_record_batches(Arc::clone(&table_def), &predicates)?;
+        recorder.set_success();
+        Ok(batches)
+    }
+
     /// Output the records for a given cache as arrow [`RecordBatch`]es
     ///
     /// This method is meant for testing.
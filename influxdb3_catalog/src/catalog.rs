@@ -10,8 +10,8 @@ use influxdb3_authz::ResourceType;
 use influxdb3_authz::TokenInfo;
 use influxdb3_authz::TokenProvider;
 use influxdb3_id::{
-    CatalogId, ColumnId, DbId, DistinctCacheId, LastCacheId, NodeId, SerdeVecMap, TableId, TokenId,
-    TriggerId,
+    CatalogId, ColumnId, ContinuousQueryId, DbId, DistinctCacheId, LastCacheId, NodeId, SecretId,
+    SerdeVecMap, TableId, TokenId, TriggerId,
 };
 use influxdb3_shutdown::ShutdownToken;
 use influxdb3_telemetry::ProcessingEngineMetrics;
@@ -53,10 +53,11 @@ use crate::snapshot::{CatalogSnapshot, Snapshot};
 use crate::{
     CatalogError, Result,
     log::{
-        AddFieldsLog, CatalogBatch, CreateTableLog, DeleteDistinctCacheLog, DeleteLastCacheLog,
+        AddFieldsLog, CatalogBatch, ContinuousQueryDefinition, CreateTableLog,
+        DeleteContinuousQueryLog, DeleteDistinctCacheLog, DeleteLastCacheLog, DeleteSecretLog,
         DeleteTriggerLog, DistinctCacheDefinition, FieldDefinition, LastCacheDefinition,
-        OrderedCatalogBatch, SoftDeleteDatabaseLog, SoftDeleteTableLog, TriggerDefinition,
-        TriggerIdentifier,
+        OrderedCatalogBatch, SecretDefinition, SoftDeleteDatabaseLog, SoftDeleteTableLog,
+        TriggerDefinition, TriggerIdentifier,
     },
 };
 
@@ -618,7 +619,7 @@ impl TokenProvider for Catalog {
 }
 
 impl ProcessingEngineMetrics for Catalog {
-    fn num_triggers(&self) -> (u64, u64, u64, u64) {
+    fn num_triggers(&self) -> (u64, u64, u64, u64, u64, u64) {
         self.inner.read().num_triggers()
     }
 }
@@ -963,28 +964,41 @@ impl InnerCatalog {
         self.databases.get_by_id(&db_id).is_some()
     }
 
-    pub fn num_triggers(&self) -> (u64, u64, u64, u64) {
+    pub fn num_triggers(&self) -> (u64, u64, u64, u64, u64, u64) {
         self.databases
             .iter()
             .map(|(_, db)| db.trigger_count_by_type())
             .fold(
-                (0, 0, 0, 0),
+                (0, 0, 0, 0, 0, 0),
                 |(
                     mut overall_wal_count,
                     mut overall_all_wal_count,
                     mut overall_schedule_count,
                     mut overall_request_count,
+                    mut overall_persisted_count,
+                    mut overall_catalog_count,
                 ),
-                 (wal_count, all_wal_count, schedule_count, request_count)| {
+                 (
+                    wal_count,
+                    all_wal_count,
+                    schedule_count,
+                    request_count,
+                    persisted_count,
+                    catalog_count,
+                )| {
                     overall_wal_count += wal_count;
                     overall_all_wal_count += all_wal_count;
                     overall_schedule_count += schedule_count;
                     overall_request_count += request_count;
+                    overall_persisted_count += persisted_count;
+                    overall_catalog_count += catalog_count;
                     (
                         overall_wal_count,
                         overall_all_wal_count,
                         overall_schedule_count,
                         overall_request_count,
+                        overall_persisted_count,
+                        overall_catalog_count,
                     )
                 },
             )
@@ -1046,6 +1060,8 @@ pub struct DatabaseSchema {
     pub name: Arc<str>,
     pub tables: Repository<TableId, TableDefinition>,
     pub processing_engine_triggers: Repository<TriggerId, TriggerDefinition>,
+    pub continuous_queries: Repository<ContinuousQueryId, ContinuousQueryDefinition>,
+    pub secrets: Repository<SecretId, SecretDefinition>,
     pub deleted: bool,
 }
 
@@ -1056,6 +1072,8 @@ impl DatabaseSchema {
             name,
             tables: Repository::new(),
             processing_engine_triggers: Repository::new(),
+            continuous_queries: Repository::new(),
+            secrets: Repository::new(),
             deleted: false,
         }
     }
@@ -1198,10 +1216,17 @@ impl DatabaseSchema {
             .collect()
     }
 
-    pub fn trigger_count_by_type(&self) -> (u64, u64, u64, u64) {
+    pub fn trigger_count_by_type(&self) -> (u64, u64, u64, u64, u64, u64) {
         self.processing_engine_triggers.iter().fold(
-            (0, 0, 0, 0),
-            |(mut wal_count, mut all_wal_count, mut schedule_count, mut request_count),
+            (0, 0, 0, 0, 0, 0),
+            |(
+                mut wal_count,
+                mut all_wal_count,
+                mut schedule_count,
+                mut request_count,
+                mut persisted_count,
+                mut catalog_count,
+            ),
              (_, trigger)| {
                 match trigger.trigger {
                     // wal
@@ -1212,8 +1237,19 @@ impl DatabaseSchema {
                     | TriggerSpecificationDefinition::Every { .. } => schedule_count += 1,
                     // request
                     TriggerSpecificationDefinition::RequestPath { .. } => request_count += 1,
+                    // persisted
+                    TriggerSpecificationDefinition::Persisted { .. } => persisted_count += 1,
+                    // catalog
+                    TriggerSpecificationDefinition::CatalogEvent { .. } => catalog_count += 1,
                 };
-                (wal_count, all_wal_count, schedule_count, request_count)
+                (
+                    wal_count,
+                    all_wal_count,
+                    schedule_count,
+                    request_count,
+                    persisted_count,
+                    catalog_count,
+                )
             },
         )
     }
@@ -1273,6 +1309,14 @@ impl UpdateDatabaseSchema for DatabaseCatalogOp {
             DatabaseCatalogOp::DisableTrigger(trigger_identifier) => {
                 DisableTrigger(trigger_identifier.clone()).update_schema(schema)
             }
+            DatabaseCatalogOp::CreateContinuousQuery(continuous_query) => {
+                continuous_query.update_schema(schema)
+            }
+            DatabaseCatalogOp::DeleteContinuousQuery(delete_continuous_query) => {
+                delete_continuous_query.update_schema(schema)
+            }
+            DatabaseCatalogOp::CreateSecret(secret) => secret.update_schema(schema),
+            DatabaseCatalogOp::DeleteSecret(delete_secret) => delete_secret.update_schema(schema),
         }
     }
 }
@@ -1464,6 +1508,74 @@ impl UpdateDatabaseSchema for DeleteTriggerLog {
     }
 }
 
+impl UpdateDatabaseSchema for ContinuousQueryDefinition {
+    fn update_schema<'a>(
+        &self,
+        mut schema: Cow<'a, DatabaseSchema>,
+    ) -> Result<Cow<'a, DatabaseSchema>> {
+        if let Some(current) = schema.continuous_queries.get_by_name(&self.name) {
+            if current.as_ref() == self {
+                return Ok(schema);
+            }
+            return Err(CatalogError::AlreadyExists);
+        }
+        schema
+            .to_mut()
+            .continuous_queries
+            .insert(self.id, Arc::new(self.clone()))
+            .expect("new continuous query should insert");
+        Ok(schema)
+    }
+}
+
+impl UpdateDatabaseSchema for DeleteContinuousQueryLog {
+    fn update_schema<'a>(
+        &self,
+        mut schema: Cow<'a, DatabaseSchema>,
+    ) -> Result<Cow<'a, DatabaseSchema>> {
+        // deleting a non-existent continuous query is a no-op to make it idempotent.
+        if !schema.continuous_queries.contains_id(&self.id) {
+            return Ok(schema);
+        }
+        schema.to_mut().continuous_queries.remove(&self.id);
+        Ok(schema)
+    }
+}
+
+impl UpdateDatabaseSchema for SecretDefinition {
+    fn update_schema<'a>(
+        &self,
+        mut schema: Cow<'a, DatabaseSchema>,
+    ) -> Result<Cow<'a, DatabaseSchema>> {
+        if let Some(current) = schema.secrets.get_by_name(&self.name) {
+            if current.as_ref() == self {
+                return Ok(schema);
+            }
+            return Err(CatalogError::AlreadyExists);
+        }
+        schema
+            .to_mut()
+            .secrets
+            .insert(self.id, Arc::new(self.clone()))
+            .expect("new secret should insert");
+        Ok(schema)
+    }
+}
+
+impl UpdateDatabaseSchema for DeleteSecretLog {
+    fn update_schema<'a>(
+        &self,
+        mut schema: Cow<'a, DatabaseSchema>,
+    ) -> Result<Cow<'a, DatabaseSchema>> {
+        // deleting a non-existent secret is a no-op to make it idempotent.
+        if !schema.secrets.contains_id(&self.id) {
+            return Ok(schema);
+        }
+        schema.to_mut().secrets.remove(&self.id);
+        Ok(schema)
+    }
+}
+
 fn make_new_name_using_deleted_time(name: &str, deletion_time: Time) -> Arc<str> {
     Arc::from(format!(
         "{}-{}",
@@ -2062,6 +2174,8 @@ mod tests {
             name: "test".into(),
             tables: Repository::new(),
             processing_engine_triggers: Default::default(),
+            continuous_queries: Default::default(),
+            secrets: Default::default(),
             deleted: false,
         };
         database
@@ -2742,4 +2856,137 @@ mod tests {
             "Update to schema would exceed number of tag columns per table limit of 250 columns"
         );
     }
+
+    #[test_log::test(tokio::test)]
+    async fn continuous_query_state_survives_reload() {
+        let obj_store =
+            Arc::new(LocalFileSystem::new_with_prefix(test_helpers::tmp_dir().unwrap()).unwrap());
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let init = async || {
+            Catalog::new(
+                "test",
+                Arc::clone(&obj_store) as _,
+                Arc::clone(&time_provider) as _,
+                Default::default(),
+            )
+            .await
+            .unwrap()
+        };
+
+        let catalog = init().await;
+        catalog.create_database("foo").await.unwrap();
+        let select = "SELECT mean(usage) INTO cpu_1h FROM cpu GROUP BY time(1h), *";
+        let query = format!("CREATE CONTINUOUS QUERY cq ON foo BEGIN {select} END");
+        let interval = std::time::Duration::from_secs(3600);
+        catalog
+            .create_continuous_query(
+                "foo",
+                "cq",
+                &query,
+                select,
+                interval,
+                std::time::Duration::ZERO,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        let err = catalog
+            .create_continuous_query(
+                "foo",
+                "cq",
+                &query,
+                select,
+                interval,
+                std::time::Duration::ZERO,
+                None,
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CatalogError::AlreadyExists));
+
+        drop(catalog);
+
+        let catalog = init().await;
+        let cq = catalog
+            .db_schema("foo")
+            .unwrap()
+            .continuous_queries
+            .get_by_name("cq")
+            .unwrap();
+        assert_eq!(interval, cq.every());
+        assert_eq!(interval, cq.run_for());
+        assert_eq!(select, cq.select);
+
+        catalog.delete_continuous_query("foo", "cq").await.unwrap();
+        assert!(matches!(
+            catalog.delete_continuous_query("foo", "cq").await,
+            Err(CatalogError::NotFound)
+        ));
+        drop(catalog);
+
+        let catalog = init().await;
+        assert!(
+            catalog
+                .db_schema("foo")
+                .unwrap()
+                .continuous_queries
+                .is_empty()
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn secrets_survive_reload() {
+        let obj_store =
+            Arc::new(LocalFileSystem::new_with_prefix(test_helpers::tmp_dir().unwrap()).unwrap());
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let init = async || {
+            Catalog::new(
+                "test",
+                Arc::clone(&obj_store) as _,
+                Arc::clone(&time_provider) as _,
+                Default::default(),
+            )
+            .await
+            .unwrap()
+        };
+
+        let catalog = init().await;
+        assert!(matches!(
+            catalog.create_secret("foo", "api_key", vec![1, 2, 3]).await,
+            Err(CatalogError::NotFound)
+        ));
+        catalog.create_database("foo").await.unwrap();
+        catalog
+            .create_secret("foo", "api_key", vec![1, 2, 3])
+            .await
+            .unwrap();
+        assert!(matches!(
+            catalog.create_secret("foo", "api_key", vec![4, 5, 6]).await,
+            Err(CatalogError::AlreadyExists)
+        ));
+        drop(catalog);
+
+        let catalog = init().await;
+        let secret = catalog
+            .db_schema("foo")
+            .unwrap()
+            .secrets
+            .get_by_name("api_key")
+            .unwrap();
+        assert_eq!(vec![1, 2, 3], secret.encrypted_value);
+        // the value is left out of debug output, so that it can't end up in logs
+        assert!(!format!("{secret:?}").contains("encrypted_value"));
+
+        catalog.delete_secret("foo", "api_key").await.unwrap();
+        assert!(matches!(
+            catalog.delete_secret("foo", "api_key").await,
+            Err(CatalogError::NotFound)
+        ));
+        drop(catalog);
+
+        let catalog = init().await;
+        assert!(catalog.db_schema("foo").unwrap().secrets.is_empty());
+    }
 }
@@ -122,6 +122,10 @@ impl AsMetricStr for DatabaseCatalogOp {
             DatabaseCatalogOp::DeleteTrigger(_) => "delete_trigger",
             DatabaseCatalogOp::EnableTrigger(_) => "enable_trigger",
             DatabaseCatalogOp::DisableTrigger(_) => "disable_trigger",
+            DatabaseCatalogOp::CreateContinuousQuery(_) => "create_continuous_query",
+            DatabaseCatalogOp::DeleteContinuousQuery(_) => "delete_continuous_query",
+            DatabaseCatalogOp::CreateSecret(_) => "create_secret",
+            DatabaseCatalogOp::DeleteSecret(_) => "delete_secret",
         }
     }
 }
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use hashbrown::HashMap;
 use influxdb3_id::ColumnId;
@@ -15,11 +15,12 @@ use crate::{
     CatalogError, Result,
     catalog::{DEFAULT_OPERATOR_TOKEN_NAME, NUM_TAG_COLUMNS_LIMIT},
     log::{
-        AddFieldsLog, CatalogBatch, CreateDatabaseLog, CreateTableLog, DatabaseCatalogOp,
-        DeleteDistinctCacheLog, DeleteLastCacheLog, DeleteTokenDetails, DeleteTriggerLog,
-        DistinctCacheDefinition, FieldDataType, FieldDefinition, LastCacheDefinition,
-        LastCacheSize, LastCacheTtl, LastCacheValueColumnsDef, MaxAge, MaxCardinality,
-        NodeCatalogOp, NodeMode, OrderedCatalogBatch, RegisterNodeLog, SoftDeleteDatabaseLog,
+        AddFieldsLog, CatalogBatch, ContinuousQueryDefinition, CreateDatabaseLog, CreateTableLog,
+        DatabaseCatalogOp, DeleteContinuousQueryLog, DeleteDistinctCacheLog, DeleteLastCacheLog,
+        DeleteSecretLog, DeleteTokenDetails, DeleteTriggerLog, DistinctCacheDefinition,
+        FieldDataType, FieldDefinition, LastCacheDefinition, LastCacheSize, LastCacheTtl,
+        LastCacheValueColumnsDef, MaxAge, MaxCardinality, NodeCatalogOp, NodeMode,
+        OrderedCatalogBatch, PluginType, RegisterNodeLog, SecretDefinition, SoftDeleteDatabaseLog,
         SoftDeleteTableLog, StopNodeLog, TokenBatch, TokenCatalogOp, TriggerDefinition,
         TriggerIdentifier, TriggerSettings, TriggerSpecificationDefinition, ValidPluginFilename,
     },
@@ -558,6 +559,36 @@ impl Catalog {
                 return Err(CatalogError::NotFound);
             };
             let trigger = TriggerSpecificationDefinition::from_string_rep(trigger_specification)?;
+            if let Some(batch_window) = trigger_settings.batch_window {
+                if trigger.plugin_type() != PluginType::WalRows {
+                    return Err(CatalogError::invalid_configuration(
+                        "a batch window can only be set on WAL triggers",
+                    ));
+                }
+                if batch_window.max_duration.is_zero() {
+                    return Err(CatalogError::invalid_configuration(
+                        "the batch window duration must be greater than 0",
+                    ));
+                }
+                if batch_window.max_rows == Some(0) {
+                    return Err(CatalogError::invalid_configuration(
+                        "the batch window max rows must be greater than 0",
+                    ));
+                }
+            }
+            if trigger_settings
+                .execution_timeout
+                .is_some_and(|timeout| timeout.is_zero())
+            {
+                return Err(CatalogError::invalid_configuration(
+                    "the execution timeout must be greater than 0",
+                ));
+            }
+            if trigger_settings.max_rows_written == Some(0) {
+                return Err(CatalogError::invalid_configuration(
+                    "the max rows written must be greater than 0",
+                ));
+            }
             if db.processing_engine_triggers.contains_name(trigger_name) {
                 return Err(CatalogError::AlreadyExists);
             }
@@ -679,6 +710,135 @@ impl Catalog {
         .await
     }
 
+    /// Create a new InfluxQL continuous query
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_continuous_query(
+        &self,
+        db_name: &str,
+        name: &str,
+        query: &str,
+        select: &str,
+        interval: Duration,
+        offset: Duration,
+        resample_every: Option<Duration>,
+        resample_for: Option<Duration>,
+    ) -> Result<OrderedCatalogBatch> {
+        info!(db_name, name, "create continuous query");
+        self.catalog_update_with_retry(|| {
+            let Some(mut db) = self.db_schema(db_name) else {
+                return Err(CatalogError::NotFound);
+            };
+            if db.continuous_queries.contains_name(name) {
+                return Err(CatalogError::AlreadyExists);
+            }
+            let id = Arc::make_mut(&mut db)
+                .continuous_queries
+                .get_and_increment_next_id();
+            Ok(CatalogBatch::database(
+                self.time_provider.now().timestamp_nanos(),
+                db.id,
+                db.name(),
+                vec![DatabaseCatalogOp::CreateContinuousQuery(
+                    ContinuousQueryDefinition {
+                        id,
+                        name: name.into(),
+                        database_name: Arc::clone(&db.name),
+                        query: query.to_string(),
+                        select: select.to_string(),
+                        interval,
+                        offset,
+                        resample_every,
+                        resample_for,
+                    },
+                )],
+            ))
+        })
+        .await
+    }
+
+    pub async fn delete_continuous_query(
+        &self,
+        db_name: &str,
+        name: &str,
+    ) -> Result<OrderedCatalogBatch> {
+        info!(db_name, name, "delete continuous query");
+        self.catalog_update_with_retry(|| {
+            let Some(db) = self.db_schema(db_name) else {
+                return Err(CatalogError::NotFound);
+            };
+            let Some(continuous_query) = db.continuous_queries.get_by_name(name) else {
+                return Err(CatalogError::NotFound);
+            };
+            Ok(CatalogBatch::database(
+                self.time_provider.now().timestamp_nanos(),
+                db.id,
+                db.name(),
+                vec![DatabaseCatalogOp::DeleteContinuousQuery(
+                    DeleteContinuousQueryLog {
+                        id: continuous_query.id,
+                        name: Arc::clone(&continuous_query.name),
+                    },
+                )],
+            ))
+        })
+        .await
+    }
+
+    /// Create a secret, given its value already encrypted, for the triggers of a database
+    pub async fn create_secret(
+        &self,
+        db_name: &str,
+        name: &str,
+        encrypted_value: Vec<u8>,
+    ) -> Result<OrderedCatalogBatch> {
+        info!(db_name, name, "create secret");
+        self.catalog_update_with_retry(|| {
+            let Some(mut db) = self.db_schema(db_name) else {
+                return Err(CatalogError::NotFound);
+            };
+            if db.secrets.contains_name(name) {
+                return Err(CatalogError::AlreadyExists);
+            }
+            let id = Arc::make_mut(&mut db).secrets.get_and_increment_next_id();
+            let time_ns = self.time_provider.now().timestamp_nanos();
+            Ok(CatalogBatch::database(
+                time_ns,
+                db.id,
+                db.name(),
+                vec![DatabaseCatalogOp::CreateSecret(SecretDefinition {
+                    id,
+                    name: name.into(),
+                    database_name: Arc::clone(&db.name),
+                    encrypted_value: encrypted_value.clone(),
+                    created_at: time_ns,
+                })],
+            ))
+        })
+        .await
+    }
+
+    pub async fn delete_secret(&self, db_name: &str, name: &str) -> Result<OrderedCatalogBatch> {
+        info!(db_name, name, "delete secret");
+        self.catalog_update_with_retry(|| {
+            let Some(db) = self.db_schema(db_name) else {
+                return Err(CatalogError::NotFound);
+            };
+            let Some(secret) = db.secrets.get_by_name(name) else {
+                return Err(CatalogError::NotFound);
+            };
+            Ok(CatalogBatch::database(
+                self.time_provider.now().timestamp_nanos(),
+                db.id,
+                db.name(),
+                vec![DatabaseCatalogOp::DeleteSecret(DeleteSecretLog {
+                    id: secret.id,
+                    name: Arc::clone(&secret.name),
+                })],
+            ))
+        })
+        .await
+    }
+
     pub async fn delete_token(&self, token_name: &str) -> Result<OrderedCatalogBatch> {
         info!(token_name, "delete token");
 
@@ -319,7 +319,10 @@ impl From<super::TriggerSpecificationDefinition> for v2::TriggerSpecificationDef
     fn from(value: super::TriggerSpecificationDefinition) -> Self {
         match value {
             super::TriggerSpecificationDefinition::SingleTableWalWrite { table_name } => {
-                v2::TriggerSpecificationDefinition::SingleTableWalWrite { table_name }
+                v2::TriggerSpecificationDefinition::SingleTableWalWrite {
+                    table_name,
+                    predicate: None,
+                }
             }
             super::TriggerSpecificationDefinition::AllTablesWalWrite => {
                 v2::TriggerSpecificationDefinition::AllTablesWalWrite
@@ -342,6 +345,10 @@ impl From<super::TriggerSettings> for v2::TriggerSettings {
         Self {
             run_async: value.run_async,
             error_behavior: value.error_behavior.into(),
+            retry_policy: Default::default(),
+            batch_window: None,
+            execution_timeout: None,
+            max_rows_written: None,
         }
     }
 }
@@ -13,13 +13,16 @@ use hashbrown::HashMap;
 use humantime::{format_duration, parse_duration};
 use influxdb_line_protocol::FieldValue;
 use influxdb3_id::{
-    ColumnId, DbId, DistinctCacheId, LastCacheId, NodeId, TableId, TokenId, TriggerId,
+    ColumnId, ContinuousQueryId, DbId, DistinctCacheId, LastCacheId, NodeId, SecretId, TableId,
+    TokenId, TriggerId,
 };
 use schema::{InfluxColumnType, InfluxFieldType};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{CatalogError, Result, catalog::CatalogSequenceNumber};
+use crate::{
+    CatalogError, Result, catalog::CatalogSequenceNumber, trigger_predicate::TriggerPredicate,
+};
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum CatalogBatch {
@@ -172,6 +175,12 @@ pub enum DatabaseCatalogOp {
     DeleteTrigger(DeleteTriggerLog),
     EnableTrigger(TriggerIdentifier),
     DisableTrigger(TriggerIdentifier),
+    // Continuous query ops:
+    CreateContinuousQuery(ContinuousQueryDefinition),
+    DeleteContinuousQuery(DeleteContinuousQueryLog),
+    // Secret ops:
+    CreateSecret(SecretDefinition),
+    DeleteSecret(DeleteSecretLog),
 }
 
 impl DatabaseCatalogOp {
@@ -657,6 +666,8 @@ pub enum PluginType {
     WalRows,
     Schedule,
     Request,
+    Persisted,
+    Catalog,
 }
 
 impl std::fmt::Display for PluginType {
@@ -700,6 +711,34 @@ pub struct TriggerDefinition {
 pub struct TriggerSettings {
     pub run_async: bool,
     pub error_behavior: ErrorBehavior,
+    /// Bounds the retries made when the `error_behavior` is [`ErrorBehavior::Retry`]
+    #[serde(default)]
+    pub retry_policy: RetryPolicy,
+    /// Accumulates rows for WAL triggers so that the plugin is run once for the combined batch,
+    /// rather than once per WAL flush
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_window: Option<BatchWindow>,
+    /// Runs of the plugin that take longer than this are interrupted, and fail with a timeout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution_timeout: Option<Duration>,
+    /// Runs of the plugin that write more than this many rows fail, without their writes being
+    /// applied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rows_written: Option<usize>,
+}
+
+/// How long a WAL trigger accumulates rows for before running its plugin
+///
+/// The accumulated rows are persisted, so that they are not lost if the server restarts before
+/// the window closes.
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub struct BatchWindow {
+    /// The longest time rows are held for, measured from when the first row was accumulated
+    pub max_duration: Duration,
+    /// The plugin is run as soon as this many rows have been accumulated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rows: Option<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone, Copy, Default, clap::ValueEnum)]
@@ -714,6 +753,55 @@ pub enum ErrorBehavior {
     Disable,
 }
 
+/// How a trigger with an [`ErrorBehavior::Retry`] error behavior retries a failed run
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub struct RetryPolicy {
+    /// The maximum number of times the plugin is run for a single event, including the first run
+    pub max_attempts: u32,
+    /// How long to wait before the first retry, which doubles for each retry after that
+    pub initial_backoff: Duration,
+    /// The longest time to wait between retries
+    pub max_backoff: Duration,
+    /// What to do once all the attempts have failed
+    pub fallback: RetryFallback,
+}
+
+impl RetryPolicy {
+    /// How long to wait after the given failed attempt, counting from 1, before running again
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            fallback: RetryFallback::Log,
+        }
+    }
+}
+
+/// What a trigger does once it has run out of retries
+///
+/// In either case, the event that the trigger failed on is recorded as a dead letter, so that it
+/// can be replayed later.
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone, Copy, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryFallback {
+    #[default]
+    /// Log the error and move on to the next event.
+    Log,
+    /// Turn off the plugin until it is manually re-enabled.
+    Disable,
+}
+
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub struct DeleteTriggerLog {
     pub trigger_id: TriggerId,
@@ -733,11 +821,30 @@ pub struct TriggerIdentifier {
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum TriggerSpecificationDefinition {
-    SingleTableWalWrite { table_name: String },
+    SingleTableWalWrite {
+        table_name: String,
+        /// Only rows that match the predicate are handed to the plugin
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        predicate: Option<TriggerPredicate>,
+    },
     AllTablesWalWrite,
-    Schedule { schedule: String },
-    RequestPath { path: String },
-    Every { duration: Duration },
+    Schedule {
+        schedule: String,
+    },
+    RequestPath {
+        path: String,
+    },
+    Every {
+        duration: Duration,
+    },
+    /// Fires when a snapshot persists Parquet files for the table
+    Persisted {
+        table_name: String,
+    },
+    /// Fires when the given change is made to the database's catalog
+    CatalogEvent {
+        event_type: CatalogEventType,
+    },
 }
 
 impl TriggerSpecificationDefinition {
@@ -745,15 +852,29 @@ impl TriggerSpecificationDefinition {
         let spec_str = spec_str.trim();
         match spec_str {
             s if s.starts_with("table:") => {
-                let table_name = s.trim_start_matches("table:").trim();
+                let s = s.trim_start_matches("table:");
+                // the table name can be followed by `where <predicate>`:
+                let (table_name, predicate) = match s.to_ascii_lowercase().find(" where ") {
+                    Some(i) => (&s[..i], Some(&s[i + " where ".len()..])),
+                    None => (s, None),
+                };
+                let table_name = table_name.trim();
                 if table_name.is_empty() {
                     return Err(CatalogError::TriggerSpecificationParseError {
                         trigger_spec: spec_str.to_string(),
                         context: Some("table name is empty".to_string()),
                     });
                 }
+                let predicate = predicate
+                    .map(TriggerPredicate::from_str)
+                    .transpose()
+                    .map_err(|e| CatalogError::TriggerSpecificationParseError {
+                        trigger_spec: spec_str.to_string(),
+                        context: Some(e.to_string()),
+                    })?;
                 Ok(TriggerSpecificationDefinition::SingleTableWalWrite {
                     table_name: table_name.to_string(),
+                    predicate,
                 })
             }
             "all_tables" => Ok(TriggerSpecificationDefinition::AllTablesWalWrite),
@@ -797,18 +918,52 @@ impl TriggerSpecificationDefinition {
                     path: path.to_string(),
                 })
             }
+            s if s.starts_with("persisted:") => {
+                let table_name = s.trim_start_matches("persisted:").trim();
+                if table_name.is_empty() {
+                    return Err(CatalogError::TriggerSpecificationParseError {
+                        trigger_spec: spec_str.to_string(),
+                        context: Some("table name is empty".to_string()),
+                    });
+                }
+                Ok(TriggerSpecificationDefinition::Persisted {
+                    table_name: table_name.to_string(),
+                })
+            }
+            s if s.starts_with("catalog:") => {
+                let event_type = s.trim_start_matches("catalog:").trim();
+                let Ok(event_type) = event_type.parse() else {
+                    return Err(CatalogError::TriggerSpecificationParseError {
+                        trigger_spec: spec_str.to_string(),
+                        context: Some(format!(
+                            "expect one of the following catalog events: {}",
+                            CatalogEventType::ALL.map(|t| t.as_str()).join(", ")
+                        )),
+                    });
+                };
+                Ok(TriggerSpecificationDefinition::CatalogEvent { event_type })
+            }
             _ => Err(CatalogError::TriggerSpecificationParseError {
                 trigger_spec: spec_str.to_string(),
-                context: Some("expect one of the following prefixes: 'table:', 'all_tables:', 'cron:', 'every:', or 'request:'".to_string()),
+                context: Some("expect one of the following prefixes: 'table:', 'all_tables:', 'cron:', 'every:', 'request:', 'persisted:', or 'catalog:'".to_string()),
             }),
         }
     }
 
     pub fn string_rep(&self) -> String {
         match self {
-            TriggerSpecificationDefinition::SingleTableWalWrite { table_name } => {
+            TriggerSpecificationDefinition::SingleTableWalWrite {
+                table_name,
+                predicate: None,
+            } => {
                 format!("table:{}", table_name)
             }
+            TriggerSpecificationDefinition::SingleTableWalWrite {
+                table_name,
+                predicate: Some(predicate),
+            } => {
+                format!("table:{} where {}", table_name, predicate)
+            }
             TriggerSpecificationDefinition::AllTablesWalWrite => "all_tables".to_string(),
             TriggerSpecificationDefinition::Schedule { schedule } => {
                 format!("cron:{}", schedule)
@@ -819,6 +974,12 @@ impl TriggerSpecificationDefinition {
             TriggerSpecificationDefinition::RequestPath { path } => {
                 format!("request:{}", path)
             }
+            TriggerSpecificationDefinition::Persisted { table_name } => {
+                format!("persisted:{}", table_name)
+            }
+            TriggerSpecificationDefinition::CatalogEvent { event_type } => {
+                format!("catalog:{}", event_type)
+            }
         }
     }
 
@@ -829,8 +990,154 @@ impl TriggerSpecificationDefinition {
             TriggerSpecificationDefinition::Schedule { .. }
             | TriggerSpecificationDefinition::Every { .. } => PluginType::Schedule,
             TriggerSpecificationDefinition::RequestPath { .. } => PluginType::Request,
+            TriggerSpecificationDefinition::Persisted { .. } => PluginType::Persisted,
+            TriggerSpecificationDefinition::CatalogEvent { .. } => PluginType::Catalog,
+        }
+    }
+}
+
+/// A change to a database's catalog that a trigger can fire on
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CatalogEventType {
+    DatabaseDeleted,
+    TableCreated,
+    TableDeleted,
+    ColumnAdded,
+    CacheCreated,
+    CacheDeleted,
+}
+
+impl CatalogEventType {
+    pub const ALL: [Self; 6] = [
+        Self::DatabaseDeleted,
+        Self::TableCreated,
+        Self::TableDeleted,
+        Self::ColumnAdded,
+        Self::CacheCreated,
+        Self::CacheDeleted,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::DatabaseDeleted => "database_deleted",
+            Self::TableCreated => "table_created",
+            Self::TableDeleted => "table_deleted",
+            Self::ColumnAdded => "column_added",
+            Self::CacheCreated => "cache_created",
+            Self::CacheDeleted => "cache_deleted",
         }
     }
+
+    /// The type of event that the given catalog operation produces, if any
+    ///
+    /// Both last caches and distinct caches produce cache events.
+    pub fn from_op(op: &DatabaseCatalogOp) -> Option<Self> {
+        match op {
+            DatabaseCatalogOp::SoftDeleteDatabase(_) => Some(Self::DatabaseDeleted),
+            DatabaseCatalogOp::CreateTable(_) => Some(Self::TableCreated),
+            DatabaseCatalogOp::SoftDeleteTable(_) => Some(Self::TableDeleted),
+            DatabaseCatalogOp::AddFields(_) => Some(Self::ColumnAdded),
+            DatabaseCatalogOp::CreateDistinctCache(_) | DatabaseCatalogOp::CreateLastCache(_) => {
+                Some(Self::CacheCreated)
+            }
+            DatabaseCatalogOp::DeleteDistinctCache(_) | DatabaseCatalogOp::DeleteLastCache(_) => {
+                Some(Self::CacheDeleted)
+            }
+            DatabaseCatalogOp::CreateDatabase(_)
+            | DatabaseCatalogOp::CreateTrigger(_)
+            | DatabaseCatalogOp::DeleteTrigger(_)
+            | DatabaseCatalogOp::EnableTrigger(_)
+            | DatabaseCatalogOp::DisableTrigger(_)
+            | DatabaseCatalogOp::CreateContinuousQuery(_)
+            | DatabaseCatalogOp::DeleteContinuousQuery(_)
+            | DatabaseCatalogOp::CreateSecret(_)
+            | DatabaseCatalogOp::DeleteSecret(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for CatalogEventType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CatalogEventType {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|event_type| event_type.as_str() == s)
+            .ok_or(())
+    }
+}
+
+/// Defines an InfluxQL continuous query, which periodically runs a `SELECT ... INTO` statement
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct ContinuousQueryDefinition {
+    pub id: ContinuousQueryId,
+    pub name: Arc<str>,
+    pub database_name: Arc<str>,
+    /// The `CREATE CONTINUOUS QUERY` statement that defined the continuous query
+    pub query: String,
+    /// The `SELECT ... INTO` statement that is run
+    pub select: String,
+    /// The interval given in the `GROUP BY time(...)` clause of the statement
+    pub interval: Duration,
+    /// The offset given in the `GROUP BY time(...)` clause of the statement
+    pub offset: Duration,
+    /// How often the statement is run, from `RESAMPLE EVERY`, defaults to the `interval`
+    pub resample_every: Option<Duration>,
+    /// The time range covered by each run, from `RESAMPLE FOR`, defaults to the `interval`
+    pub resample_for: Option<Duration>,
+}
+
+impl ContinuousQueryDefinition {
+    /// How often the statement is run
+    pub fn every(&self) -> Duration {
+        self.resample_every.unwrap_or(self.interval)
+    }
+
+    /// The time range covered by each run
+    pub fn run_for(&self) -> Duration {
+        self.resample_for.unwrap_or(self.interval)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct DeleteContinuousQueryLog {
+    pub id: ContinuousQueryId,
+    pub name: Arc<str>,
+}
+
+/// A secret that the plugins of the database's triggers can read. The catalog only holds the
+/// value encrypted, with a key that is given to the server, and not kept in the catalog.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct SecretDefinition {
+    pub id: SecretId,
+    pub name: Arc<str>,
+    pub database_name: Arc<str>,
+    pub encrypted_value: Vec<u8>,
+    pub created_at: i64,
+}
+
+impl std::fmt::Debug for SecretDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretDefinition")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("database_name", &self.database_name)
+            .field("created_at", &self.created_at)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct DeleteSecretLog {
+    pub id: SecretId,
+    pub name: Arc<str>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Default)]
@@ -1,10 +1,16 @@
 use std::sync::Arc;
 
-use influxdb3_id::{ColumnId, DbId, DistinctCacheId, LastCacheId, NodeId, TableId, TriggerId};
+use influxdb3_id::{
+    ColumnId, ContinuousQueryId, DbId, DistinctCacheId, LastCacheId, NodeId, SecretId, TableId,
+    TriggerId,
+};
 
 use crate::{
     catalog::{ColumnDefinition, DatabaseSchema, NodeDefinition, TableDefinition},
-    log::{DistinctCacheDefinition, LastCacheDefinition, TriggerDefinition},
+    log::{
+        ContinuousQueryDefinition, DistinctCacheDefinition, LastCacheDefinition, SecretDefinition,
+        TriggerDefinition,
+    },
 };
 
 pub trait CatalogResource: Clone {
@@ -62,6 +68,30 @@ impl CatalogResource for TriggerDefinition {
     }
 }
 
+impl CatalogResource for ContinuousQueryDefinition {
+    type Identifier = ContinuousQueryId;
+
+    fn id(&self) -> Self::Identifier {
+        self.id
+    }
+
+    fn name(&self) -> Arc<str> {
+        Arc::clone(&self.name)
+    }
+}
+
+impl CatalogResource for SecretDefinition {
+    type Identifier = SecretId;
+
+    fn id(&self) -> Self::Identifier {
+        self.id
+    }
+
+    fn name(&self) -> Arc<str> {
+        Arc::clone(&self.name)
+    }
+}
+
 impl CatalogResource for ColumnDefinition {
     type Identifier = ColumnId;
 
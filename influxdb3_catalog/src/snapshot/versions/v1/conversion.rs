@@ -59,6 +59,8 @@ impl From<super::DatabaseSnapshot> for v2::DatabaseSnapshot {
             name: value.name,
             tables: value.tables.into(),
             processing_engine_triggers: value.processing_engine_triggers.into(),
+            continuous_queries: v2::RepositorySnapshot::default(),
+            secrets: v2::RepositorySnapshot::default(),
             deleted: value.deleted,
         }
     }
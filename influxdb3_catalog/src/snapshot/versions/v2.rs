@@ -3,8 +3,9 @@ use crate::catalog::{
     NodeState, Repository, TableDefinition, TokenRepository,
 };
 use crate::log::{
-    DistinctCacheDefinition, LastCacheDefinition, LastCacheTtl, LastCacheValueColumnsDef, MaxAge,
-    MaxCardinality, NodeMode, TriggerDefinition, TriggerSettings, TriggerSpecificationDefinition,
+    ContinuousQueryDefinition, DistinctCacheDefinition, LastCacheDefinition, LastCacheTtl,
+    LastCacheValueColumnsDef, MaxAge, MaxCardinality, NodeMode, SecretDefinition,
+    TriggerDefinition, TriggerSettings, TriggerSpecificationDefinition,
 };
 use crate::resource::CatalogResource;
 use arrow::datatypes::DataType as ArrowDataType;
@@ -14,12 +15,13 @@ use influxdb3_authz::{
     Actions, CrudActions, DatabaseActions, Permission, ResourceIdentifier, ResourceType, TokenInfo,
 };
 use influxdb3_id::{
-    CatalogId, ColumnId, DbId, DistinctCacheId, LastCacheId, NodeId, SerdeVecMap, TableId, TokenId,
-    TriggerId,
+    CatalogId, ColumnId, ContinuousQueryId, DbId, DistinctCacheId, LastCacheId, NodeId, SecretId,
+    SerdeVecMap, TableId, TokenId, TriggerId,
 };
 use schema::{InfluxColumnType, InfluxFieldType, TIME_DATA_TIMEZONE};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
 pub(crate) trait Snapshot {
@@ -327,6 +329,10 @@ pub(crate) struct DatabaseSnapshot {
     pub(crate) tables: RepositorySnapshot<TableId, TableSnapshot>,
     pub(crate) processing_engine_triggers:
         RepositorySnapshot<TriggerId, ProcessingEngineTriggerSnapshot>,
+    #[serde(default)]
+    pub(crate) continuous_queries: RepositorySnapshot<ContinuousQueryId, ContinuousQuerySnapshot>,
+    #[serde(default)]
+    pub(crate) secrets: RepositorySnapshot<SecretId, SecretSnapshot>,
     pub(crate) deleted: bool,
 }
 
@@ -339,6 +345,8 @@ impl Snapshot for DatabaseSchema {
             name: Arc::clone(&self.name),
             tables: self.tables.snapshot(),
             processing_engine_triggers: self.processing_engine_triggers.snapshot(),
+            continuous_queries: self.continuous_queries.snapshot(),
+            secrets: self.secrets.snapshot(),
             deleted: self.deleted,
         }
     }
@@ -349,6 +357,8 @@ impl Snapshot for DatabaseSchema {
             name: snap.name,
             tables: Repository::from_snapshot(snap.tables),
             processing_engine_triggers: Repository::from_snapshot(snap.processing_engine_triggers),
+            continuous_queries: Repository::from_snapshot(snap.continuous_queries),
+            secrets: Repository::from_snapshot(snap.secrets),
             deleted: snap.deleted,
         }
     }
@@ -475,6 +485,84 @@ impl Snapshot for TriggerDefinition {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub(crate) struct ContinuousQuerySnapshot {
+    pub id: ContinuousQueryId,
+    pub name: Arc<str>,
+    pub database_name: Arc<str>,
+    pub query: String,
+    pub select: String,
+    pub interval: Duration,
+    pub offset: Duration,
+    pub resample_every: Option<Duration>,
+    pub resample_for: Option<Duration>,
+}
+
+impl Snapshot for ContinuousQueryDefinition {
+    type Serialized = ContinuousQuerySnapshot;
+
+    fn snapshot(&self) -> Self::Serialized {
+        Self::Serialized {
+            id: self.id,
+            name: Arc::clone(&self.name),
+            database_name: Arc::clone(&self.database_name),
+            query: self.query.clone(),
+            select: self.select.clone(),
+            interval: self.interval,
+            offset: self.offset,
+            resample_every: self.resample_every,
+            resample_for: self.resample_for,
+        }
+    }
+
+    fn from_snapshot(snap: Self::Serialized) -> Self {
+        Self {
+            id: snap.id,
+            name: snap.name,
+            database_name: snap.database_name,
+            query: snap.query,
+            select: snap.select,
+            interval: snap.interval,
+            offset: snap.offset,
+            resample_every: snap.resample_every,
+            resample_for: snap.resample_for,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub(crate) struct SecretSnapshot {
+    pub id: SecretId,
+    pub name: Arc<str>,
+    pub database_name: Arc<str>,
+    pub encrypted_value: Vec<u8>,
+    pub created_at: i64,
+}
+
+impl Snapshot for SecretDefinition {
+    type Serialized = SecretSnapshot;
+
+    fn snapshot(&self) -> Self::Serialized {
+        Self::Serialized {
+            id: self.id,
+            name: Arc::clone(&self.name),
+            database_name: Arc::clone(&self.database_name),
+            encrypted_value: self.encrypted_value.clone(),
+            created_at: self.created_at,
+        }
+    }
+
+    fn from_snapshot(snap: Self::Serialized) -> Self {
+        Self {
+            id: snap.id,
+            name: snap.name,
+            database_name: snap.database_name,
+            encrypted_value: snap.encrypted_value,
+            created_at: snap.created_at,
+        }
+    }
+}
+
 /// The inner column definition for a [`TableSnapshot`]
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct ColumnDefinitionSnapshot {
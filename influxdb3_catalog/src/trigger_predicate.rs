@@ -0,0 +1,550 @@
+//! Predicates that filter the rows handed to a WAL trigger
+//!
+//! A predicate is a boolean expression over the columns of a table, for example:
+//!
+//! ```text
+//! region = 'eu' and (status != 'ok' or usage >= 90.5)
+//! ```
+//!
+//! Comparisons are made between a column and a literal, which can be a single-quoted string, a
+//! number, or `true`/`false`, and can be combined with `and`, `or`, and parentheses. Column names
+//! that are not plain identifiers can be double-quoted. A comparison against a column that a row
+//! has no value for, or whose value is of a different type than the literal, does not match.
+
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
+
+use influxdb3_id::ColumnId;
+use influxdb3_wal::{FieldData, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::TableDefinition;
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid predicate: {0}")]
+pub struct ParsePredicateError(String);
+
+/// A predicate on the rows of a table, parsed from its source text
+///
+/// The predicate is stored in the catalog as its source text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TriggerPredicate {
+    source: String,
+    expr: Expr,
+}
+
+impl TriggerPredicate {
+    /// Resolve the column names in the predicate against a table, so that it can be evaluated
+    /// on the table's rows
+    pub fn bind(&self, table_def: &TableDefinition) -> BoundTriggerPredicate {
+        BoundTriggerPredicate {
+            expr: self.expr.bind(table_def),
+        }
+    }
+}
+
+impl PartialEq for TriggerPredicate {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for TriggerPredicate {}
+
+impl FromStr for TriggerPredicate {
+    type Err = ParsePredicateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(ParsePredicateError(format!(
+                "unexpected {token} after the end of the expression"
+            )));
+        }
+        Ok(Self {
+            source: s.trim().to_string(),
+            expr,
+        })
+    }
+}
+
+impl TryFrom<String> for TriggerPredicate {
+    type Error = ParsePredicateError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<TriggerPredicate> for String {
+    fn from(value: TriggerPredicate) -> Self {
+        value.source
+    }
+}
+
+impl Display for TriggerPredicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// A [`TriggerPredicate`] whose columns have been resolved against a table
+#[derive(Debug, Clone)]
+pub struct BoundTriggerPredicate {
+    expr: Expr<Option<ColumnId>>,
+}
+
+impl BoundTriggerPredicate {
+    pub fn matches(&self, row: &Row) -> bool {
+        self.expr.matches(row)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr<C = String> {
+    And(Box<Expr<C>>, Box<Expr<C>>),
+    Or(Box<Expr<C>>, Box<Expr<C>>),
+    Compare {
+        column: C,
+        op: CompareOp,
+        literal: Literal,
+    },
+}
+
+impl Expr {
+    fn bind(&self, table_def: &TableDefinition) -> Expr<Option<ColumnId>> {
+        match self {
+            Self::And(left, right) => Expr::And(
+                Box::new(left.bind(table_def)),
+                Box::new(right.bind(table_def)),
+            ),
+            Self::Or(left, right) => Expr::Or(
+                Box::new(left.bind(table_def)),
+                Box::new(right.bind(table_def)),
+            ),
+            Self::Compare {
+                column,
+                op,
+                literal,
+            } => Expr::Compare {
+                column: table_def.column_name_to_id(column),
+                op: *op,
+                literal: literal.clone(),
+            },
+        }
+    }
+}
+
+impl Expr<Option<ColumnId>> {
+    fn matches(&self, row: &Row) -> bool {
+        match self {
+            Self::And(left, right) => left.matches(row) && right.matches(row),
+            Self::Or(left, right) => left.matches(row) || right.matches(row),
+            Self::Compare {
+                column,
+                op,
+                literal,
+            } => column
+                .and_then(|id| row.fields.iter().find(|field| field.id == id))
+                .and_then(|field| literal.compare_to(&field.value))
+                .is_some_and(|ordering| op.matches(ordering)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+impl CompareOp {
+    /// Whether the op holds, given the ordering of a column's value relative to the literal
+    fn matches(&self, ordering: Ordering) -> bool {
+        match self {
+            Self::Eq => ordering.is_eq(),
+            Self::NotEq => ordering.is_ne(),
+            Self::Lt => ordering.is_lt(),
+            Self::LtEq => ordering.is_le(),
+            Self::Gt => ordering.is_gt(),
+            Self::GtEq => ordering.is_ge(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl Literal {
+    /// The ordering of `value` relative to the literal, or `None` if they can't be compared
+    fn compare_to(&self, value: &FieldData) -> Option<Ordering> {
+        match (value, self) {
+            (
+                FieldData::Key(v) | FieldData::Tag(v) | FieldData::String(v),
+                Self::String(literal),
+            ) => Some(v.as_str().cmp(literal.as_str())),
+            (FieldData::Integer(v) | FieldData::Timestamp(v), Self::Integer(literal)) => {
+                Some(v.cmp(literal))
+            }
+            (FieldData::UInteger(v), Self::Integer(literal)) => {
+                Some(i128::from(*v).cmp(&i128::from(*literal)))
+            }
+            (FieldData::Integer(v) | FieldData::Timestamp(v), Self::Float(literal)) => {
+                (*v as f64).partial_cmp(literal)
+            }
+            (FieldData::UInteger(v), Self::Float(literal)) => (*v as f64).partial_cmp(literal),
+            (FieldData::Float(v), Self::Integer(literal)) => v.partial_cmp(&(*literal as f64)),
+            (FieldData::Float(v), Self::Float(literal)) => v.partial_cmp(literal),
+            (FieldData::Boolean(v), Self::Boolean(literal)) => Some(v.cmp(literal)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    Literal(Literal),
+    Op(CompareOp),
+    And,
+    Or,
+    LeftParen,
+    RightParen,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Identifier(name) => write!(f, "column {name:?}"),
+            Self::Literal(literal) => write!(f, "literal {literal:?}"),
+            Self::Op(op) => write!(f, "operator {op:?}"),
+            Self::And => f.write_str("'and'"),
+            Self::Or => f.write_str("'or'"),
+            Self::LeftParen => f.write_str("'('"),
+            Self::RightParen => f.write_str("')'"),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, ParsePredicateError> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LeftParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RightParen);
+            }
+            '=' => {
+                chars.next();
+                // allow both `=` and `==`
+                chars.next_if_eq(&'=');
+                tokens.push(Token::Op(CompareOp::Eq));
+            }
+            '!' => {
+                chars.next();
+                if chars.next_if_eq(&'=').is_none() {
+                    return Err(ParsePredicateError("expected '=' after '!'".to_string()));
+                }
+                tokens.push(Token::Op(CompareOp::NotEq));
+            }
+            '<' => {
+                chars.next();
+                let op = if chars.next_if_eq(&'=').is_some() {
+                    CompareOp::LtEq
+                } else if chars.next_if_eq(&'>').is_some() {
+                    CompareOp::NotEq
+                } else {
+                    CompareOp::Lt
+                };
+                tokens.push(Token::Op(op));
+            }
+            '>' => {
+                chars.next();
+                let op = if chars.next_if_eq(&'=').is_some() {
+                    CompareOp::GtEq
+                } else {
+                    CompareOp::Gt
+                };
+                tokens.push(Token::Op(op));
+            }
+            '\'' | '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        // a doubled quote is an escaped quote
+                        Some(q) if q == c && chars.next_if_eq(&c).is_some() => value.push(c),
+                        Some(q) if q == c => break,
+                        Some(other) => value.push(other),
+                        None => {
+                            return Err(ParsePredicateError(format!(
+                                "unterminated quoted string {c}{value}"
+                            )));
+                        }
+                    }
+                }
+                tokens.push(if c == '\'' {
+                    Token::Literal(Literal::String(value))
+                } else {
+                    Token::Identifier(value)
+                });
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut number = String::new();
+                while let Some(c) =
+                    chars.next_if(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.'))
+                {
+                    number.push(c);
+                }
+                let literal = if let Ok(integer) = number.parse() {
+                    Literal::Integer(integer)
+                } else if let Ok(float) = number.parse() {
+                    Literal::Float(float)
+                } else {
+                    return Err(ParsePredicateError(format!("invalid number {number}")));
+                };
+                tokens.push(Token::Literal(literal));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    word.push(c);
+                }
+                tokens.push(match word.to_ascii_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "true" => Token::Literal(Literal::Boolean(true)),
+                    "false" => Token::Literal(Literal::Boolean(false)),
+                    _ => Token::Identifier(word),
+                });
+            }
+            c => return Err(ParsePredicateError(format!("unexpected character {c:?}"))),
+        }
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser, in which `and` binds more tightly than `or`
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ParsePredicateError> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ParsePredicateError> {
+        let mut expr = self.parse_primary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_primary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ParsePredicateError> {
+        match self.next() {
+            Some(Token::LeftParen) => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RightParen) => Ok(expr),
+                    Some(token) => Err(ParsePredicateError(format!("expected ')', got {token}"))),
+                    None => Err(ParsePredicateError("expected ')'".to_string())),
+                }
+            }
+            Some(Token::Identifier(column)) => {
+                let op = match self.next() {
+                    Some(Token::Op(op)) => op,
+                    Some(token) => {
+                        return Err(ParsePredicateError(format!(
+                            "expected a comparison operator after column {column:?}, got {token}"
+                        )));
+                    }
+                    None => {
+                        return Err(ParsePredicateError(format!(
+                            "expected a comparison operator after column {column:?}"
+                        )));
+                    }
+                };
+                match self.next() {
+                    Some(Token::Literal(literal)) => Ok(Expr::Compare {
+                        column,
+                        op,
+                        literal,
+                    }),
+                    Some(token) => Err(ParsePredicateError(format!(
+                        "expected a literal to compare column {column:?} to, got {token}"
+                    ))),
+                    None => Err(ParsePredicateError(format!(
+                        "expected a literal to compare column {column:?} to"
+                    ))),
+                }
+            }
+            Some(token) => Err(ParsePredicateError(format!(
+                "expected a column or '(', got {token}"
+            ))),
+            None => Err(ParsePredicateError("expected a column or '('".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use influxdb3_id::ColumnId;
+    use influxdb3_wal::{Field, FieldData, Row};
+
+    use super::{Expr, TriggerPredicate};
+
+    /// Bind the predicate by looking column names up in `columns`
+    fn bind(predicate: &str, columns: &[&str]) -> Expr<Option<ColumnId>> {
+        fn bind(expr: &Expr, columns: &[&str]) -> Expr<Option<ColumnId>> {
+            match expr {
+                Expr::And(left, right) => Expr::And(
+                    Box::new(bind(left, columns)),
+                    Box::new(bind(right, columns)),
+                ),
+                Expr::Or(left, right) => Expr::Or(
+                    Box::new(bind(left, columns)),
+                    Box::new(bind(right, columns)),
+                ),
+                Expr::Compare {
+                    column,
+                    op,
+                    literal,
+                } => Expr::Compare {
+                    column: columns
+                        .iter()
+                        .position(|c| c == column)
+                        .map(|i| ColumnId::from(i as u16)),
+                    op: *op,
+                    literal: literal.clone(),
+                },
+            }
+        }
+        let predicate: TriggerPredicate = predicate.parse().unwrap();
+        bind(&predicate.expr, columns)
+    }
+
+    fn row(fields: Vec<FieldData>) -> Row {
+        Row {
+            time: 0,
+            fields: fields
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| Field::new(ColumnId::from(i as u16), value))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        for predicate in [
+            "region = 'eu'",
+            "region == 'eu' AND status != 'ok'",
+            "(region = 'eu' or region = 'us') and usage >= 90.5",
+            "\"my column\" <> 'it''s' or flag = true",
+            "count < -10",
+        ] {
+            let parsed: TriggerPredicate = predicate.parse().unwrap();
+            assert_eq!(predicate, parsed.to_string());
+        }
+
+        for predicate in [
+            "",
+            "region",
+            "region =",
+            "region = eu",
+            "'eu' = region",
+            "region = 'eu' and",
+            "(region = 'eu'",
+            "region = 'eu')",
+            "region = 'eu",
+            "region ! 'eu'",
+            "usage > 1.2.3",
+        ] {
+            assert!(
+                predicate.parse::<TriggerPredicate>().is_err(),
+                "{predicate} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn matches() {
+        let columns = ["region", "status", "usage", "count", "flag"];
+        let eu_error = row(vec![
+            FieldData::Tag("eu".to_string()),
+            FieldData::String("error".to_string()),
+            FieldData::Float(95.0),
+            FieldData::UInteger(3),
+            FieldData::Boolean(true),
+        ]);
+        let us_ok = row(vec![
+            FieldData::Tag("us".to_string()),
+            FieldData::String("ok".to_string()),
+            FieldData::Float(10.0),
+            FieldData::UInteger(0),
+            FieldData::Boolean(false),
+        ]);
+        // only has a region:
+        let eu = row(vec![FieldData::Tag("eu".to_string())]);
+
+        for (predicate, expected) in [
+            ("region = 'eu'", [true, false, true]),
+            ("status != 'ok'", [true, false, false]),
+            ("region = 'eu' and status != 'ok'", [true, false, false]),
+            ("region = 'us' or usage > 90", [true, true, false]),
+            (
+                "(region = 'us' or usage > 90) and flag = false",
+                [false, true, false],
+            ),
+            ("count >= 3", [true, false, false]),
+            ("count > 2.5", [true, false, false]),
+            // type mismatches and unknown columns never match:
+            ("usage = 'high'", [false, false, false]),
+            ("missing = 'eu' or region = 'us'", [false, true, false]),
+        ] {
+            let expr = bind(predicate, &columns);
+            let actual = [&eu_error, &us_ok, &eu].map(|row| expr.matches(row));
+            assert_eq!(expected, actual, "{predicate}");
+        }
+    }
+}
@@ -12,6 +12,16 @@ pub struct ProcessingEngineConfig {
     pub virtual_env_location: Option<PathBuf>,
     #[clap(long = "package-manager", default_value = "discover")]
     pub package_manager: PackageManager,
+    /// If given, checkpoint the cache that plugins keep state in to object store on this
+    /// interval, and restore it when the server starts, so that it is kept across restarts
+    #[clap(
+        long = "plugin-cache-checkpoint-interval",
+        env = "INFLUXDB3_PLUGIN_CACHE_CHECKPOINT_INTERVAL"
+    )]
+    pub plugin_cache_checkpoint_interval: Option<humantime::Duration>,
+    /// File holding the hex encoded 32 byte key that trigger secrets are encrypted with
+    #[clap(long = "secrets-key-file", env = "INFLUXDB3_SECRETS_KEY_FILE")]
+    pub secrets_key_file: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
@@ -1,4 +1,5 @@
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use hashbrown::HashMap;
 use influxdb3_catalog::log::{OrderedCatalogBatch, TriggerSettings};
 use iox_query_params::StatementParam;
@@ -202,6 +203,7 @@ impl Client {
                 query_str: query.into(),
                 format: None,
                 params: None,
+                chunk_size: None,
             },
         }
     }
@@ -235,6 +237,7 @@ impl Client {
                 query_str: query.into(),
                 format: None,
                 params: None,
+                chunk_size: None,
             },
         }
     }
@@ -537,6 +540,50 @@ impl Client {
         Ok(())
     }
 
+    /// Make a request to `POST /api/v3/configure/secret`
+    pub async fn api_v3_configure_secret_create(
+        &self,
+        db: impl Into<String> + Send,
+        name: impl Into<String> + Send,
+        value: impl Into<String> + Send,
+    ) -> Result<()> {
+        let _bytes = self
+            .send_json_get_bytes(
+                Method::POST,
+                "/api/v3/configure/secret",
+                Some(SecretCreateRequest {
+                    db: db.into(),
+                    name: name.into(),
+                    value: value.into(),
+                }),
+                None::<()>,
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Make a request to `DELETE /api/v3/configure/secret`
+    pub async fn api_v3_configure_secret_delete(
+        &self,
+        db: impl Into<String> + Send,
+        name: impl Into<String> + Send,
+    ) -> Result<()> {
+        let _bytes = self
+            .send_json_get_bytes(
+                Method::DELETE,
+                "/api/v3/configure/secret",
+                Some(SecretDeleteRequest {
+                    db: db.into(),
+                    name: name.into(),
+                }),
+                None::<()>,
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
     /// Make a request to `POST /api/v3/configure/processing_engine_trigger/enable`
     pub async fn api_v3_configure_processing_engine_trigger_enable(
         &self,
@@ -558,6 +605,62 @@ impl Client {
         Ok(())
     }
 
+    /// Make a request to `POST /api/v3/configure/processing_engine_trigger/replay_dead_letters`
+    ///
+    /// Replays the dead letter with the given id through the trigger, or all of the trigger's
+    /// dead letters that have not yet been replayed if no id is given, and returns the number of
+    /// dead letters that were replayed.
+    pub async fn api_v3_configure_processing_engine_trigger_replay_dead_letters(
+        &self,
+        db: impl Into<String> + Send,
+        trigger_name: impl Into<String> + Send,
+        dead_letter_id: Option<u64>,
+    ) -> Result<usize> {
+        let response: ProcessingEngineTriggerReplayDeadLettersResponse = self
+            .send_json(
+                Method::POST,
+                "/api/v3/configure/processing_engine_trigger/replay_dead_letters",
+                Some(ProcessingEngineTriggerReplayDeadLettersRequest {
+                    db: db.into(),
+                    trigger_name: trigger_name.into(),
+                    dead_letter_id,
+                }),
+                None::<()>,
+            )
+            .await?;
+        Ok(response.replayed)
+    }
+
+    /// Make a request to `POST /api/v3/configure/processing_engine_trigger/backfill`
+    ///
+    /// Starts running the trigger's plugin over the data written between `start_time` and
+    /// `end_time`, and returns the id of the backfill, which can be used to follow its progress
+    /// in the `system.processing_engine_backfills` table.
+    pub async fn api_v3_configure_processing_engine_trigger_backfill(
+        &self,
+        db: impl Into<String> + Send,
+        trigger_name: impl Into<String> + Send,
+        start_time: DateTime<Utc>,
+        end_time: Option<DateTime<Utc>>,
+        window_seconds: Option<u64>,
+    ) -> Result<u64> {
+        let response: ProcessingEngineTriggerBackfillResponse = self
+            .send_json(
+                Method::POST,
+                "/api/v3/configure/processing_engine_trigger/backfill",
+                Some(ProcessingEngineTriggerBackfillRequest {
+                    db: db.into(),
+                    trigger_name: trigger_name.into(),
+                    start_time,
+                    end_time,
+                    window_seconds,
+                }),
+                None::<()>,
+            )
+            .await?;
+        Ok(response.backfill_id)
+    }
+
     /// Make a request to `POST /api/v3/configure/plugin_environment/install_packages`
     pub async fn api_v3_configure_plugin_environment_install_packages(
         &self,
@@ -643,6 +746,20 @@ impl Client {
         .await
     }
 
+    /// Make a request to the `POST /api/v3/plugin_test/request` API
+    pub async fn request_plugin_test(
+        &self,
+        request_plugin_test_request: RequestPluginTestRequest,
+    ) -> Result<RequestPluginTestResponse> {
+        self.send_json(
+            Method::POST,
+            "/api/v3/plugin_test/request",
+            Some(request_plugin_test_request),
+            None::<()>,
+        )
+        .await
+    }
+
     /// Send a `/ping` request to the target `influxdb3` server to check its
     /// status and gather `version` and `revision` information
     pub async fn ping(&self) -> Result<PingResponse> {
@@ -948,6 +1065,15 @@ impl QueryRequestBuilder<'_> {
         self
     }
 
+    /// Specify the maximum number of rows the server will encode into each chunk of the
+    /// streamed response
+    ///
+    /// This only has an effect on the streaming formats, i.e., `json`, `jsonl`, and `csv`.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.request.chunk_size = Some(chunk_size);
+        self
+    }
+
     /// Set a query parameter value with the given `name`
     ///
     /// # Example
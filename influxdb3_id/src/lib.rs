@@ -63,6 +63,8 @@ catalog_identifier_type!(NodeId, u32);
 catalog_identifier_type!(DbId, u32);
 catalog_identifier_type!(TableId, u32);
 catalog_identifier_type!(TriggerId, u32);
+catalog_identifier_type!(ContinuousQueryId, u32);
+catalog_identifier_type!(SecretId, u32);
 catalog_identifier_type!(ColumnId, u16);
 catalog_identifier_type!(LastCacheId, u16);
 catalog_identifier_type!(DistinctCacheId, u16);
@@ -1,4 +1,5 @@
 use async_trait::async_trait;
+use datafusion::arrow::datatypes::SchemaRef;
 use datafusion::arrow::error::ArrowError;
 use datafusion::common::DataFusionError;
 use datafusion::execution::SendableRecordBatchStream;
@@ -25,6 +26,8 @@ pub enum QueryExecutorError {
     DatabasesToRecordBatch(#[source] ArrowError),
     #[error("unable to compose record batches from retention policies: {0}")]
     RetentionPoliciesToRecordBatch(#[source] ArrowError),
+    #[error("prepared statement not found: {statement_id}")]
+    PreparedStatementNotFound { statement_id: u64 },
     #[error("invokded a method that is not implemented: {0}")]
     MethodNotImplemented(&'static str),
     #[error(transparent)]
@@ -71,6 +74,41 @@ pub trait QueryExecutor: QueryDatabase + Debug + Send + Sync + 'static {
         external_span_ctx: Option<RequestLogContext>,
     ) -> Result<SendableRecordBatchStream, QueryExecutorError>;
 
+    /// Plan a SQL query once, so that it can be executed many times, with different parameters,
+    /// using [`QueryExecutor::query_prepared`]
+    async fn prepare_sql(
+        &self,
+        database: &str,
+        q: &str,
+        span_ctx: Option<SpanContext>,
+    ) -> Result<PreparedStatement, QueryExecutorError>;
+
+    /// Execute a statement that was prepared with [`QueryExecutor::prepare_sql`]
+    async fn query_prepared(
+        &self,
+        statement_id: u64,
+        params: Option<StatementParams>,
+        span_ctx: Option<SpanContext>,
+        external_span_ctx: Option<RequestLogContext>,
+    ) -> Result<SendableRecordBatchStream, QueryExecutorError>;
+
+    /// Release a statement that was prepared with [`QueryExecutor::prepare_sql`]
+    fn close_prepared(&self, statement_id: u64) -> Result<(), QueryExecutorError>;
+
+    /// Record a query whose result, with the given `schema`, was served from the query result
+    /// cache rather than being run, so that it still shows up in the query log and telemetry
+    #[allow(clippy::too_many_arguments)]
+    async fn record_cached_query(
+        &self,
+        database: &str,
+        query_type: &'static str,
+        q: &str,
+        params: Option<StatementParams>,
+        schema: SchemaRef,
+        span_ctx: Option<SpanContext>,
+        external_span_ctx: Option<RequestLogContext>,
+    ) -> Result<(), QueryExecutorError>;
+
     fn show_databases(
         &self,
         include_deleted: bool,
@@ -85,6 +123,15 @@ pub trait QueryExecutor: QueryDatabase + Debug + Send + Sync + 'static {
     fn upcast(&self) -> Arc<(dyn QueryDatabase + 'static)>;
 }
 
+/// A SQL statement that has been planned by [`QueryExecutor::prepare_sql`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreparedStatement {
+    /// The handle used to execute or close the statement
+    pub id: u64,
+    /// The names of the `$` parameters in the statement, without the `$` prefix
+    pub parameters: Vec<String>,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct UnimplementedQueryExecutor;
 
@@ -136,6 +183,44 @@ impl QueryExecutor for UnimplementedQueryExecutor {
         Err(QueryExecutorError::MethodNotImplemented("query_influxql"))
     }
 
+    async fn prepare_sql(
+        &self,
+        _database: &str,
+        _q: &str,
+        _span_ctx: Option<SpanContext>,
+    ) -> Result<PreparedStatement, QueryExecutorError> {
+        Err(QueryExecutorError::MethodNotImplemented("prepare_sql"))
+    }
+
+    async fn query_prepared(
+        &self,
+        _statement_id: u64,
+        _params: Option<StatementParams>,
+        _span_ctx: Option<SpanContext>,
+        _external_span_ctx: Option<RequestLogContext>,
+    ) -> Result<SendableRecordBatchStream, QueryExecutorError> {
+        Err(QueryExecutorError::MethodNotImplemented("query_prepared"))
+    }
+
+    fn close_prepared(&self, _statement_id: u64) -> Result<(), QueryExecutorError> {
+        Err(QueryExecutorError::MethodNotImplemented("close_prepared"))
+    }
+
+    async fn record_cached_query(
+        &self,
+        _database: &str,
+        _query_type: &'static str,
+        _q: &str,
+        _params: Option<StatementParams>,
+        _schema: SchemaRef,
+        _span_ctx: Option<SpanContext>,
+        _external_span_ctx: Option<RequestLogContext>,
+    ) -> Result<(), QueryExecutorError> {
+        Err(QueryExecutorError::MethodNotImplemented(
+            "record_cached_query",
+        ))
+    }
+
     fn show_databases(
         &self,
         _include_deleted: bool,
@@ -0,0 +1,335 @@
+//! Backfills run a WAL trigger's plugin over data that was written before the trigger was enabled
+//!
+//! The data for the backfill's time range is queried back out of the database, which covers both
+//! the buffered and the persisted data, one window of time at a time. Each window is converted
+//! back into the rows of a write batch, the same as the plugin is run against for new writes,
+//! and handed to the running trigger. The next window is only read once the plugin has run
+//! against the previous one, so the plugin sees the data in time order.
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::array::{Array, AsArray, RecordBatch};
+use arrow::compute::cast;
+use arrow::datatypes::{
+    DataType, Float64Type, Int64Type, TimeUnit, TimestampNanosecondType, UInt64Type,
+};
+use arrow::error::ArrowError;
+use futures_util::TryStreamExt;
+use influxdb3_catalog::catalog::TableDefinition;
+use influxdb3_id::SerdeVecMap;
+use influxdb3_py_api::logging::ProcessingEngineBackfill;
+use influxdb3_wal::{
+    Field, FieldData, Row, TableChunks, WalContents, WalFileSequenceNumber, WalOp, WriteBatch,
+};
+use iox_time::Time;
+use observability_deps::tracing::{info, warn};
+use schema::{InfluxColumnType, InfluxFieldType};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{ProcessingEngineManagerImpl, WalEvent};
+
+/// How long to wait for the trigger to start, when a backfill is requested for a trigger that
+/// was just enabled
+const TRIGGER_START_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Run the backfill, recording its progress in `backfill`
+pub(crate) async fn run_backfill(
+    manager: Arc<ProcessingEngineManagerImpl>,
+    backfill: ProcessingEngineBackfill,
+    table_names: Vec<Arc<str>>,
+    window: Duration,
+) {
+    info!(
+        backfill_id = backfill.id,
+        database_name = %backfill.database_name,
+        trigger_name = %backfill.trigger_name,
+        "starting backfill"
+    );
+    match backfill_windows(&manager, &backfill, &table_names, window).await {
+        Ok(()) => {
+            info!(backfill_id = backfill.id, "backfill completed");
+            backfill.complete();
+        }
+        Err(error) => {
+            warn!(backfill_id = backfill.id, %error, "backfill failed");
+            backfill.fail(error);
+        }
+    }
+}
+
+async fn backfill_windows(
+    manager: &ProcessingEngineManagerImpl,
+    backfill: &ProcessingEngineBackfill,
+    table_names: &[Arc<str>],
+    window: Duration,
+) -> Result<(), anyhow::Error> {
+    let window_ns = i64::try_from(window.as_nanos()).unwrap_or(i64::MAX);
+    let end_ns = backfill.end_time.timestamp_nanos();
+    let mut window_start_ns = backfill.start_time.timestamp_nanos();
+    while window_start_ns < end_ns {
+        let window_end_ns = window_start_ns.saturating_add(window_ns).min(end_ns);
+        let db_schema = manager
+            .catalog
+            .db_schema(&backfill.database_name)
+            .ok_or_else(|| anyhow::anyhow!("database {} not found", backfill.database_name))?;
+
+        let mut table_chunks = SerdeVecMap::new();
+        let mut row_count = 0;
+        for table_name in table_names {
+            // tables can be deleted while the backfill runs
+            let Some(table_def) = db_schema.table_definition(table_name.as_ref()) else {
+                continue;
+            };
+            let query = format!(
+                "SELECT * FROM \"{}\" WHERE time >= to_timestamp_nanos({window_start_ns}) \
+                 AND time < to_timestamp_nanos({window_end_ns}) ORDER BY time",
+                table_name.replace('"', "\"\"")
+            );
+            let batches: Vec<RecordBatch> = manager
+                .query_executor
+                .query_sql(&backfill.database_name, &query, None, None, None)
+                .await?
+                .try_collect()
+                .await?;
+
+            let mut chunks = TableChunks::default();
+            for batch in &batches {
+                for row in record_batch_to_rows(&table_def, batch)? {
+                    chunks.push_row(window_start_ns, row);
+                }
+            }
+            if chunks.row_count() > 0 {
+                row_count += chunks.row_count();
+                table_chunks.insert(table_def.table_id, chunks);
+            }
+        }
+
+        if !table_chunks.is_empty() {
+            let write_batch = WriteBatch::new(
+                manager.catalog.sequence_number().get(),
+                db_schema.id,
+                Arc::clone(&db_schema.name),
+                table_chunks.into_iter().collect(),
+            );
+            let wal_contents = WalContents {
+                persist_timestamp_ms: manager.time_provider.now().timestamp_millis(),
+                min_timestamp_ns: write_batch.min_time_ns,
+                max_timestamp_ns: write_batch.max_time_ns,
+                wal_file_number: WalFileSequenceNumber::new(0),
+                ops: vec![WalOp::Write(write_batch)],
+                snapshot: None,
+            };
+            let sender = trigger_sender(manager, backfill).await?;
+            let (done_tx, done_rx) = oneshot::channel();
+            sender
+                .send(WalEvent::Backfill(Arc::new(wal_contents), done_tx))
+                .await
+                .map_err(|_| anyhow::anyhow!("trigger stopped during the backfill"))?;
+            done_rx
+                .await
+                .map_err(|_| anyhow::anyhow!("trigger stopped during the backfill"))?;
+        }
+
+        backfill.record_progress(row_count as u64, Time::from_timestamp_nanos(window_end_ns));
+        window_start_ns = window_end_ns;
+    }
+    Ok(())
+}
+
+/// The channel to the running trigger, waiting for it to start if it was just enabled
+async fn trigger_sender(
+    manager: &ProcessingEngineManagerImpl,
+    backfill: &ProcessingEngineBackfill,
+) -> Result<mpsc::Sender<WalEvent>, anyhow::Error> {
+    let started = tokio::time::Instant::now();
+    loop {
+        if let Some(sender) = manager
+            .plugin_event_tx
+            .read()
+            .await
+            .wal_triggers
+            .get(backfill.database_name.as_ref())
+            .and_then(|triggers| triggers.get(backfill.trigger_name.as_ref()))
+        {
+            return Ok(sender.clone());
+        }
+        if started.elapsed() >= TRIGGER_START_TIMEOUT {
+            anyhow::bail!("trigger {} is not running", backfill.trigger_name);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Convert the result of a query on a table back into the rows of a write to that table
+pub(crate) fn record_batch_to_rows(
+    table_def: &TableDefinition,
+    batch: &RecordBatch,
+) -> Result<Vec<Row>, ArrowError> {
+    let mut rows = vec![
+        Row {
+            time: 0,
+            fields: vec![],
+        };
+        batch.num_rows()
+    ];
+    let schema = batch.schema();
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        let Some(column_def) = table_def.column_definition(field.name()) else {
+            continue;
+        };
+        let id = column_def.id;
+        match column_def.data_type {
+            InfluxColumnType::Tag | InfluxColumnType::Field(InfluxFieldType::String) => {
+                let is_tag = matches!(column_def.data_type, InfluxColumnType::Tag);
+                let values = cast(column, &DataType::Utf8)?;
+                for (row, value) in rows.iter_mut().zip(values.as_string::<i32>()) {
+                    if let Some(value) = value {
+                        let value = if is_tag {
+                            FieldData::Tag(value.to_string())
+                        } else {
+                            FieldData::String(value.to_string())
+                        };
+                        row.fields.push(Field::new(id, value));
+                    }
+                }
+            }
+            InfluxColumnType::Field(InfluxFieldType::Integer) => {
+                let values = cast(column, &DataType::Int64)?;
+                for (row, value) in rows.iter_mut().zip(values.as_primitive::<Int64Type>()) {
+                    if let Some(value) = value {
+                        row.fields.push(Field::new(id, FieldData::Integer(value)));
+                    }
+                }
+            }
+            InfluxColumnType::Field(InfluxFieldType::UInteger) => {
+                let values = cast(column, &DataType::UInt64)?;
+                for (row, value) in rows.iter_mut().zip(values.as_primitive::<UInt64Type>()) {
+                    if let Some(value) = value {
+                        row.fields.push(Field::new(id, FieldData::UInteger(value)));
+                    }
+                }
+            }
+            InfluxColumnType::Field(InfluxFieldType::Float) => {
+                let values = cast(column, &DataType::Float64)?;
+                for (row, value) in rows.iter_mut().zip(values.as_primitive::<Float64Type>()) {
+                    if let Some(value) = value {
+                        row.fields.push(Field::new(id, FieldData::Float(value)));
+                    }
+                }
+            }
+            InfluxColumnType::Field(InfluxFieldType::Boolean) => {
+                let values = cast(column, &DataType::Boolean)?;
+                for (row, value) in rows.iter_mut().zip(values.as_boolean()) {
+                    if let Some(value) = value {
+                        row.fields.push(Field::new(id, FieldData::Boolean(value)));
+                    }
+                }
+            }
+            InfluxColumnType::Timestamp => {
+                let values = cast(column, &DataType::Timestamp(TimeUnit::Nanosecond, None))?;
+                let values = values.as_primitive::<TimestampNanosecondType>();
+                if values.null_count() > 0 {
+                    return Err(ArrowError::InvalidArgumentError(
+                        "time column contains nulls".to_string(),
+                    ));
+                }
+                for (row, time) in rows.iter_mut().zip(values.values()) {
+                    row.time = *time;
+                    row.fields.push(Field::new(id, FieldData::Timestamp(*time)));
+                }
+            }
+        }
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{
+        DictionaryArray, Float64Array, Int64Array, RecordBatch, StringArray,
+        TimestampNanosecondArray,
+    };
+    use arrow::datatypes::Int32Type;
+    use data_types::NamespaceName;
+    use influxdb3_catalog::catalog::Catalog;
+    use influxdb3_wal::{FieldData, Gen1Duration};
+    use influxdb3_write::Precision;
+    use influxdb3_write::write_buffer::validator::WriteValidator;
+    use iox_time::{MockProvider, Time};
+    use object_store::memory::InMemory;
+
+    use super::record_batch_to_rows;
+
+    #[tokio::test]
+    async fn query_results_to_rows() {
+        let now = Time::from_timestamp_nanos(0);
+        let catalog = Arc::new(
+            Catalog::new(
+                "foo",
+                Arc::new(InMemory::new()),
+                Arc::new(MockProvider::new(now)),
+                Default::default(),
+            )
+            .await
+            .unwrap(),
+        );
+        let validator =
+            WriteValidator::initialize(NamespaceName::new("foodb").unwrap(), Arc::clone(&catalog))
+                .unwrap();
+        let lp = "cpu,host=a usage=1.5,count=2i,note=\"hi\" 10";
+        let parsed = validator
+            .v1_parse_lines_and_catalog_updates(lp, false, now, Precision::Nanosecond)
+            .unwrap();
+        let db = parsed.inner().txn().db_schema_cloned();
+        let expected =
+            parsed.ignore_catalog_changes_and_convert_lines_to_buffer(Gen1Duration::new_1m());
+        let table_def = db.table_definition("cpu").unwrap();
+
+        // the same row, as it comes back from a query, with a null note:
+        let batch = RecordBatch::try_from_iter([
+            ("count", Arc::new(Int64Array::from(vec![2, 3])) as _),
+            (
+                "host",
+                Arc::new(
+                    vec!["a", "b"]
+                        .into_iter()
+                        .collect::<DictionaryArray<Int32Type>>(),
+                ) as _,
+            ),
+            (
+                "note",
+                Arc::new(StringArray::from(vec![Some("hi"), None])) as _,
+            ),
+            (
+                "time",
+                Arc::new(TimestampNanosecondArray::from(vec![10, 20])) as _,
+            ),
+            ("usage", Arc::new(Float64Array::from(vec![1.5, 2.5])) as _),
+        ])
+        .unwrap();
+        let rows = record_batch_to_rows(&table_def, &batch).unwrap();
+        assert_eq!(2, rows.len());
+
+        let expected_row = &expected.valid_data.table_chunks[&table_def.table_id]
+            .chunk_time_to_chunk
+            .values()
+            .next()
+            .unwrap()
+            .rows[0];
+        let mut first = rows[0].fields.clone();
+        let mut expected_fields = expected_row.fields.clone();
+        first.sort_by_key(|field| field.id);
+        expected_fields.sort_by_key(|field| field.id);
+        assert_eq!(expected_row.time, rows[0].time);
+        assert_eq!(expected_fields, first);
+
+        assert_eq!(20, rows[1].time);
+        let note_id = table_def.column_name_to_id("note").unwrap();
+        assert!(rows[1].fields.iter().all(|field| field.id != note_id));
+        let host_id = table_def.column_name_to_id("host").unwrap();
+        assert!(rows[1].fields.iter().any(|field| field.id == host_id
+            && matches!(&field.value, FieldData::Tag(host) if host == "b")));
+    }
+}
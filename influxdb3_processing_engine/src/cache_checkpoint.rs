@@ -0,0 +1,147 @@
+//! Checkpoints of the cache that plugins keep state in
+//!
+//! The global and trigger caches behind `influxdb3_local.cache` are held in memory. When the
+//! server is started with a plugin cache checkpoint interval, they are written to object store on
+//! that interval and restored when the triggers are started, so that plugins keep their state
+//! across restarts. Changes made since the last checkpoint are lost if the server stops.
+//!
+//! When the server has a secrets key, checkpoints are signed with it, and a checkpoint that
+//! wasn't signed with the key is not loaded.
+use std::sync::Arc;
+
+use bytes::Bytes;
+use influxdb3_catalog::catalog::Catalog;
+use object_store::{ObjectStore, path::Path as ObjPath};
+use thiserror::Error;
+
+use crate::secrets::{SIGNATURE_LEN, SecretsKey};
+
+#[derive(Debug, Error)]
+pub enum CacheCheckpointError {
+    #[error("object store error: {0}")]
+    ObjectStore(#[from] object_store::Error),
+
+    #[error("the plugin cache checkpoint was not signed with the secrets key")]
+    InvalidSignature,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CacheCheckpointStore {
+    object_store: Arc<dyn ObjectStore>,
+    path: ObjPath,
+    key: Option<Arc<SecretsKey>>,
+}
+
+impl CacheCheckpointStore {
+    pub(crate) fn new(catalog: &Catalog, key: Option<Arc<SecretsKey>>) -> Self {
+        Self {
+            object_store: catalog.object_store(),
+            path: ObjPath::from(format!(
+                "{}/processing_engine/plugin_cache/checkpoint",
+                catalog.object_store_prefix()
+            )),
+            key,
+        }
+    }
+
+    /// The last checkpoint that was saved, if there is one
+    pub(crate) async fn load(&self) -> Result<Option<Bytes>, CacheCheckpointError> {
+        let stored = match self.object_store.get(&self.path).await {
+            Ok(result) => result.bytes().await?,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let Some(key) = &self.key else {
+            return Ok(Some(stored));
+        };
+        if stored.len() < SIGNATURE_LEN {
+            return Err(CacheCheckpointError::InvalidSignature);
+        }
+        let checkpoint = stored.slice(SIGNATURE_LEN..);
+        if !key.verify(&checkpoint, &stored[..SIGNATURE_LEN]) {
+            return Err(CacheCheckpointError::InvalidSignature);
+        }
+        Ok(Some(checkpoint))
+    }
+
+    pub(crate) async fn save(&self, checkpoint: Bytes) -> Result<(), CacheCheckpointError> {
+        let stored = match &self.key {
+            Some(key) => Bytes::from([key.sign(&checkpoint), checkpoint.to_vec()].concat()),
+            None => checkpoint,
+        };
+        self.object_store.put(&self.path, stored.into()).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iox_time::{MockProvider, Time, TimeProvider};
+    use object_store::memory::InMemory;
+
+    #[tokio::test]
+    async fn test_save_and_load() {
+        let time_provider: Arc<dyn TimeProvider> =
+            Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let catalog = Catalog::new(
+            "node",
+            Arc::new(InMemory::new()),
+            time_provider,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        let store = CacheCheckpointStore::new(&catalog, None);
+
+        assert_eq!(store.load().await.unwrap(), None);
+
+        store.save(Bytes::from_static(b"first")).await.unwrap();
+        store.save(Bytes::from_static(b"second")).await.unwrap();
+        assert_eq!(
+            store.load().await.unwrap(),
+            Some(Bytes::from_static(b"second"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signed_checkpoint() {
+        let time_provider: Arc<dyn TimeProvider> =
+            Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let catalog = Catalog::new(
+            "node",
+            Arc::new(InMemory::new()),
+            time_provider,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        let key = Arc::new(SecretsKey::new(&[7; 32]).unwrap());
+        let store = CacheCheckpointStore::new(&catalog, Some(Arc::clone(&key)));
+
+        store.save(Bytes::from_static(b"checkpoint")).await.unwrap();
+        assert_eq!(
+            store.load().await.unwrap(),
+            Some(Bytes::from_static(b"checkpoint"))
+        );
+
+        // a checkpoint written by anything without the key is not loaded
+        CacheCheckpointStore::new(&catalog, None)
+            .save(Bytes::from_static(b"forged"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.load().await,
+            Err(CacheCheckpointError::InvalidSignature)
+        ));
+        let other_key = Arc::new(SecretsKey::new(&[8; 32]).unwrap());
+        CacheCheckpointStore::new(&catalog, Some(other_key))
+            .save(Bytes::from_static(b"forged"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.load().await,
+            Err(CacheCheckpointError::InvalidSignature)
+        ));
+    }
+}
@@ -0,0 +1,382 @@
+//! Dead letters recorded by triggers that gave up on an event after retrying it
+//!
+//! Each dead letter is kept in object store, so that it survives a restart of the server and can
+//! still be replayed after it has fallen out of the `system.processing_engine_dead_letters`
+//! table. A dead letter is only marked as replayed once its trigger has run successfully against
+//! it.
+use std::sync::Arc;
+
+use bytes::Bytes;
+use chrono::DateTime;
+use futures_util::TryStreamExt;
+use hashbrown::HashMap;
+use influxdb3_catalog::catalog::Catalog;
+use influxdb3_catalog::log::{CatalogEventType, DatabaseCatalogOp};
+use influxdb3_id::{DbId, TriggerId};
+use influxdb3_py_api::logging::{DeadLetterEvent, ProcessingEngineDeadLetter};
+use influxdb3_py_api::system_py::{CatalogEvent, PersistedParquetFile, PluginRequest};
+use influxdb3_wal::WriteBatch;
+use iox_time::Time;
+use object_store::{ObjectStore, path::Path as ObjPath};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DeadLetterStoreError {
+    #[error("object store error: {0}")]
+    ObjectStore(#[from] object_store::Error),
+
+    #[error("failed to serialize dead letter: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+type Result<T, E = DeadLetterStoreError> = std::result::Result<T, E>;
+
+/// Where dead letters are kept in object store, one object per dead letter
+#[derive(Debug, Clone)]
+pub(crate) struct DeadLetterStore {
+    object_store: Arc<dyn ObjectStore>,
+    prefix: String,
+}
+
+impl DeadLetterStore {
+    pub(crate) fn new(catalog: &Catalog) -> Self {
+        Self {
+            object_store: catalog.object_store(),
+            prefix: format!(
+                "{}/processing_engine/dead_letters",
+                catalog.object_store_prefix()
+            ),
+        }
+    }
+
+    fn trigger_prefix(&self, database_name: &str, trigger_id: TriggerId) -> ObjPath {
+        ObjPath::from(format!("{}/{database_name}/{trigger_id}", self.prefix))
+    }
+
+    /// Write the dead letter, replacing it if it was saved before
+    pub(crate) async fn save(
+        &self,
+        trigger_id: TriggerId,
+        dead_letter: &ProcessingEngineDeadLetter,
+    ) -> Result<()> {
+        let path = ObjPath::from(format!(
+            "{}/{}.json",
+            self.trigger_prefix(&dead_letter.database_name, trigger_id),
+            dead_letter.id
+        ));
+        let bytes = Bytes::from(serde_json::to_vec(&StoredDeadLetter::from(dead_letter))?);
+        self.object_store.put(&path, bytes.into()).await?;
+        Ok(())
+    }
+
+    /// The dead letters of one trigger, in the order they were recorded
+    pub(crate) async fn load(
+        &self,
+        database_name: &str,
+        trigger_id: TriggerId,
+    ) -> Result<Vec<ProcessingEngineDeadLetter>> {
+        self.load_prefix(&self.trigger_prefix(database_name, trigger_id))
+            .await
+    }
+
+    /// The dead letters of every trigger, in the order they were recorded
+    pub(crate) async fn load_all(&self) -> Result<Vec<ProcessingEngineDeadLetter>> {
+        self.load_prefix(&ObjPath::from(self.prefix.as_str())).await
+    }
+
+    async fn load_prefix(&self, prefix: &ObjPath) -> Result<Vec<ProcessingEngineDeadLetter>> {
+        let locations: Vec<ObjPath> = self
+            .object_store
+            .list(Some(prefix))
+            .map_ok(|meta| meta.location)
+            .try_collect()
+            .await?;
+        let mut dead_letters = Vec::with_capacity(locations.len());
+        for location in locations {
+            let bytes = match self.object_store.get(&location).await {
+                Ok(result) => result.bytes().await?,
+                // removed since it was listed:
+                Err(object_store::Error::NotFound { .. }) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let stored: StoredDeadLetter = serde_json::from_slice(&bytes)?;
+            dead_letters.push(stored.into());
+        }
+        dead_letters.sort_unstable_by_key(|dead_letter| dead_letter.id);
+        Ok(dead_letters)
+    }
+
+    /// Remove all of a trigger's dead letters, once the trigger is deleted
+    pub(crate) async fn delete_trigger(
+        &self,
+        database_name: &str,
+        trigger_id: TriggerId,
+    ) -> Result<()> {
+        let locations: Vec<ObjPath> = self
+            .object_store
+            .list(Some(&self.trigger_prefix(database_name, trigger_id)))
+            .map_ok(|meta| meta.location)
+            .try_collect()
+            .await?;
+        for location in locations {
+            match self.object_store.delete(&location).await {
+                Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A [`ProcessingEngineDeadLetter`] as it is written to object store
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredDeadLetter {
+    id: u64,
+    event_time_ns: i64,
+    database_name: String,
+    trigger_name: String,
+    attempts: u32,
+    error: String,
+    replayed: bool,
+    event: StoredDeadLetterEvent,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StoredDeadLetterEvent {
+    WriteBatch {
+        write_batch: WriteBatch,
+    },
+    Schedule {
+        trigger_time_ns: i64,
+    },
+    Request {
+        method: String,
+        path: String,
+        query_params: HashMap<String, String>,
+        headers: HashMap<String, String>,
+        body: Vec<u8>,
+        token_id: Option<u64>,
+    },
+    PersistedFiles {
+        files: Vec<StoredParquetFile>,
+    },
+    Catalog {
+        event_type: CatalogEventType,
+        time_ns: i64,
+        database_id: DbId,
+        database_name: String,
+        op: DatabaseCatalogOp,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredParquetFile {
+    table_name: String,
+    path: String,
+    size_bytes: u64,
+    row_count: u64,
+    min_time: i64,
+    max_time: i64,
+}
+
+impl From<&ProcessingEngineDeadLetter> for StoredDeadLetter {
+    fn from(dead_letter: &ProcessingEngineDeadLetter) -> Self {
+        let event = match &dead_letter.event {
+            DeadLetterEvent::WriteBatch(write_batch) => StoredDeadLetterEvent::WriteBatch {
+                write_batch: WriteBatch::clone(write_batch),
+            },
+            DeadLetterEvent::Schedule(trigger_time) => StoredDeadLetterEvent::Schedule {
+                trigger_time_ns: trigger_time.timestamp_nanos_opt().unwrap_or_default(),
+            },
+            DeadLetterEvent::Request(request) => StoredDeadLetterEvent::Request {
+                method: request.method.clone(),
+                path: request.path.clone(),
+                query_params: request.query_params.clone(),
+                headers: request.headers.clone(),
+                body: request.body.to_vec(),
+                token_id: request.token_id,
+            },
+            DeadLetterEvent::PersistedFiles(files) => StoredDeadLetterEvent::PersistedFiles {
+                files: files
+                    .iter()
+                    .map(|file| StoredParquetFile {
+                        table_name: file.table_name.clone(),
+                        path: file.path.clone(),
+                        size_bytes: file.size_bytes,
+                        row_count: file.row_count,
+                        min_time: file.min_time,
+                        max_time: file.max_time,
+                    })
+                    .collect(),
+            },
+            DeadLetterEvent::Catalog(event) => StoredDeadLetterEvent::Catalog {
+                event_type: event.event_type,
+                time_ns: event.time_ns,
+                database_id: event.database_id,
+                database_name: event.database_name.to_string(),
+                op: event.op.clone(),
+            },
+        };
+        Self {
+            id: dead_letter.id,
+            event_time_ns: dead_letter.event_time.timestamp_nanos(),
+            database_name: dead_letter.database_name.to_string(),
+            trigger_name: dead_letter.trigger_name.to_string(),
+            attempts: dead_letter.attempts,
+            error: dead_letter.error.clone(),
+            replayed: dead_letter.is_replayed(),
+            event,
+        }
+    }
+}
+
+impl From<StoredDeadLetter> for ProcessingEngineDeadLetter {
+    fn from(stored: StoredDeadLetter) -> Self {
+        let event = match stored.event {
+            StoredDeadLetterEvent::WriteBatch { write_batch } => {
+                DeadLetterEvent::WriteBatch(Arc::new(write_batch))
+            }
+            StoredDeadLetterEvent::Schedule { trigger_time_ns } => {
+                DeadLetterEvent::Schedule(DateTime::from_timestamp_nanos(trigger_time_ns))
+            }
+            StoredDeadLetterEvent::Request {
+                method,
+                path,
+                query_params,
+                headers,
+                body,
+                token_id,
+            } => DeadLetterEvent::Request(Arc::new(PluginRequest {
+                method,
+                path,
+                query_params,
+                headers,
+                body: Bytes::from(body),
+                token_id,
+            })),
+            StoredDeadLetterEvent::PersistedFiles { files } => DeadLetterEvent::PersistedFiles(
+                files
+                    .into_iter()
+                    .map(|file| PersistedParquetFile {
+                        table_name: file.table_name,
+                        path: file.path,
+                        size_bytes: file.size_bytes,
+                        row_count: file.row_count,
+                        min_time: file.min_time,
+                        max_time: file.max_time,
+                    })
+                    .collect(),
+            ),
+            StoredDeadLetterEvent::Catalog {
+                event_type,
+                time_ns,
+                database_id,
+                database_name,
+                op,
+            } => DeadLetterEvent::Catalog(Arc::new(CatalogEvent {
+                event_type,
+                time_ns,
+                database_id,
+                database_name: database_name.into(),
+                op,
+            })),
+        };
+        let dead_letter = Self::new(
+            stored.id,
+            Time::from_timestamp_nanos(stored.event_time_ns),
+            stored.database_name.into(),
+            stored.trigger_name.into(),
+            stored.attempts,
+            stored.error,
+            event,
+        );
+        if stored.replayed {
+            dead_letter.mark_replayed();
+        }
+        dead_letter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::DateTime;
+    use influxdb3_catalog::catalog::Catalog;
+    use influxdb3_id::TriggerId;
+    use influxdb3_py_api::logging::{DeadLetterEvent, ProcessingEngineDeadLetter};
+    use iox_time::{MockProvider, Time};
+    use object_store::memory::InMemory;
+
+    use super::DeadLetterStore;
+
+    fn schedule_dead_letter(id: u64, trigger_name: &str) -> ProcessingEngineDeadLetter {
+        ProcessingEngineDeadLetter::new(
+            id,
+            Time::from_timestamp_nanos(100),
+            "foo".into(),
+            trigger_name.into(),
+            3,
+            "boom".to_string(),
+            DeadLetterEvent::Schedule(DateTime::from_timestamp_nanos(1_000)),
+        )
+    }
+
+    #[tokio::test]
+    async fn save_load_and_delete() {
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let catalog = Catalog::new(
+            "test-node",
+            Arc::new(InMemory::new()),
+            time_provider,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        let store = DeadLetterStore::new(&catalog);
+        assert!(store.load_all().await.unwrap().is_empty());
+
+        store
+            .save(TriggerId::new(0), &schedule_dead_letter(1, "a"))
+            .await
+            .unwrap();
+        store
+            .save(TriggerId::new(0), &schedule_dead_letter(0, "a"))
+            .await
+            .unwrap();
+        store
+            .save(TriggerId::new(1), &schedule_dead_letter(2, "b"))
+            .await
+            .unwrap();
+
+        let loaded = store.load("foo", TriggerId::new(0)).await.unwrap();
+        assert_eq!(vec![0, 1], loaded.iter().map(|d| d.id).collect::<Vec<_>>());
+        assert_eq!(3, loaded[0].attempts);
+        assert_eq!("boom", loaded[0].error);
+        assert!(!loaded[0].is_replayed());
+        let DeadLetterEvent::Schedule(trigger_time) = loaded[0].event else {
+            panic!("unexpected event {:?}", loaded[0].event);
+        };
+        assert_eq!(DateTime::from_timestamp_nanos(1_000), trigger_time);
+
+        // saving again replaces the dead letter:
+        loaded[1].mark_replayed();
+        store.save(TriggerId::new(0), &loaded[1]).await.unwrap();
+        let all = store.load_all().await.unwrap();
+        assert_eq!(
+            vec![(0, false), (1, true), (2, false)],
+            all.iter()
+                .map(|d| (d.id, d.is_replayed()))
+                .collect::<Vec<_>>()
+        );
+
+        store
+            .delete_trigger("foo", TriggerId::new(0))
+            .await
+            .unwrap();
+        let all = store.load_all().await.unwrap();
+        assert_eq!(vec![2], all.iter().map(|d| d.id).collect::<Vec<_>>());
+    }
+}
@@ -17,6 +17,9 @@ pub enum PluginEnvironmentError {
 
     #[error("Virtual environment error: {0}")]
     VenvError(#[from] VenvError),
+
+    #[error("{command} failed: {stderr}")]
+    CommandFailed { command: String, stderr: String },
 }
 
 pub trait PythonEnvironmentManager: Debug + Send + Sync + 'static {
@@ -29,6 +32,14 @@ pub trait PythonEnvironmentManager: Debug + Send + Sync + 'static {
 
     fn install_requirements(&self, requirements_path: String)
     -> Result<(), PluginEnvironmentError>;
+
+    /// Create a virtual environment at `venv_path`, separate from the shared one, and install
+    /// `requirements` into it
+    fn create_plugin_venv(
+        &self,
+        venv_path: &Path,
+        requirements: &[String],
+    ) -> Result<(), PluginEnvironmentError>;
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -39,6 +50,28 @@ pub struct PipManager;
 #[derive(Debug, Copy, Clone)]
 pub struct DisabledManager;
 
+/// The python executable of the virtual environment at `venv_path`
+fn venv_python(venv_path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        venv_path.join("Scripts").join("python.exe")
+    } else {
+        venv_path.join("bin").join("python")
+    }
+}
+
+/// Run `command`, failing if it exits unsuccessfully
+fn run_checked(command: &mut Command) -> Result<(), PluginEnvironmentError> {
+    let output = command.output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(PluginEnvironmentError::CommandFailed {
+            command: format!("{command:?}"),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
+
 fn is_valid_venv(venv_path: &Path) -> bool {
     if cfg!(windows) {
         venv_path.join("Scripts").join("activate.bat").exists()
@@ -83,6 +116,27 @@ impl PythonEnvironmentManager for UVManager {
             .output()?;
         Ok(())
     }
+
+    fn create_plugin_venv(
+        &self,
+        venv_path: &Path,
+        requirements: &[String],
+    ) -> Result<(), PluginEnvironmentError> {
+        // the environment has to use the same python as the server, which embeds it
+        run_checked(
+            Command::new("uv")
+                .arg("venv")
+                .arg("--python")
+                .arg(find_python())
+                .arg(venv_path),
+        )?;
+        run_checked(
+            Command::new("uv")
+                .args(["pip", "install", "--python"])
+                .arg(venv_python(venv_path))
+                .args(requirements),
+        )
+    }
 }
 
 impl PythonEnvironmentManager for PipManager {
@@ -132,6 +186,23 @@ impl PythonEnvironmentManager for PipManager {
 
         Ok(())
     }
+
+    fn create_plugin_venv(
+        &self,
+        venv_path: &Path,
+        requirements: &[String],
+    ) -> Result<(), PluginEnvironmentError> {
+        run_checked(
+            Command::new(find_python())
+                .args(["-m", "venv"])
+                .arg(venv_path),
+        )?;
+        run_checked(
+            Command::new(venv_python(venv_path))
+                .args(["-m", "pip", "install"])
+                .args(requirements),
+        )
+    }
 }
 
 impl PythonEnvironmentManager for DisabledManager {
@@ -153,4 +224,12 @@ impl PythonEnvironmentManager for DisabledManager {
     ) -> Result<(), PluginEnvironmentError> {
         Err(PluginEnvironmentDisabled)
     }
+
+    fn create_plugin_venv(
+        &self,
+        _venv_path: &Path,
+        _requirements: &[String],
+    ) -> Result<(), PluginEnvironmentError> {
+        Err(PluginEnvironmentDisabled)
+    }
 }
@@ -1,41 +1,76 @@
+use crate::cache_checkpoint::CacheCheckpointStore;
+use crate::dead_letters::DeadLetterStore;
 use crate::environment::PythonEnvironmentManager;
 use crate::manager::ProcessingEngineError;
+use crate::pending_batch::PendingBatchStore;
+use crate::plugin_environments::{PluginEnvironments, PluginVenv};
+use crate::plugin_package::PluginPackage;
+use crate::plugin_store::{PluginStore, is_valid_plugin_name, parse_plugin_filename};
 
 use crate::plugins::PluginContext;
 use crate::plugins::{PluginError, ProcessingEngineEnvironmentManager};
+use crate::secrets::{DatabaseSecrets, SecretsError};
+use crate::trigger_metrics::TriggerMetrics;
+use crate::value_caches::DatabaseValueCaches;
+use crate::wasm_plugin::is_wasm_plugin;
 use anyhow::Context;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use hashbrown::HashMap;
 use hyper::{Body, Response};
 use influxdb3_catalog::CatalogError;
 use influxdb3_catalog::catalog::Catalog;
 use influxdb3_catalog::channel::CatalogUpdateReceiver;
 use influxdb3_catalog::log::{
-    CatalogBatch, DatabaseCatalogOp, DeleteTriggerLog, PluginType, TriggerDefinition,
-    TriggerIdentifier, TriggerSpecificationDefinition, ValidPluginFilename,
+    CatalogBatch, CatalogEventType, DatabaseBatch, DatabaseCatalogOp, DeleteTriggerLog, PluginType,
+    TriggerDefinition, TriggerIdentifier, TriggerSpecificationDefinition, ValidPluginFilename,
 };
+use influxdb3_id::TriggerId;
 use influxdb3_internal_api::query_executor::QueryExecutor;
-use influxdb3_py_api::system_py::CacheStore;
+use influxdb3_py_api::logging::{
+    DeadLetterEvent, ProcessingEngineBackfill, ProcessingEngineDeadLetter,
+};
+use influxdb3_py_api::system_py::{
+    CacheStore, CatalogEvent, PersistedParquetFile, PluginRequest, SecretReader, ValueCacheReader,
+};
+use influxdb3_shutdown::ShutdownToken;
 use influxdb3_sys_events::SysEventStore;
 use influxdb3_types::http::{
-    SchedulePluginTestRequest, SchedulePluginTestResponse, WalPluginTestRequest,
-    WalPluginTestResponse,
+    RequestPluginTestRequest, RequestPluginTestResponse, SchedulePluginTestRequest,
+    SchedulePluginTestResponse, WalPluginTestRequest, WalPluginTestResponse,
 };
-use influxdb3_wal::{SnapshotDetails, WalContents, WalFileNotifier};
-use influxdb3_write::WriteBuffer;
-use iox_time::TimeProvider;
-use observability_deps::tracing::{debug, error, warn};
+use influxdb3_wal::{
+    SnapshotDetails, WalContents, WalFileNotifier, WalFileSequenceNumber, WalOp, WriteBatch,
+};
+use influxdb3_write::{PersistedSnapshotVersion, WriteBuffer};
+use iox_time::{Time, TimeProvider};
+use metric::Registry;
+use observability_deps::tracing::{debug, error, info, warn};
 use parking_lot::Mutex;
 use std::any::Any;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::oneshot::Receiver;
-use tokio::sync::{RwLock, mpsc, oneshot};
+use tokio::sync::{RwLock, broadcast, mpsc, oneshot};
 
+pub mod backfill;
+pub mod cache_checkpoint;
+pub mod dead_letters;
 pub mod environment;
+pub mod line_writer;
 pub mod manager;
+pub mod pending_batch;
+pub mod plugin_environments;
+pub mod plugin_package;
+pub mod plugin_store;
 pub mod plugins;
+pub mod secrets;
+pub mod trigger_metrics;
+pub mod value_caches;
+pub mod wasm_plugin;
 
 pub mod virtualenv;
 
@@ -49,7 +84,17 @@ pub struct ProcessingEngineManagerImpl {
     time_provider: Arc<dyn TimeProvider>,
     sys_event_store: Arc<SysEventStore>,
     cache: Arc<Mutex<CacheStore>>,
+    cache_checkpoint_store: CacheCheckpointStore,
     plugin_event_tx: RwLock<PluginChannels>,
+    dead_letter_store: DeadLetterStore,
+    next_dead_letter_id: AtomicU64,
+    next_backfill_id: AtomicU64,
+    plugin_store: PluginStore,
+    trigger_metrics: TriggerMetrics,
+    plugin_environments: PluginEnvironments,
+    /// Held until the plugin cache checkpoints start, which then take a final checkpoint on
+    /// shutdown
+    cache_checkpoint_shutdown: Mutex<Option<ShutdownToken>>,
 }
 
 #[derive(Debug, Default)]
@@ -60,6 +105,10 @@ struct PluginChannels {
     schedule_triggers: HashMap<String, HashMap<String, mpsc::Sender<ScheduleEvent>>>,
     /// Map of request path to the request trigger handler
     request_triggers: HashMap<String, mpsc::Sender<RequestEvent>>,
+    /// Map of database to persisted trigger name to handler
+    persisted_triggers: HashMap<String, HashMap<String, mpsc::Sender<PersistedEvent>>>,
+    /// Map of database to catalog trigger name to handler
+    catalog_triggers: HashMap<String, HashMap<String, mpsc::Sender<CatalogTriggerEvent>>>,
 }
 
 const PLUGIN_EVENT_BUFFER_SIZE: usize = 60;
@@ -118,6 +167,40 @@ impl PluginChannels {
                     return Ok(Some(rx));
                 }
             }
+            TriggerSpecificationDefinition::Persisted { .. } => {
+                if let Some(trigger_map) = self.persisted_triggers.get(&db) {
+                    if let Some(sender) = trigger_map.get(&trigger) {
+                        // create a one shot to wait for the shutdown to complete
+                        let (tx, rx) = oneshot::channel();
+                        if sender.send(PersistedEvent::Shutdown(tx)).await.is_err() {
+                            return Err(ProcessingEngineError::TriggerShutdownError {
+                                database: db,
+                                trigger_name: trigger,
+                            });
+                        }
+                        return Ok(Some(rx));
+                    }
+                }
+            }
+            TriggerSpecificationDefinition::CatalogEvent { .. } => {
+                if let Some(trigger_map) = self.catalog_triggers.get(&db) {
+                    if let Some(sender) = trigger_map.get(&trigger) {
+                        // create a one shot to wait for the shutdown to complete
+                        let (tx, rx) = oneshot::channel();
+                        if sender
+                            .send(CatalogTriggerEvent::Shutdown(tx))
+                            .await
+                            .is_err()
+                        {
+                            return Err(ProcessingEngineError::TriggerShutdownError {
+                                database: db,
+                                trigger_name: trigger,
+                            });
+                        }
+                        return Ok(Some(rx));
+                    }
+                }
+            }
         }
 
         Ok(None)
@@ -145,6 +228,16 @@ impl PluginChannels {
             TriggerSpecificationDefinition::RequestPath { .. } => {
                 self.request_triggers.remove(&trigger);
             }
+            TriggerSpecificationDefinition::Persisted { .. } => {
+                if let Some(trigger_map) = self.persisted_triggers.get_mut(&db) {
+                    trigger_map.remove(&trigger);
+                }
+            }
+            TriggerSpecificationDefinition::CatalogEvent { .. } => {
+                if let Some(trigger_map) = self.catalog_triggers.get_mut(&db) {
+                    trigger_map.remove(&trigger);
+                }
+            }
         }
     }
 
@@ -173,30 +266,215 @@ impl PluginChannels {
         rx
     }
 
-    async fn send_wal_contents(&self, wal_contents: Arc<WalContents>) {
+    fn add_persisted_trigger(
+        &mut self,
+        db: String,
+        trigger: String,
+    ) -> mpsc::Receiver<PersistedEvent> {
+        let (tx, rx) = mpsc::channel(PLUGIN_EVENT_BUFFER_SIZE);
+        self.persisted_triggers
+            .entry(db)
+            .or_default()
+            .insert(trigger, tx);
+        rx
+    }
+
+    fn add_catalog_trigger(
+        &mut self,
+        db: String,
+        trigger: String,
+    ) -> mpsc::Receiver<CatalogTriggerEvent> {
+        let (tx, rx) = mpsc::channel(PLUGIN_EVENT_BUFFER_SIZE);
+        self.catalog_triggers
+            .entry(db)
+            .or_default()
+            .insert(trigger, tx);
+        rx
+    }
+
+    /// Send WAL contents to the WAL triggers, returning the database and name of each trigger
+    /// whose queue was full
+    ///
+    /// This never waits on a trigger, as the WAL flush that sends the contents would otherwise
+    /// wait on a plugin that can itself be waiting on the flush, in `write_sync`.
+    fn send_wal_contents(&self, wal_contents: &Arc<WalContents>) -> Vec<(String, String)> {
+        let mut undelivered = vec![];
         for (db, trigger_map) in &self.wal_triggers {
+            for (trigger, sender) in trigger_map {
+                match sender.try_send(WalEvent::WriteWalContents(Arc::clone(wal_contents))) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => {
+                        undelivered.push((db.clone(), trigger.clone()));
+                    }
+                    Err(e @ TrySendError::Closed(_)) => {
+                        warn!(%e, %db, ?trigger, "error sending wal contents to plugin");
+                    }
+                }
+            }
+        }
+        undelivered
+    }
+
+    async fn send_persisted_snapshot(&self, snapshot: Arc<PersistedSnapshotVersion>) {
+        for (db, trigger_map) in &self.persisted_triggers {
             for (trigger, sender) in trigger_map {
                 if let Err(e) = sender
-                    .send(WalEvent::WriteWalContents(Arc::clone(&wal_contents)))
+                    .send(PersistedEvent::Snapshot(Arc::clone(&snapshot)))
                     .await
                 {
-                    warn!(%e, %db, ?trigger, "error sending wal contents to plugin");
+                    warn!(%e, %db, ?trigger, "error sending persisted snapshot to plugin");
+                }
+            }
+        }
+    }
+
+    /// Send each change in a catalog batch to the catalog triggers on the batch's database,
+    /// returning the changes that could not be queued because a trigger's queue was full
+    ///
+    /// This never waits on a trigger, so that a slow catalog plugin cannot hold up the catalog.
+    fn send_catalog_batch(&self, batch: &DatabaseBatch) -> Vec<(String, Arc<CatalogEvent>)> {
+        let mut undelivered = vec![];
+        let Some(trigger_map) = self.catalog_triggers.get(batch.database_name.as_ref()) else {
+            return undelivered;
+        };
+        for op in &batch.ops {
+            let Some(event_type) = CatalogEventType::from_op(op) else {
+                continue;
+            };
+            let event = Arc::new(CatalogEvent {
+                event_type,
+                time_ns: batch.time_ns,
+                database_id: batch.database_id,
+                database_name: Arc::clone(&batch.database_name),
+                op: op.clone(),
+            });
+            for (trigger, sender) in trigger_map {
+                match sender.try_send(CatalogTriggerEvent::Change(Arc::clone(&event))) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => {
+                        undelivered.push((trigger.clone(), Arc::clone(&event)));
+                    }
+                    Err(e @ TrySendError::Closed(_)) => {
+                        warn!(%e, db = %batch.database_name, ?trigger, "error sending catalog event to plugin");
+                    }
                 }
             }
         }
+        undelivered
+    }
+
+    /// Send the event from a dead letter to the trigger that failed on it
+    async fn send_replay(
+        &self,
+        db: &str,
+        trigger: &str,
+        trigger_spec: &TriggerSpecificationDefinition,
+        replay: DeadLetterReplay,
+    ) -> Result<(), ProcessingEngineError> {
+        let not_running = || ProcessingEngineError::TriggerNotRunning {
+            database: db.to_string(),
+            trigger_name: trigger.to_string(),
+        };
+        let sent = match (trigger_spec.plugin_type(), replay.dead_letter.event.clone()) {
+            (PluginType::WalRows, DeadLetterEvent::WriteBatch(write_batch)) => {
+                let sender = self
+                    .wal_triggers
+                    .get(db)
+                    .and_then(|triggers| triggers.get(trigger))
+                    .ok_or_else(not_running)?;
+                // the write batch is wrapped up as if it were the only write in a WAL file:
+                let wal_contents = WalContents {
+                    persist_timestamp_ms: 0,
+                    min_timestamp_ns: write_batch.min_time_ns,
+                    max_timestamp_ns: write_batch.max_time_ns,
+                    wal_file_number: WalFileSequenceNumber::new(0),
+                    ops: vec![WalOp::Write(WriteBatch::clone(&write_batch))],
+                    snapshot: None,
+                };
+                sender
+                    .send(WalEvent::Replay(Arc::new(wal_contents), replay))
+                    .await
+                    .is_ok()
+            }
+            (PluginType::Schedule, DeadLetterEvent::Schedule(trigger_time)) => {
+                let sender = self
+                    .schedule_triggers
+                    .get(db)
+                    .and_then(|triggers| triggers.get(trigger))
+                    .ok_or_else(not_running)?;
+                sender
+                    .send(ScheduleEvent::Replay(trigger_time, replay))
+                    .await
+                    .is_ok()
+            }
+            (PluginType::Request, DeadLetterEvent::Request(request)) => {
+                let TriggerSpecificationDefinition::RequestPath { path } = trigger_spec else {
+                    unreachable!()
+                };
+                let sender = self.request_triggers.get(path).ok_or_else(not_running)?;
+                // nobody is waiting on the response to a replayed request:
+                let (response_tx, _) = oneshot::channel();
+                let request = Request {
+                    request: PluginRequest::clone(&request),
+                    response_tx,
+                    replay: Some(replay),
+                };
+                sender.send(RequestEvent::Request(request)).await.is_ok()
+            }
+            (PluginType::Persisted, DeadLetterEvent::PersistedFiles(files)) => {
+                let sender = self
+                    .persisted_triggers
+                    .get(db)
+                    .and_then(|triggers| triggers.get(trigger))
+                    .ok_or_else(not_running)?;
+                sender
+                    .send(PersistedEvent::Replay(files, replay))
+                    .await
+                    .is_ok()
+            }
+            (PluginType::Catalog, DeadLetterEvent::Catalog(event)) => {
+                let sender = self
+                    .catalog_triggers
+                    .get(db)
+                    .and_then(|triggers| triggers.get(trigger))
+                    .ok_or_else(not_running)?;
+                sender
+                    .send(CatalogTriggerEvent::Replay(event, replay))
+                    .await
+                    .is_ok()
+            }
+            _ => {
+                return Err(ProcessingEngineError::DeadLetterTriggerMismatch {
+                    id: replay.id(),
+                    trigger_name: trigger.to_string(),
+                });
+            }
+        };
+        if sent { Ok(()) } else { Err(not_running()) }
     }
 
+    /// Send a request to the trigger with the longest path that `path` is, or is below, setting
+    /// the request's path to the rest of `path`
     async fn send_request(
         &self,
-        trigger_path: &str,
-        request: Request,
+        path: &str,
+        mut request: Request,
     ) -> Result<(), ProcessingEngineError> {
-        let event = RequestEvent::Request(request);
-        if let Some(sender) = self.request_triggers.get(trigger_path) {
-            if sender.send(event).await.is_err() {
-                return Err(ProcessingEngineError::RequestTriggerNotFound);
+        let mut trigger_path = path;
+        let sender = loop {
+            if let Some(sender) = self.request_triggers.get(trigger_path) {
+                break sender;
             }
-        } else {
+            match trigger_path.rsplit_once('/') {
+                Some((parent, _)) => trigger_path = parent,
+                None => return Err(ProcessingEngineError::RequestTriggerNotFound),
+            }
+        };
+        request.request.path = path[trigger_path.len()..]
+            .trim_start_matches('/')
+            .to_string();
+
+        if sender.send(RequestEvent::Request(request)).await.is_err() {
             return Err(ProcessingEngineError::RequestTriggerNotFound);
         }
 
@@ -213,6 +491,8 @@ impl ProcessingEngineManagerImpl {
         query_executor: Arc<dyn QueryExecutor>,
         time_provider: Arc<dyn TimeProvider>,
         sys_event_store: Arc<SysEventStore>,
+        metric_registry: Arc<Registry>,
+        shutdown: ShutdownToken,
     ) -> Arc<Self> {
         // if given a plugin dir, try to initialize the virtualenv.
         if let Some(plugin_dir) = &environment.plugin_dir {
@@ -226,12 +506,48 @@ impl ProcessingEngineManagerImpl {
         }
 
         let catalog_sub = catalog.subscribe_to_updates("processing_engine").await;
+        let persisted_snapshots = write_buffer.subscribe_persisted_snapshots();
 
         let cache = Arc::new(Mutex::new(CacheStore::new(
             Arc::clone(&time_provider),
             Duration::from_secs(10),
         )));
 
+        let cache_checkpoint_store =
+            CacheCheckpointStore::new(&catalog, environment.secrets_key.clone());
+        // dead letters recorded before a restart are put back in the system table, and ids carry
+        // on from the last one
+        let dead_letter_store = DeadLetterStore::new(&catalog);
+        let next_dead_letter_id = match dead_letter_store.load_all().await {
+            Ok(dead_letters) => {
+                let next_id = dead_letters
+                    .last()
+                    .map_or(0, |dead_letter| dead_letter.id + 1);
+                for dead_letter in dead_letters {
+                    sys_event_store.record(dead_letter);
+                }
+                next_id
+            }
+            Err(error) => {
+                error!(?error, "failed to load dead letters");
+                // start past any id that could have been used, so stored dead letters are not
+                // overwritten
+                time_provider.now().timestamp_nanos().max(0) as u64
+            }
+        };
+        let plugin_store = PluginStore::new(&catalog);
+        let trigger_metrics = TriggerMetrics::new(&metric_registry, Arc::clone(&sys_event_store));
+        let plugin_environments = PluginEnvironments::new(
+            Arc::clone(&environment.package_manager),
+            Arc::clone(&time_provider),
+            Arc::clone(&sys_event_store),
+        );
+        // without checkpoints there is nothing to do on shutdown, so the token is dropped, which
+        // completes it:
+        let cache_checkpoint_shutdown = environment
+            .cache_checkpoint_interval
+            .and(environment.plugin_dir.as_ref())
+            .map(|_| shutdown);
         let pem = Arc::new(Self {
             environment_manager: environment,
             catalog,
@@ -242,9 +558,18 @@ impl ProcessingEngineManagerImpl {
             time_provider,
             plugin_event_tx: Default::default(),
             cache,
+            cache_checkpoint_store,
+            dead_letter_store,
+            next_dead_letter_id: AtomicU64::new(next_dead_letter_id),
+            next_backfill_id: Default::default(),
+            plugin_store,
+            trigger_metrics,
+            plugin_environments,
+            cache_checkpoint_shutdown: Mutex::new(cache_checkpoint_shutdown),
         });
 
         background_catalog_update(Arc::clone(&pem), catalog_sub);
+        background_persisted_snapshots(Arc::clone(&pem), persisted_snapshots);
 
         pem
     }
@@ -278,11 +603,20 @@ impl ProcessingEngineManagerImpl {
                 return Err(PluginError::FetchingFromGithub(resp.status(), url));
             }
 
+            if is_wasm_plugin(plugin_path) {
+                let resp_body = resp
+                    .bytes()
+                    .await
+                    .context("error reading plugin from github repo")?;
+                return Ok(PluginCode::Wasm(WasmPlugin::from_bytes(resp_body)));
+            }
             let resp_body = resp
                 .text()
                 .await
                 .context("error reading plugin from github repo")?;
-            return Ok(PluginCode::Github(Arc::from(resp_body)));
+            return Ok(PluginCode::Python(Arc::new(PythonPluginCode::Github(
+                Arc::from(resp_body),
+            ))));
         }
 
         // otherwise we assume it is a local file
@@ -293,28 +627,83 @@ impl ProcessingEngineManagerImpl {
             .ok_or(PluginError::NoPluginDir)?;
         let plugin_path = plugin_dir.join(name);
 
+        // plugins uploaded through the API may not have been written to this node's plugin
+        // directory yet, e.g. after a restart with a fresh disk, or for a newly pinned version
+        if !plugin_path.exists() {
+            let (plugin_name, version) = parse_plugin_filename(name);
+            if is_valid_plugin_name(plugin_name) {
+                self.plugin_store
+                    .materialize(plugin_name, version, &plugin_dir)
+                    .await?;
+            }
+        }
+
         // read it at least once to make sure it's there
-        let code = std::fs::read_to_string(plugin_path.clone())?;
+        if is_wasm_plugin(name) {
+            return Ok(PluginCode::Wasm(WasmPlugin::new(plugin_path)?));
+        }
+        Ok(PluginCode::Python(Arc::new(PythonPluginCode::Local(
+            LocalPlugin::new(plugin_path)?,
+        ))))
+    }
 
-        // now we can return it
-        Ok(PluginCode::Local(LocalPlugin {
-            plugin_path,
-            last_read_and_code: Mutex::new((SystemTime::now(), Arc::from(code))),
-        }))
+    /// Store a new version of a plugin uploaded through the API, returning its version
+    ///
+    /// The new version is also written to the plugin directory, so that triggers running the
+    /// plugin without a pinned version reload it.
+    pub async fn upload_plugin(&self, name: &str, code: Bytes) -> Result<u64, PluginError> {
+        let plugin_dir = self
+            .environment_manager
+            .plugin_dir
+            .clone()
+            .ok_or(PluginError::NoPluginDir)?;
+        let version = self.plugin_store.put(name, code).await?;
+        self.plugin_store
+            .materialize(name, None, &plugin_dir)
+            .await?;
+        Ok(version)
+    }
+
+    /// The versions of a plugin that have been uploaded through the API
+    pub async fn plugin_versions(&self, name: &str) -> Result<Vec<u64>, PluginError> {
+        Ok(self.plugin_store.versions(name).await?)
     }
 }
 
 #[derive(Debug)]
 pub enum PluginCode {
+    Python(Arc<PythonPluginCode>),
+    /// A WASM module, which is run by the WASM runtime rather than python, see [`wasm_plugin`]
+    Wasm(WasmPlugin),
+}
+
+impl PluginCode {
+    /// The code of a python plugin, for the triggers that can only run python plugins
+    pub(crate) fn python(
+        &self,
+        plugin_type: PluginType,
+    ) -> Result<Arc<PythonPluginCode>, PluginError> {
+        match self {
+            PluginCode::Python(code) => Ok(Arc::clone(code)),
+            PluginCode::Wasm(_) => {
+                Err(PluginError::UnsupportedWasmTrigger(plugin_type.to_string()))
+            }
+        }
+    }
+}
+
+/// The code of a python plugin, which every type of trigger can run
+#[derive(Debug)]
+pub enum PythonPluginCode {
     Github(Arc<str>),
     Local(LocalPlugin),
 }
 
-impl PluginCode {
+impl PythonPluginCode {
     pub(crate) fn code(&self) -> Arc<str> {
         match self {
-            PluginCode::Github(code) => Arc::clone(code),
-            PluginCode::Local(plugin) => plugin.read_if_modified(),
+            PythonPluginCode::Github(code) => Arc::clone(code),
+            PythonPluginCode::Local(plugin) => plugin.read_if_modified(),
         }
     }
 }
@@ -323,37 +712,130 @@ impl PluginCode {
 #[derive(Debug)]
 pub struct LocalPlugin {
     plugin_path: PathBuf,
+    /// Whether the plugin is a directory of several files, see [`plugin_package`]
+    is_package: bool,
     last_read_and_code: Mutex<(SystemTime, Arc<str>)>,
 }
 
 impl LocalPlugin {
-    fn read_if_modified(&self) -> Arc<str> {
-        let metadata = std::fs::metadata(&self.plugin_path);
+    fn new(plugin_path: PathBuf) -> Result<Self, PluginError> {
+        let is_package = plugin_path.is_dir();
+        let last_read = SystemTime::now();
+        let code = read_local_plugin(&plugin_path, is_package, last_read)?;
+        Ok(Self {
+            plugin_path,
+            is_package,
+            last_read_and_code: Mutex::new((last_read, Arc::from(code))),
+        })
+    }
 
+    fn read_if_modified(&self) -> Arc<str> {
         let mut last_read_and_code = self.last_read_and_code.lock();
         let (last_read, code) = &mut *last_read_and_code;
 
-        match metadata {
-            Ok(metadata) => {
-                let is_modified = match metadata.modified() {
+        let is_modified = if self.is_package {
+            // any of the package's files may have changed
+            match plugin_package::last_modified(&self.plugin_path) {
+                Ok(modified) => modified > *last_read,
+                Err(_) => false,
+            }
+        } else {
+            match std::fs::metadata(&self.plugin_path) {
+                Ok(metadata) => match metadata.modified() {
                     Ok(modified) => modified > *last_read,
                     Err(_) => true, // if we can't get the modified time, assume it is modified
-                };
+                },
+                Err(_) => false,
+            }
+        };
 
-                if is_modified {
-                    // attempt to read the code, if it fails we will return the last known code
-                    if let Ok(new_code) = std::fs::read_to_string(&self.plugin_path) {
-                        *last_read = SystemTime::now();
-                        *code = Arc::from(new_code);
-                    } else {
-                        error!("error reading plugin file {:?}", self.plugin_path);
-                    }
+        if is_modified {
+            // attempt to read the code, if it fails we will return the last known code
+            let now = SystemTime::now();
+            match read_local_plugin(&self.plugin_path, self.is_package, now) {
+                Ok(new_code) => {
+                    *last_read = now;
+                    *code = Arc::from(new_code);
                 }
+                Err(e) => error!("error reading plugin {:?}: {}", self.plugin_path, e),
+            }
+        }
+
+        Arc::clone(code)
+    }
+}
+
+/// A WASM module, from the plugin directory or from Github
+#[derive(Debug)]
+pub struct WasmPlugin {
+    /// The file the module is read from, or `None` if it was fetched from Github
+    plugin_path: Option<PathBuf>,
+    last_read_and_code: Mutex<(SystemTime, Bytes)>,
+}
+
+impl WasmPlugin {
+    fn new(plugin_path: PathBuf) -> Result<Self, PluginError> {
+        let last_read = SystemTime::now();
+        let code = Bytes::from(std::fs::read(&plugin_path)?);
+        Ok(Self {
+            plugin_path: Some(plugin_path),
+            last_read_and_code: Mutex::new((last_read, code)),
+        })
+    }
+
+    fn from_bytes(code: Bytes) -> Self {
+        Self {
+            plugin_path: None,
+            last_read_and_code: Mutex::new((SystemTime::now(), code)),
+        }
+    }
+
+    pub(crate) fn read_if_modified(&self) -> Bytes {
+        let mut last_read_and_code = self.last_read_and_code.lock();
+        let (last_read, code) = &mut *last_read_and_code;
+        let Some(plugin_path) = &self.plugin_path else {
+            return code.clone();
+        };
 
-                Arc::clone(code)
+        let is_modified = match std::fs::metadata(plugin_path) {
+            Ok(metadata) => match metadata.modified() {
+                Ok(modified) => modified > *last_read,
+                Err(_) => true, // if we can't get the modified time, assume it is modified
+            },
+            Err(_) => false,
+        };
+
+        if is_modified {
+            // attempt to read the module, if it fails we will return the last known module
+            let now = SystemTime::now();
+            match std::fs::read(plugin_path) {
+                Ok(new_code) => {
+                    *last_read = now;
+                    *code = Bytes::from(new_code);
+                }
+                Err(e) => error!("error reading plugin {:?}: {}", plugin_path, e),
             }
-            Err(_) => Arc::clone(code),
         }
+
+        code.clone()
+    }
+}
+
+/// Read the code of a plugin file, or generate the code that runs a plugin package, in which case
+/// `read_at` is used to have python import the package's modules again
+fn read_local_plugin(
+    plugin_path: &Path,
+    is_package: bool,
+    read_at: SystemTime,
+) -> Result<String, PluginError> {
+    if is_package {
+        let generation = read_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        Ok(PluginPackage::load(plugin_path)?.bootstrap_code(generation))
+    } else {
+        Ok(std::fs::read_to_string(plugin_path)?)
     }
 }
 
@@ -395,7 +877,9 @@ impl ProcessingEngineManagerImpl {
                 manager: Arc::clone(&self),
             };
             let plugin_code = Arc::new(self.read_plugin_code(&trigger.plugin_filename).await?);
-            match trigger.trigger.plugin_type() {
+            let plugin_type = trigger.trigger.plugin_type();
+            wasm_plugin::validate_trigger_type(&trigger.plugin_filename, plugin_type)?;
+            match plugin_type {
                 PluginType::WalRows => {
                     let rec = self
                         .plugin_event_tx
@@ -446,6 +930,36 @@ impl ProcessingEngineManagerImpl {
                         rec,
                     )
                 }
+                PluginType::Persisted => {
+                    let rec = self
+                        .plugin_event_tx
+                        .write()
+                        .await
+                        .add_persisted_trigger(db_name.to_string(), trigger_name.to_string());
+
+                    plugins::run_persisted_files_plugin(
+                        db_name.to_string(),
+                        plugin_code.python(plugin_type)?,
+                        trigger,
+                        plugin_context,
+                        rec,
+                    )
+                }
+                PluginType::Catalog => {
+                    let rec = self
+                        .plugin_event_tx
+                        .write()
+                        .await
+                        .add_catalog_trigger(db_name.to_string(), trigger_name.to_string());
+
+                    plugins::run_catalog_plugin(
+                        db_name.to_string(),
+                        plugin_code.python(plugin_type)?,
+                        trigger,
+                        plugin_context,
+                        rec,
+                    )
+                }
             }
         }
 
@@ -504,6 +1018,18 @@ impl ProcessingEngineManagerImpl {
 
     pub async fn start_triggers(self: Arc<Self>) -> Result<(), ProcessingEngineError> {
         let triggers = self.catalog.active_triggers();
+        // python is only initialized when there is a plugin directory
+        if let (Some(interval), Some(_)) = (
+            self.environment_manager.cache_checkpoint_interval,
+            &self.environment_manager.plugin_dir,
+        ) {
+            // restore before checkpointing starts, so the saved checkpoint isn't replaced by an
+            // empty cache
+            self.restore_plugin_cache(&triggers).await;
+            if let Some(shutdown) = self.cache_checkpoint_shutdown.lock().take() {
+                background_cache_checkpoints(Arc::clone(&self), interval, shutdown);
+            }
+        }
         for (db_name, trigger_name) in triggers {
             Arc::clone(&self)
                 .run_trigger(&db_name, &trigger_name)
@@ -512,6 +1038,46 @@ impl ProcessingEngineManagerImpl {
         Ok(())
     }
 
+    /// Restore the plugin cache from the last checkpoint. Caches of triggers that are no longer
+    /// active are not restored. Failures are logged rather than returned, as a plugin that
+    /// starts with an empty cache is better than triggers that don't start at all.
+    async fn restore_plugin_cache(&self, triggers: &[(Arc<str>, Arc<str>)]) {
+        let checkpoint = match self.cache_checkpoint_store.load().await {
+            Ok(Some(checkpoint)) => checkpoint,
+            Ok(None) => return,
+            Err(error) => {
+                error!(?error, "failed to load the plugin cache checkpoint");
+                return;
+            }
+        };
+        let active_triggers = triggers.to_vec();
+        let cache = Arc::clone(&self.cache);
+        let restored = tokio::task::spawn_blocking(move || {
+            CacheStore::restore(&cache, &checkpoint, |database, trigger_name| {
+                active_triggers.iter().any(|(db, trigger)| {
+                    db.as_ref() == database && trigger.as_ref() == trigger_name
+                })
+            })
+        })
+        .await;
+        match restored {
+            Ok(Ok(count)) => info!(count, "restored plugin cache entries from checkpoint"),
+            Ok(Err(error)) => error!(?error, "failed to restore the plugin cache checkpoint"),
+            Err(error) => error!(?error, "plugin cache restore task failed"),
+        }
+    }
+
+    /// Write a checkpoint of the plugin cache to object store
+    async fn checkpoint_plugin_cache(&self) -> Result<(), anyhow::Error> {
+        let cache = Arc::clone(&self.cache);
+        let checkpoint =
+            tokio::task::spawn_blocking(move || CacheStore::checkpoint(&cache)).await??;
+        self.cache_checkpoint_store
+            .save(Bytes::from(checkpoint))
+            .await
+            .context("failed to save the plugin cache checkpoint")
+    }
+
     pub async fn test_wal_plugin(
         &self,
         request: WalPluginTestRequest,
@@ -523,22 +1089,14 @@ impl ProcessingEngineManagerImpl {
 
             let code = self.read_plugin_code(&request.filename).await?;
             let cache = Arc::clone(&self.cache);
-            let code_string = code.code().to_string();
 
             let res = tokio::task::spawn_blocking(move || {
-                plugins::run_test_wal_plugin(
-                    now,
-                    catalog,
-                    query_executor,
-                    code_string,
-                    cache,
-                    request,
-                )
-                .unwrap_or_else(|e| WalPluginTestResponse {
-                    log_lines: vec![],
-                    database_writes: Default::default(),
-                    errors: vec![e.to_string()],
-                })
+                plugins::run_test_wal_plugin(now, catalog, query_executor, code, cache, request)
+                    .unwrap_or_else(|e| WalPluginTestResponse {
+                        log_lines: vec![],
+                        database_writes: Default::default(),
+                        errors: vec![e.to_string()],
+                    })
             })
             .await?;
 
@@ -556,7 +1114,6 @@ impl ProcessingEngineManagerImpl {
             let now = self.time_provider.now();
 
             let code = self.read_plugin_code(&request.filename).await?;
-            let code_string = code.code().to_string();
             let cache = Arc::clone(&self.cache);
 
             let res = tokio::task::spawn_blocking(move || {
@@ -564,7 +1121,7 @@ impl ProcessingEngineManagerImpl {
                     now,
                     catalog,
                     query_executor,
-                    code_string,
+                    code,
                     cache,
                     request,
                 )
@@ -581,26 +1138,64 @@ impl ProcessingEngineManagerImpl {
         }
     }
 
+    pub async fn test_request_plugin(
+        &self,
+        request: RequestPluginTestRequest,
+        query_executor: Arc<dyn QueryExecutor>,
+    ) -> Result<RequestPluginTestResponse, PluginError> {
+        let catalog = Arc::clone(&self.catalog);
+        let now = self.time_provider.now();
+
+        let code = self.read_plugin_code(&request.filename).await?;
+        let cache = Arc::clone(&self.cache);
+
+        let res = tokio::task::spawn_blocking(move || {
+            plugins::run_test_request_plugin(now, catalog, query_executor, code, cache, request)
+        })
+        .await?
+        .unwrap_or_else(|e| RequestPluginTestResponse {
+            status: None,
+            headers: Default::default(),
+            body: String::new(),
+            log_lines: vec![],
+            database_writes: Default::default(),
+            errors: vec![e.to_string()],
+        });
+
+        Ok(res)
+    }
+
+    /// Run the request trigger for `path`, the path of the request below `/api/v3/engine/`.
+    /// A request is handled by the trigger whose path is the longest one that `path` is, or is
+    /// below, and the plugin is given the rest of the path.
     pub async fn request_trigger(
         &self,
-        trigger_path: &str,
+        path: &str,
+        method: String,
         query_params: HashMap<String, String>,
         request_headers: HashMap<String, String>,
         request_body: Bytes,
+        token_id: Option<u64>,
     ) -> Result<Response<Body>, ProcessingEngineError> {
         // oneshot channel for the response
         let (tx, rx) = oneshot::channel();
         let request = Request {
-            query_params,
-            headers: request_headers,
-            body: request_body,
+            request: PluginRequest {
+                method,
+                path: String::new(),
+                query_params,
+                headers: request_headers,
+                body: request_body,
+                token_id,
+            },
             response_tx: tx,
+            replay: None,
         };
 
         self.plugin_event_tx
             .write()
             .await
-            .send_request(trigger_path, request)
+            .send_request(path, request)
             .await?;
 
         rx.await.map_err(|e| {
@@ -609,45 +1204,374 @@ impl ProcessingEngineManagerImpl {
         })
     }
 
-    pub fn get_environment_manager(&self) -> Arc<dyn PythonEnvironmentManager> {
-        Arc::clone(&self.environment_manager.package_manager)
-    }
-}
-
-#[async_trait::async_trait]
-impl WalFileNotifier for ProcessingEngineManagerImpl {
-    async fn notify(&self, write: Arc<WalContents>) {
-        let plugin_channels = self.plugin_event_tx.read().await;
-        plugin_channels.send_wal_contents(write).await;
-    }
-
-    async fn notify_and_snapshot(
+    /// Record an event that a trigger failed to process as a dead letter, returning its id
+    pub(crate) async fn record_dead_letter(
         &self,
-        write: Arc<WalContents>,
-        snapshot_details: SnapshotDetails,
-    ) -> Receiver<SnapshotDetails> {
-        let plugin_channels = self.plugin_event_tx.read().await;
-        plugin_channels.send_wal_contents(write).await;
-
-        // configure a reciever that we immediately close
-        let (tx, rx) = oneshot::channel();
-        tx.send(snapshot_details).ok();
-        rx
+        trigger: &TriggerDefinition,
+        attempts: u32,
+        error: String,
+        event: DeadLetterEvent,
+    ) -> u64 {
+        let id = self.next_dead_letter_id.fetch_add(1, Ordering::Relaxed);
+        let dead_letter = ProcessingEngineDeadLetter::new(
+            id,
+            self.time_provider.now(),
+            Arc::clone(&trigger.database_name),
+            Arc::clone(&trigger.trigger_name),
+            attempts,
+            error,
+            event,
+        );
+        if let Err(error) = self
+            .dead_letter_store
+            .save(trigger.trigger_id, &dead_letter)
+            .await
+        {
+            error!(?error, id, "failed to persist dead letter");
+        }
+        self.sys_event_store.record(dead_letter);
+        id
     }
 
-    fn as_any(&self) -> &dyn Any {
-        self
+    /// Record a catalog change that could not be queued for a catalog trigger, because the
+    /// trigger's queue was full, as a dead letter so that it can be replayed
+    async fn record_undelivered_catalog_event(&self, trigger_name: &str, event: Arc<CatalogEvent>) {
+        let Some(trigger) =
+            self.catalog
+                .db_schema_by_id(&event.database_id)
+                .and_then(|db_schema| {
+                    db_schema
+                        .processing_engine_triggers
+                        .get_by_name(trigger_name)
+                })
+        else {
+            return;
+        };
+        let id = self
+            .record_dead_letter(
+                &trigger,
+                0,
+                "the trigger's queue was full".to_string(),
+                DeadLetterEvent::Catalog(event),
+            )
+            .await;
+        warn!(
+            db = %trigger.database_name,
+            trigger = %trigger.trigger_name,
+            id,
+            "catalog trigger queue is full, recorded the catalog event as a dead letter"
+        );
     }
-}
 
-#[allow(dead_code)]
-pub(crate) enum WalEvent {
-    WriteWalContents(Arc<WalContents>),
+    /// Send WAL contents to the WAL triggers, recording the writes that a trigger's full queue
+    /// could not take as dead letters, so that they can be replayed
+    async fn deliver_wal_contents(&self, wal_contents: Arc<WalContents>) {
+        let undelivered = self
+            .plugin_event_tx
+            .read()
+            .await
+            .send_wal_contents(&wal_contents);
+        for (db_name, trigger_name) in undelivered {
+            let Some(trigger) = self.catalog.db_schema(&db_name).and_then(|db_schema| {
+                db_schema
+                    .processing_engine_triggers
+                    .get_by_name(&trigger_name)
+            }) else {
+                continue;
+            };
+            let mut ids = vec![];
+            for op in &wal_contents.ops {
+                let WalOp::Write(write_batch) = op else {
+                    continue;
+                };
+                if write_batch.database_name.as_ref() != db_name {
+                    continue;
+                }
+                ids.push(
+                    self.record_dead_letter(
+                        &trigger,
+                        0,
+                        "the trigger's queue was full".to_string(),
+                        DeadLetterEvent::WriteBatch(Arc::new(write_batch.clone())),
+                    )
+                    .await,
+                );
+            }
+            warn!(
+                db = %trigger.database_name,
+                trigger = %trigger.trigger_name,
+                ?ids,
+                "wal trigger queue is full, recorded the wal contents as dead letters"
+            );
+        }
+    }
+
+    /// Replay dead letters through the trigger that failed on them, returning how many were
+    /// sent to the trigger
+    ///
+    /// If a `dead_letter_id` is given, only that dead letter is replayed, even if it was replayed
+    /// before. Otherwise, all of the trigger's dead letters that have not yet been replayed are.
+    /// A dead letter is marked as replayed once the trigger has run successfully against it.
+    pub async fn replay_dead_letters(
+        &self,
+        db_name: &str,
+        trigger_name: &str,
+        dead_letter_id: Option<u64>,
+    ) -> Result<usize, ProcessingEngineError> {
+        let db_schema = self
+            .catalog
+            .db_schema(db_name)
+            .ok_or_else(|| ProcessingEngineError::DatabaseNotFound(db_name.to_string()))?;
+        let trigger = db_schema
+            .processing_engine_triggers
+            .get_by_name(trigger_name)
+            .ok_or_else(|| CatalogError::ProcessingEngineTriggerNotFound {
+                database_name: db_name.to_string(),
+                trigger_name: trigger_name.to_string(),
+            })?;
+
+        // dead letters that are still in the system table are replayed from there, so that
+        // marking them as replayed shows up in it
+        let recorded: HashMap<u64, ProcessingEngineDeadLetter> = self
+            .sys_event_store
+            .as_vec::<ProcessingEngineDeadLetter>()
+            .into_iter()
+            .map(|event| (event.data.id, event.data))
+            .collect();
+        let dead_letters = self
+            .dead_letter_store
+            .load(db_name, trigger.trigger_id)
+            .await?
+            .into_iter()
+            .map(|dead_letter| match recorded.get(&dead_letter.id) {
+                Some(recorded) => recorded.clone(),
+                None => dead_letter,
+            })
+            .filter(|dead_letter| match dead_letter_id {
+                Some(id) => dead_letter.id == id,
+                None => !dead_letter.is_replayed(),
+            })
+            .collect::<Vec<_>>();
+        if let Some(id) = dead_letter_id {
+            if dead_letters.is_empty() {
+                return Err(ProcessingEngineError::DeadLetterNotFound(id));
+            }
+        }
+
+        let count = dead_letters.len();
+        let plugin_channels = self.plugin_event_tx.read().await;
+        for dead_letter in dead_letters {
+            let replay = DeadLetterReplay {
+                trigger_id: trigger.trigger_id,
+                dead_letter,
+                failed: Default::default(),
+            };
+            plugin_channels
+                .send_replay(db_name, trigger_name, &trigger.trigger, replay)
+                .await?;
+        }
+        Ok(count)
+    }
+
+    /// Called by a trigger once it has run against a replayed dead letter, which marks the dead
+    /// letter as replayed if the run succeeded
+    pub(crate) async fn finish_replay(&self, replay: &DeadLetterReplay, succeeded: bool) {
+        if !succeeded || replay.failed.load(Ordering::Acquire) {
+            return;
+        }
+        replay.dead_letter.mark_replayed();
+        if let Err(error) = self
+            .dead_letter_store
+            .save(replay.trigger_id, &replay.dead_letter)
+            .await
+        {
+            error!(
+                ?error,
+                id = replay.id(),
+                "failed to persist replayed dead letter"
+            );
+        }
+    }
+
+    /// Run a WAL trigger's plugin over the data written between `start_time` and `end_time`,
+    /// returning the id of the backfill
+    ///
+    /// The backfill runs in the background, reading the data back one `window` of time at a
+    /// time. Its progress is recorded in the `system.processing_engine_backfills` table.
+    pub async fn backfill_trigger(
+        self: Arc<Self>,
+        db_name: &str,
+        trigger_name: &str,
+        start_time: Time,
+        end_time: Time,
+        window: Duration,
+    ) -> Result<u64, ProcessingEngineError> {
+        let db_schema = self
+            .catalog
+            .db_schema(db_name)
+            .ok_or_else(|| ProcessingEngineError::DatabaseNotFound(db_name.to_string()))?;
+        let trigger = db_schema
+            .processing_engine_triggers
+            .get_by_name(trigger_name)
+            .ok_or_else(|| CatalogError::ProcessingEngineTriggerNotFound {
+                database_name: db_name.to_string(),
+                trigger_name: trigger_name.to_string(),
+            })?;
+        let table_names = match &trigger.trigger {
+            TriggerSpecificationDefinition::SingleTableWalWrite { table_name, .. } => {
+                vec![Arc::from(table_name.as_str())]
+            }
+            TriggerSpecificationDefinition::AllTablesWalWrite => db_schema
+                .tables()
+                .filter(|table_def| !table_def.deleted)
+                .map(|table_def| Arc::clone(&table_def.table_name))
+                .collect(),
+            _ => {
+                return Err(ProcessingEngineError::BackfillNotSupported {
+                    trigger_name: trigger_name.to_string(),
+                });
+            }
+        };
+        if trigger.disabled {
+            return Err(ProcessingEngineError::TriggerNotRunning {
+                database: db_name.to_string(),
+                trigger_name: trigger_name.to_string(),
+            });
+        }
+        if start_time >= end_time || window.is_zero() {
+            return Err(ProcessingEngineError::InvalidBackfillRange);
+        }
+
+        let id = self.next_backfill_id.fetch_add(1, Ordering::Relaxed);
+        let backfill = ProcessingEngineBackfill::new(
+            id,
+            self.time_provider.now(),
+            Arc::clone(&trigger.database_name),
+            Arc::clone(&trigger.trigger_name),
+            start_time,
+            end_time,
+        );
+        self.sys_event_store.record(backfill.clone());
+        tokio::spawn(backfill::run_backfill(
+            Arc::clone(&self),
+            backfill,
+            table_names,
+            window,
+        ));
+        Ok(id)
+    }
+
+    pub fn get_environment_manager(&self) -> Arc<dyn PythonEnvironmentManager> {
+        Arc::clone(&self.environment_manager.package_manager)
+    }
+
+    /// Encrypt the value of a secret with the server's secrets key, so that it can be stored in
+    /// the catalog
+    pub fn encrypt_secret(
+        &self,
+        db_name: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<Vec<u8>, ProcessingEngineError> {
+        if !is_valid_secret_name(name) {
+            return Err(ProcessingEngineError::InvalidSecretName(name.to_string()));
+        }
+        let key = self
+            .environment_manager
+            .secrets_key
+            .as_ref()
+            .ok_or(SecretsError::NoKey)?;
+        Ok(key.encrypt(db_name, name, value)?)
+    }
+
+    /// The secrets that the plugins of a database's triggers can read
+    pub(crate) fn database_secrets(&self, db_name: &str) -> Arc<dyn SecretReader> {
+        Arc::new(DatabaseSecrets::new(
+            Arc::clone(&self.catalog),
+            db_name,
+            self.environment_manager.secrets_key.clone(),
+        ))
+    }
+
+    /// The virtual environment of its own for a plugin package that declares requirements, which
+    /// starts being built the first time that it is needed
+    pub(crate) fn plugin_environment(&self, plugin_code: &PluginCode) -> Option<Arc<PluginVenv>> {
+        let plugin_dir = self.environment_manager.plugin_dir.as_ref()?;
+        let PluginCode::Python(python_code) = plugin_code else {
+            return None;
+        };
+        let PythonPluginCode::Local(plugin) = &**python_code else {
+            return None;
+        };
+        if !plugin.is_package {
+            return None;
+        }
+        // a package that can't be loaded fails when its code is read
+        let package = PluginPackage::load(&plugin.plugin_path).ok()?;
+        if package.requirements().is_empty() {
+            return None;
+        }
+        Some(self.plugin_environments.environment(
+            plugin_dir,
+            &package.name(),
+            package.requirements(),
+        ))
+    }
+
+    /// The last and distinct value caches that the plugins of a database's triggers can read
+    pub(crate) fn database_value_caches(&self, db_name: &str) -> Arc<dyn ValueCacheReader> {
+        Arc::new(DatabaseValueCaches::new(
+            Arc::clone(&self.write_buffer),
+            db_name,
+        ))
+    }
+}
+
+fn is_valid_secret_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+#[async_trait::async_trait]
+impl WalFileNotifier for ProcessingEngineManagerImpl {
+    async fn notify(&self, write: Arc<WalContents>) {
+        self.deliver_wal_contents(write).await;
+    }
+
+    async fn notify_and_snapshot(
+        &self,
+        write: Arc<WalContents>,
+        snapshot_details: SnapshotDetails,
+    ) -> Receiver<SnapshotDetails> {
+        self.deliver_wal_contents(write).await;
+
+        // configure a reciever that we immediately close
+        let (tx, rx) = oneshot::channel();
+        tx.send(snapshot_details).ok();
+        rx
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[allow(dead_code)]
+pub(crate) enum WalEvent {
+    WriteWalContents(Arc<WalContents>),
+    /// Data read for a backfill, acknowledged once the plugin has run against it
+    Backfill(Arc<WalContents>, oneshot::Sender<()>),
+    /// The write batch of a dead letter that the trigger previously failed on
+    Replay(Arc<WalContents>, DeadLetterReplay),
     Shutdown(oneshot::Sender<()>),
 }
 
 #[allow(dead_code)]
 pub(crate) enum ScheduleEvent {
+    /// Run the plugin for a trigger time that it previously failed on
+    Replay(DateTime<Utc>, DeadLetterReplay),
     Shutdown(oneshot::Sender<()>),
 }
 
@@ -657,13 +1581,48 @@ pub(crate) enum RequestEvent {
     Shutdown(oneshot::Sender<()>),
 }
 
+pub(crate) enum PersistedEvent {
+    Snapshot(Arc<PersistedSnapshotVersion>),
+    /// Run the plugin for persisted files that it previously failed on
+    Replay(Arc<[PersistedParquetFile]>, DeadLetterReplay),
+    Shutdown(oneshot::Sender<()>),
+}
+
+pub(crate) enum CatalogTriggerEvent {
+    Change(Arc<CatalogEvent>),
+    /// Run the plugin for a catalog change that it previously failed on
+    Replay(Arc<CatalogEvent>, DeadLetterReplay),
+    Shutdown(oneshot::Sender<()>),
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub(crate) struct Request {
-    pub query_params: HashMap<String, String>,
-    pub headers: HashMap<String, String>,
-    pub body: Bytes,
+    pub request: PluginRequest,
     pub response_tx: oneshot::Sender<Response<Body>>,
+    /// Set if the request is a dead letter that the trigger previously failed on
+    pub replay: Option<DeadLetterReplay>,
+}
+
+/// A dead letter that is being replayed through the trigger that failed on it. The dead letter
+/// is marked as replayed once the trigger has run successfully against it.
+#[derive(Debug, Clone)]
+pub(crate) struct DeadLetterReplay {
+    trigger_id: TriggerId,
+    dead_letter: ProcessingEngineDeadLetter,
+    /// Set if the trigger fails on the dead letter again
+    failed: Arc<AtomicBool>,
+}
+
+impl DeadLetterReplay {
+    pub(crate) fn id(&self) -> u64 {
+        self.dead_letter.id
+    }
+
+    /// Record that the trigger failed on the dead letter again, so it is not marked as replayed
+    pub(crate) fn fail(&self) {
+        self.failed.store(true, Ordering::Release);
+    }
 }
 
 fn background_catalog_update(
@@ -672,10 +1631,20 @@ fn background_catalog_update(
 ) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
         while let Some(catalog_update) = subscription.recv().await {
+            // the changes for catalog triggers are only sent once the update has been
+            // acknowledged, so that catalog plugins can't hold up the catalog
+            let mut catalog_events = vec![];
             for batch in catalog_update
                 .batches()
                 .filter_map(CatalogBatch::as_database)
             {
+                if batch
+                    .ops
+                    .iter()
+                    .any(|op| CatalogEventType::from_op(op).is_some())
+                {
+                    catalog_events.push(batch.clone());
+                }
                 for op in batch.ops.iter() {
                     let processing_engine_manager = Arc::clone(&processing_engine_manager);
                     match op {
@@ -707,16 +1676,40 @@ fn background_catalog_update(
                             }
                         }
                         DatabaseCatalogOp::DeleteTrigger(DeleteTriggerLog {
+                            trigger_id,
                             trigger_name,
-                            force: true,
-                            ..
+                            force,
                         }) => {
+                            if *force {
+                                if let Err(error) = processing_engine_manager
+                                    .stop_trigger(&batch.database_name, trigger_name)
+                                    .await
+                                {
+                                    error!(?error, "failed to disable the trigger");
+                                }
+                            }
+                            // drop any rows that a batched WAL trigger accumulated but had not
+                            // yet run its plugin against
+                            if let Err(error) = PendingBatchStore::new(
+                                &processing_engine_manager.catalog,
+                                &batch.database_name,
+                                *trigger_id,
+                            )
+                            .delete()
+                            .await
+                            {
+                                error!(?error, "failed to remove the trigger's pending batch");
+                            }
                             if let Err(error) = processing_engine_manager
-                                .stop_trigger(&batch.database_name, trigger_name)
+                                .dead_letter_store
+                                .delete_trigger(&batch.database_name, *trigger_id)
                                 .await
                             {
-                                error!(?error, "failed to disable the trigger");
+                                error!(?error, "failed to remove the trigger's dead letters");
                             }
+                            processing_engine_manager
+                                .trigger_metrics
+                                .remove(&batch.database_name, trigger_name);
                         }
                         DatabaseCatalogOp::DisableTrigger(TriggerIdentifier {
                             db_name,
@@ -737,23 +1730,101 @@ fn background_catalog_update(
                     }
                 }
             }
+            drop(catalog_update);
+
+            for batch in catalog_events {
+                let undelivered = processing_engine_manager
+                    .plugin_event_tx
+                    .read()
+                    .await
+                    .send_catalog_batch(&batch);
+                for (trigger_name, event) in undelivered {
+                    processing_engine_manager
+                        .record_undelivered_catalog_event(&trigger_name, event)
+                        .await;
+                }
+            }
+        }
+    })
+}
+
+/// Checkpoint the plugin cache on every `interval`, and once more on shutdown
+fn background_cache_checkpoints(
+    processing_engine_manager: Arc<ProcessingEngineManagerImpl>,
+    interval: Duration,
+    shutdown: ShutdownToken,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // the first tick completes immediately, and there is nothing new to save yet
+        interval.tick().await;
+        loop {
+            let shutting_down = tokio::select! {
+                _ = shutdown.wait_for_shutdown() => true,
+                _ = interval.tick() => false,
+            };
+            if let Err(error) = processing_engine_manager.checkpoint_plugin_cache().await {
+                error!(?error, "failed to checkpoint the plugin cache");
+            }
+            if shutting_down {
+                shutdown.complete();
+                return;
+            }
+        }
+    })
+}
+
+/// Forward each snapshot that is persisted to the persisted triggers
+fn background_persisted_snapshots(
+    processing_engine_manager: Arc<ProcessingEngineManagerImpl>,
+    mut persisted_snapshots: broadcast::Receiver<PersistedSnapshotVersion>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let snapshot = match persisted_snapshots.recv().await {
+                Ok(snapshot) => snapshot,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    error!(
+                        missed,
+                        "persisted triggers missed snapshots by falling behind"
+                    );
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            let plugin_channels = processing_engine_manager.plugin_event_tx.read().await;
+            plugin_channels
+                .send_persisted_snapshot(Arc::new(snapshot))
+                .await;
         }
     })
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ProcessingEngineManagerImpl;
     use crate::environment::DisabledManager;
+    use crate::manager::ProcessingEngineError;
     use crate::plugins::ProcessingEngineEnvironmentManager;
+    use crate::{
+        CatalogTriggerEvent, LocalPlugin, PLUGIN_EVENT_BUFFER_SIZE, PluginChannels,
+        ProcessingEngineManagerImpl,
+    };
     use data_types::NamespaceName;
     use datafusion_util::config::register_iox_object_store;
     use influxdb3_cache::distinct_cache::DistinctCacheProvider;
     use influxdb3_cache::last_cache::LastCacheProvider;
     use influxdb3_catalog::CatalogError;
     use influxdb3_catalog::catalog::Catalog;
-    use influxdb3_catalog::log::{TriggerSettings, TriggerSpecificationDefinition};
+    use influxdb3_catalog::log::{
+        DatabaseBatch, DatabaseCatalogOp, SoftDeleteTableLog, TriggerSettings,
+        TriggerSpecificationDefinition,
+    };
+    use influxdb3_id::{DbId, TableId};
     use influxdb3_internal_api::query_executor::UnimplementedQueryExecutor;
+    use influxdb3_py_api::logging::{
+        DeadLetterEvent, ProcessingEngineBackfill, ProcessingEngineDeadLetter,
+    };
     use influxdb3_shutdown::ShutdownManager;
     use influxdb3_sys_events::SysEventStore;
     use influxdb3_wal::{Gen1Duration, WalConfig};
@@ -927,6 +1998,307 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_replay_dead_letters() -> influxdb3_write::write_buffer::Result<()> {
+        let start_time = Time::from_rfc3339("2024-11-14T11:00:00+00:00").unwrap();
+        let test_store = Arc::new(InMemory::new());
+        let wal_config = WalConfig {
+            gen1_duration: Gen1Duration::new_1m(),
+            max_write_buffer_size: 100,
+            flush_interval: Duration::from_millis(10),
+            snapshot_size: 1,
+        };
+        let (pem, file) = setup(start_time, Arc::clone(&test_store) as _, wal_config).await;
+        let file_name = file
+            .path()
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        // Create the DB by inserting a line.
+        pem.write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "cpu,warehouse=us-east,room=01a,device=10001 reading=37\n",
+                start_time,
+                false,
+                Precision::Nanosecond,
+                false,
+            )
+            .await?;
+
+        let file_name = pem.validate_plugin_filename(&file_name).await.unwrap();
+        // Create a disabled trigger, so that nothing is running to replay the dead letters to
+        pem.catalog
+            .create_processing_engine_trigger(
+                "foo",
+                "test_trigger",
+                Arc::clone(&pem.node_id),
+                file_name,
+                &TriggerSpecificationDefinition::Every {
+                    duration: Duration::from_secs(60),
+                }
+                .string_rep(),
+                TriggerSettings::default(),
+                &None,
+                true,
+            )
+            .await
+            .unwrap();
+        let trigger = pem
+            .catalog
+            .db_schema("foo")
+            .unwrap()
+            .processing_engine_triggers
+            .get_by_name("test_trigger")
+            .unwrap();
+
+        let id = pem
+            .record_dead_letter(
+                &trigger,
+                3,
+                "boom".to_string(),
+                DeadLetterEvent::Schedule(start_time.date_time()),
+            )
+            .await;
+        assert_eq!(0, id);
+
+        let Err(ProcessingEngineError::DeadLetterNotFound(1)) = pem
+            .replay_dead_letters("foo", "test_trigger", Some(1))
+            .await
+        else {
+            panic!("should receive not found error for a non existent dead letter");
+        };
+        let Err(ProcessingEngineError::TriggerNotRunning { .. }) =
+            pem.replay_dead_letters("foo", "test_trigger", None).await
+        else {
+            panic!("should not be able to replay to a disabled trigger");
+        };
+
+        // the dead letter was not replayed, so it is still pending:
+        let dead_letters = pem.sys_event_store.as_vec::<ProcessingEngineDeadLetter>();
+        assert_eq!(1, dead_letters.len());
+        assert!(!dead_letters[0].data.is_replayed());
+        assert_eq!(3, dead_letters[0].data.attempts);
+
+        // the dead letter outlives a restart, and ids carry on from it:
+        let (restarted, _file) = setup(start_time, test_store, wal_config).await;
+        let dead_letters = restarted
+            .sys_event_store
+            .as_vec::<ProcessingEngineDeadLetter>();
+        assert_eq!(1, dead_letters.len());
+        assert_eq!(0, dead_letters[0].data.id);
+        assert!(!dead_letters[0].data.is_replayed());
+        assert_eq!("boom", dead_letters[0].data.error);
+        let id = restarted
+            .record_dead_letter(
+                &trigger,
+                1,
+                "bang".to_string(),
+                DeadLetterEvent::Schedule(start_time.date_time()),
+            )
+            .await;
+        assert_eq!(1, id);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_send_catalog_batch_does_not_wait_on_a_full_queue() {
+        let mut channels = PluginChannels::default();
+        let mut receiver = channels.add_catalog_trigger("foo".to_string(), "trigger".to_string());
+        let ops = (0..PLUGIN_EVENT_BUFFER_SIZE + 2)
+            .map(|i| {
+                DatabaseCatalogOp::SoftDeleteTable(SoftDeleteTableLog {
+                    database_id: DbId::new(0),
+                    database_name: "foo".into(),
+                    table_id: TableId::new(i as u32),
+                    table_name: format!("table_{i}").into(),
+                    deletion_time: 0,
+                })
+            })
+            .collect();
+        let batch = DatabaseBatch {
+            time_ns: 0,
+            database_id: DbId::new(0),
+            database_name: "foo".into(),
+            ops,
+        };
+
+        // the changes that don't fit in the queue are handed back, rather than waited on:
+        let undelivered = channels.send_catalog_batch(&batch);
+        assert_eq!(2, undelivered.len());
+        assert!(undelivered.iter().all(|(trigger, _)| trigger == "trigger"));
+        let mut delivered = 0;
+        while let Ok(CatalogTriggerEvent::Change(_)) = receiver.try_recv() {
+            delivered += 1;
+        }
+        assert_eq!(PLUGIN_EVENT_BUFFER_SIZE, delivered);
+    }
+
+    #[test]
+    fn test_send_wal_contents_does_not_wait_on_a_full_queue() {
+        let mut channels = PluginChannels::default();
+        let mut receiver = channels.add_wal_trigger("foo".to_string(), "trigger".to_string());
+        let wal_contents = Arc::new(WalContents {
+            persist_timestamp_ms: 0,
+            min_timestamp_ns: 0,
+            max_timestamp_ns: 0,
+            wal_file_number: WalFileSequenceNumber::new(0),
+            ops: vec![],
+            snapshot: None,
+        });
+
+        // a saturated queue hands the trigger back, rather than waiting for it to drain:
+        for _ in 0..PLUGIN_EVENT_BUFFER_SIZE {
+            assert!(channels.send_wal_contents(&wal_contents).is_empty());
+        }
+        assert_eq!(
+            vec![("foo".to_string(), "trigger".to_string())],
+            channels.send_wal_contents(&wal_contents)
+        );
+        let mut delivered = 0;
+        while let Ok(WalEvent::WriteWalContents(_)) = receiver.try_recv() {
+            delivered += 1;
+        }
+        assert_eq!(PLUGIN_EVENT_BUFFER_SIZE, delivered);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_trigger_validation() -> influxdb3_write::write_buffer::Result<()> {
+        let start_time = Time::from_rfc3339("2024-11-14T11:00:00+00:00").unwrap();
+        let test_store = Arc::new(InMemory::new());
+        let wal_config = WalConfig {
+            gen1_duration: Gen1Duration::new_1m(),
+            max_write_buffer_size: 100,
+            flush_interval: Duration::from_millis(10),
+            snapshot_size: 1,
+        };
+        let (pem, file) = setup(start_time, test_store, wal_config).await;
+        let file_name = file
+            .path()
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        // Create the DB by inserting a line.
+        pem.write_buffer
+            .write_lp(
+                NamespaceName::new("foo").unwrap(),
+                "cpu,warehouse=us-east,room=01a,device=10001 reading=37\n",
+                start_time,
+                false,
+                Precision::Nanosecond,
+                false,
+            )
+            .await?;
+
+        let file_name = pem.validate_plugin_filename(&file_name).await.unwrap();
+        for (trigger_name, spec) in [
+            (
+                "schedule_trigger",
+                TriggerSpecificationDefinition::Every {
+                    duration: Duration::from_secs(60),
+                },
+            ),
+            (
+                "table_trigger",
+                TriggerSpecificationDefinition::SingleTableWalWrite {
+                    table_name: "cpu".to_string(),
+                    predicate: None,
+                },
+            ),
+        ] {
+            pem.catalog
+                .create_processing_engine_trigger(
+                    "foo",
+                    trigger_name,
+                    Arc::clone(&pem.node_id),
+                    file_name.clone(),
+                    &spec.string_rep(),
+                    TriggerSettings::default(),
+                    &None,
+                    true,
+                )
+                .await
+                .unwrap();
+        }
+
+        let end_time = start_time + Duration::from_secs(3600);
+        let window = Duration::from_secs(60);
+        let Err(ProcessingEngineError::BackfillNotSupported { .. }) = Arc::clone(&pem)
+            .backfill_trigger("foo", "schedule_trigger", start_time, end_time, window)
+            .await
+        else {
+            panic!("should not be able to backfill a schedule trigger");
+        };
+        let Err(ProcessingEngineError::TriggerNotRunning { .. }) = Arc::clone(&pem)
+            .backfill_trigger("foo", "table_trigger", start_time, end_time, window)
+            .await
+        else {
+            panic!("should not be able to backfill a disabled trigger");
+        };
+
+        pem.catalog
+            .enable_processing_engine_trigger("foo", "table_trigger")
+            .await
+            .unwrap();
+        let Err(ProcessingEngineError::InvalidBackfillRange) = Arc::clone(&pem)
+            .backfill_trigger("foo", "table_trigger", end_time, start_time, window)
+            .await
+        else {
+            panic!("should not be able to backfill with the end before the start");
+        };
+        let Err(ProcessingEngineError::InvalidBackfillRange) = Arc::clone(&pem)
+            .backfill_trigger("foo", "table_trigger", start_time, end_time, Duration::ZERO)
+            .await
+        else {
+            panic!("should not be able to backfill with an empty window");
+        };
+
+        // none of the backfills were started:
+        assert!(
+            pem.sys_event_store
+                .as_vec::<ProcessingEngineBackfill>()
+                .is_empty()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_plugin_package_reloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let package_dir = dir.path().join("pkg");
+        std::fs::create_dir_all(package_dir.join("helpers")).unwrap();
+        std::fs::write(package_dir.join("__init__.py"), "").unwrap();
+        std::fs::write(package_dir.join("helpers/__init__.py"), "x = 1").unwrap();
+        std::fs::write(
+            package_dir.join("manifest.json"),
+            r#"{"entry_points": {"wal": "writes:process"}}"#,
+        )
+        .unwrap();
+
+        let plugin = LocalPlugin::new(package_dir.clone()).unwrap();
+        let code = plugin.read_if_modified();
+        assert!(code.contains("__influxdb3_load_package"));
+        assert_eq!(code, plugin.read_if_modified());
+
+        // a change to any of the package's files reloads it
+        let helper = std::fs::File::options()
+            .write(true)
+            .open(package_dir.join("helpers/__init__.py"))
+            .unwrap();
+        helper
+            .set_modified(std::time::SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        assert_ne!(code, plugin.read_if_modified());
+    }
+
     #[tokio::test]
     async fn test_enable_nonexistent_trigger() -> influxdb3_write::write_buffer::Result<()> {
         let start_time = Time::from_rfc3339("2024-11-14T11:00:00+00:00").unwrap();
@@ -1028,6 +2400,8 @@ def process_writes(influxdb3_local, table_batches, args=None):
             plugin_dir: Some(file.path().parent().unwrap().to_path_buf()),
             virtual_env_location: None,
             package_manager: Arc::new(DisabledManager),
+            cache_checkpoint_interval: None,
+            secrets_key: None,
         };
 
         let sys_event_store = Arc::new(SysEventStore::new(Arc::clone(&time_provider)));
@@ -1041,6 +2415,8 @@ def process_writes(influxdb3_local, table_batches, args=None):
                 qe,
                 time_provider,
                 sys_event_store,
+                metric_registry,
+                shutdown.register(),
             )
             .await,
             file,
@@ -0,0 +1,172 @@
+//! Synchronous writes by plugins, through `influxdb3_local.write_sync()`
+//!
+//! Unlike `write()` and `write_to_db()`, whose lines are written after the plugin returns, these
+//! go to the write buffer straight away, so the plugin learns which lines were rejected and can
+//! query what it wrote.
+use std::sync::Arc;
+
+use anyhow::Context;
+use data_types::NamespaceName;
+use futures_util::FutureExt;
+use futures_util::future::BoxFuture;
+use hashbrown::HashMap;
+use influxdb3_catalog::catalog::Catalog;
+use influxdb3_py_api::system_py::{LineWriter, LinesWritten, RejectedLine, SyncWriteOptions};
+use influxdb3_wal::Gen1Duration;
+use influxdb3_write::WriteBuffer;
+use influxdb3_write::write_buffer::validator::WriteValidator;
+use iox_time::{Time, TimeProvider};
+use parking_lot::Mutex;
+
+use crate::trigger_metrics::TriggerMetricsRecorder;
+
+/// Writes lines for the plugin of a trigger, to the trigger's database unless the plugin names
+/// another one
+#[derive(Debug)]
+pub(crate) struct TriggerLineWriter {
+    write_buffer: Arc<dyn WriteBuffer>,
+    time_provider: Arc<dyn TimeProvider>,
+    db_name: String,
+    metrics: TriggerMetricsRecorder,
+}
+
+impl TriggerLineWriter {
+    pub(crate) fn new(
+        write_buffer: Arc<dyn WriteBuffer>,
+        time_provider: Arc<dyn TimeProvider>,
+        db_name: impl Into<String>,
+        metrics: TriggerMetricsRecorder,
+    ) -> Self {
+        Self {
+            write_buffer,
+            time_provider,
+            db_name: db_name.into(),
+            metrics,
+        }
+    }
+}
+
+impl LineWriter for TriggerLineWriter {
+    fn write_lp(
+        &self,
+        db_name: Option<String>,
+        lines: String,
+        options: SyncWriteOptions,
+    ) -> BoxFuture<'static, Result<LinesWritten, anyhow::Error>> {
+        let write_buffer = Arc::clone(&self.write_buffer);
+        let ingest_time = self.time_provider.now();
+        let db_name = db_name.unwrap_or_else(|| self.db_name.clone());
+        let metrics = self.metrics.clone();
+        async move {
+            let namespace_name = NamespaceName::new(db_name.clone())
+                .with_context(|| format!("invalid database name: {db_name}"))?;
+            let result = write_buffer
+                .write_lp(
+                    namespace_name,
+                    &lines,
+                    ingest_time,
+                    options.accept_partial,
+                    options.precision,
+                    options.no_sync,
+                )
+                .await?;
+            metrics.record_lines_written(result.line_count as u64);
+            Ok(LinesWritten {
+                accepted: result.line_count,
+                rejected: result
+                    .invalid_lines
+                    .into_iter()
+                    .map(|line| RejectedLine {
+                        line_number: line.line_number,
+                        line: line.original_line,
+                        error: line.error_message,
+                    })
+                    .collect(),
+            })
+        }
+        .boxed()
+    }
+}
+
+/// Checks the lines that a plugin writes synchronously while it is being tested, without writing
+/// them, and keeps the lines that would have been written so they can be reported along with the
+/// test's other writes
+#[derive(Debug)]
+pub(crate) struct ValidatingLineWriter {
+    catalog: Arc<Catalog>,
+    now_time: Time,
+    db_name: String,
+    writes: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl ValidatingLineWriter {
+    pub(crate) fn new(catalog: Arc<Catalog>, now_time: Time, db_name: impl Into<String>) -> Self {
+        Self {
+            catalog,
+            now_time,
+            db_name: db_name.into(),
+            writes: Default::default(),
+        }
+    }
+
+    /// The lines that would have been written, by database
+    pub(crate) fn take_writes(&self) -> HashMap<String, Vec<String>> {
+        std::mem::take(&mut *self.writes.lock())
+    }
+
+    fn validate(
+        &self,
+        db_name: Option<String>,
+        lines: &str,
+        options: SyncWriteOptions,
+    ) -> Result<LinesWritten, anyhow::Error> {
+        let db_name = db_name.unwrap_or_else(|| self.db_name.clone());
+        let namespace_name = NamespaceName::new(db_name.clone())
+            .with_context(|| format!("invalid database name: {db_name}"))?;
+        let validated = WriteValidator::initialize(namespace_name, Arc::clone(&self.catalog))?
+            .v1_parse_lines_and_catalog_updates(
+                lines,
+                options.accept_partial,
+                self.now_time,
+                options.precision,
+            )?
+            .ignore_catalog_changes_and_convert_lines_to_buffer(Gen1Duration::new_1m());
+        let rejected: Vec<_> = validated
+            .errors
+            .into_iter()
+            .map(|line| RejectedLine {
+                line_number: line.line_number,
+                line: line.original_line,
+                error: line.error_message,
+            })
+            .collect();
+        let accepted: Vec<_> = lines
+            .lines()
+            .enumerate()
+            .filter(|(i, _)| !rejected.iter().any(|line| line.line_number == i + 1))
+            .map(|(_, line)| line.to_string())
+            .collect();
+        let accepted_count = accepted.len();
+        self.writes
+            .lock()
+            .entry(db_name)
+            .or_default()
+            .extend(accepted);
+        Ok(LinesWritten {
+            accepted: accepted_count,
+            rejected,
+        })
+    }
+}
+
+impl LineWriter for ValidatingLineWriter {
+    fn write_lp(
+        &self,
+        db_name: Option<String>,
+        lines: String,
+        options: SyncWriteOptions,
+    ) -> BoxFuture<'static, Result<LinesWritten, anyhow::Error>> {
+        let result = self.validate(db_name, &lines, options);
+        async move { result }.boxed()
+    }
+}
@@ -1,4 +1,5 @@
 use crate::environment::PluginEnvironmentError;
+use crate::secrets::SecretsError;
 use influxdb3_catalog::CatalogError;
 use std::fmt::Debug;
 use thiserror::Error;
@@ -35,6 +36,33 @@ pub enum ProcessingEngineError {
     #[error("request handler for trigger down")]
     RequestHandlerDown,
 
+    #[error("trigger {trigger_name} in database {database} is not running")]
+    TriggerNotRunning {
+        database: String,
+        trigger_name: String,
+    },
+
+    #[error("dead letter not found: {0}")]
+    DeadLetterNotFound(u64),
+
+    #[error("dead letter {id} cannot be replayed through trigger {trigger_name}")]
+    DeadLetterTriggerMismatch { id: u64, trigger_name: String },
+
+    #[error("dead letter store error: {0}")]
+    DeadLetterStore(#[from] crate::dead_letters::DeadLetterStoreError),
+
+    #[error("trigger {trigger_name} cannot be backfilled, only WAL triggers can")]
+    BackfillNotSupported { trigger_name: String },
+
+    #[error("the backfill start time must be before its end time, and its window must be non-zero")]
+    InvalidBackfillRange,
+
     #[error("error installing python packages: {0}")]
     PythonPackageError(#[from] PluginEnvironmentError),
+
+    #[error("invalid secret name {0:?}, names may only contain letters, digits, '_', '-' and '.'")]
+    InvalidSecretName(String),
+
+    #[error("secrets error: {0}")]
+    Secrets(#[from] SecretsError),
 }
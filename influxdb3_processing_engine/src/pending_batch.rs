@@ -0,0 +1,243 @@
+//! Rows accumulated by WAL triggers that have a [`BatchWindow`] set
+//!
+//! The rows are persisted to object store as they are accumulated, so that a server restart
+//! in the middle of a window does not lose the rows that were already taken out of the WAL.
+//!
+//! [`BatchWindow`]: influxdb3_catalog::log::BatchWindow
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures_util::TryStreamExt;
+use influxdb3_catalog::catalog::Catalog;
+use influxdb3_id::TriggerId;
+use influxdb3_wal::WriteBatch;
+use object_store::{ObjectStore, path::Path as ObjPath};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PendingBatchError {
+    #[error("object store error: {0}")]
+    ObjectStore(#[from] object_store::Error),
+
+    #[error("failed to serialize pending batch: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+type Result<T, E = PendingBatchError> = std::result::Result<T, E>;
+
+/// Rows that a WAL trigger has accumulated, but not yet run its plugin against
+#[derive(Debug, Clone)]
+pub(crate) struct PendingBatch {
+    /// When the first row of the batch was accumulated, in nanoseconds since the epoch
+    pub(crate) started_at_ns: i64,
+    pub(crate) write_batch: WriteBatch,
+    /// The number of segments the batch has been persisted in
+    segments: u64,
+}
+
+impl PendingBatch {
+    /// An empty batch for the database that `first_rows` were written to
+    pub(crate) fn new(started_at_ns: i64, first_rows: &WriteBatch) -> Self {
+        Self {
+            started_at_ns,
+            write_batch: WriteBatch {
+                catalog_sequence: first_rows.catalog_sequence,
+                database_id: first_rows.database_id,
+                database_name: Arc::clone(&first_rows.database_name),
+                table_chunks: Default::default(),
+                min_time_ns: i64::MAX,
+                max_time_ns: i64::MIN,
+            },
+            segments: 0,
+        }
+    }
+
+    pub(crate) fn add(&mut self, rows: WriteBatch) {
+        self.write_batch.catalog_sequence = rows.catalog_sequence;
+        self.write_batch
+            .add_write_batch(rows.table_chunks, rows.min_time_ns, rows.max_time_ns);
+    }
+
+    pub(crate) fn row_count(&self) -> usize {
+        self.write_batch
+            .table_chunks
+            .values()
+            .map(|chunks| chunks.row_count())
+            .sum()
+    }
+}
+
+/// The rows added to a [`PendingBatch`] from one WAL flush, as they are kept in object store
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingSegment<W> {
+    started_at_ns: i64,
+    write_batch: W,
+}
+
+/// Where a trigger's [`PendingBatch`] is kept in object store
+///
+/// Each WAL flush's rows are written as a segment of their own, so that accumulating a batch
+/// doesn't rewrite the rows that were already persisted.
+#[derive(Debug, Clone)]
+pub(crate) struct PendingBatchStore {
+    object_store: Arc<dyn ObjectStore>,
+    prefix: ObjPath,
+}
+
+impl PendingBatchStore {
+    pub(crate) fn new(catalog: &Catalog, database_name: &str, trigger_id: TriggerId) -> Self {
+        let prefix = catalog.object_store_prefix();
+        Self {
+            object_store: catalog.object_store(),
+            prefix: ObjPath::from(format!(
+                "{prefix}/processing_engine/pending_batches/{database_name}/{trigger_id}"
+            )),
+        }
+    }
+
+    fn segment_path(&self, segment: u64) -> ObjPath {
+        ObjPath::from(format!("{}/{segment}.json", self.prefix))
+    }
+
+    /// The segments of the pending batch, in the order they were written
+    async fn segments(&self) -> Result<Vec<u64>> {
+        let mut segments: Vec<u64> = self
+            .object_store
+            .list(Some(&self.prefix))
+            .try_filter_map(|meta| async move {
+                Ok(meta
+                    .location
+                    .filename()
+                    .and_then(|f| f.strip_suffix(".json"))
+                    .and_then(|f| f.parse().ok()))
+            })
+            .try_collect()
+            .await?;
+        segments.sort_unstable();
+        Ok(segments)
+    }
+
+    /// Load the pending batch left behind by a previous run of the trigger, if there is one
+    pub(crate) async fn load(&self) -> Result<Option<PendingBatch>> {
+        let mut pending: Option<PendingBatch> = None;
+        for segment in self.segments().await? {
+            let bytes = match self.object_store.get(&self.segment_path(segment)).await {
+                Ok(result) => result.bytes().await?,
+                Err(object_store::Error::NotFound { .. }) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let PendingSegment {
+                started_at_ns,
+                write_batch,
+            } = serde_json::from_slice::<PendingSegment<WriteBatch>>(&bytes)?;
+            let pending =
+                pending.get_or_insert_with(|| PendingBatch::new(started_at_ns, &write_batch));
+            pending.add(write_batch);
+            pending.segments = segment + 1;
+        }
+        Ok(pending)
+    }
+
+    /// Persist `rows` as the next segment of `batch`, before they are added to it
+    pub(crate) async fn append(&self, batch: &mut PendingBatch, rows: &WriteBatch) -> Result<()> {
+        let segment = batch.segments;
+        batch.segments += 1;
+        let bytes = Bytes::from(serde_json::to_vec(&PendingSegment {
+            started_at_ns: batch.started_at_ns,
+            write_batch: rows,
+        })?);
+        self.object_store
+            .put(&self.segment_path(segment), bytes.into())
+            .await?;
+        Ok(())
+    }
+
+    /// Remove the pending batch, once the plugin has been run against it
+    pub(crate) async fn delete(&self) -> Result<()> {
+        for segment in self.segments().await? {
+            match self.object_store.delete(&self.segment_path(segment)).await {
+                Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use influxdb3_catalog::catalog::Catalog;
+    use influxdb3_id::{ColumnId, DbId, TableId, TriggerId};
+    use influxdb3_wal::{Field, FieldData, Row, TableChunks, WriteBatch};
+    use iox_time::{MockProvider, Time};
+    use object_store::memory::InMemory;
+
+    use super::{PendingBatch, PendingBatchStore};
+
+    #[tokio::test]
+    async fn append_load_and_delete() {
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let catalog = Catalog::new(
+            "test-node",
+            Arc::new(InMemory::new()),
+            time_provider,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        let store = PendingBatchStore::new(&catalog, "foo", TriggerId::new(0));
+        assert!(store.load().await.unwrap().is_none());
+
+        let rows = |catalog_sequence: u64, times: [i64; 2]| {
+            let mut table_chunks = TableChunks::default();
+            for time in times {
+                table_chunks.push_row(
+                    0,
+                    Row {
+                        time,
+                        fields: vec![Field::new(ColumnId::new(0), FieldData::Integer(time))],
+                    },
+                );
+            }
+            WriteBatch::new(
+                catalog_sequence,
+                DbId::new(0),
+                "foo".into(),
+                [(TableId::new(0), table_chunks)].into_iter().collect(),
+            )
+        };
+        let first = rows(1, [10, 20]);
+        let second = rows(2, [30, 40]);
+        let mut batch = PendingBatch::new(5, &first);
+        for rows in [first, second] {
+            store.append(&mut batch, &rows).await.unwrap();
+            batch.add(rows);
+        }
+
+        let mut loaded = store.load().await.unwrap().unwrap();
+        assert_eq!(5, loaded.started_at_ns);
+        assert_eq!(4, loaded.row_count());
+        assert_eq!(batch.write_batch, loaded.write_batch);
+        assert_eq!(2, loaded.write_batch.catalog_sequence);
+        assert_eq!(
+            (10, 40),
+            (
+                loaded.write_batch.min_time_ns,
+                loaded.write_batch.max_time_ns
+            )
+        );
+
+        // a segment appended after loading the batch is added to it:
+        let third = rows(3, [50, 60]);
+        store.append(&mut loaded, &third).await.unwrap();
+        assert_eq!(6, store.load().await.unwrap().unwrap().row_count());
+
+        store.delete().await.unwrap();
+        assert!(store.load().await.unwrap().is_none());
+        // deleting again is not an error:
+        store.delete().await.unwrap();
+    }
+}
@@ -0,0 +1,302 @@
+//! Virtual environments of their own for plugin packages that declare requirements
+//!
+//! All plugins otherwise share one virtual environment, so two plugins that need different
+//! versions of a package can't both run. A [plugin package][crate::plugin_package] can instead
+//! list `requirements` in its manifest, and each distinct set of requirements gets a virtual
+//! environment in `<plugin-dir>/.venvs`, shared by the triggers that use the package.
+//!
+//! An environment is built in the background when the first trigger that needs it is started,
+//! and is reused across restarts once it has been built. Triggers wait for the build before
+//! running their plugin, and fail their runs if it failed. Builds can be followed in the
+//! `system.processing_engine_environments` table.
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use anyhow::bail;
+use hashbrown::HashMap;
+use influxdb3_py_api::logging::{EnvironmentStatus, ProcessingEnginePluginEnvironment};
+use influxdb3_py_api::system_py::PluginEnvironment;
+use influxdb3_sys_events::SysEventStore;
+use iox_time::TimeProvider;
+use observability_deps::tracing::{error, info};
+use parking_lot::Mutex;
+use tokio::sync::watch;
+
+use crate::environment::{PluginEnvironmentError, PythonEnvironmentManager};
+use crate::virtualenv::VenvError;
+
+/// The directory in the plugin directory that the environments are created in
+pub const VENVS_DIR_NAME: &str = ".venvs";
+
+/// Written into an environment once its requirements have been installed
+const BUILT_MARKER_FILE_NAME: &str = "influxdb3_requirements.txt";
+
+/// The virtual environments of plugin packages, by their path
+#[derive(Debug)]
+pub(crate) struct PluginEnvironments {
+    package_manager: Arc<dyn PythonEnvironmentManager>,
+    time_provider: Arc<dyn TimeProvider>,
+    sys_event_store: Arc<SysEventStore>,
+    environments: Mutex<HashMap<PathBuf, Arc<PluginVenv>>>,
+}
+
+impl PluginEnvironments {
+    pub(crate) fn new(
+        package_manager: Arc<dyn PythonEnvironmentManager>,
+        time_provider: Arc<dyn TimeProvider>,
+        sys_event_store: Arc<SysEventStore>,
+    ) -> Self {
+        Self {
+            package_manager,
+            time_provider,
+            sys_event_store,
+            environments: Default::default(),
+        }
+    }
+
+    /// The environment in `plugin_dir` for the plugin package `plugin_name` with
+    /// `requirements`, which starts being built if it hasn't been already
+    pub(crate) fn environment(
+        &self,
+        plugin_dir: &Path,
+        plugin_name: &str,
+        requirements: &[String],
+    ) -> Arc<PluginVenv> {
+        let venv_path = plugin_dir
+            .join(VENVS_DIR_NAME)
+            .join(format!("{plugin_name}-{}", requirements_hash(requirements)));
+        let mut environments = self.environments.lock();
+        if let Some(environment) = environments.get(&venv_path) {
+            return Arc::clone(environment);
+        }
+
+        let record = ProcessingEnginePluginEnvironment::new(
+            self.time_provider.now(),
+            plugin_name.into(),
+            venv_path.to_string_lossy().into(),
+            requirements.into(),
+        );
+        self.sys_event_store.record(record.clone());
+        let (status_tx, status_rx) = watch::channel(EnvironmentStatus::Building);
+        let environment = Arc::new(PluginVenv {
+            record,
+            site_packages: OnceLock::new(),
+            status: status_rx,
+        });
+        environments.insert(venv_path.clone(), Arc::clone(&environment));
+
+        let package_manager = Arc::clone(&self.package_manager);
+        let time_provider = Arc::clone(&self.time_provider);
+        let building = Arc::clone(&environment);
+        let requirements = requirements.to_vec();
+        tokio::task::spawn_blocking(move || {
+            info!(path = %venv_path.display(), "building plugin environment");
+            match build_venv(package_manager.as_ref(), &venv_path, &requirements) {
+                Ok(site_packages) => {
+                    building
+                        .site_packages
+                        .set(site_packages)
+                        .expect("environments are only built once");
+                    building.record.ready(time_provider.now());
+                    status_tx.send_replace(EnvironmentStatus::Ready);
+                }
+                Err(e) => {
+                    error!(path = %venv_path.display(), %e, "failed to build plugin environment");
+                    building.record.fail(time_provider.now(), e);
+                    status_tx.send_replace(EnvironmentStatus::Failed);
+                }
+            }
+        });
+
+        environment
+    }
+}
+
+/// The virtual environment of a plugin package
+#[derive(Debug)]
+pub(crate) struct PluginVenv {
+    record: ProcessingEnginePluginEnvironment,
+    /// Set once the environment has been built
+    site_packages: OnceLock<PathBuf>,
+    status: watch::Receiver<EnvironmentStatus>,
+}
+
+impl PluginVenv {
+    /// Wait until the environment has either been built or failed to build
+    pub(crate) async fn wait_for_build(&self) {
+        let mut status = self.status.clone();
+        // the sender is only dropped once the build is finished
+        let _ = status
+            .wait_for(|status| *status != EnvironmentStatus::Building)
+            .await;
+    }
+}
+
+impl PluginEnvironment for PluginVenv {
+    fn site_packages(&self) -> Result<PathBuf, anyhow::Error> {
+        let build = self.record.build();
+        match build.status {
+            EnvironmentStatus::Building => {
+                bail!(
+                    "the plugin environment {} is still being built",
+                    self.record.path
+                )
+            }
+            EnvironmentStatus::Failed => bail!(
+                "the plugin environment {} failed to build: {}",
+                self.record.path,
+                build.error.unwrap_or_default()
+            ),
+            EnvironmentStatus::Ready => Ok(self
+                .site_packages
+                .get()
+                .cloned()
+                .expect("site-packages is set before the environment is ready")),
+        }
+    }
+}
+
+/// Build the environment at `venv_path`, unless it was already built, and return its
+/// site-packages directory
+fn build_venv(
+    package_manager: &dyn PythonEnvironmentManager,
+    venv_path: &Path,
+    requirements: &[String],
+) -> Result<PathBuf, PluginEnvironmentError> {
+    let marker_path = venv_path.join(BUILT_MARKER_FILE_NAME);
+    if !marker_path.is_file() {
+        // clear out what is left of a build that didn't finish
+        if venv_path.exists() {
+            std::fs::remove_dir_all(venv_path)?;
+        }
+        package_manager.create_plugin_venv(venv_path, requirements)?;
+        std::fs::write(&marker_path, requirements.join("\n"))?;
+    }
+    find_site_packages(venv_path)
+}
+
+fn find_site_packages(venv_path: &Path) -> Result<PathBuf, PluginEnvironmentError> {
+    let site_packages = if cfg!(windows) {
+        Some(venv_path.join("Lib").join("site-packages"))
+    } else {
+        std::fs::read_dir(venv_path.join("lib"))?
+            .filter_map(Result::ok)
+            .find(|entry| entry.file_name().to_string_lossy().starts_with("python"))
+            .map(|entry| entry.path().join("site-packages"))
+    };
+    site_packages.filter(|path| path.is_dir()).ok_or_else(|| {
+        VenvError::InitError(format!("no site-packages in {}", venv_path.display())).into()
+    })
+}
+
+/// A short hash of `requirements`, so that a package whose requirements change gets a new
+/// environment rather than replacing one that a trigger may be running in
+fn requirements_hash(requirements: &[String]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, requirements.join("\n").as_bytes());
+    hex::encode(&digest.as_ref()[..8])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use influxdb3_py_api::logging::{EnvironmentStatus, ProcessingEnginePluginEnvironment};
+    use influxdb3_py_api::system_py::PluginEnvironment;
+    use influxdb3_sys_events::SysEventStore;
+    use iox_time::{MockProvider, Time};
+
+    use super::PluginEnvironments;
+    use crate::environment::{PluginEnvironmentError, PythonEnvironmentManager};
+
+    /// Creates the directories of a virtual environment, failing for a requirement of "missing"
+    #[derive(Debug)]
+    struct TestManager;
+
+    impl PythonEnvironmentManager for TestManager {
+        fn init_pyenv(
+            &self,
+            _plugin_dir: &Path,
+            _virtual_env_location: Option<&std::path::PathBuf>,
+        ) -> Result<(), PluginEnvironmentError> {
+            Ok(())
+        }
+
+        fn install_packages(&self, _packages: Vec<String>) -> Result<(), PluginEnvironmentError> {
+            Ok(())
+        }
+
+        fn install_requirements(
+            &self,
+            _requirements_path: String,
+        ) -> Result<(), PluginEnvironmentError> {
+            Ok(())
+        }
+
+        fn create_plugin_venv(
+            &self,
+            venv_path: &Path,
+            requirements: &[String],
+        ) -> Result<(), PluginEnvironmentError> {
+            if requirements.iter().any(|r| r == "missing") {
+                return Err(PluginEnvironmentError::CommandFailed {
+                    command: "pip install missing".to_string(),
+                    stderr: "no matching distribution found".to_string(),
+                });
+            }
+            let site_packages = if cfg!(windows) {
+                venv_path.join("Lib").join("site-packages")
+            } else {
+                venv_path
+                    .join("lib")
+                    .join("python3.13")
+                    .join("site-packages")
+            };
+            std::fs::create_dir_all(site_packages)?;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn build_environments() {
+        let plugin_dir = tempfile::tempdir().unwrap();
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let sys_event_store = Arc::new(SysEventStore::new(Arc::clone(&time_provider) as _));
+        let environments = PluginEnvironments::new(
+            Arc::new(TestManager),
+            time_provider,
+            Arc::clone(&sys_event_store),
+        );
+
+        let requirements = vec!["requests>=2.31".to_string()];
+        let environment = environments.environment(plugin_dir.path(), "alerts", &requirements);
+        environment.wait_for_build().await;
+        let site_packages = environment.site_packages().unwrap();
+        assert!(site_packages.starts_with(plugin_dir.path().join(".venvs")));
+        assert!(site_packages.ends_with("site-packages"));
+
+        // the same requirements share an environment, different ones get their own:
+        assert!(Arc::ptr_eq(
+            &environment,
+            &environments.environment(plugin_dir.path(), "alerts", &requirements)
+        ));
+        let failed =
+            environments.environment(plugin_dir.path(), "alerts", &["missing".to_string()]);
+        failed.wait_for_build().await;
+        let err = failed.site_packages().unwrap_err().to_string();
+        assert!(err.contains("no matching distribution found"), "{err}");
+
+        let recorded = sys_event_store.as_vec::<ProcessingEnginePluginEnvironment>();
+        let statuses: Vec<_> = recorded
+            .iter()
+            .map(|event| (event.data.requirements.to_vec(), event.data.build().status))
+            .collect();
+        assert_eq!(
+            vec![
+                (requirements, EnvironmentStatus::Ready),
+                (vec!["missing".to_string()], EnvironmentStatus::Failed),
+            ],
+            statuses
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Plugins made up of several python files
+//!
+//! A plugin package is a directory in the plugin directory with an `__init__.py` and a
+//! `manifest.json` that declares the package's entry points, e.g.:
+//!
+//! ```json
+//! {
+//!     "entry_points": {
+//!         "wal": "writes:process",
+//!         "schedule": "jobs:run",
+//!         "request": "process_request"
+//!     },
+//!     "requirements": ["requests>=2.31"]
+//! }
+//! ```
+//!
+//! An entry point is either `module:function`, for a function in a module of the package, or
+//! just `function`, for a function in the package's `__init__.py`. Trigger types without an
+//! entry point use the function of the usual name, e.g. `process_writes`, from `__init__.py`.
+//!
+//! A package with `requirements` runs in a virtual environment of its own with them installed,
+//! see [`plugin_environments`][crate::plugin_environments]. Requirements are given as they would
+//! be in a pip requirements file, but options such as `--index-url` are not allowed.
+//!
+//! The package is run through generated python code that imports the package, with its
+//! directory on `sys.path`, and binds the entry points to the names that the plugin is called
+//! through.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+const INIT_FILE_NAME: &str = "__init__.py";
+
+#[derive(Debug, Error)]
+pub enum PluginPackageError {
+    #[error("plugin package {0:?} has no {INIT_FILE_NAME}")]
+    MissingInit(PathBuf),
+
+    #[error("reading plugin package manifest: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid plugin package manifest: {0}")]
+    InvalidManifest(#[from] serde_json::Error),
+
+    #[error("invalid entry point {entry_point:?} for {trigger_type} triggers")]
+    InvalidEntryPoint {
+        trigger_type: String,
+        entry_point: String,
+    },
+
+    #[error("unknown trigger type {0:?} in entry points, expected one of {TRIGGER_TYPES:?}")]
+    UnknownTriggerType(String),
+
+    #[error("invalid requirement {0:?}")]
+    InvalidRequirement(String),
+}
+
+type Result<T, E = PluginPackageError> = std::result::Result<T, E>;
+
+/// The trigger types that can be given an entry point, and the name the plugin is called through
+/// for each of them
+const TRIGGER_TYPES: [(&str, &str); 5] = [
+    ("wal", "process_writes"),
+    ("schedule", "process_scheduled_call"),
+    ("request", "process_request"),
+    ("persisted", "process_persisted_files"),
+    ("catalog", "process_catalog_event"),
+];
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PluginManifest {
+    /// Map of trigger type to the entry point for that trigger type
+    #[serde(default)]
+    pub entry_points: BTreeMap<String, String>,
+    /// Packages to install into the plugin's own virtual environment
+    #[serde(default)]
+    pub requirements: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct PluginPackage {
+    dir: PathBuf,
+    manifest: PluginManifest,
+}
+
+impl PluginPackage {
+    /// Load the package in `dir`, validating its manifest
+    pub fn load(dir: &Path) -> Result<Self> {
+        if !dir.join(INIT_FILE_NAME).is_file() {
+            return Err(PluginPackageError::MissingInit(dir.to_path_buf()));
+        }
+        let manifest: PluginManifest =
+            serde_json::from_slice(&std::fs::read(dir.join(MANIFEST_FILE_NAME))?)?;
+        for (trigger_type, entry_point) in &manifest.entry_points {
+            if !TRIGGER_TYPES.iter().any(|(t, _)| t == trigger_type) {
+                return Err(PluginPackageError::UnknownTriggerType(trigger_type.clone()));
+            }
+            if parse_entry_point(entry_point).is_none() {
+                return Err(PluginPackageError::InvalidEntryPoint {
+                    trigger_type: trigger_type.clone(),
+                    entry_point: entry_point.clone(),
+                });
+            }
+        }
+        for requirement in &manifest.requirements {
+            if !is_valid_requirement(requirement) {
+                return Err(PluginPackageError::InvalidRequirement(requirement.clone()));
+            }
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            manifest,
+        })
+    }
+
+    /// The name of the package, which is the name of its directory
+    pub fn name(&self) -> String {
+        self.dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// The packages that the plugin needs installed in its own virtual environment
+    pub fn requirements(&self) -> &[String] {
+        &self.manifest.requirements
+    }
+
+    /// The python code that imports the package and binds its entry points
+    ///
+    /// `generation` must change whenever the package's files change, so that the modules of the
+    /// package that were already imported are imported again.
+    pub fn bootstrap_code(&self, generation: u128) -> String {
+        let dir = python_str(&self.dir.to_string_lossy());
+        let module_name = python_str(&package_module_name(&self.dir));
+        let mut code = format!(
+            r#"
+import importlib, importlib.util, os, sys
+
+def __influxdb3_load_package(package_dir, module_name, generation):
+    generations = globals().setdefault("__influxdb3_package_generations", {{}})
+    if generations.get(package_dir) != generation:
+        # the package changed, so forget the modules that were imported from it
+        for name, module in list(sys.modules.items()):
+            module_file = getattr(module, "__file__", None) or ""
+            if name == module_name or name.startswith(module_name + ".") or module_file.startswith(package_dir + os.sep):
+                del sys.modules[name]
+        importlib.invalidate_caches()
+        generations[package_dir] = generation
+    if package_dir not in sys.path:
+        sys.path.insert(0, package_dir)
+    package = sys.modules.get(module_name)
+    if package is None:
+        spec = importlib.util.spec_from_file_location(
+            module_name,
+            os.path.join(package_dir, "{INIT_FILE_NAME}"),
+            submodule_search_locations=[package_dir],
+        )
+        package = importlib.util.module_from_spec(spec)
+        sys.modules[module_name] = package
+        try:
+            spec.loader.exec_module(package)
+        except BaseException:
+            del sys.modules[module_name]
+            raise
+    return package
+
+__influxdb3_package = __influxdb3_load_package({dir}, {module_name}, {generation})
+"#
+        );
+        for (trigger_type, call_site) in TRIGGER_TYPES {
+            let binding = match self
+                .manifest
+                .entry_points
+                .get(trigger_type)
+                .and_then(|entry_point| parse_entry_point(entry_point))
+            {
+                Some((Some(module), function)) => format!(
+                    "{call_site} = getattr(importlib.import_module({} + \".\" + {}), {})\n",
+                    module_name,
+                    python_str(module),
+                    python_str(function),
+                ),
+                Some((None, function)) => format!(
+                    "{call_site} = getattr(__influxdb3_package, {})\n",
+                    python_str(function)
+                ),
+                None => format!(
+                    "if hasattr(__influxdb3_package, \"{call_site}\"):\n    \
+                     {call_site} = __influxdb3_package.{call_site}\n"
+                ),
+            };
+            code.push_str(&binding);
+        }
+        code
+    }
+}
+
+/// The most recent modification time of any of the files in a package's directory
+///
+/// The modification times of the directories themselves are not used, as they change when
+/// python writes compiled modules into them.
+pub(crate) fn last_modified(dir: &Path) -> std::io::Result<SystemTime> {
+    let mut latest = SystemTime::UNIX_EPOCH;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        // compiled modules are written by python itself
+        if entry.file_name() == "__pycache__" {
+            continue;
+        }
+        let modified = if file_type.is_dir() {
+            last_modified(&entry.path())?
+        } else {
+            entry.metadata()?.modified()?
+        };
+        latest = latest.max(modified);
+    }
+    Ok(latest)
+}
+
+/// Split an entry point into its module, if it has one, and function
+fn parse_entry_point(entry_point: &str) -> Option<(Option<&str>, &str)> {
+    let is_identifier = |s: &str| {
+        !s.is_empty()
+            && !s.starts_with(|c: char| c.is_ascii_digit())
+            && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    match entry_point.split_once(':') {
+        Some((module, function)) => {
+            let is_valid = module.split('.').all(is_identifier) && is_identifier(function);
+            is_valid.then_some((Some(module), function))
+        }
+        None => is_identifier(entry_point).then_some((None, entry_point)),
+    }
+}
+
+/// Whether `requirement` is a single requirement, rather than an option to the package manager,
+/// such as `-r` or `--index-url`, or several lines
+fn is_valid_requirement(requirement: &str) -> bool {
+    let requirement = requirement.trim();
+    !requirement.is_empty() && !requirement.starts_with('-') && !requirement.contains(['\n', '\r'])
+}
+
+/// The name the package is imported as, which is unique to its directory so that packages
+/// with the same module names don't clash
+fn package_module_name(dir: &Path) -> String {
+    let name: String = dir
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("influxdb3_plugin_{name}")
+}
+
+/// Quote `s` as a python string literal. JSON strings are valid python string literals.
+fn python_str(s: &str) -> String {
+    serde_json::to_string(s).expect("strings can always be serialized")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{PluginPackage, PluginPackageError, parse_entry_point};
+
+    #[test]
+    fn load_package() {
+        let dir = tempfile::tempdir().unwrap();
+        let Err(PluginPackageError::MissingInit(_)) = PluginPackage::load(dir.path()) else {
+            panic!("a package needs an __init__.py");
+        };
+
+        fs::write(dir.path().join("__init__.py"), "").unwrap();
+        fs::write(
+            dir.path().join("manifest.json"),
+            r#"{"entry_points": {"wal": "writes:process", "request": "handle"}}"#,
+        )
+        .unwrap();
+        let package = PluginPackage::load(dir.path()).unwrap();
+        let code = package.bootstrap_code(1);
+        assert!(code.contains(r#"process_writes = getattr(importlib.import_module("#));
+        assert!(code.contains(r#"process_request = getattr(__influxdb3_package, "handle")"#));
+        assert!(code.contains("if hasattr(__influxdb3_package, \"process_scheduled_call\")"));
+
+        fs::write(
+            dir.path().join("manifest.json"),
+            r#"{"entry_points": {"wall": "process"}}"#,
+        )
+        .unwrap();
+        let Err(PluginPackageError::UnknownTriggerType(_)) = PluginPackage::load(dir.path()) else {
+            panic!("should not accept an unknown trigger type");
+        };
+
+        fs::write(
+            dir.path().join("manifest.json"),
+            r#"{"requirements": ["requests>=2.31", "pyyaml"]}"#,
+        )
+        .unwrap();
+        let package = PluginPackage::load(dir.path()).unwrap();
+        assert_eq!(["requests>=2.31", "pyyaml"], package.requirements());
+
+        fs::write(
+            dir.path().join("manifest.json"),
+            r#"{"requirements": ["--index-url=https://example.com"]}"#,
+        )
+        .unwrap();
+        let Err(PluginPackageError::InvalidRequirement(_)) = PluginPackage::load(dir.path()) else {
+            panic!("should not accept package manager options as requirements");
+        };
+    }
+
+    #[test]
+    fn entry_points() {
+        assert_eq!(Some((None, "process")), parse_entry_point("process"));
+        assert_eq!(
+            Some((Some("jobs.daily"), "run")),
+            parse_entry_point("jobs.daily:run")
+        );
+        for invalid in [
+            "",
+            ":run",
+            "jobs:",
+            "jobs:run()",
+            "1jobs:run",
+            "import os; x",
+        ] {
+            assert_eq!(None, parse_entry_point(invalid), "{invalid:?}");
+        }
+    }
+}
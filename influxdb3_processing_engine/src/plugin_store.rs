@@ -0,0 +1,354 @@
+//! Plugin files uploaded through the API
+//!
+//! Every upload of a plugin is kept in object store as a new version, so that triggers can pin
+//! the version they run with a `<name>@<version>` plugin filename. Plugins are run from the plugin
+//! directory, so uploads are also written there: the latest version to `<name>`, where it is
+//! picked up by the hot reload of running triggers, and pinned versions to `<name>@<version>`.
+//!
+//! A plugin is either a single python file, or a zip of a plugin package, which is extracted
+//! into a directory of the same name.
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures_util::TryStreamExt;
+use influxdb3_catalog::catalog::Catalog;
+use object_store::{ObjectStore, PutMode, path::Path as ObjPath};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PluginStoreError {
+    #[error(
+        "invalid plugin name {0:?}, plugin names must be a single file name and cannot contain '@' or ':'"
+    )]
+    InvalidName(String),
+
+    #[error("plugin {name} has no version {version}")]
+    VersionNotFound { name: String, version: u64 },
+
+    #[error("object store error: {0}")]
+    ObjectStore(#[from] object_store::Error),
+
+    #[error("writing plugin to the plugin directory: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid plugin package: {0}")]
+    InvalidPackage(#[from] zip::result::ZipError),
+}
+
+type Result<T, E = PluginStoreError> = std::result::Result<T, E>;
+
+/// The start of every zip file
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+#[derive(Debug, Clone)]
+pub(crate) struct PluginStore {
+    object_store: Arc<dyn ObjectStore>,
+    prefix: String,
+}
+
+impl PluginStore {
+    pub(crate) fn new(catalog: &Catalog) -> Self {
+        Self {
+            object_store: catalog.object_store(),
+            prefix: format!(
+                "{}/processing_engine/plugin_files",
+                catalog.object_store_prefix()
+            ),
+        }
+    }
+
+    /// Store a new version of the plugin, returning its version number
+    pub(crate) async fn put(&self, name: &str, code: Bytes) -> Result<u64> {
+        validate_name(name)?;
+        if code.starts_with(ZIP_MAGIC) {
+            // don't keep a package that can't be extracted
+            zip::ZipArchive::new(Cursor::new(&code[..]))?;
+        }
+        let mut version = self.versions(name).await?.last().map_or(1, |v| v + 1);
+        loop {
+            // another upload of the same plugin may have taken this version in the meantime
+            match self
+                .object_store
+                .put_opts(
+                    &self.version_path(name, version),
+                    code.clone().into(),
+                    PutMode::Create.into(),
+                )
+                .await
+            {
+                Ok(_) => return Ok(version),
+                Err(object_store::Error::AlreadyExists { .. }) => version += 1,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Get the given version of the plugin, or its latest version if none is given
+    pub(crate) async fn get(&self, name: &str, version: Option<u64>) -> Result<Option<Bytes>> {
+        validate_name(name)?;
+        let version = match version {
+            Some(version) => version,
+            None => match self.versions(name).await?.last() {
+                Some(version) => *version,
+                None => return Ok(None),
+            },
+        };
+        match self
+            .object_store
+            .get(&self.version_path(name, version))
+            .await
+        {
+            Ok(result) => Ok(Some(result.bytes().await?)),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// All stored versions of the plugin, in ascending order
+    pub(crate) async fn versions(&self, name: &str) -> Result<Vec<u64>> {
+        validate_name(name)?;
+        let prefix = ObjPath::from(format!("{}/{name}", self.prefix));
+        let mut versions: Vec<u64> = self
+            .object_store
+            .list(Some(&prefix))
+            .try_filter_map(|meta| async move {
+                Ok(meta.location.filename().and_then(|f| f.parse().ok()))
+            })
+            .try_collect()
+            .await?;
+        versions.sort_unstable();
+        Ok(versions)
+    }
+
+    /// Write the given version of the plugin, or its latest version, into the plugin directory,
+    /// returning false if the plugin has not been uploaded
+    pub(crate) async fn materialize(
+        &self,
+        name: &str,
+        version: Option<u64>,
+        plugin_dir: &Path,
+    ) -> Result<bool> {
+        let Some(code) = self.get(name, version).await? else {
+            return match version {
+                Some(version) => Err(PluginStoreError::VersionNotFound {
+                    name: name.to_string(),
+                    version,
+                }),
+                None => Ok(false),
+            };
+        };
+        write_plugin(&plugin_dir.join(plugin_filename(name, version)), &code)?;
+        Ok(true)
+    }
+
+    fn version_path(&self, name: &str, version: u64) -> ObjPath {
+        // zero padded, so that versions list in order
+        ObjPath::from(format!("{}/{name}/{version:020}", self.prefix))
+    }
+}
+
+/// Split a plugin filename into the plugin name and the version it is pinned to, if any
+pub(crate) fn parse_plugin_filename(filename: &str) -> (&str, Option<u64>) {
+    match filename.rsplit_once('@') {
+        Some((name, version)) => match version.parse() {
+            Ok(version) => (name, Some(version)),
+            Err(_) => (filename, None),
+        },
+        None => (filename, None),
+    }
+}
+
+fn plugin_filename(name: &str, version: Option<u64>) -> String {
+    match version {
+        Some(version) => format!("{name}@{version}"),
+        None => name.to_string(),
+    }
+}
+
+/// Whether `name` can be used as the name of an uploaded plugin
+pub(crate) fn is_valid_plugin_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '@', ':'])
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if is_valid_plugin_name(name) {
+        Ok(())
+    } else {
+        Err(PluginStoreError::InvalidName(name.to_string()))
+    }
+}
+
+/// Write the plugin to `path`, replacing what is there. The plugin is written next to `path`
+/// first and then renamed into place, so that running triggers never read a partial plugin.
+fn write_plugin(path: &Path, code: &[u8]) -> Result<()> {
+    let mut staging = path.as_os_str().to_owned();
+    staging.push(".uploading");
+    let staging = PathBuf::from(staging);
+    remove_path(&staging)?;
+
+    if code.starts_with(ZIP_MAGIC) {
+        let mut archive = zip::ZipArchive::new(Cursor::new(code))?;
+        std::fs::create_dir_all(&staging)?;
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            // skip entries that would be written outside of the plugin's directory
+            let Some(relative_path) = file.enclosed_name() else {
+                continue;
+            };
+            let out_path = staging.join(relative_path);
+            if file.is_dir() {
+                std::fs::create_dir_all(&out_path)?;
+                continue;
+            }
+            if let Some(parent) = out_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut out = std::fs::File::create(&out_path)?;
+            std::io::copy(&mut file, &mut out)?;
+        }
+    } else {
+        std::fs::File::create(&staging)?.write_all(code)?;
+    }
+
+    // a file can be renamed over another in one step, but directories have to be removed first
+    if path.is_dir() || staging.is_dir() {
+        remove_path(path)?;
+    }
+    std::fs::rename(&staging, path)?;
+    Ok(())
+}
+
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+        Ok(_) => std::fs::remove_file(path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use influxdb3_catalog::catalog::Catalog;
+    use iox_time::{MockProvider, Time};
+    use object_store::memory::InMemory;
+
+    use super::{PluginStore, PluginStoreError, parse_plugin_filename};
+
+    async fn store() -> PluginStore {
+        let catalog = Catalog::new(
+            "test-node",
+            Arc::new(InMemory::new()),
+            Arc::new(MockProvider::new(Time::from_timestamp_nanos(0))),
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        PluginStore::new(&catalog)
+    }
+
+    #[tokio::test]
+    async fn versions_and_materialize() {
+        let store = store().await;
+        let plugin_dir = tempfile::tempdir().unwrap();
+        assert!(
+            !store
+                .materialize("plugin.py", None, plugin_dir.path())
+                .await
+                .unwrap()
+        );
+
+        for code in ["v1", "v2", "v3"] {
+            store
+                .put("plugin.py", Bytes::from_static(code.as_bytes()))
+                .await
+                .unwrap();
+        }
+        assert_eq!(vec![1, 2, 3], store.versions("plugin.py").await.unwrap());
+
+        assert!(
+            store
+                .materialize("plugin.py", None, plugin_dir.path())
+                .await
+                .unwrap()
+        );
+        assert!(
+            store
+                .materialize("plugin.py", Some(2), plugin_dir.path())
+                .await
+                .unwrap()
+        );
+        let read = |name: &str| std::fs::read_to_string(plugin_dir.path().join(name)).unwrap();
+        assert_eq!("v3", read("plugin.py"));
+        assert_eq!("v2", read("plugin.py@2"));
+
+        let Err(PluginStoreError::VersionNotFound { version: 4, .. }) = store
+            .materialize("plugin.py", Some(4), plugin_dir.path())
+            .await
+        else {
+            panic!("should not be able to materialize a version that does not exist");
+        };
+        for name in ["", "..", "dir/plugin.py", "plugin.py@1", "gh:plugin.py"] {
+            let Err(PluginStoreError::InvalidName(_)) =
+                store.put(name, Bytes::from_static(b"")).await
+            else {
+                panic!("{name:?} should not be a valid plugin name");
+            };
+        }
+    }
+
+    #[tokio::test]
+    async fn package_is_extracted() {
+        let store = store().await;
+        let plugin_dir = tempfile::tempdir().unwrap();
+
+        let mut package = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        package.start_file("__init__.py", options).unwrap();
+        package.write_all(b"from .helpers import x").unwrap();
+        package.start_file("helpers/__init__.py", options).unwrap();
+        package.write_all(b"x = 1").unwrap();
+        package.start_file("../escape.py", options).unwrap();
+        package.write_all(b"nope").unwrap();
+        let package = package.finish().unwrap().into_inner();
+
+        // a single file plugin is replaced by the package:
+        store
+            .put("pkg", Bytes::from_static(b"single file"))
+            .await
+            .unwrap();
+        store
+            .materialize("pkg", None, plugin_dir.path())
+            .await
+            .unwrap();
+        store.put("pkg", Bytes::from(package)).await.unwrap();
+        store
+            .materialize("pkg", None, plugin_dir.path())
+            .await
+            .unwrap();
+
+        let pkg_dir = plugin_dir.path().join("pkg");
+        assert!(pkg_dir.is_dir());
+        assert_eq!(
+            "x = 1",
+            std::fs::read_to_string(pkg_dir.join("helpers/__init__.py")).unwrap()
+        );
+        assert!(!plugin_dir.path().join("escape.py").exists());
+    }
+
+    #[test]
+    fn plugin_filenames() {
+        assert_eq!(("plugin.py", None), parse_plugin_filename("plugin.py"));
+        assert_eq!(("plugin.py", Some(3)), parse_plugin_filename("plugin.py@3"));
+        assert_eq!(
+            ("user@example.py", None),
+            parse_plugin_filename("user@example.py")
+        );
+    }
+}
@@ -1,7 +1,19 @@
-use crate::PluginCode;
 use crate::ProcessingEngineManagerImpl;
 use crate::environment::PythonEnvironmentManager;
-use crate::{RequestEvent, ScheduleEvent, WalEvent};
+use crate::line_writer::ValidatingLineWriter;
+use crate::pending_batch::{PendingBatch, PendingBatchStore};
+use crate::plugin_environments::PluginVenv;
+use crate::secrets::SecretsKey;
+use crate::trigger_metrics::TriggerMetricsRecorder;
+use crate::wasm_plugin::WasmPluginModule;
+use crate::{
+    CatalogTriggerEvent, DeadLetterReplay, PersistedEvent, Request, RequestEvent, ScheduleEvent,
+    WalEvent,
+};
+use crate::{PluginCode, PythonPluginCode};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as B64_STANDARD;
+use bytes::Bytes;
 use data_types::NamespaceName;
 use hashbrown::HashMap;
 use influxdb3_catalog::catalog::Catalog;
@@ -9,7 +21,7 @@ use influxdb3_catalog::log::TriggerDefinition;
 use influxdb3_catalog::log::TriggerSpecificationDefinition;
 use influxdb3_internal_api::query_executor::QueryExecutor;
 use influxdb3_py_api::system_py::ProcessingEngineLogger;
-use influxdb3_py_api::system_py::{CacheStore, PyCache};
+use influxdb3_py_api::system_py::{CacheStore, PluginModule, PluginRequest, PyCache, ResponseBody};
 
 use influxdb3_sys_events::SysEventStore;
 
@@ -87,6 +99,17 @@ pub enum PluginError {
 
     #[error("Node not configured with plugin directory")]
     NoPluginDir,
+
+    #[error(transparent)]
+    PluginStore(#[from] crate::plugin_store::PluginStoreError),
+
+    #[error(transparent)]
+    PluginPackage(#[from] crate::plugin_package::PluginPackageError),
+
+    #[error(
+        "WASM plugins can't be used by {0} triggers, only by WAL, schedule and request triggers"
+    )]
+    UnsupportedWasmTrigger(String),
 }
 
 pub(crate) fn run_wal_contents_plugin(
@@ -99,10 +122,12 @@ pub(crate) fn run_wal_contents_plugin(
     let trigger_plugin = TriggerPlugin::new(db_name, plugin_code, trigger_definition, context);
 
     tokio::task::spawn(async move {
+        trigger_plugin.wait_for_environment().await;
         trigger_plugin
             .run_wal_contents_plugin(plugin_receiver)
             .await
             .expect("trigger plugin failed");
+        trigger_plugin.shutdown_plugin().await;
     });
 }
 
@@ -111,6 +136,11 @@ pub struct ProcessingEngineEnvironmentManager {
     pub plugin_dir: Option<PathBuf>,
     pub virtual_env_location: Option<PathBuf>,
     pub package_manager: Arc<dyn PythonEnvironmentManager>,
+    /// How often to checkpoint the plugin cache to object store, if it should be kept across
+    /// restarts
+    pub cache_checkpoint_interval: Option<std::time::Duration>,
+    /// The key that trigger secrets are encrypted with, if the server was given one
+    pub secrets_key: Option<Arc<SecretsKey>>,
 }
 
 pub(crate) fn run_schedule_plugin(
@@ -137,10 +167,12 @@ pub(crate) fn run_schedule_plugin(
         Arc::clone(&time_provider),
     )?;
     tokio::task::spawn(async move {
+        trigger_plugin.wait_for_environment().await;
         trigger_plugin
             .run_schedule_plugin(plugin_receiver, runner, time_provider)
             .await
             .expect("cron trigger plugin failed");
+        trigger_plugin.shutdown_plugin().await;
     });
 
     Ok(())
@@ -155,10 +187,60 @@ pub(crate) fn run_request_plugin(
 ) {
     let trigger_plugin = TriggerPlugin::new(db_name, plugin_code, trigger_definition, context);
     tokio::task::spawn(async move {
+        trigger_plugin.wait_for_environment().await;
         trigger_plugin
             .run_request_plugin(plugin_receiver)
             .await
             .expect("trigger plugin failed");
+        trigger_plugin.shutdown_plugin().await;
+    });
+}
+
+/// Only python plugins can be used by persisted triggers, so this takes python code
+pub(crate) fn run_persisted_files_plugin(
+    db_name: String,
+    plugin_code: Arc<PythonPluginCode>,
+    trigger_definition: Arc<TriggerDefinition>,
+    context: PluginContext,
+    plugin_receiver: mpsc::Receiver<PersistedEvent>,
+) {
+    let trigger_plugin = TriggerPlugin::new(
+        db_name,
+        Arc::new(PluginCode::Python(Arc::clone(&plugin_code))),
+        trigger_definition,
+        context,
+    );
+    tokio::task::spawn(async move {
+        trigger_plugin.wait_for_environment().await;
+        trigger_plugin
+            .run_persisted_files_plugin(plugin_receiver, plugin_code)
+            .await
+            .expect("trigger plugin failed");
+        trigger_plugin.shutdown_plugin().await;
+    });
+}
+
+/// Only python plugins can be used by catalog triggers, so this takes python code
+pub(crate) fn run_catalog_plugin(
+    db_name: String,
+    plugin_code: Arc<PythonPluginCode>,
+    trigger_definition: Arc<TriggerDefinition>,
+    context: PluginContext,
+    plugin_receiver: mpsc::Receiver<CatalogTriggerEvent>,
+) {
+    let trigger_plugin = TriggerPlugin::new(
+        db_name,
+        Arc::new(PluginCode::Python(Arc::clone(&plugin_code))),
+        trigger_definition,
+        context,
+    );
+    tokio::task::spawn(async move {
+        trigger_plugin.wait_for_environment().await;
+        trigger_plugin
+            .run_catalog_plugin(plugin_receiver, plugin_code)
+            .await
+            .expect("trigger plugin failed");
+        trigger_plugin.shutdown_plugin().await;
     });
 }
 
@@ -182,10 +264,23 @@ struct TriggerPlugin {
     query_executor: Arc<dyn QueryExecutor>,
     manager: Arc<ProcessingEngineManagerImpl>,
     logger: ProcessingEngineLogger,
+    metrics: TriggerMetricsRecorder,
+    /// The plugin's code compiled into a python module, which is kept between runs
+    plugin_module: Arc<PluginModule>,
+    /// The plugin's own virtual environment, if it has one
+    environment: Option<Arc<PluginVenv>>,
+    /// The plugin's compiled module, which is kept between runs, if it is a WASM plugin
+    wasm_module: Arc<WasmPluginModule>,
+    /// The dead letter being replayed, for the copy of the trigger that replays it
+    replay: Option<DeadLetterReplay>,
 }
 
 mod python_plugin {
     use super::*;
+    use crate::line_writer::TriggerLineWriter;
+    use crate::wasm_plugin::{
+        execute_wasm_request_trigger, execute_wasm_schedule_trigger, execute_wasm_with_batch,
+    };
     use anyhow::{Context, anyhow};
     use chrono::{DateTime, Duration, Utc};
     use cron::{OwnedScheduleIterator, Schedule as CronSchedule};
@@ -195,19 +290,24 @@ mod python_plugin {
     use humantime::{format_duration, parse_duration};
     use hyper::http::HeaderValue;
     use hyper::{Body, Response, StatusCode};
-    use influxdb3_catalog::catalog::DatabaseSchema;
-    use influxdb3_catalog::log::ErrorBehavior;
-    use influxdb3_py_api::logging::LogLevel;
+    use influxdb3_catalog::catalog::{DatabaseSchema, TableDefinition};
+    use influxdb3_catalog::log::{BatchWindow, ErrorBehavior, RetryFallback};
+    use influxdb3_catalog::trigger_predicate::BoundTriggerPredicate;
+    use influxdb3_id::{SerdeVecMap, TableId};
+    use influxdb3_py_api::ExecutePluginError;
+    use influxdb3_py_api::logging::{DeadLetterEvent, LogLevel};
     use influxdb3_py_api::system_py::{
-        PluginReturnState, ProcessingEngineLogger, PyCache, execute_python_with_batch,
-        execute_request_trigger, execute_schedule_trigger,
+        CatalogEvent, ExecutionLimits, PersistedParquetFile, PluginReturnState,
+        ProcessingEngineLogger, PyCache, ResponseStream, execute_catalog_trigger,
+        execute_persisted_files_trigger, execute_python_with_batch, execute_request_trigger,
+        execute_schedule_trigger,
     };
-    use influxdb3_wal::{WalContents, WalOp};
-    use influxdb3_write::Precision;
+    use influxdb3_wal::{TableChunks, WalContents, WalFileSequenceNumber, WalOp, WriteBatch};
+    use influxdb3_write::{PersistedSnapshotVersion, Precision};
     use iox_time::Time;
     use observability_deps::tracing::{info, warn};
     use std::str::FromStr;
-    use std::time::SystemTime;
+    use std::time::{Instant, SystemTime};
     use tokio::sync::mpsc::Receiver;
 
     impl TriggerPlugin {
@@ -221,6 +321,35 @@ mod python_plugin {
                 context.sys_event_store,
                 Arc::clone(&trigger_definition.trigger_name),
             );
+            let settings = trigger_definition.trigger_settings;
+            let metrics = context.manager.trigger_metrics.recorder(
+                &trigger_definition.database_name,
+                &trigger_definition.trigger_name,
+                trigger_definition.trigger_id,
+            );
+            let line_writer = TriggerLineWriter::new(
+                Arc::clone(&context.write_buffer),
+                Arc::clone(&context.manager.time_provider),
+                db_name.clone(),
+                metrics.clone(),
+            );
+            let mut plugin_module = PluginModule::new(trigger_definition.trigger_name.to_string())
+                .with_secrets(context.manager.database_secrets(&db_name))
+                .with_value_caches(context.manager.database_value_caches(&db_name))
+                .with_line_writer(Arc::new(line_writer))
+                .with_limits(ExecutionLimits {
+                    timeout: settings.execution_timeout,
+                    max_rows_written: settings.max_rows_written,
+                });
+            let environment = context.manager.plugin_environment(&plugin_code);
+            if let Some(environment) = &environment {
+                plugin_module = plugin_module.with_environment(Arc::clone(environment) as _);
+            }
+            let plugin_module = Arc::new(plugin_module);
+            let wasm_module = Arc::new(WasmPluginModule::default().with_limits(ExecutionLimits {
+                timeout: settings.execution_timeout,
+                max_rows_written: settings.max_rows_written,
+            }));
             Self {
                 trigger_definition,
                 plugin_code,
@@ -229,6 +358,27 @@ mod python_plugin {
                 query_executor: Arc::clone(&context.query_executor),
                 manager: Arc::clone(&context.manager),
                 logger,
+                metrics,
+                plugin_module,
+                environment,
+                wasm_module,
+                replay: None,
+            }
+        }
+
+        /// A copy of the trigger that runs the plugin against a replayed dead letter
+        fn replaying(&self, replay: &DeadLetterReplay) -> Self {
+            Self {
+                replay: Some(replay.clone()),
+                ..self.clone()
+            }
+        }
+
+        /// Wait for the plugin's own virtual environment to be built, if it has one, so that
+        /// the plugin isn't run before it is ready
+        pub(crate) async fn wait_for_environment(&self) {
+            if let Some(environment) = &self.environment {
+                environment.wait_for_build().await;
             }
         }
 
@@ -236,6 +386,11 @@ mod python_plugin {
             &self,
             mut receiver: Receiver<WalEvent>,
         ) -> Result<(), PluginError> {
+            if let Some(batch_window) = self.trigger_definition.trigger_settings.batch_window {
+                return self
+                    .run_batched_wal_contents_plugin(receiver, batch_window)
+                    .await;
+            }
             info!(?self.trigger_definition.trigger_name, ?self.trigger_definition.database_name, ?self.trigger_definition.plugin_filename, "starting wal contents plugin");
             let mut futures = FuturesUnordered::new();
             loop {
@@ -258,7 +413,7 @@ mod python_plugin {
                                             self.send_disable_trigger();
                                             while let Some(event) = receiver.recv().await {
                                                 match event {
-                                                    WalEvent::WriteWalContents(_) => {
+                                                    WalEvent::WriteWalContents(_) | WalEvent::Backfill(..) | WalEvent::Replay(..) => {
                                                         warn!("skipping wal contents because trigger is being disabled")
                                                     }
                                                     WalEvent::Shutdown(shutdown) => {
@@ -274,6 +429,67 @@ mod python_plugin {
 
                                 }
                             }
+                            Some(WalEvent::Backfill(wal_contents, done)) => {
+                                // backfilled data is always run in order, even for asynchronous
+                                // triggers, and acknowledged once the plugin has run against it
+                                match self.process_wal_contents(wal_contents).await? {
+                                    PluginNextState::SuccessfulRun => {}
+                                    PluginNextState::LogError(error_log) => {
+                                        self.logger.log(LogLevel::Error, error_log);
+                                    }
+                                    PluginNextState::Disable(trigger_definition) => {
+                                        warn!("disabling trigger {}", trigger_definition.trigger_name);
+                                        // the backfill fails when it is not acknowledged
+                                        drop(done);
+                                        self.send_disable_trigger();
+                                        while let Some(event) = receiver.recv().await {
+                                            match event {
+                                                WalEvent::WriteWalContents(_) | WalEvent::Backfill(..) | WalEvent::Replay(..) => {
+                                                    warn!("skipping wal contents because trigger is being disabled")
+                                                }
+                                                WalEvent::Shutdown(shutdown) => {
+                                                    if shutdown.send(()).is_err() {
+                                                        error!("failed to send back shutdown for trigger {}", trigger_definition.trigger_name);
+                                                    }
+                                                    break;
+                                                }
+                                            }
+                                        }
+                                        break;
+                                    }
+                                }
+                                let _ = done.send(());
+                            }
+                            Some(WalEvent::Replay(wal_contents, replay)) => {
+                                // replayed dead letters are run in order, even for asynchronous
+                                // triggers
+                                let next_state = self.replaying(&replay).process_wal_contents(wal_contents).await;
+                                self.manager.finish_replay(&replay, next_state.is_ok()).await;
+                                match next_state? {
+                                    PluginNextState::SuccessfulRun => {}
+                                    PluginNextState::LogError(error_log) => {
+                                        self.logger.log(LogLevel::Error, error_log);
+                                    }
+                                    PluginNextState::Disable(trigger_definition) => {
+                                        warn!("disabling trigger {}", trigger_definition.trigger_name);
+                                        self.send_disable_trigger();
+                                        while let Some(event) = receiver.recv().await {
+                                            match event {
+                                                WalEvent::WriteWalContents(_) | WalEvent::Backfill(..) | WalEvent::Replay(..) => {
+                                                    warn!("skipping wal contents because trigger is being disabled")
+                                                }
+                                                WalEvent::Shutdown(shutdown) => {
+                                                    if shutdown.send(()).is_err() {
+                                                        error!("failed to send back shutdown for trigger {}", trigger_definition.trigger_name);
+                                                    }
+                                                    break;
+                                                }
+                                            }
+                                        }
+                                        break;
+                                    }
+                                }
+                            }
                             Some(WalEvent::Shutdown(sender)) => {
                                 sender.send(()).map_err(|_| PluginError::FailedToShutdown)?;
                                 break;
@@ -294,7 +510,7 @@ mod python_plugin {
                                         self.send_disable_trigger();
                                         while let Some(event) = receiver.recv().await {
                                             match event {
-                                                WalEvent::WriteWalContents(_) => {
+                                                WalEvent::WriteWalContents(_) | WalEvent::Backfill(..) | WalEvent::Replay(..) => {
                                                     warn!("skipping wal contents because trigger is being disabled")
                                                 }
                                                 WalEvent::Shutdown(shutdown) => {
@@ -319,6 +535,250 @@ mod python_plugin {
             Ok(())
         }
 
+        /// Accumulates the rows from each WAL flush into a pending batch, and runs the plugin
+        /// against the whole batch once the window closes or it holds enough rows.
+        ///
+        /// Batches are always run one at a time, in order, regardless of `run_async`.
+        async fn run_batched_wal_contents_plugin(
+            &self,
+            mut receiver: Receiver<WalEvent>,
+            batch_window: BatchWindow,
+        ) -> Result<(), PluginError> {
+            info!(?self.trigger_definition.trigger_name, ?self.trigger_definition.database_name, ?self.trigger_definition.plugin_filename, ?batch_window, "starting batched wal contents plugin");
+            let time_provider = Arc::clone(&self.manager.time_provider);
+            let store = PendingBatchStore::new(
+                &self.write_buffer.catalog(),
+                self.db_name.as_str(),
+                self.trigger_definition.trigger_id,
+            );
+            // pick up any rows accumulated before the trigger was last stopped
+            let mut pending = match store.load().await {
+                Ok(pending) => pending,
+                Err(error) => {
+                    error!(?self.trigger_definition, ?error, "failed to load pending batch");
+                    None
+                }
+            };
+            let max_duration_ns =
+                i64::try_from(batch_window.max_duration.as_nanos()).unwrap_or(i64::MAX);
+
+            loop {
+                let window_end = pending.as_ref().map(|pending| {
+                    Time::from_timestamp_nanos(
+                        pending.started_at_ns.saturating_add(max_duration_ns),
+                    )
+                });
+                let window_closed = async {
+                    match window_end {
+                        Some(window_end) => time_provider.sleep_until(window_end).await,
+                        None => std::future::pending().await,
+                    }
+                };
+
+                let run_batch = tokio::select! {
+                    _ = window_closed => true,
+                    event = receiver.recv() => {
+                        match event {
+                            Some(WalEvent::WriteWalContents(wal_contents)) => {
+                                let rows = self.trigger_rows(&wal_contents)?;
+                                self.add_pending_rows(&store, &mut pending, rows, time_provider.now(), batch_window).await
+                            }
+                            Some(WalEvent::Backfill(wal_contents, done)) => {
+                                let rows = self.trigger_rows(&wal_contents)?;
+                                let run_batch = self.add_pending_rows(&store, &mut pending, rows, time_provider.now(), batch_window).await;
+                                // the backfilled rows are now part of the persisted pending batch:
+                                let _ = done.send(());
+                                run_batch
+                            }
+                            Some(WalEvent::Replay(wal_contents, replay)) => {
+                                let rows = match self.trigger_rows(&wal_contents) {
+                                    Ok(rows) => rows,
+                                    Err(error) => {
+                                        self.manager.finish_replay(&replay, false).await;
+                                        return Err(error);
+                                    }
+                                };
+                                let run_batch = self.add_pending_rows(&store, &mut pending, rows, time_provider.now(), batch_window).await;
+                                // like backfilled rows, the replayed rows are done with once they
+                                // are part of the persisted pending batch
+                                self.manager.finish_replay(&replay, true).await;
+                                run_batch
+                            }
+                            Some(WalEvent::Shutdown(sender)) => {
+                                // the pending batch has already been persisted, so it will be
+                                // picked up when the trigger is started again
+                                sender.send(()).map_err(|_| PluginError::FailedToShutdown)?;
+                                break;
+                            }
+                            None => break,
+                        }
+                    }
+                };
+
+                if !run_batch {
+                    continue;
+                }
+                let Some(batch) = pending.take() else {
+                    continue;
+                };
+                let next_state = self.run_pending_batch(batch, time_provider.now()).await?;
+                if let Err(error) = store.delete().await {
+                    error!(?self.trigger_definition, ?error, "failed to remove pending batch");
+                }
+                match next_state {
+                    PluginNextState::SuccessfulRun => {}
+                    PluginNextState::LogError(error_log) => {
+                        self.logger.log(LogLevel::Error, error_log);
+                    }
+                    PluginNextState::Disable(trigger_definition) => {
+                        warn!("disabling trigger {}", trigger_definition.trigger_name);
+                        self.send_disable_trigger();
+                        while let Some(event) = receiver.recv().await {
+                            match event {
+                                WalEvent::WriteWalContents(_)
+                                | WalEvent::Backfill(..)
+                                | WalEvent::Replay(..) => {
+                                    warn!("skipping wal contents because trigger is being disabled")
+                                }
+                                WalEvent::Shutdown(shutdown) => {
+                                    if shutdown.send(()).is_err() {
+                                        error!(
+                                            "failed to send back shutdown for trigger {}",
+                                            trigger_definition.trigger_name
+                                        );
+                                    }
+                                    break;
+                                }
+                            }
+                        }
+                        break;
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        /// The rows for this trigger from `wal_contents`, if there are any
+        fn trigger_rows(
+            &self,
+            wal_contents: &WalContents,
+        ) -> Result<Option<WriteBatch>, PluginError> {
+            let Some(schema) = self.write_buffer.catalog().db_schema(self.db_name.as_str()) else {
+                return Err(PluginError::MissingDb);
+            };
+            let mut rows: Option<WriteBatch> = None;
+            for wal_op in &wal_contents.ops {
+                let WalOp::Write(write_batch) = wal_op else {
+                    continue;
+                };
+                if write_batch.database_name != self.trigger_definition.database_name {
+                    continue;
+                }
+                let table_chunks = self.trigger_table_chunks(&schema, write_batch);
+                if table_chunks.is_empty() {
+                    continue;
+                }
+                let (min_time_ns, max_time_ns) = table_chunks
+                    .values()
+                    .fold((i64::MAX, i64::MIN), |(min, max), chunks| {
+                        (min.min(chunks.min_time), max.max(chunks.max_time))
+                    });
+                match &mut rows {
+                    Some(rows) => {
+                        rows.catalog_sequence = write_batch.catalog_sequence;
+                        rows.add_write_batch(table_chunks, min_time_ns, max_time_ns);
+                    }
+                    None => {
+                        rows = Some(WriteBatch {
+                            catalog_sequence: write_batch.catalog_sequence,
+                            database_id: write_batch.database_id,
+                            database_name: Arc::clone(&write_batch.database_name),
+                            table_chunks,
+                            min_time_ns,
+                            max_time_ns,
+                        })
+                    }
+                }
+            }
+            Ok(rows)
+        }
+
+        /// Persist `rows` as a segment of the pending batch and add them to it, returning whether
+        /// the batch now holds enough rows to run the plugin against
+        async fn add_pending_rows(
+            &self,
+            store: &PendingBatchStore,
+            pending: &mut Option<PendingBatch>,
+            rows: Option<WriteBatch>,
+            now: Time,
+            batch_window: BatchWindow,
+        ) -> bool {
+            let Some(rows) = rows else {
+                return false;
+            };
+            let pending =
+                pending.get_or_insert_with(|| PendingBatch::new(now.timestamp_nanos(), &rows));
+            if let Err(error) = store.append(pending, &rows).await {
+                error!(?self.trigger_definition, ?error, "failed to persist pending batch");
+            }
+            pending.add(rows);
+            batch_window
+                .max_rows
+                .is_some_and(|max_rows| pending.row_count() >= max_rows)
+        }
+
+        /// The rows in `write_batch` that this WAL trigger's plugin is run against
+        fn trigger_table_chunks(
+            &self,
+            schema: &DatabaseSchema,
+            write_batch: &WriteBatch,
+        ) -> SerdeVecMap<TableId, TableChunks> {
+            match &self.trigger_definition.trigger {
+                TriggerSpecificationDefinition::SingleTableWalWrite {
+                    table_name,
+                    predicate,
+                } => {
+                    let Some(table_def) = schema.table_definition(table_name) else {
+                        return Default::default();
+                    };
+                    match predicate {
+                        Some(predicate) => {
+                            filter_write_batch(write_batch, &table_def, &predicate.bind(&table_def))
+                                .map(|filtered| filtered.table_chunks)
+                                .unwrap_or_default()
+                        }
+                        None => write_batch
+                            .table_chunks
+                            .get(&table_def.table_id)
+                            .map(|chunks| {
+                                [(table_def.table_id, chunks.clone())].into_iter().collect()
+                            })
+                            .unwrap_or_default(),
+                    }
+                }
+                _ => write_batch.table_chunks.clone(),
+            }
+        }
+
+        /// Run the plugin against a batch that was accumulated from several WAL flushes
+        async fn run_pending_batch(
+            &self,
+            batch: PendingBatch,
+            now: Time,
+        ) -> Result<PluginNextState, PluginError> {
+            let write_batch = batch.write_batch;
+            let wal_contents = Arc::new(WalContents {
+                persist_timestamp_ms: now.timestamp_millis(),
+                min_timestamp_ns: write_batch.min_time_ns,
+                max_timestamp_ns: write_batch.max_time_ns,
+                wal_file_number: WalFileSequenceNumber::new(0),
+                ops: vec![WalOp::Write(write_batch)],
+                snapshot: None,
+            });
+            self.process_wal_contents(wal_contents).await
+        }
+
         /// This sends the disable trigger command to the processing engine manager,
         /// it is done in a separate task so that the caller can send back shutdown.
         pub(crate) fn send_disable_trigger(&self) {
@@ -399,6 +859,37 @@ mod python_plugin {
                                 sender.send(()).map_err(|_| PluginError::FailedToShutdown)?;
                                 break;
                             }
+                            Some(ScheduleEvent::Replay(trigger_time, replay)) => {
+                                let Some(schema) = self.write_buffer.catalog().db_schema(self.db_name.as_str()) else {
+                                    return Err(PluginError::MissingDb);
+                                };
+                                let result = ScheduleTriggerRunner::run_at_time(self.replaying(&replay), trigger_time, schema).await;
+                                self.manager.finish_replay(&replay, result.is_ok()).await;
+                                match result {
+                                    Ok(PluginNextState::SuccessfulRun) => {}
+                                    Ok(PluginNextState::LogError(err)) => {
+                                        self.logger.log(LogLevel::Error, format!("error replaying scheduled plugin: {}", err));
+                                        error!(?self.trigger_definition, "error replaying scheduled plugin: {}", err);
+                                    }
+                                    Ok(PluginNextState::Disable(trigger_definition)) => {
+                                        warn!("disabling trigger {} due to error", trigger_definition.trigger_name);
+                                        self.send_disable_trigger();
+                                        let Some(ScheduleEvent::Shutdown(sender)) = receiver.recv().await else {
+                                            warn!("didn't receive shutdown notification from receiver");
+                                            break;
+                                        };
+
+                                        if sender.send(()).is_err() {
+                                            error!("failed to send shutdown message back");
+                                        }
+                                        break;
+                                    }
+                                    Err(err) => {
+                                        self.logger.log(LogLevel::Error, format!("error replaying scheduled plugin: {}", err));
+                                        error!(?self.trigger_definition, "error replaying scheduled plugin: {}", err);
+                                    }
+                                }
+                            }
                         }
                     }
                     Some(result) = futures.next() => {
@@ -457,39 +948,48 @@ mod python_plugin {
                             error!(?self.trigger_definition, "missing db schema");
                             return Err(PluginError::MissingDb);
                         };
-                        let plugin_code = self.plugin_code.code();
+                        let Request {
+                            request,
+                            response_tx,
+                            replay,
+                        } = request;
+                        let request = Arc::new(request);
+                        let plugin_code = Arc::clone(&self.plugin_code);
+                        let plugin_module = Arc::clone(&self.plugin_module);
+                        let wasm_module = Arc::clone(&self.wasm_module);
                         let query_executor = Arc::clone(&self.query_executor);
-                        let logger = Some(self.logger.clone());
+                        let logger = self.logger.clone();
                         let trigger_arguments = self.trigger_definition.trigger_arguments.clone();
-                        let py_cache = PyCache::new_trigger_cache(
-                            Arc::clone(&self.manager.cache),
-                            self.trigger_definition.database_name.to_string(),
-                            self.trigger_definition.trigger_name.to_string(),
-                        );
-
-                        let result = tokio::task::spawn_blocking(move || {
-                            execute_request_trigger(
-                                plugin_code.as_ref(),
-                                schema,
-                                query_executor,
-                                logger,
-                                &trigger_arguments,
-                                request.query_params,
-                                request.headers,
-                                request.body,
-                                py_cache,
-                            )
-                        })
-                        .await?;
+                        let py_cache = self.py_cache();
+                        let request_clone = Arc::clone(&request);
+                        let result = self
+                            .execute_with_retries(move || match &*plugin_code {
+                                PluginCode::Wasm(wasm_plugin) => execute_wasm_request_trigger(
+                                    &wasm_module,
+                                    &wasm_plugin.read_if_modified(),
+                                    Arc::clone(&schema),
+                                    Arc::clone(&query_executor),
+                                    Some(logger.clone()),
+                                    &trigger_arguments,
+                                    &request_clone,
+                                    py_cache.clone(),
+                                ),
+                                PluginCode::Python(python_code) => execute_request_trigger(
+                                    &plugin_module,
+                                    &python_code.code(),
+                                    Arc::clone(&schema),
+                                    Arc::clone(&query_executor),
+                                    Some(logger.clone()),
+                                    &trigger_arguments,
+                                    &request_clone,
+                                    py_cache.clone(),
+                                ),
+                            })
+                            .await?;
 
                         // produce the HTTP response
                         let response = match result {
-                            Ok((
-                                response_code,
-                                response_headers,
-                                response_body,
-                                plugin_return_state,
-                            )) => {
+                            Ok((plugin_response, plugin_return_state)) => {
                                 let errors = self.handle_return_state(plugin_return_state).await;
                                 // TODO: here is one spot we'll pick up errors to put into the plugin system table
                                 for error in errors {
@@ -500,11 +1000,11 @@ mod python_plugin {
                                     error!(?self.trigger_definition, "error running request plugin: {}", error);
                                 }
 
-                                let response_status = StatusCode::from_u16(response_code)
+                                let response_status = StatusCode::from_u16(plugin_response.status)
                                     .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
                                 let mut response = Response::builder().status(response_status);
 
-                                for (key, value) in response_headers {
+                                for (key, value) in plugin_response.headers {
                                     response = response.header(
                                         key.as_str(),
                                         HeaderValue::from_str(&value)
@@ -512,18 +1012,42 @@ mod python_plugin {
                                     );
                                 }
 
-                                response
-                                    .body(Body::from(response_body))
-                                    .context("building response")?
+                                let body = match plugin_response.body {
+                                    ResponseBody::Full(body) => Body::from(body),
+                                    ResponseBody::Stream(stream) => self.stream_response(stream),
+                                };
+                                response.body(body).context("building response")?
                             }
-                            Err(e) => {
+                            Err(failed_run) => {
+                                let error = failed_run.error.clone();
+                                let event = || DeadLetterEvent::Request(Arc::clone(&request));
+                                let next_state = match &replay {
+                                    Some(replay) => {
+                                        self.replaying(replay)
+                                            .handle_failed_run(failed_run, event)
+                                            .await
+                                    }
+                                    None => self.handle_failed_run(failed_run, event).await,
+                                };
+                                match next_state {
+                                    PluginNextState::LogError(err) => {
+                                        self.logger.log(
+                                            LogLevel::Error,
+                                            format!("error running request plugin: {}", err),
+                                        );
+                                        error!(?self.trigger_definition, "error running request plugin: {}", err);
+                                    }
+                                    PluginNextState::Disable(trigger_definition) => {
+                                        warn!(
+                                            "disabling trigger {} due to error",
+                                            trigger_definition.trigger_name
+                                        );
+                                        self.send_disable_trigger();
+                                    }
+                                    PluginNextState::SuccessfulRun => {}
+                                }
                                 // build json string with the error with serde so that it is {"error": "error message"}
-                                self.logger.log(
-                                    LogLevel::Error,
-                                    format!("error running request plugin: {}", e),
-                                );
-                                error!(?self.trigger_definition, "error running request plugin: {}", e);
-                                let body = serde_json::json!({"error": e.to_string()}).to_string();
+                                let body = serde_json::json!({"error": error}).to_string();
                                 Response::builder()
                                     .status(StatusCode::INTERNAL_SERVER_ERROR)
                                     .body(Body::from(body))
@@ -531,7 +1055,10 @@ mod python_plugin {
                             }
                         };
 
-                        if request.response_tx.send(response).is_err() {
+                        if let Some(replay) = &replay {
+                            // nobody is waiting on the response to a replayed request
+                            self.manager.finish_replay(replay, true).await;
+                        } else if response_tx.send(response).is_err() {
                             error!(?self.trigger_definition, "error sending response");
                         }
                     }
@@ -545,33 +1072,350 @@ mod python_plugin {
             Ok(())
         }
 
-        async fn process_wal_contents(
+        pub(crate) async fn run_persisted_files_plugin(
             &self,
-            wal_contents: Arc<WalContents>,
+            mut receiver: Receiver<PersistedEvent>,
+            plugin_code: Arc<PythonPluginCode>,
+        ) -> Result<(), PluginError> {
+            info!(?self.trigger_definition.trigger_name, ?self.trigger_definition.database_name, ?self.trigger_definition.plugin_filename, "starting persisted files plugin");
+            while let Some(event) = receiver.recv().await {
+                let next_state = match event {
+                    PersistedEvent::Snapshot(snapshot) => {
+                        let persisted_files = self.persisted_files(&snapshot)?;
+                        if persisted_files.is_empty() {
+                            continue;
+                        }
+                        self.process_persisted_files(&plugin_code, persisted_files)
+                            .await
+                    }
+                    PersistedEvent::Replay(persisted_files, replay) => {
+                        let next_state = self
+                            .replaying(&replay)
+                            .process_persisted_files(&plugin_code, persisted_files)
+                            .await;
+                        self.manager
+                            .finish_replay(&replay, next_state.is_ok())
+                            .await;
+                        next_state
+                    }
+                    PersistedEvent::Shutdown(sender) => {
+                        sender.send(()).map_err(|_| PluginError::FailedToShutdown)?;
+                        break;
+                    }
+                };
+
+                match next_state? {
+                    PluginNextState::SuccessfulRun => {}
+                    PluginNextState::LogError(err) => {
+                        self.logger.log(
+                            LogLevel::Error,
+                            format!("error running persisted files plugin: {}", err),
+                        );
+                        error!(?self.trigger_definition, "error running persisted files plugin: {}", err);
+                    }
+                    PluginNextState::Disable(trigger_definition) => {
+                        warn!(
+                            "disabling trigger {} due to error",
+                            trigger_definition.trigger_name
+                        );
+                        self.send_disable_trigger();
+                        while let Some(event) = receiver.recv().await {
+                            match event {
+                                PersistedEvent::Snapshot(_) | PersistedEvent::Replay(..) => {
+                                    warn!(
+                                        "skipping persisted files because trigger is being disabled"
+                                    )
+                                }
+                                PersistedEvent::Shutdown(shutdown) => {
+                                    if shutdown.send(()).is_err() {
+                                        error!(
+                                            "failed to send back shutdown for trigger {}",
+                                            trigger_definition.trigger_name
+                                        );
+                                    }
+                                    break;
+                                }
+                            }
+                        }
+                        break;
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        /// The Parquet files in a snapshot that were persisted for the trigger's table
+        fn persisted_files(
+            &self,
+            snapshot: &PersistedSnapshotVersion,
+        ) -> Result<Arc<[PersistedParquetFile]>, PluginError> {
+            let TriggerSpecificationDefinition::Persisted { table_name } =
+                &self.trigger_definition.trigger
+            else {
+                return Err(anyhow!(
+                    "unexpectedly found trigger specification {} for persisted files plugin {}",
+                    self.trigger_definition.trigger.string_rep(),
+                    self.trigger_definition.trigger_name
+                )
+                .into());
+            };
+            let Some(schema) = self.write_buffer.catalog().db_schema(self.db_name.as_str()) else {
+                return Err(PluginError::MissingDb);
+            };
+            // the table may not have been written to yet, in which case none of its files can
+            // have been persisted:
+            let Some(table_id) = schema.table_name_to_id(table_name.as_str()) else {
+                return Ok(Arc::from([]));
+            };
+
+            let PersistedSnapshotVersion::V1(snapshot) = snapshot;
+            let Some(parquet_files) = snapshot
+                .databases
+                .get(&schema.id)
+                .and_then(|db_tables| db_tables.tables.get(&table_id))
+            else {
+                return Ok(Arc::from([]));
+            };
+            Ok(parquet_files
+                .iter()
+                .map(|parquet_file| PersistedParquetFile {
+                    table_name: table_name.clone(),
+                    path: parquet_file.path.clone(),
+                    size_bytes: parquet_file.size_bytes,
+                    row_count: parquet_file.row_count,
+                    min_time: parquet_file.min_time,
+                    max_time: parquet_file.max_time,
+                })
+                .collect())
+        }
+
+        async fn process_persisted_files(
+            &self,
+            plugin_code: &Arc<PythonPluginCode>,
+            persisted_files: Arc<[PersistedParquetFile]>,
         ) -> Result<PluginNextState, PluginError> {
             let Some(schema) = self.write_buffer.catalog().db_schema(self.db_name.as_str()) else {
                 return Err(PluginError::MissingDb);
             };
 
-            for (op_index, wal_op) in wal_contents.ops.iter().enumerate() {
-                match wal_op {
-                    WalOp::Write(write_batch) => {
-                        // determine if this write batch is for this database
+            let plugin_code = Arc::clone(plugin_code);
+            let plugin_module = Arc::clone(&self.plugin_module);
+            let query_executor = Arc::clone(&self.query_executor);
+            let logger = self.logger.clone();
+            let trigger_arguments = self.trigger_definition.trigger_arguments.clone();
+            let py_cache = self.py_cache();
+            let files = Arc::clone(&persisted_files);
+            let result = self
+                .execute_with_retries(move || {
+                    execute_persisted_files_trigger(
+                        &plugin_module,
+                        &plugin_code.code(),
+                        &files,
+                        Arc::clone(&schema),
+                        Arc::clone(&query_executor),
+                        Some(logger.clone()),
+                        &trigger_arguments,
+                        py_cache.clone(),
+                    )
+                })
+                .await?;
+
+            match result {
+                Ok(result) => {
+                    let errors = self.handle_return_state(result).await;
+                    for error in errors {
+                        self.logger.log(
+                            LogLevel::Error,
+                            format!("error running persisted files plugin: {}", error),
+                        );
+                        error!(?self.trigger_definition, "error running persisted files plugin: {}", error);
+                    }
+                    Ok(PluginNextState::SuccessfulRun)
+                }
+                Err(failed_run) => Ok(self
+                    .handle_failed_run(failed_run, || {
+                        DeadLetterEvent::PersistedFiles(persisted_files)
+                    })
+                    .await),
+            }
+        }
+
+        pub(crate) async fn run_catalog_plugin(
+            &self,
+            mut receiver: Receiver<CatalogTriggerEvent>,
+            plugin_code: Arc<PythonPluginCode>,
+        ) -> Result<(), PluginError> {
+            info!(?self.trigger_definition.trigger_name, ?self.trigger_definition.database_name, ?self.trigger_definition.plugin_filename, "starting catalog plugin");
+            let TriggerSpecificationDefinition::CatalogEvent { event_type } =
+                self.trigger_definition.trigger
+            else {
+                return Err(anyhow!(
+                    "unexpectedly found trigger specification {} for catalog plugin {}",
+                    self.trigger_definition.trigger.string_rep(),
+                    self.trigger_definition.trigger_name
+                )
+                .into());
+            };
+            while let Some(event) = receiver.recv().await {
+                let next_state = match event {
+                    CatalogTriggerEvent::Change(event) => {
+                        if event.event_type != event_type {
+                            continue;
+                        }
+                        self.process_catalog_event(&plugin_code, event).await
+                    }
+                    CatalogTriggerEvent::Replay(event, replay) => {
+                        let next_state = self
+                            .replaying(&replay)
+                            .process_catalog_event(&plugin_code, event)
+                            .await;
+                        self.manager
+                            .finish_replay(&replay, next_state.is_ok())
+                            .await;
+                        next_state
+                    }
+                    CatalogTriggerEvent::Shutdown(sender) => {
+                        sender.send(()).map_err(|_| PluginError::FailedToShutdown)?;
+                        break;
+                    }
+                };
+
+                match next_state? {
+                    PluginNextState::SuccessfulRun => {}
+                    PluginNextState::LogError(err) => {
+                        self.logger.log(
+                            LogLevel::Error,
+                            format!("error running catalog plugin: {}", err),
+                        );
+                        error!(?self.trigger_definition, "error running catalog plugin: {}", err);
+                    }
+                    PluginNextState::Disable(trigger_definition) => {
+                        warn!(
+                            "disabling trigger {} due to error",
+                            trigger_definition.trigger_name
+                        );
+                        self.send_disable_trigger();
+                        while let Some(event) = receiver.recv().await {
+                            match event {
+                                CatalogTriggerEvent::Change(_)
+                                | CatalogTriggerEvent::Replay(..) => {
+                                    warn!(
+                                        "skipping catalog event because trigger is being disabled"
+                                    )
+                                }
+                                CatalogTriggerEvent::Shutdown(shutdown) => {
+                                    if shutdown.send(()).is_err() {
+                                        error!(
+                                            "failed to send back shutdown for trigger {}",
+                                            trigger_definition.trigger_name
+                                        );
+                                    }
+                                    break;
+                                }
+                            }
+                        }
+                        break;
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        async fn process_catalog_event(
+            &self,
+            plugin_code: &Arc<PythonPluginCode>,
+            event: Arc<CatalogEvent>,
+        ) -> Result<PluginNextState, PluginError> {
+            // look the database up by id, since a deleted database is renamed:
+            let Some(schema) = self
+                .write_buffer
+                .catalog()
+                .db_schema_by_id(&event.database_id)
+            else {
+                return Err(PluginError::MissingDb);
+            };
+
+            let plugin_code = Arc::clone(plugin_code);
+            let plugin_module = Arc::clone(&self.plugin_module);
+            let query_executor = Arc::clone(&self.query_executor);
+            let logger = self.logger.clone();
+            let trigger_arguments = self.trigger_definition.trigger_arguments.clone();
+            let py_cache = self.py_cache();
+            let event_clone = Arc::clone(&event);
+            let result = self
+                .execute_with_retries(move || {
+                    execute_catalog_trigger(
+                        &plugin_module,
+                        &plugin_code.code(),
+                        &event_clone,
+                        Arc::clone(&schema),
+                        Arc::clone(&query_executor),
+                        Some(logger.clone()),
+                        &trigger_arguments,
+                        py_cache.clone(),
+                    )
+                })
+                .await?;
+
+            match result {
+                Ok(result) => {
+                    let errors = self.handle_return_state(result).await;
+                    for error in errors {
+                        self.logger.log(
+                            LogLevel::Error,
+                            format!("error running catalog plugin: {}", error),
+                        );
+                        error!(?self.trigger_definition, "error running catalog plugin: {}", error);
+                    }
+                    Ok(PluginNextState::SuccessfulRun)
+                }
+                Err(failed_run) => Ok(self
+                    .handle_failed_run(failed_run, || DeadLetterEvent::Catalog(event))
+                    .await),
+            }
+        }
+
+        async fn process_wal_contents(
+            &self,
+            wal_contents: Arc<WalContents>,
+        ) -> Result<PluginNextState, PluginError> {
+            let Some(schema) = self.write_buffer.catalog().db_schema(self.db_name.as_str()) else {
+                return Err(PluginError::MissingDb);
+            };
+
+            for (op_index, wal_op) in wal_contents.ops.iter().enumerate() {
+                match wal_op {
+                    WalOp::Write(write_batch) => {
+                        // determine if this write batch is for this database
                         if write_batch.database_name != self.trigger_definition.database_name {
                             continue;
                         }
-                        let table_filter = match &self.trigger_definition.trigger {
+                        let (table_filter, filtered_batch) = match &self.trigger_definition.trigger {
                                 TriggerSpecificationDefinition::AllTablesWalWrite => {
                                     // no filter
-                                    None
+                                    (None, None)
                                 }
                                 TriggerSpecificationDefinition::SingleTableWalWrite {
                                     table_name,
+                                    predicate,
                                 } => {
-                                    let table_id = schema
-                                        .table_name_to_id(table_name)
+                                    let table_def = schema
+                                        .table_definition(table_name)
                                         .context("table not found")?;
-                                    Some(table_id)
+                                    let filtered_batch = match predicate {
+                                        Some(predicate) => {
+                                            let predicate = predicate.bind(&table_def);
+                                            match filter_write_batch(write_batch, &table_def, &predicate) {
+                                                Some(batch) => Some(Arc::new(batch)),
+                                                // no rows matched, so there is nothing to run the plugin against
+                                                None => continue,
+                                            }
+                                        }
+                                        None => None,
+                                    };
+                                    (Some(table_def.table_id), filtered_batch)
                                 }
                                 // This should not occur
                                 TriggerSpecificationDefinition::Schedule {
@@ -587,74 +1431,108 @@ mod python_plugin {
                                 TriggerSpecificationDefinition::RequestPath { path } => {
                                     return Err(anyhow!("unexpectedly found request path trigger specification {} for WAL plugin {}", path, self.trigger_definition.trigger_name).into())
                                 }
+                                TriggerSpecificationDefinition::Persisted { table_name } => {
+                                    return Err(anyhow!("unexpectedly found persisted trigger specification persisted:{} for WAL plugin {}", table_name, self.trigger_definition.trigger_name).into())
+                                }
+                                TriggerSpecificationDefinition::CatalogEvent { event_type } => {
+                                    return Err(anyhow!("unexpectedly found catalog trigger specification catalog:{} for WAL plugin {}", event_type, self.trigger_definition.trigger_name).into())
+                                }
                             };
 
-                        // loop for retries, in general it will only run once.
-                        loop {
-                            let logger = Some(self.logger.clone());
-                            let plugin_code = Arc::clone(&self.plugin_code.code());
-                            let query_executor = Arc::clone(&self.query_executor);
-                            let schema_clone = Arc::clone(&schema);
-                            let trigger_arguments =
-                                self.trigger_definition.trigger_arguments.clone();
-                            let wal_contents_clone = Arc::clone(&wal_contents);
-                            let py_cache = PyCache::new_trigger_cache(
-                                Arc::clone(&self.manager.cache),
-                                self.trigger_definition.database_name.to_string(),
-                                self.trigger_definition.trigger_name.to_string(),
-                            );
+                        let rows_received = match (&filtered_batch, table_filter) {
+                            (Some(batch), _) => batch
+                                .table_chunks
+                                .values()
+                                .map(|chunks| chunks.row_count())
+                                .sum(),
+                            (None, Some(table_id)) => write_batch
+                                .table_chunks
+                                .get(&table_id)
+                                .map_or(0, |chunks| chunks.row_count()),
+                            (None, None) => write_batch
+                                .table_chunks
+                                .values()
+                                .map(|chunks| chunks.row_count())
+                                .sum(),
+                        };
+                        self.metrics.record_rows_received(rows_received as u64);
 
-                            let result = tokio::task::spawn_blocking(move || {
-                                let write_batch = match &wal_contents_clone.ops[op_index] {
-                                    WalOp::Write(wb) => wb,
-                                    _ => unreachable!("Index was checked."),
+                        let plugin_code = Arc::clone(&self.plugin_code);
+                        let plugin_module = Arc::clone(&self.plugin_module);
+                        let wasm_module = Arc::clone(&self.wasm_module);
+                        let query_executor = Arc::clone(&self.query_executor);
+                        let logger = self.logger.clone();
+                        let schema_clone = Arc::clone(&schema);
+                        let trigger_arguments = self.trigger_definition.trigger_arguments.clone();
+                        let wal_contents_clone = Arc::clone(&wal_contents);
+                        let py_cache = self.py_cache();
+                        let filtered_batch_clone = filtered_batch.clone();
+                        let result = self
+                            .execute_with_retries(move || {
+                                let write_batch = match filtered_batch_clone.as_deref() {
+                                    Some(wb) => wb,
+                                    None => match &wal_contents_clone.ops[op_index] {
+                                        WalOp::Write(wb) => wb,
+                                        _ => unreachable!("Index was checked."),
+                                    },
                                 };
-                                execute_python_with_batch(
-                                    plugin_code.as_ref(),
-                                    write_batch,
-                                    schema_clone,
-                                    query_executor,
-                                    logger,
-                                    table_filter,
-                                    &trigger_arguments,
-                                    py_cache,
-                                )
+                                match &*plugin_code {
+                                    PluginCode::Wasm(wasm_plugin) => execute_wasm_with_batch(
+                                        &wasm_module,
+                                        &wasm_plugin.read_if_modified(),
+                                        write_batch,
+                                        Arc::clone(&schema_clone),
+                                        Arc::clone(&query_executor),
+                                        Some(logger.clone()),
+                                        table_filter,
+                                        &trigger_arguments,
+                                        py_cache.clone(),
+                                    ),
+                                    PluginCode::Python(python_code) => execute_python_with_batch(
+                                        &plugin_module,
+                                        &python_code.code(),
+                                        write_batch,
+                                        Arc::clone(&schema_clone),
+                                        Arc::clone(&query_executor),
+                                        Some(logger.clone()),
+                                        table_filter,
+                                        &trigger_arguments,
+                                        py_cache.clone(),
+                                    ),
+                                }
                             })
                             .await?;
 
-                            match result {
-                                Ok(result) => {
-                                    let errors = self.handle_return_state(result).await;
-                                    for error in errors {
+                        match result {
+                            Ok(result) => {
+                                let errors = self.handle_return_state(result).await;
+                                for error in errors {
+                                    self.logger.log(
+                                        LogLevel::Error,
+                                        format!("error running wal plugin: {}", error),
+                                    );
+                                    error!(?self.trigger_definition, "error running wal plugin: {}", error);
+                                }
+                            }
+                            Err(failed_run) => {
+                                let event = || {
+                                    DeadLetterEvent::WriteBatch(
+                                        filtered_batch
+                                            .unwrap_or_else(|| Arc::new(write_batch.clone())),
+                                    )
+                                };
+                                match self.handle_failed_run(failed_run, event).await {
+                                    PluginNextState::LogError(err) => {
                                         self.logger.log(
                                             LogLevel::Error,
-                                            format!("error running wal plugin: {}", error),
+                                            format!("error executing against batch {}", err),
                                         );
-                                        error!(?self.trigger_definition, "error running wal plugin: {}", error);
+                                        error!(?self.trigger_definition, "error running against batch: {}", err);
                                     }
-                                    break;
-                                }
-                                Err(err) => {
-                                    match self.trigger_definition.trigger_settings.error_behavior {
-                                        ErrorBehavior::Log => {
-                                            self.logger.log(
-                                                LogLevel::Error,
-                                                format!("error executing against batch {}", err),
-                                            );
-                                            error!(?self.trigger_definition, "error running against batch: {}", err);
-                                        }
-                                        ErrorBehavior::Retry => {
-                                            info!(
-                                                "error running against batch {}, will retry",
-                                                err
-                                            );
-                                        }
-                                        ErrorBehavior::Disable => {
-                                            return Ok(PluginNextState::Disable(Arc::clone(
-                                                &self.trigger_definition,
-                                            )));
-                                        }
+                                    next_state @ PluginNextState::Disable(_) => {
+                                        return Ok(next_state);
                                     }
+                                    PluginNextState::SuccessfulRun => {}
                                 }
                             }
                         }
@@ -675,6 +1553,7 @@ mod python_plugin {
             let mut errors = Vec::new();
 
             if !plugin_return_state.write_back_lines.is_empty() {
+                let line_count = plugin_return_state.write_back_lines.len() as u64;
                 if let Err(e) = self
                     .write_buffer
                     .write_lp(
@@ -688,6 +1567,8 @@ mod python_plugin {
                     .await
                 {
                     errors.push(format!("error writing back lines: {}", e));
+                } else {
+                    self.metrics.record_lines_written(line_count);
                 }
             }
 
@@ -696,6 +1577,7 @@ mod python_plugin {
                     errors.push(format!("invalid database name: {}", db_name));
                     continue;
                 };
+                let line_count = lines.len() as u64;
 
                 if let Err(e) = self
                     .write_buffer
@@ -710,11 +1592,207 @@ mod python_plugin {
                     .await
                 {
                     errors.push(format!("error writing back lines to {}: {}", db_name, e));
+                } else {
+                    self.metrics.record_lines_written(line_count);
                 }
             }
 
             errors
         }
+
+        fn py_cache(&self) -> PyCache {
+            PyCache::new_trigger_cache(
+                Arc::clone(&self.manager.cache),
+                self.trigger_definition.database_name.to_string(),
+                self.trigger_definition.trigger_name.to_string(),
+            )
+        }
+
+        /// The body of a response that is streamed from the plugin, which is read from it a
+        /// chunk at a time, on a blocking thread, as the response is sent
+        fn stream_response(&self, stream: ResponseStream) -> Body {
+            let (mut sender, body) = Body::channel();
+            let stream = Arc::new(stream);
+            let logger = self.logger.clone();
+            tokio::spawn(async move {
+                loop {
+                    let stream = Arc::clone(&stream);
+                    let chunk = match tokio::task::spawn_blocking(move || stream.next_chunk()).await
+                    {
+                        Ok(chunk) => chunk,
+                        Err(error) => Some(Err(anyhow!("streaming response panicked: {error}"))),
+                    };
+                    match chunk {
+                        Some(Ok(chunk)) => {
+                            // the client has gone away if the chunk can't be sent
+                            if sender.send_data(chunk).await.is_err() {
+                                break;
+                            }
+                        }
+                        Some(Err(error)) => {
+                            logger.log(
+                                LogLevel::Error,
+                                format!("error streaming response: {error}"),
+                            );
+                            sender.abort();
+                            break;
+                        }
+                        None => break,
+                    }
+                }
+            });
+            body
+        }
+
+        /// Run the `shutdown` hook of the plugin's module, once the trigger has stopped, and
+        /// handle anything it wrote or logged
+        pub(crate) async fn shutdown_plugin(&self) {
+            let Some(schema) = self.write_buffer.catalog().db_schema(self.db_name.as_str()) else {
+                return;
+            };
+            let plugin_module = Arc::clone(&self.plugin_module);
+            let query_executor = Arc::clone(&self.query_executor);
+            let logger = self.logger.clone();
+            let py_cache = self.py_cache();
+            let result = tokio::task::spawn_blocking(move || {
+                plugin_module.shutdown(schema, query_executor, Some(logger), py_cache)
+            })
+            .await;
+            match result {
+                Ok(Ok(return_state)) => {
+                    for error in self.handle_return_state(return_state).await {
+                        self.logger.log(
+                            LogLevel::Error,
+                            format!("error running plugin shutdown: {}", error),
+                        );
+                    }
+                }
+                Ok(Err(error)) => {
+                    self.logger.log(LogLevel::Error, error.to_string());
+                    error!(?self.trigger_definition, "error running plugin shutdown: {}", error);
+                }
+                Err(error) => {
+                    error!(?self.trigger_definition, "plugin shutdown panicked: {}", error);
+                }
+            }
+        }
+
+        /// Run the plugin with `execute` on a blocking thread. If it fails, and the trigger's
+        /// error behavior is [`ErrorBehavior::Retry`], it is run again after a backoff, up to
+        /// the number of attempts allowed by the trigger's retry policy.
+        async fn execute_with_retries<T, F>(
+            &self,
+            execute: F,
+        ) -> Result<Result<T, FailedRun>, PluginError>
+        where
+            T: Send + 'static,
+            F: Fn() -> Result<T, ExecutePluginError> + Send + Sync + 'static,
+        {
+            let settings = self.trigger_definition.trigger_settings;
+            let max_attempts = match settings.error_behavior {
+                ErrorBehavior::Retry => settings.retry_policy.max_attempts.max(1),
+                ErrorBehavior::Log | ErrorBehavior::Disable => 1,
+            };
+            let execute = Arc::new(execute);
+            let mut attempt = 1;
+            let mut execution_times = Vec::with_capacity(1);
+            loop {
+                let execute = Arc::clone(&execute);
+                let started = Instant::now();
+                let result = tokio::task::spawn_blocking(move || execute()).await?;
+                execution_times.push(started.elapsed());
+                match result {
+                    Ok(result) => {
+                        self.metrics.record_invocation(&execution_times, true);
+                        return Ok(Ok(result));
+                    }
+                    Err(err) if attempt < max_attempts => {
+                        let backoff = settings.retry_policy.backoff(attempt);
+                        self.logger.log(
+                            LogLevel::Warn,
+                            format!(
+                                "attempt {attempt} of {max_attempts} failed, retrying in {}: {err}",
+                                format_duration(backoff)
+                            ),
+                        );
+                        warn!(
+                            trigger_name = %self.trigger_definition.trigger_name,
+                            attempt,
+                            ?backoff,
+                            "retrying trigger on error: {}",
+                            err
+                        );
+                        tokio::time::sleep(backoff).await;
+                        attempt += 1;
+                    }
+                    Err(err) => {
+                        self.metrics.record_invocation(&execution_times, false);
+                        return Ok(Err(FailedRun {
+                            error: err.to_string(),
+                            attempts: attempt,
+                        }));
+                    }
+                }
+            }
+        }
+
+        /// Decide what to do about a run that failed on all of its attempts. If the trigger
+        /// retried the run, the event produced by `event` is recorded as a dead letter, so that
+        /// it can be replayed later, unless the run was itself the replay of a dead letter.
+        async fn handle_failed_run(
+            &self,
+            failed_run: FailedRun,
+            event: impl FnOnce() -> DeadLetterEvent,
+        ) -> PluginNextState {
+            if let Some(replay) = &self.replay {
+                replay.fail();
+            }
+            let settings = self.trigger_definition.trigger_settings;
+            match settings.error_behavior {
+                ErrorBehavior::Log => PluginNextState::LogError(failed_run.error),
+                ErrorBehavior::Disable => {
+                    PluginNextState::Disable(Arc::clone(&self.trigger_definition))
+                }
+                ErrorBehavior::Retry => {
+                    let message = match &self.replay {
+                        // the dead letter is kept, and is still not replayed:
+                        Some(replay) => format!(
+                            "giving up after {} attempts, dead letter {} is not replayed",
+                            failed_run.attempts,
+                            replay.id()
+                        ),
+                        None => {
+                            let id = self
+                                .manager
+                                .record_dead_letter(
+                                    &self.trigger_definition,
+                                    failed_run.attempts,
+                                    failed_run.error.clone(),
+                                    event(),
+                                )
+                                .await;
+                            format!(
+                                "giving up after {} attempts, recorded dead letter {id}",
+                                failed_run.attempts
+                            )
+                        }
+                    };
+                    self.logger.log(LogLevel::Error, message);
+                    match settings.retry_policy.fallback {
+                        RetryFallback::Log => PluginNextState::LogError(failed_run.error),
+                        RetryFallback::Disable => {
+                            PluginNextState::Disable(Arc::clone(&self.trigger_definition))
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// A run of a plugin that failed on every attempt it was allowed
+    struct FailedRun {
+        error: String,
+        attempts: u32,
     }
 
     enum Schedule {
@@ -746,6 +1824,12 @@ mod python_plugin {
                 TriggerSpecificationDefinition::RequestPath { .. } => {
                     Err(anyhow!("shouldn't have request path trigger for scheduled plugin").into())
                 }
+                TriggerSpecificationDefinition::Persisted { .. } => {
+                    Err(anyhow!("shouldn't have persisted trigger for scheduled plugin").into())
+                }
+                TriggerSpecificationDefinition::CatalogEvent { .. } => {
+                    Err(anyhow!("shouldn't have catalog trigger for scheduled plugin").into())
+                }
                 TriggerSpecificationDefinition::Schedule { schedule } => {
                     let schedule = CronSchedule::from_str(schedule.as_str())
                         .context("cron schedule should be parsable")?;
@@ -795,55 +1879,49 @@ mod python_plugin {
             trigger_time: DateTime<Utc>,
             db_schema: Arc<DatabaseSchema>,
         ) -> Result<PluginNextState, PluginError> {
-            // This loop is here just for the retry case.
-            loop {
-                let plugin_code = plugin.plugin_code.code();
-                let query_executor = Arc::clone(&plugin.query_executor);
-                let logger = Some(plugin.logger.clone());
-                let trigger_arguments = plugin.trigger_definition.trigger_arguments.clone();
-                let schema = Arc::clone(&db_schema);
-                let py_cache = PyCache::new_trigger_cache(
-                    Arc::clone(&plugin.manager.cache),
-                    plugin.trigger_definition.database_name.to_string(),
-                    plugin.trigger_definition.trigger_name.to_string(),
-                );
-
-                let result = tokio::task::spawn_blocking(move || {
-                    execute_schedule_trigger(
-                        plugin_code.as_ref(),
+            let plugin_code = Arc::clone(&plugin.plugin_code);
+            let plugin_module = Arc::clone(&plugin.plugin_module);
+            let wasm_module = Arc::clone(&plugin.wasm_module);
+            let query_executor = Arc::clone(&plugin.query_executor);
+            let logger = plugin.logger.clone();
+            let trigger_arguments = plugin.trigger_definition.trigger_arguments.clone();
+            let py_cache = plugin.py_cache();
+            let result = plugin
+                .execute_with_retries(move || match &*plugin_code {
+                    PluginCode::Wasm(wasm_plugin) => execute_wasm_schedule_trigger(
+                        &wasm_module,
+                        &wasm_plugin.read_if_modified(),
                         trigger_time,
-                        schema,
-                        query_executor,
-                        logger,
+                        Arc::clone(&db_schema),
+                        Arc::clone(&query_executor),
+                        Some(logger.clone()),
                         &trigger_arguments,
-                        py_cache,
-                    )
+                        py_cache.clone(),
+                    ),
+                    PluginCode::Python(python_code) => execute_schedule_trigger(
+                        &plugin_module,
+                        &python_code.code(),
+                        trigger_time,
+                        Arc::clone(&db_schema),
+                        Arc::clone(&query_executor),
+                        Some(logger.clone()),
+                        &trigger_arguments,
+                        py_cache.clone(),
+                    ),
                 })
                 .await?;
-                match result {
-                    Ok(result) => {
-                        let errors = plugin.handle_return_state(result).await;
-                        // TODO: here is one spot we'll pick up errors to put into the plugin system table
-                        for error in errors {
-                            error!(?plugin.trigger_definition, "error running schedule plugin: {}", error);
-                        }
-                        return Ok(PluginNextState::SuccessfulRun);
+            match result {
+                Ok(result) => {
+                    let errors = plugin.handle_return_state(result).await;
+                    // TODO: here is one spot we'll pick up errors to put into the plugin system table
+                    for error in errors {
+                        error!(?plugin.trigger_definition, "error running schedule plugin: {}", error);
                     }
-                    Err(err) => match &plugin.trigger_definition.trigger_settings.error_behavior {
-                        ErrorBehavior::Log => {
-                            return Ok(PluginNextState::LogError(err.to_string()));
-                        }
-                        ErrorBehavior::Retry => {
-                            warn!(
-                                "retrying trigger {} on error",
-                                plugin.trigger_definition.trigger_name
-                            );
-                        }
-                        ErrorBehavior::Disable => {
-                            return Ok(PluginNextState::Disable(plugin.trigger_definition));
-                        }
-                    },
+                    Ok(PluginNextState::SuccessfulRun)
                 }
+                Err(failed_run) => Ok(plugin
+                    .handle_failed_run(failed_run, || DeadLetterEvent::Schedule(trigger_time))
+                    .await),
             }
         }
 
@@ -860,13 +1938,41 @@ mod python_plugin {
             Some(next_trigger_time)
         }
     }
+
+    /// Build a copy of `write_batch` holding only the rows for `table_def` that match the trigger's
+    /// predicate, or `None` if no rows match.
+    pub(super) fn filter_write_batch(
+        write_batch: &WriteBatch,
+        table_def: &TableDefinition,
+        predicate: &BoundTriggerPredicate,
+    ) -> Option<WriteBatch> {
+        let table_chunks = write_batch.table_chunks.get(&table_def.table_id)?;
+        let mut filtered = TableChunks::default();
+        for (chunk_time, chunk) in &table_chunks.chunk_time_to_chunk {
+            for row in chunk.rows.iter().filter(|row| predicate.matches(row)) {
+                filtered.push_row(*chunk_time, row.clone());
+            }
+        }
+        if filtered.row_count() == 0 {
+            return None;
+        }
+
+        Some(WriteBatch {
+            catalog_sequence: write_batch.catalog_sequence,
+            database_id: write_batch.database_id,
+            database_name: Arc::clone(&write_batch.database_name),
+            min_time_ns: filtered.min_time,
+            max_time_ns: filtered.max_time,
+            table_chunks: [(table_def.table_id, filtered)].into_iter().collect(),
+        })
+    }
 }
 
 pub(crate) fn run_test_wal_plugin(
     now_time: iox_time::Time,
     catalog: Arc<Catalog>,
     query_executor: Arc<dyn QueryExecutor>,
-    code: String,
+    code: PluginCode,
     cache: Arc<Mutex<CacheStore>>,
     request: WalPluginTestRequest,
 ) -> Result<WalPluginTestResponse, PluginError> {
@@ -889,26 +1995,44 @@ pub(crate) fn run_test_wal_plugin(
     let db = parsed.inner().txn().db_schema_cloned();
     let data = parsed.ignore_catalog_changes_and_convert_lines_to_buffer(Gen1Duration::new_1m());
 
-    let plugin_return_state = influxdb3_py_api::system_py::execute_python_with_batch(
-        &code,
-        &data.valid_data,
-        db,
-        query_executor,
-        None,
-        None,
-        &request.input_arguments,
-        PyCache::new_test_cache(
-            cache,
-            request
-                .cache_name
-                .unwrap_or_else(|| "_shared_test".to_string()),
-        ),
-    )?;
+    let cache_name = request
+        .cache_name
+        .unwrap_or_else(|| "_shared_test".to_string());
+    let line_writer = Arc::new(ValidatingLineWriter::new(
+        Arc::clone(&catalog),
+        now_time,
+        database.clone(),
+    ));
+    let plugin_return_state = match &code {
+        PluginCode::Wasm(wasm_plugin) => crate::wasm_plugin::execute_wasm_with_batch(
+            &WasmPluginModule::default(),
+            &wasm_plugin.read_if_modified(),
+            &data.valid_data,
+            db,
+            query_executor,
+            None,
+            None,
+            &request.input_arguments,
+            PyCache::new_test_cache(cache, cache_name),
+        )?,
+        PluginCode::Python(python_code) => influxdb3_py_api::system_py::execute_python_with_batch(
+            &PluginModule::new("test").with_line_writer(Arc::clone(&line_writer) as _),
+            &python_code.code(),
+            &data.valid_data,
+            db,
+            query_executor,
+            None,
+            None,
+            &request.input_arguments,
+            PyCache::new_test_cache(cache, cache_name),
+        )?,
+    };
 
     let log_lines = plugin_return_state.log();
 
     let mut database_writes = plugin_return_state.write_db_lines;
     database_writes.insert(database, plugin_return_state.write_back_lines);
+    add_sync_writes(&mut database_writes, &line_writer);
 
     let test_write_handler = TestWriteHandler::new(Arc::clone(&catalog), now_time);
     let errors = test_write_handler.validate_all_writes(&database_writes);
@@ -920,6 +2044,16 @@ pub(crate) fn run_test_wal_plugin(
     })
 }
 
+/// Add the lines that a tested plugin wrote through `write_sync()` to its other writes
+fn add_sync_writes(
+    database_writes: &mut HashMap<String, Vec<String>>,
+    line_writer: &ValidatingLineWriter,
+) {
+    for (db_name, lines) in line_writer.take_writes() {
+        database_writes.entry(db_name).or_default().extend(lines);
+    }
+}
+
 #[derive(Debug)]
 pub struct TestWriteHandler {
     catalog: Arc<Catalog>,
@@ -1006,7 +2140,7 @@ pub(crate) fn run_test_schedule_plugin(
     now_time: iox_time::Time,
     catalog: Arc<Catalog>,
     query_executor: Arc<dyn QueryExecutor>,
-    code: String,
+    code: PluginCode,
     cache: Arc<Mutex<CacheStore>>,
     request: influxdb3_types::http::SchedulePluginTestRequest,
 ) -> Result<influxdb3_types::http::SchedulePluginTestResponse, PluginError> {
@@ -1022,20 +2156,36 @@ pub(crate) fn run_test_schedule_plugin(
         ));
     };
 
-    let plugin_return_state = influxdb3_py_api::system_py::execute_schedule_trigger(
-        &code,
-        schedule_time,
-        db,
-        query_executor,
-        None,
-        &request.input_arguments,
-        PyCache::new_test_cache(
-            cache,
-            request
-                .cache_name
-                .unwrap_or_else(|| "_shared_test".to_string()),
-        ),
-    )?;
+    let cache_name = request
+        .cache_name
+        .unwrap_or_else(|| "_shared_test".to_string());
+    let line_writer = Arc::new(ValidatingLineWriter::new(
+        Arc::clone(&catalog),
+        now_time,
+        database.clone(),
+    ));
+    let plugin_return_state = match &code {
+        PluginCode::Wasm(wasm_plugin) => crate::wasm_plugin::execute_wasm_schedule_trigger(
+            &WasmPluginModule::default(),
+            &wasm_plugin.read_if_modified(),
+            schedule_time,
+            db,
+            query_executor,
+            None,
+            &request.input_arguments,
+            PyCache::new_test_cache(cache, cache_name),
+        )?,
+        PluginCode::Python(python_code) => influxdb3_py_api::system_py::execute_schedule_trigger(
+            &PluginModule::new("test").with_line_writer(Arc::clone(&line_writer) as _),
+            &python_code.code(),
+            schedule_time,
+            db,
+            query_executor,
+            None,
+            &request.input_arguments,
+            PyCache::new_test_cache(cache, cache_name),
+        )?,
+    };
 
     let log_lines = plugin_return_state.log();
 
@@ -1043,6 +2193,7 @@ pub(crate) fn run_test_schedule_plugin(
     if !plugin_return_state.write_back_lines.is_empty() {
         database_writes.insert(database, plugin_return_state.write_back_lines);
     }
+    add_sync_writes(&mut database_writes, &line_writer);
 
     let test_write_handler = TestWriteHandler::new(Arc::clone(&catalog), now_time);
     let errors = test_write_handler.validate_all_writes(&database_writes);
@@ -1056,13 +2207,117 @@ pub(crate) fn run_test_schedule_plugin(
     })
 }
 
+pub(crate) fn run_test_request_plugin(
+    now_time: iox_time::Time,
+    catalog: Arc<Catalog>,
+    query_executor: Arc<dyn QueryExecutor>,
+    code: PluginCode,
+    cache: Arc<Mutex<CacheStore>>,
+    request: influxdb3_types::http::RequestPluginTestRequest,
+) -> Result<influxdb3_types::http::RequestPluginTestResponse, PluginError> {
+    let database = request.database;
+    let db = catalog.db_schema(&database).ok_or(PluginError::MissingDb)?;
+
+    let plugin_request = PluginRequest {
+        method: request.method.unwrap_or_else(|| "GET".to_string()),
+        path: request.path.unwrap_or_default(),
+        query_params: request.query_params.unwrap_or_default(),
+        headers: request.headers.unwrap_or_default(),
+        body: request.body.map(Bytes::from).unwrap_or_default(),
+        token_id: None,
+    };
+
+    let cache_name = request
+        .cache_name
+        .unwrap_or_else(|| "_shared_test".to_string());
+    let line_writer = Arc::new(ValidatingLineWriter::new(
+        Arc::clone(&catalog),
+        now_time,
+        database.clone(),
+    ));
+    let (response, plugin_return_state) = match &code {
+        PluginCode::Wasm(wasm_plugin) => crate::wasm_plugin::execute_wasm_request_trigger(
+            &WasmPluginModule::default(),
+            &wasm_plugin.read_if_modified(),
+            db,
+            query_executor,
+            None,
+            &request.input_arguments,
+            &plugin_request,
+            PyCache::new_test_cache(cache, cache_name),
+        )?,
+        PluginCode::Python(python_code) => influxdb3_py_api::system_py::execute_request_trigger(
+            &PluginModule::new("test").with_line_writer(Arc::clone(&line_writer) as _),
+            &python_code.code(),
+            db,
+            query_executor,
+            None,
+            &request.input_arguments,
+            &plugin_request,
+            PyCache::new_test_cache(cache, cache_name),
+        )?,
+    };
+
+    let mut errors = Vec::new();
+    let body = match response.body {
+        ResponseBody::Full(body) => body.to_vec(),
+        ResponseBody::Stream(stream) => {
+            // we're already on a blocking thread, so read the whole stream here
+            let mut body = Vec::new();
+            while let Some(chunk) = stream.next_chunk() {
+                match chunk {
+                    Ok(chunk) => body.extend_from_slice(&chunk),
+                    Err(e) => {
+                        errors.push(format!("error streaming response body: {e}"));
+                        break;
+                    }
+                }
+            }
+            body
+        }
+    };
+
+    let log_lines = plugin_return_state.log();
+
+    let mut database_writes = plugin_return_state.write_db_lines;
+    if !plugin_return_state.write_back_lines.is_empty() {
+        database_writes.insert(database, plugin_return_state.write_back_lines);
+    }
+    add_sync_writes(&mut database_writes, &line_writer);
+
+    let test_write_handler = TestWriteHandler::new(Arc::clone(&catalog), now_time);
+    errors.extend(test_write_handler.validate_all_writes(&database_writes));
+
+    Ok(influxdb3_types::http::RequestPluginTestResponse {
+        status: Some(response.status),
+        headers: response.headers,
+        body: B64_STANDARD.encode(&body),
+        log_lines,
+        database_writes,
+        errors,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::secrets::DatabaseSecrets;
     use crate::virtualenv::init_pyo3;
+    use chrono::Utc;
+    use futures_util::FutureExt;
+    use futures_util::future::BoxFuture;
     use hashbrown::HashMap;
-    use influxdb3_catalog::catalog::Catalog;
+    use influxdb3_catalog::catalog::{Catalog, DatabaseSchema};
+    use influxdb3_catalog::log::FieldDataType;
+    use influxdb3_catalog::trigger_predicate::TriggerPredicate;
+    use influxdb3_id::DbId;
     use influxdb3_internal_api::query_executor::UnimplementedQueryExecutor;
+    use influxdb3_py_api::ExecutePluginError;
+    use influxdb3_py_api::system_py::{
+        ExecutionLimits, LineWriter, LinesWritten, PluginEnvironment, RejectedLine,
+        SyncWriteOptions,
+    };
+    use influxdb3_types::http::RequestPluginTestRequest;
     use influxdb3_write::Precision;
     use influxdb3_write::write_buffer::validator::WriteValidator;
     use iox_time::{MockProvider, Time};
@@ -1136,7 +2391,7 @@ def process_writes(influxdb3_local, table_batches, args=None):
             now,
             Arc::new(catalog),
             executor,
-            code.to_string(),
+            PluginCode::Python(Arc::new(PythonPluginCode::Github(Arc::from(code)))),
             cache,
             request,
         )
@@ -1241,7 +2496,7 @@ def process_writes(influxdb3_local, table_batches, args=None):
             now,
             Arc::clone(&catalog),
             executor,
-            code.to_string(),
+            PluginCode::Python(Arc::new(PythonPluginCode::Github(Arc::from(code)))),
             cache,
             request,
         )
@@ -1271,4 +2526,890 @@ def process_writes(influxdb3_local, table_batches, args=None):
         let expected_error = "line protocol parse error on write to db foodb: WriteLineError { original_line: \"cpu,host=A f1=not_an_int\", line_number: 2, error_message: \"invalid column type for column 'f1', expected iox::column_type::field::integer, got iox::column_type::field::string\" }";
         assert_eq!(response.errors[0], expected_error);
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_wal_plugin_write_sync() {
+        init_pyo3();
+        let now = Time::from_timestamp_nanos(1);
+        let time_provider: Arc<dyn TimeProvider> = Arc::new(MockProvider::new(now));
+        let cache = Arc::new(Mutex::new(CacheStore::new(
+            Arc::clone(&time_provider),
+            Duration::from_secs(10),
+        )));
+        let catalog = Arc::new(
+            Catalog::new(
+                "foo",
+                Arc::new(InMemory::new()),
+                time_provider,
+                Default::default(),
+            )
+            .await
+            .unwrap(),
+        );
+
+        let code = r#"
+def process_writes(influxdb3_local, table_batches, args=None):
+    valid = LineBuilder("cpu").tag("host", "A").int64_field("f1", 10)
+    invalid = LineBuilder("cpu").tag("host", "A").string_field("f1", "not_an_int")
+    result = influxdb3_local.write_sync([valid, invalid], db_name="foodb")
+    influxdb3_local.info(result["accepted"], result["rejected"])"#;
+
+        let request = WalPluginTestRequest {
+            filename: "test".into(),
+            database: "_testdb".into(),
+            input_lp: "mem,host=B user=43.1 120".to_string(),
+            cache_name: None,
+            input_arguments: None,
+        };
+
+        // the test runs plugins on a blocking thread, as the server does
+        let response = tokio::task::spawn_blocking(move || {
+            run_test_wal_plugin(
+                now,
+                catalog,
+                Arc::new(UnimplementedQueryExecutor),
+                PluginCode::Python(Arc::new(PythonPluginCode::Github(Arc::from(code)))),
+                cache,
+                request,
+            )
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(vec!["INFO: 1 1".to_string()], response.log_lines);
+        // only the line that would have been accepted is reported as written
+        assert_eq!(
+            &vec!["cpu,host=A f1=10i".to_string()],
+            response.database_writes.get("foodb").unwrap()
+        );
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+    }
+
+    #[tokio::test]
+    async fn test_request_plugin() {
+        init_pyo3();
+        let now = Time::from_timestamp_nanos(1);
+        let time_provider: Arc<dyn TimeProvider> = Arc::new(MockProvider::new(now));
+        let cache = Arc::new(Mutex::new(CacheStore::new(
+            Arc::clone(&time_provider),
+            Duration::from_secs(10),
+        )));
+        let catalog = Arc::new(
+            Catalog::new(
+                "foo",
+                Arc::new(InMemory::new()),
+                time_provider,
+                Default::default(),
+            )
+            .await
+            .unwrap(),
+        );
+        catalog.create_database("foodb").await.unwrap();
+        catalog
+            .create_table("foodb", "cpu", &["host"], &[("f1", FieldDataType::Integer)])
+            .await
+            .unwrap();
+
+        let code = r#"
+def process_request(influxdb3_local, query_parameters, request_headers, request_body, args=None, *, method=None, path=None):
+    influxdb3_local.info(f"{method} /{path} {query_parameters['q']} {request_headers['h']}")
+    influxdb3_local.write(LineBuilder("cpu").tag("host", "A").int64_field("f1", 1))
+    influxdb3_local.write(LineBuilder("cpu").tag("host", "A").string_field("f1", "not_an_int"))
+
+    def body():
+        yield "got "
+        yield request_body.upper()
+    return body(), 201, {"Content-Type": "text/plain"}"#;
+
+        let request = RequestPluginTestRequest {
+            filename: "test".into(),
+            database: "foodb".into(),
+            method: Some("POST".into()),
+            path: Some("sub/path".into()),
+            query_params: Some(HashMap::from([("q".to_string(), "1".to_string())])),
+            headers: Some(HashMap::from([("h".to_string(), "2".to_string())])),
+            body: Some("hello".into()),
+            cache_name: None,
+            input_arguments: None,
+        };
+
+        let executor: Arc<dyn QueryExecutor> = Arc::new(UnimplementedQueryExecutor);
+
+        let response = run_test_request_plugin(
+            now,
+            Arc::clone(&catalog),
+            executor,
+            PluginCode::Python(Arc::new(PythonPluginCode::Github(Arc::from(code)))),
+            cache,
+            request,
+        )
+        .unwrap();
+
+        assert_eq!(response.status, Some(201));
+        assert_eq!(
+            response.headers.get("Content-Type").map(String::as_str),
+            Some("text/plain")
+        );
+        assert_eq!(B64_STANDARD.decode(&response.body).unwrap(), b"got HELLO");
+        assert_eq!(response.log_lines, vec!["INFO: POST /sub/path 1 2"]);
+        assert_eq!(
+            response.database_writes.get("foodb").unwrap(),
+            &vec![
+                "cpu,host=A f1=1i".to_string(),
+                "cpu,host=A f1=\"not_an_int\"".to_string(),
+            ]
+        );
+        assert_eq!(response.errors.len(), 1);
+        assert!(response.errors[0].contains("invalid column type for column 'f1'"));
+
+        // a binary body comes back intact:
+        let code = r#"
+def process_request(influxdb3_local, query_parameters, request_headers, request_body, args=None):
+    return b"\x00\xff\xfe", 200, {"Content-Type": "application/octet-stream"}"#;
+        let request = RequestPluginTestRequest {
+            filename: "test".into(),
+            database: "foodb".into(),
+            method: None,
+            path: None,
+            query_params: None,
+            headers: None,
+            body: None,
+            cache_name: None,
+            input_arguments: None,
+        };
+        let response = run_test_request_plugin(
+            now,
+            Arc::clone(&catalog),
+            Arc::new(UnimplementedQueryExecutor),
+            PluginCode::Python(Arc::new(PythonPluginCode::Github(Arc::from(code)))),
+            Arc::new(Mutex::new(CacheStore::new(
+                Arc::new(MockProvider::new(now)),
+                Duration::from_secs(10),
+            ))),
+            request,
+        )
+        .unwrap();
+        assert_eq!(
+            B64_STANDARD.decode(&response.body).unwrap(),
+            [0x00, 0xff, 0xfe]
+        );
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+    }
+
+    #[tokio::test]
+    async fn test_filter_write_batch_with_predicate() {
+        let now = Time::from_timestamp_nanos(1);
+        let time_provider: Arc<dyn TimeProvider> = Arc::new(MockProvider::new(now));
+        let catalog = Arc::new(
+            Catalog::new(
+                "foo",
+                Arc::new(InMemory::new()),
+                time_provider,
+                Default::default(),
+            )
+            .await
+            .unwrap(),
+        );
+        let namespace = NamespaceName::new("foodb").unwrap();
+        let validator =
+            WriteValidator::initialize(namespace.clone(), Arc::clone(&catalog)).unwrap();
+        let lp = [
+            "cpu,host=A,region=west usage=95.0 100",
+            "cpu,host=B,region=east usage=42.0 200",
+            "cpu,host=C,region=west usage=12.0 300",
+            "mem,host=A free=10i 400",
+        ]
+        .join("\n");
+        let parsed = validator
+            .v1_parse_lines_and_catalog_updates(&lp, false, now, Precision::Nanosecond)
+            .unwrap();
+        let db = parsed.inner().txn().db_schema_cloned();
+        let data =
+            parsed.ignore_catalog_changes_and_convert_lines_to_buffer(Gen1Duration::new_1m());
+        let table_def = db.table_definition("cpu").unwrap();
+
+        let predicate: TriggerPredicate = "region = 'west' and usage > 50".parse().unwrap();
+        let filtered = python_plugin::filter_write_batch(
+            &data.valid_data,
+            &table_def,
+            &predicate.bind(&table_def),
+        )
+        .unwrap();
+        assert_eq!(1, filtered.table_chunks.len());
+        let chunks = filtered.table_chunks.get(&table_def.table_id).unwrap();
+        assert_eq!(1, chunks.row_count());
+        assert_eq!(100, filtered.min_time_ns);
+        assert_eq!(100, filtered.max_time_ns);
+
+        let predicate: TriggerPredicate = "host = 'D'".parse().unwrap();
+        assert!(
+            python_plugin::filter_write_batch(
+                &data.valid_data,
+                &table_def,
+                &predicate.bind(&table_def)
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_plugin_module_is_retained() {
+        init_pyo3();
+        let time_provider: Arc<dyn TimeProvider> =
+            Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let cache = Arc::new(Mutex::new(CacheStore::new(
+            time_provider,
+            Duration::from_secs(10),
+        )));
+        let schema = Arc::new(DatabaseSchema::new(DbId::new(0), "foo".into()));
+        let executor: Arc<dyn QueryExecutor> = Arc::new(UnimplementedQueryExecutor);
+        let code: Arc<str> = Arc::from(
+            r#"
+calls = 0
+
+def init(influxdb3_local, args=None):
+    influxdb3_local.info("init " + args["name"])
+
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    global calls
+    calls += 1
+    influxdb3_local.info(f"calls: {calls}")
+
+def shutdown(influxdb3_local):
+    influxdb3_local.write(LineBuilder("shutdown").int64_field("calls", calls))
+"#,
+        );
+        let args = Some(HashMap::from([("name".to_string(), "test".to_string())]));
+        let plugin_module = PluginModule::new("test");
+        let run = |code: &Arc<str>| {
+            influxdb3_py_api::system_py::execute_schedule_trigger(
+                &plugin_module,
+                code,
+                Utc::now(),
+                Arc::clone(&schema),
+                Arc::clone(&executor),
+                None,
+                &args,
+                PyCache::new_test_cache(Arc::clone(&cache), "test".to_string()),
+            )
+            .unwrap()
+            .log()
+        };
+
+        // the module is compiled, and initialized, only on the first run:
+        assert_eq!(vec!["INFO: init test", "INFO: calls: 1"], run(&code));
+        assert_eq!(vec!["INFO: calls: 2"], run(&code));
+
+        // changed code is compiled into a new module, after the old one is shut down:
+        let changed: Arc<str> = Arc::from(format!("{code}\n# changed"));
+        let state = influxdb3_py_api::system_py::execute_schedule_trigger(
+            &plugin_module,
+            &changed,
+            Utc::now(),
+            Arc::clone(&schema),
+            Arc::clone(&executor),
+            None,
+            &args,
+            PyCache::new_test_cache(Arc::clone(&cache), "test".to_string()),
+        )
+        .unwrap();
+        assert_eq!(vec!["shutdown calls=2i"], state.write_back_lines);
+        assert_eq!(vec!["INFO: init test", "INFO: calls: 1"], state.log());
+
+        let state = plugin_module
+            .shutdown(
+                schema,
+                executor,
+                None,
+                PyCache::new_test_cache(cache, "test".to_string()),
+            )
+            .unwrap();
+        assert_eq!(vec!["shutdown calls=1i"], state.write_back_lines);
+    }
+
+    #[test]
+    fn test_cache_checkpoint_round_trip() {
+        init_pyo3();
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let new_cache = || {
+            Arc::new(Mutex::new(CacheStore::new(
+                Arc::clone(&time_provider) as _,
+                Duration::from_secs(10),
+            )))
+        };
+        let schema = Arc::new(DatabaseSchema::new(DbId::new(0), "foo".into()));
+        let executor: Arc<dyn QueryExecutor> = Arc::new(UnimplementedQueryExecutor);
+        let run = |code: &str, py_cache: PyCache| {
+            influxdb3_py_api::system_py::execute_schedule_trigger(
+                &PluginModule::new("test"),
+                &Arc::from(code),
+                Utc::now(),
+                Arc::clone(&schema),
+                Arc::clone(&executor),
+                None,
+                &None,
+                py_cache,
+            )
+            .unwrap()
+            .log()
+        };
+        let trigger_cache = |cache: &Arc<Mutex<CacheStore>>, trigger_name: &str| {
+            PyCache::new_trigger_cache(Arc::clone(cache), "foo".into(), trigger_name.into())
+        };
+
+        let cache = new_cache();
+        run(
+            r#"
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    influxdb3_local.cache.put("counts", {"a": 1, "b": [2, 3]})
+    influxdb3_local.cache.put("expiring", "soon", ttl=5)
+    influxdb3_local.cache.put("shared", 42, use_global=True)
+    influxdb3_local.cache.put("lock", __import__("threading").Lock())"#,
+            trigger_cache(&cache, "kept"),
+        );
+        run(
+            r#"
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    influxdb3_local.cache.put("other", 1)"#,
+            trigger_cache(&cache, "removed"),
+        );
+        run(
+            r#"
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    influxdb3_local.cache.put("test", 1)"#,
+            PyCache::new_test_cache(Arc::clone(&cache), "test".into()),
+        );
+        let checkpoint = CacheStore::checkpoint(&cache).unwrap();
+
+        // restore once the entry with a ttl has expired, and the "removed" trigger is gone
+        time_provider.set(Time::from_timestamp_nanos(10_000_000_000));
+        let restored_cache = new_cache();
+        let restored = CacheStore::restore(&restored_cache, &checkpoint, |_, trigger_name| {
+            trigger_name == "kept"
+        })
+        .unwrap();
+        assert_eq!(2, restored);
+
+        let read = r#"
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    cache = influxdb3_local.cache
+    for key in ["counts", "expiring", "lock", "other", "test"]:
+        influxdb3_local.info(f"{key}: {cache.get(key)}")
+    influxdb3_local.info(f"shared: {cache.get('shared', use_global=True)}")"#;
+        assert_eq!(
+            vec![
+                "INFO: counts: {'a': 1, 'b': [2, 3]}",
+                "INFO: expiring: None",
+                "INFO: lock: None",
+                "INFO: other: None",
+                "INFO: test: None",
+                "INFO: shared: 42",
+            ],
+            run(read, trigger_cache(&restored_cache, "kept"))
+        );
+        assert_eq!(
+            vec![
+                "INFO: counts: None",
+                "INFO: expiring: None",
+                "INFO: lock: None",
+                "INFO: other: None",
+                "INFO: test: None",
+                "INFO: shared: 42",
+            ],
+            run(read, trigger_cache(&restored_cache, "removed"))
+        );
+    }
+
+    #[test]
+    fn test_cache_checkpoint_only_restores_allowed_classes() {
+        use pyo3::Python;
+        use pyo3::prelude::PyAnyMethods;
+        use pyo3::types::PyBytes;
+
+        init_pyo3();
+        let cache = Arc::new(Mutex::new(CacheStore::new(
+            Arc::new(MockProvider::new(Time::from_timestamp_nanos(0))),
+            Duration::from_secs(10),
+        )));
+        // a pickle that runs code when it's loaded
+        let exploit: &[u8] = b"cbuiltins\nexec\n(S'import builtins; builtins.pwned = True'\ntR.";
+
+        let checkpoint = Python::with_gil(|py| -> pyo3::PyResult<Vec<u8>> {
+            let pickle = py.import("pickle")?;
+            let allowed = pickle.call_method1(
+                "dumps",
+                (py.import("datetime")?.getattr("timedelta")?.call1((1,))?,),
+            )?;
+            let entries = vec![
+                (
+                    "exploit".to_string(),
+                    PyBytes::new(py, exploit).into_any(),
+                    None::<i64>,
+                ),
+                ("allowed".to_string(), allowed, None),
+            ];
+            pickle
+                .call_method1("dumps", ((1u32, vec![(vec!["global"], entries)]),))?
+                .extract()
+        })
+        .unwrap();
+
+        assert_eq!(
+            1,
+            CacheStore::restore(&cache, &checkpoint, |_, _| true).unwrap()
+        );
+        assert!(CacheStore::restore(&cache, exploit, |_, _| true).is_err());
+        Python::with_gil(|py| {
+            assert!(!py.import("builtins").unwrap().hasattr("pwned").unwrap());
+        });
+    }
+
+    #[tokio::test]
+    async fn test_plugin_secrets() {
+        init_pyo3();
+        let time_provider: Arc<dyn TimeProvider> =
+            Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let cache = Arc::new(Mutex::new(CacheStore::new(
+            Arc::clone(&time_provider),
+            Duration::from_secs(10),
+        )));
+        let catalog = Arc::new(
+            Catalog::new(
+                "foo",
+                Arc::new(InMemory::new()),
+                time_provider,
+                Default::default(),
+            )
+            .await
+            .unwrap(),
+        );
+        catalog.create_database("foo").await.unwrap();
+        let key = Arc::new(SecretsKey::new(&[7; 32]).unwrap());
+        catalog
+            .create_secret(
+                "foo",
+                "api_key",
+                key.encrypt("foo", "api_key", "hunter2").unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let code: Arc<str> = Arc::from(
+            r#"
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    api_key = influxdb3_local.secrets.get(args["secret"])
+    influxdb3_local.info(f"using {api_key} for {len(api_key)} chars")
+    influxdb3_local.info(influxdb3_local.secrets.get("missing", "fallback"))
+    influxdb3_local.info(str(influxdb3_local.secrets.get("missing")))"#,
+        );
+        let args = Some(HashMap::from([(
+            "secret".to_string(),
+            "api_key".to_string(),
+        )]));
+        let run = |plugin_module: &PluginModule, code: &Arc<str>| {
+            influxdb3_py_api::system_py::execute_schedule_trigger(
+                plugin_module,
+                code,
+                Utc::now(),
+                catalog.db_schema("foo").unwrap(),
+                Arc::new(UnimplementedQueryExecutor),
+                None,
+                &args,
+                PyCache::new_test_cache(Arc::clone(&cache), "test".to_string()),
+            )
+        };
+
+        // the value is available to the plugin, but redacted from its logs:
+        let secrets = DatabaseSecrets::new(Arc::clone(&catalog), "foo", Some(key));
+        let plugin_module = PluginModule::new("test").with_secrets(Arc::new(secrets));
+        assert_eq!(
+            vec![
+                "INFO: using ****** for 7 chars",
+                "INFO: fallback",
+                "INFO: None"
+            ],
+            run(&plugin_module, &code).unwrap().log()
+        );
+
+        // and from the errors that the plugin raises:
+        let raising: Arc<str> = Arc::from(
+            r#"
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    api_key = influxdb3_local.secrets.get(args["secret"])
+    raise ValueError(f"request with {api_key} was rejected")"#,
+        );
+        let err = format!("{:?}", run(&plugin_module, &raising).unwrap_err());
+        assert!(err.contains("request with ****** was rejected"), "{err}");
+        assert!(!err.contains("hunter2"), "{err}");
+
+        // without a key, the secret can't be read:
+        let secrets = DatabaseSecrets::new(Arc::clone(&catalog), "foo", None);
+        let plugin_module = PluginModule::new("test").with_secrets(Arc::new(secrets));
+        let err = run(&plugin_module, &code).unwrap_err().to_string();
+        assert!(err.contains("secrets key"), "{err}");
+    }
+
+    #[test]
+    fn test_execution_limits() {
+        init_pyo3();
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let cache = Arc::new(Mutex::new(CacheStore::new(
+            time_provider,
+            Duration::from_secs(10),
+        )));
+        let schema = Arc::new(DatabaseSchema::new(DbId::new(0), "foo".into()));
+        let plugin_module = PluginModule::new("test").with_limits(ExecutionLimits {
+            timeout: Some(Duration::from_millis(200)),
+            max_rows_written: Some(2),
+        });
+        let run = |code: &str| {
+            influxdb3_py_api::system_py::execute_schedule_trigger(
+                &plugin_module,
+                &Arc::from(code),
+                Utc::now(),
+                Arc::clone(&schema),
+                Arc::new(UnimplementedQueryExecutor),
+                None,
+                &None,
+                PyCache::new_test_cache(Arc::clone(&cache), "test".to_string()),
+            )
+        };
+
+        // a plugin stuck in a loop is interrupted, even if it catches exceptions:
+        let err = run(r#"
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    while True:
+        try:
+            pass
+        except Exception:
+            pass"#)
+        .unwrap_err();
+        assert!(
+            matches!(err, ExecutePluginError::Timeout(t) if t == Duration::from_millis(200)),
+            "{err}"
+        );
+
+        let write = r#"
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    for i in range(int(args["rows"]) if args else 2):
+        try:
+            influxdb3_local.write(LineBuilder("cpu").int64_field("i", i))
+        except Exception:
+            pass"#;
+        // a run within the limits is unaffected by the earlier timeout:
+        let state = run(write).unwrap();
+        assert_eq!(vec!["cpu i=0i", "cpu i=1i"], state.write_back_lines);
+
+        // writing over the limit fails the run, even if the plugin catches the error:
+        let err = influxdb3_py_api::system_py::execute_schedule_trigger(
+            &plugin_module,
+            &Arc::from(write),
+            Utc::now(),
+            Arc::clone(&schema),
+            Arc::new(UnimplementedQueryExecutor),
+            None,
+            &Some(HashMap::from([("rows".to_string(), "3".to_string())])),
+            PyCache::new_test_cache(Arc::clone(&cache), "test".to_string()),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err, ExecutePluginError::RowLimitExceeded(2)),
+            "{err}"
+        );
+    }
+
+    /// Accepts lines unless they contain "bad", recording what it was asked to write
+    #[derive(Debug, Default)]
+    struct TestLineWriter {
+        writes: Mutex<Vec<(Option<String>, String, Precision, bool)>>,
+    }
+
+    impl LineWriter for TestLineWriter {
+        fn write_lp(
+            &self,
+            db_name: Option<String>,
+            lines: String,
+            options: SyncWriteOptions,
+        ) -> BoxFuture<'static, Result<LinesWritten, anyhow::Error>> {
+            let mut written = LinesWritten::default();
+            for (i, line) in lines.lines().enumerate() {
+                if line.contains("bad") {
+                    written.rejected.push(RejectedLine {
+                        line_number: i + 1,
+                        line: line.to_string(),
+                        error: "bad line".to_string(),
+                    });
+                } else {
+                    written.accepted += 1;
+                }
+            }
+            self.writes
+                .lock()
+                .push((db_name, lines, options.precision, options.no_sync));
+            futures_util::future::ready(Ok(written)).boxed()
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_write_sync() {
+        init_pyo3();
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let cache = Arc::new(Mutex::new(CacheStore::new(
+            time_provider,
+            Duration::from_secs(10),
+        )));
+        let line_writer = Arc::new(TestLineWriter::default());
+        let plugin_module = PluginModule::new("test")
+            .with_line_writer(Arc::clone(&line_writer) as _)
+            .with_limits(ExecutionLimits {
+                timeout: None,
+                max_rows_written: Some(3),
+            });
+        let run = |code: &str| {
+            influxdb3_py_api::system_py::execute_schedule_trigger(
+                &plugin_module,
+                &Arc::from(code),
+                Utc::now(),
+                Arc::new(DatabaseSchema::new(DbId::new(0), "foo".into())),
+                Arc::new(UnimplementedQueryExecutor),
+                None,
+                &None,
+                PyCache::new_test_cache(Arc::clone(&cache), "test".to_string()),
+            )
+        };
+
+        let state = run(r#"
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    result = influxdb3_local.write_sync(LineBuilder("cpu").int64_field("i", 0))
+    influxdb3_local.info(result["accepted"], result["rejected"])
+    result = influxdb3_local.write_sync(
+        [LineBuilder("mem").int64_field("i", 1), LineBuilder("bad").int64_field("i", 2)],
+        db_name="other",
+        precision="ms",
+        no_sync=True,
+    )
+    influxdb3_local.info(result["accepted"], result["rejected"], result["errors"])"#)
+        .unwrap();
+        assert_eq!(
+            vec![
+                "INFO: 1 0",
+                "INFO: 1 1 [{'line_number': 2, 'line': 'bad i=2i', 'error': 'bad line'}]"
+            ],
+            state.log()
+        );
+        // the lines were written during the run, rather than returned for writing after it:
+        assert!(state.write_back_lines.is_empty());
+        assert_eq!(
+            vec![
+                (None, "cpu i=0i".to_string(), Precision::Auto, false),
+                (
+                    Some("other".to_string()),
+                    "mem i=1i\nbad i=2i".to_string(),
+                    Precision::Millisecond,
+                    true
+                ),
+            ],
+            *line_writer.writes.lock()
+        );
+
+        // the lines count towards the limit of rows written, and are not written over it:
+        let err = run(r#"
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    influxdb3_local.write(LineBuilder("cpu").int64_field("i", 0))
+    influxdb3_local.write_sync([LineBuilder("cpu").int64_field("i", i) for i in range(3)])"#)
+        .unwrap_err();
+        assert!(
+            matches!(err, ExecutePluginError::RowLimitExceeded(3)),
+            "{err}"
+        );
+        assert_eq!(2, line_writer.writes.lock().len());
+
+        // an unknown precision is an error:
+        let err = run(r#"
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    influxdb3_local.write_sync(LineBuilder("cpu").int64_field("i", 0), precision="h")"#)
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("unrecognized precision unit"), "{err}");
+    }
+
+    /// An environment whose site-packages is a directory made by the test
+    #[derive(Debug)]
+    struct TestEnvironment(std::path::PathBuf);
+
+    impl PluginEnvironment for TestEnvironment {
+        fn site_packages(&self) -> Result<std::path::PathBuf, anyhow::Error> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn test_plugin_environments() {
+        init_pyo3();
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let cache = Arc::new(Mutex::new(CacheStore::new(
+            time_provider,
+            Duration::from_secs(10),
+        )));
+        // two environments with different versions of the same package:
+        let dir = tempfile::tempdir().unwrap();
+        let environment = |version: &str| {
+            let site_packages = dir.path().join(version).join("site-packages");
+            std::fs::create_dir_all(&site_packages).unwrap();
+            std::fs::write(
+                site_packages.join("influxdb3_test_lib.py"),
+                format!("VERSION = {version:?}"),
+            )
+            .unwrap();
+            PluginModule::new(version).with_environment(Arc::new(TestEnvironment(site_packages)))
+        };
+        let (v1, v2) = (environment("v1"), environment("v2"));
+        let shared = PluginModule::new("shared");
+        let code: Arc<str> = Arc::from(
+            r#"
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    try:
+        import influxdb3_test_lib
+        influxdb3_local.info(influxdb3_test_lib.VERSION)
+    except ImportError:
+        influxdb3_local.info("not installed")"#,
+        );
+        let run = |plugin_module: &PluginModule| {
+            influxdb3_py_api::system_py::execute_schedule_trigger(
+                plugin_module,
+                &code,
+                Utc::now(),
+                Arc::new(DatabaseSchema::new(DbId::new(0), "foo".into())),
+                Arc::new(UnimplementedQueryExecutor),
+                None,
+                &None,
+                PyCache::new_test_cache(Arc::clone(&cache), "test".to_string()),
+            )
+            .unwrap()
+            .log()
+        };
+
+        // each plugin imports the version in its own environment, however the runs interleave:
+        assert_eq!(vec!["INFO: v1"], run(&v1));
+        assert_eq!(vec!["INFO: v2"], run(&v2));
+        assert_eq!(vec!["INFO: v1"], run(&v1));
+        // and the package isn't left behind for plugins in the shared environment:
+        assert_eq!(vec!["INFO: not installed"], run(&shared));
+
+        // a plugin in the shared environment isn't held up by a plugin in an environment of its
+        // own:
+        let sleeping_code: Arc<str> = Arc::from(
+            r#"
+import time
+
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    import influxdb3_test_lib
+    time.sleep(0.5)
+    influxdb3_local.info(influxdb3_test_lib.VERSION)"#,
+        );
+        std::thread::scope(|scope| {
+            let sleeping = scope.spawn(|| {
+                influxdb3_py_api::system_py::execute_schedule_trigger(
+                    &v1,
+                    &sleeping_code,
+                    Utc::now(),
+                    Arc::new(DatabaseSchema::new(DbId::new(0), "foo".into())),
+                    Arc::new(UnimplementedQueryExecutor),
+                    None,
+                    &None,
+                    PyCache::new_test_cache(Arc::clone(&cache), "test".to_string()),
+                )
+                .unwrap()
+                .log()
+            });
+            std::thread::sleep(Duration::from_millis(100));
+            let started = std::time::Instant::now();
+            run(&shared);
+            assert!(started.elapsed() < Duration::from_millis(300));
+            assert_eq!(vec!["INFO: v1"], sleeping.join().unwrap());
+        });
+    }
+
+    /// Takes half a second to write lines
+    #[derive(Debug)]
+    struct SlowLineWriter;
+
+    impl LineWriter for SlowLineWriter {
+        fn write_lp(
+            &self,
+            _db_name: Option<String>,
+            lines: String,
+            _options: SyncWriteOptions,
+        ) -> BoxFuture<'static, Result<LinesWritten, anyhow::Error>> {
+            async move {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                Ok(LinesWritten {
+                    accepted: lines.lines().count(),
+                    ..Default::default()
+                })
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_plugin_environment_released_during_host_calls() {
+        init_pyo3();
+        let cache = Arc::new(Mutex::new(CacheStore::new(
+            Arc::new(MockProvider::new(Time::from_timestamp_nanos(0))),
+            Duration::from_secs(10),
+        )));
+        let dir = tempfile::tempdir().unwrap();
+        let environment = |version: &str| {
+            let site_packages = dir.path().join(version).join("site-packages");
+            std::fs::create_dir_all(&site_packages).unwrap();
+            std::fs::write(
+                site_packages.join("influxdb3_host_call_lib.py"),
+                format!("VERSION = {version:?}"),
+            )
+            .unwrap();
+            Arc::new(
+                PluginModule::new(version)
+                    .with_environment(Arc::new(TestEnvironment(site_packages)))
+                    .with_line_writer(Arc::new(SlowLineWriter)),
+            )
+        };
+        let (v1, v2) = (environment("v1"), environment("v2"));
+        let code: Arc<str> = Arc::from(
+            r#"
+def process_scheduled_call(influxdb3_local, call_time, args=None):
+    import influxdb3_host_call_lib
+    if args:
+        influxdb3_local.write_sync(LineBuilder("cpu").int64_field("i", 0))
+    influxdb3_local.info(influxdb3_host_call_lib.VERSION)"#,
+        );
+        let run = |plugin_module: Arc<PluginModule>, write: bool| {
+            let code = Arc::clone(&code);
+            let cache = Arc::clone(&cache);
+            tokio::task::spawn_blocking(move || {
+                influxdb3_py_api::system_py::execute_schedule_trigger(
+                    &plugin_module,
+                    &code,
+                    Utc::now(),
+                    Arc::new(DatabaseSchema::new(DbId::new(0), "foo".into())),
+                    Arc::new(UnimplementedQueryExecutor),
+                    None,
+                    &write.then(|| HashMap::from([("write".to_string(), "1".to_string())])),
+                    PyCache::new_test_cache(cache, "test".to_string()),
+                )
+                .unwrap()
+                .log()
+            })
+        };
+
+        // while a plugin waits on a write, a plugin in another environment can run, and each
+        // sees its own environment's modules:
+        let writing = run(Arc::clone(&v1), true);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let started = std::time::Instant::now();
+        assert_eq!(vec!["INFO: v2"], run(v2, false).await.unwrap());
+        assert!(started.elapsed() < Duration::from_millis(300));
+        assert_eq!(vec!["INFO: v1"], writing.await.unwrap());
+    }
 }
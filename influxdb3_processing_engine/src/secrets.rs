@@ -0,0 +1,241 @@
+//! Secrets that the plugins of a database's triggers can read
+//!
+//! Secrets are kept in the catalog with the database they belong to, encrypted with AES-256-GCM
+//! under a key that the server reads from a file given at startup, so the catalog never holds
+//! a secret's value in plaintext. The key file holds a 32 byte key, hex encoded, such as one
+//! made with `openssl rand -hex 32`.
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use influxdb3_catalog::catalog::Catalog;
+use influxdb3_py_api::system_py::SecretReader;
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SecretsError {
+    #[error("unable to read secrets key file {path:?}: {source}")]
+    ReadKeyFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("secrets key file {0:?} must hold a 32 byte key, hex encoded")]
+    InvalidKey(PathBuf),
+
+    #[error("the server was not started with a secrets key file")]
+    NoKey,
+
+    #[error("failed to encrypt secret {0}")]
+    Encrypt(String),
+
+    #[error("failed to decrypt secret {0}, it may have been encrypted with a different key")]
+    Decrypt(String),
+}
+
+/// The key that secrets are encrypted with
+pub struct SecretsKey {
+    key: LessSafeKey,
+    /// Derived from the same key, for authenticating data that the server writes to object store
+    /// and reads back
+    signing_key: hmac::Key,
+    rng: SystemRandom,
+}
+
+impl std::fmt::Debug for SecretsKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretsKey").finish_non_exhaustive()
+    }
+}
+
+impl SecretsKey {
+    pub fn from_file(path: &Path) -> Result<Self, SecretsError> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|source| SecretsError::ReadKeyFile {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        let key = hex::decode(contents.trim())
+            .map_err(|_| SecretsError::InvalidKey(path.to_path_buf()))?;
+        Self::new(&key).ok_or_else(|| SecretsError::InvalidKey(path.to_path_buf()))
+    }
+
+    pub(crate) fn new(key: &[u8]) -> Option<Self> {
+        let signing_key = hmac::sign(
+            &hmac::Key::new(hmac::HMAC_SHA256, key),
+            b"influxdb3 signing key",
+        );
+        let key = UnboundKey::new(&AES_256_GCM, key).ok()?;
+        Some(Self {
+            key: LessSafeKey::new(key),
+            signing_key: hmac::Key::new(hmac::HMAC_SHA256, signing_key.as_ref()),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Sign `data`, returning a tag of [`SIGNATURE_LEN`] bytes that [`SecretsKey::verify`]
+    /// accepts
+    pub(crate) fn sign(&self, data: &[u8]) -> Vec<u8> {
+        hmac::sign(&self.signing_key, data).as_ref().to_vec()
+    }
+
+    /// Whether `tag` is the signature of `data`, made with this key
+    pub(crate) fn verify(&self, data: &[u8], tag: &[u8]) -> bool {
+        hmac::verify(&self.signing_key, data, tag).is_ok()
+    }
+
+    /// Encrypt the value of a secret, returning the nonce followed by the ciphertext. The
+    /// database and secret names are authenticated along with the value, so that an encrypted
+    /// value can't be moved to another secret.
+    pub fn encrypt(&self, db_name: &str, name: &str, value: &str) -> Result<Vec<u8>, SecretsError> {
+        let mut nonce = [0; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| SecretsError::Encrypt(name.to_string()))?;
+        let mut in_out = value.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(associated_data(db_name, name)),
+                &mut in_out,
+            )
+            .map_err(|_| SecretsError::Encrypt(name.to_string()))?;
+
+        let mut encrypted = nonce.to_vec();
+        encrypted.append(&mut in_out);
+        Ok(encrypted)
+    }
+
+    pub fn decrypt(
+        &self,
+        db_name: &str,
+        name: &str,
+        encrypted: &[u8],
+    ) -> Result<String, SecretsError> {
+        let err = || SecretsError::Decrypt(name.to_string());
+        if encrypted.len() < NONCE_LEN {
+            return Err(err());
+        }
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| err())?;
+        let mut in_out = ciphertext.to_vec();
+        let value = self
+            .key
+            .open_in_place(
+                nonce,
+                Aad::from(associated_data(db_name, name)),
+                &mut in_out,
+            )
+            .map_err(|_| err())?;
+        String::from_utf8(value.to_vec()).map_err(|_| err())
+    }
+}
+
+/// The length of a signature made by [`SecretsKey::sign`]
+pub(crate) const SIGNATURE_LEN: usize = 32;
+
+fn associated_data(db_name: &str, name: &str) -> Vec<u8> {
+    // names can't contain a NUL, so this can't be ambiguous
+    [db_name.as_bytes(), b"\0", name.as_bytes()].concat()
+}
+
+/// Reads the secrets of a database, from the latest version of the catalog, so that secrets
+/// created after a trigger started can be read by its plugin.
+#[derive(Debug)]
+pub(crate) struct DatabaseSecrets {
+    catalog: Arc<Catalog>,
+    db_name: String,
+    key: Option<Arc<SecretsKey>>,
+}
+
+impl DatabaseSecrets {
+    pub(crate) fn new(
+        catalog: Arc<Catalog>,
+        db_name: impl Into<String>,
+        key: Option<Arc<SecretsKey>>,
+    ) -> Self {
+        Self {
+            catalog,
+            db_name: db_name.into(),
+            key,
+        }
+    }
+}
+
+impl SecretReader for DatabaseSecrets {
+    fn read(&self, name: &str) -> Result<Option<String>, anyhow::Error> {
+        let key = self.key.as_ref().ok_or(SecretsError::NoKey)?;
+        let Some(secret) = self
+            .catalog
+            .db_schema(&self.db_name)
+            .and_then(|db| db.secrets.get_by_name(name))
+        else {
+            return Ok(None);
+        };
+        Ok(Some(key.decrypt(
+            &self.db_name,
+            name,
+            &secret.encrypted_value,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key = SecretsKey::new(&[7; 32]).unwrap();
+        let encrypted = key.encrypt("db", "api_key", "hunter2").unwrap();
+        assert!(!encrypted.windows(7).any(|w| w == b"hunter2"));
+        assert_eq!("hunter2", key.decrypt("db", "api_key", &encrypted).unwrap());
+
+        // the same value is encrypted differently each time
+        assert_ne!(encrypted, key.encrypt("db", "api_key", "hunter2").unwrap());
+
+        // the value is bound to its database and secret name, and to the key
+        assert!(key.decrypt("db", "other_key", &encrypted).is_err());
+        assert!(key.decrypt("other_db", "api_key", &encrypted).is_err());
+        let other_key = SecretsKey::new(&[8; 32]).unwrap();
+        assert!(other_key.decrypt("db", "api_key", &encrypted).is_err());
+        assert!(key.decrypt("db", "api_key", &encrypted[..4]).is_err());
+    }
+
+    #[test]
+    fn test_sign_verify() {
+        let key = SecretsKey::new(&[7; 32]).unwrap();
+        let tag = key.sign(b"checkpoint");
+        assert_eq!(SIGNATURE_LEN, tag.len());
+        assert!(key.verify(b"checkpoint", &tag));
+        assert!(!key.verify(b"checkpoint!", &tag));
+        assert!(!key.verify(b"checkpoint", &tag[1..]));
+        let other_key = SecretsKey::new(&[8; 32]).unwrap();
+        assert!(!other_key.verify(b"checkpoint", &tag));
+    }
+
+    #[test]
+    fn test_key_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        std::fs::write(&path, format!("{}\n", hex::encode([1; 32]))).unwrap();
+        assert!(SecretsKey::from_file(&path).is_ok());
+
+        std::fs::write(&path, hex::encode([1; 16])).unwrap();
+        assert!(matches!(
+            SecretsKey::from_file(&path),
+            Err(SecretsError::InvalidKey(_))
+        ));
+        std::fs::write(&path, "not hex").unwrap();
+        assert!(matches!(
+            SecretsKey::from_file(&path),
+            Err(SecretsError::InvalidKey(_))
+        ));
+        assert!(matches!(
+            SecretsKey::from_file(&dir.path().join("missing")),
+            Err(SecretsError::ReadKeyFile { .. })
+        ));
+    }
+}
@@ -0,0 +1,294 @@
+//! Execution metrics for the triggers run by the processing engine
+//!
+//! Each trigger's metrics are exposed both as prometheus metrics, and through the
+//! `system.processing_engine_trigger_stats` table, which is backed by a
+//! [`ProcessingEngineTriggerStats`] recorded in the [`SysEventStore`].
+use std::sync::Arc;
+use std::time::Duration;
+
+use hashbrown::HashMap;
+use influxdb3_id::TriggerId;
+use influxdb3_py_api::logging::ProcessingEngineTriggerStats;
+use influxdb3_sys_events::SysEventStore;
+use metric::{Attributes, DurationHistogram, Metric, Registry, U64Counter, U64Gauge};
+use parking_lot::Mutex;
+
+const INVOCATIONS_METRIC_NAME: &str = "influxdb3_processing_engine_trigger_invocations";
+const INVOCATIONS_METRIC_DESCRIPTION: &str =
+    "number of times a trigger's plugin was run, not counting retries";
+
+const ERRORS_METRIC_NAME: &str = "influxdb3_processing_engine_trigger_errors";
+const ERRORS_METRIC_DESCRIPTION: &str =
+    "number of runs of a trigger's plugin that failed on all of their attempts";
+
+const RETRIES_METRIC_NAME: &str = "influxdb3_processing_engine_trigger_retries";
+const RETRIES_METRIC_DESCRIPTION: &str = "number of times a trigger's plugin was retried";
+
+const EXECUTION_DURATION_METRIC_NAME: &str =
+    "influxdb3_processing_engine_trigger_execution_duration";
+const EXECUTION_DURATION_METRIC_DESCRIPTION: &str =
+    "time taken by each attempt to run a trigger's plugin";
+
+const ROWS_RECEIVED_METRIC_NAME: &str = "influxdb3_processing_engine_trigger_rows_received";
+const ROWS_RECEIVED_METRIC_DESCRIPTION: &str =
+    "number of written rows that a WAL trigger's plugin was run against";
+
+const LINES_WRITTEN_METRIC_NAME: &str = "influxdb3_processing_engine_trigger_lines_written";
+const LINES_WRITTEN_METRIC_DESCRIPTION: &str =
+    "number of lines that a trigger's plugin wrote back to the database";
+
+const LAST_SUCCESS_METRIC_NAME: &str =
+    "influxdb3_processing_engine_trigger_last_success_timestamp_seconds";
+const LAST_SUCCESS_METRIC_DESCRIPTION: &str =
+    "time of the last run of a trigger's plugin that succeeded";
+
+const LAST_FAILURE_METRIC_NAME: &str =
+    "influxdb3_processing_engine_trigger_last_failure_timestamp_seconds";
+const LAST_FAILURE_METRIC_DESCRIPTION: &str =
+    "time of the last run of a trigger's plugin that failed";
+
+#[derive(Debug)]
+pub(crate) struct TriggerMetrics {
+    invocations: Metric<U64Counter>,
+    errors: Metric<U64Counter>,
+    retries: Metric<U64Counter>,
+    execution_duration: Metric<DurationHistogram>,
+    rows_received: Metric<U64Counter>,
+    lines_written: Metric<U64Counter>,
+    last_success: Metric<U64Gauge>,
+    last_failure: Metric<U64Gauge>,
+    sys_event_store: Arc<SysEventStore>,
+    /// The recorders of the triggers that have been run, by database and trigger name, which are
+    /// kept so that a trigger's statistics carry on from where they were when it is restarted
+    recorders: Mutex<HashMap<(Arc<str>, Arc<str>), TriggerMetricsRecorder>>,
+}
+
+impl TriggerMetrics {
+    pub(crate) fn new(registry: &Registry, sys_event_store: Arc<SysEventStore>) -> Self {
+        Self {
+            invocations: registry
+                .register_metric(INVOCATIONS_METRIC_NAME, INVOCATIONS_METRIC_DESCRIPTION),
+            errors: registry.register_metric(ERRORS_METRIC_NAME, ERRORS_METRIC_DESCRIPTION),
+            retries: registry.register_metric(RETRIES_METRIC_NAME, RETRIES_METRIC_DESCRIPTION),
+            execution_duration: registry.register_metric(
+                EXECUTION_DURATION_METRIC_NAME,
+                EXECUTION_DURATION_METRIC_DESCRIPTION,
+            ),
+            rows_received: registry
+                .register_metric(ROWS_RECEIVED_METRIC_NAME, ROWS_RECEIVED_METRIC_DESCRIPTION),
+            lines_written: registry
+                .register_metric(LINES_WRITTEN_METRIC_NAME, LINES_WRITTEN_METRIC_DESCRIPTION),
+            last_success: registry
+                .register_metric(LAST_SUCCESS_METRIC_NAME, LAST_SUCCESS_METRIC_DESCRIPTION),
+            last_failure: registry
+                .register_metric(LAST_FAILURE_METRIC_NAME, LAST_FAILURE_METRIC_DESCRIPTION),
+            sys_event_store,
+            recorders: Default::default(),
+        }
+    }
+
+    /// The recorder for the given trigger
+    ///
+    /// The prometheus metrics are labelled with the trigger's id as well as its name, so that a
+    /// trigger that is deleted and created again starts its metrics from zero.
+    pub(crate) fn recorder(
+        &self,
+        database_name: &Arc<str>,
+        trigger_name: &Arc<str>,
+        trigger_id: TriggerId,
+    ) -> TriggerMetricsRecorder {
+        self.recorders
+            .lock()
+            .entry((Arc::clone(database_name), Arc::clone(trigger_name)))
+            .or_insert_with(|| {
+                let stats = ProcessingEngineTriggerStats::new(
+                    Arc::clone(database_name),
+                    Arc::clone(trigger_name),
+                );
+                self.sys_event_store.record(stats.clone());
+                let attributes = Attributes::from([
+                    ("db", database_name.to_string().into()),
+                    ("trigger_name", trigger_name.to_string().into()),
+                    ("trigger_id", trigger_id.to_string().into()),
+                ]);
+                TriggerMetricsRecorder {
+                    stats,
+                    invocations: self.invocations.recorder(attributes.clone()),
+                    errors: self.errors.recorder(attributes.clone()),
+                    retries: self.retries.recorder(attributes.clone()),
+                    execution_duration: self.execution_duration.recorder(attributes.clone()),
+                    rows_received: self.rows_received.recorder(attributes.clone()),
+                    lines_written: self.lines_written.recorder(attributes.clone()),
+                    last_success: self.last_success.recorder(attributes.clone()),
+                    last_failure: self.last_failure.recorder(attributes),
+                    sys_event_store: Arc::clone(&self.sys_event_store),
+                }
+            })
+            .clone()
+    }
+
+    /// Forget the statistics of a trigger that has been deleted, removing them from the
+    /// `system.processing_engine_trigger_stats` table
+    pub(crate) fn remove(&self, database_name: &str, trigger_name: &str) {
+        self.recorders.lock().retain(|(db, trigger), _| {
+            !(db.as_ref() == database_name && trigger.as_ref() == trigger_name)
+        });
+        self.sys_event_store
+            .retain::<ProcessingEngineTriggerStats>(|stats| {
+                !(stats.database_name.as_ref() == database_name
+                    && stats.trigger_name.as_ref() == trigger_name)
+            });
+    }
+}
+
+/// Records the metrics of a single trigger
+#[derive(Debug, Clone)]
+pub(crate) struct TriggerMetricsRecorder {
+    stats: ProcessingEngineTriggerStats,
+    invocations: U64Counter,
+    errors: U64Counter,
+    retries: U64Counter,
+    execution_duration: DurationHistogram,
+    rows_received: U64Counter,
+    lines_written: U64Counter,
+    last_success: U64Gauge,
+    last_failure: U64Gauge,
+    sys_event_store: Arc<SysEventStore>,
+}
+
+impl TriggerMetricsRecorder {
+    /// Record a run of the plugin, with the time taken by each of its attempts
+    pub(crate) fn record_invocation(&self, execution_times: &[Duration], succeeded: bool) {
+        let now = self.sys_event_store.time_provider().now();
+        self.stats
+            .record_invocation(execution_times, succeeded, now);
+
+        self.invocations.inc(1);
+        self.retries
+            .inc(execution_times.len().saturating_sub(1) as u64);
+        for execution_time in execution_times {
+            self.execution_duration.record(*execution_time);
+        }
+        let now_seconds = u64::try_from(now.timestamp()).unwrap_or_default();
+        if succeeded {
+            self.last_success.set(now_seconds);
+        } else {
+            self.errors.inc(1);
+            self.last_failure.set(now_seconds);
+        }
+    }
+
+    pub(crate) fn record_rows_received(&self, rows: u64) {
+        self.stats.record_rows_received(rows);
+        self.rows_received.inc(rows);
+    }
+
+    pub(crate) fn record_lines_written(&self, lines: u64) {
+        self.stats.record_lines_written(lines);
+        self.lines_written.inc(lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use influxdb3_id::TriggerId;
+    use influxdb3_py_api::logging::ProcessingEngineTriggerStats;
+    use influxdb3_sys_events::SysEventStore;
+    use iox_time::{MockProvider, Time};
+    use metric::{Attributes, Metric, Registry, U64Counter};
+
+    use super::{INVOCATIONS_METRIC_NAME, TriggerMetrics};
+
+    #[test]
+    fn records_stats_and_metrics() {
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let sys_event_store = Arc::new(SysEventStore::new(time_provider));
+        let registry = Registry::new();
+        let metrics = TriggerMetrics::new(&registry, Arc::clone(&sys_event_store));
+
+        let db: Arc<str> = "foo".into();
+        let trigger: Arc<str> = "trigger".into();
+        let recorder = metrics.recorder(&db, &trigger, TriggerId::new(0));
+        recorder.record_invocation(&[Duration::from_millis(10)], true);
+        recorder.record_invocation(
+            &[Duration::from_millis(20), Duration::from_millis(30)],
+            false,
+        );
+        recorder.record_rows_received(5);
+        // a restarted trigger carries on with the same statistics:
+        let recorder = metrics.recorder(&db, &trigger, TriggerId::new(0));
+        recorder.record_lines_written(3);
+
+        let recorded = sys_event_store.as_vec::<ProcessingEngineTriggerStats>();
+        assert_eq!(1, recorded.len());
+        let stats = recorded[0].data.stats();
+        assert_eq!(2, stats.invocations);
+        assert_eq!(1, stats.errors);
+        assert_eq!(1, stats.retries);
+        assert_eq!(5, stats.rows_received);
+        assert_eq!(3, stats.lines_written);
+        assert_eq!(
+            Some(Duration::from_millis(20)),
+            stats.execution_time_percentile(0.5)
+        );
+        assert_eq!(
+            Some(Duration::from_millis(30)),
+            stats.execution_time_percentile(0.99)
+        );
+        assert!(stats.last_success_time.is_some());
+        assert!(stats.last_failure_time.is_some());
+
+        let invocations = registry
+            .get_instrument::<Metric<U64Counter>>(INVOCATIONS_METRIC_NAME)
+            .unwrap()
+            .get_observer(&Attributes::from(&[
+                ("db", "foo"),
+                ("trigger_name", "trigger"),
+                ("trigger_id", "0"),
+            ]))
+            .unwrap()
+            .fetch();
+        assert_eq!(2, invocations);
+    }
+
+    #[test]
+    fn removed_trigger_starts_from_zero() {
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let sys_event_store = Arc::new(SysEventStore::new(time_provider));
+        let registry = Registry::new();
+        let metrics = TriggerMetrics::new(&registry, Arc::clone(&sys_event_store));
+
+        let db: Arc<str> = "foo".into();
+        let trigger: Arc<str> = "trigger".into();
+        let other: Arc<str> = "other".into();
+        metrics
+            .recorder(&db, &trigger, TriggerId::new(0))
+            .record_invocation(&[Duration::from_millis(10)], true);
+        metrics
+            .recorder(&db, &other, TriggerId::new(1))
+            .record_invocation(&[Duration::from_millis(10)], true);
+
+        metrics.remove(&db, &trigger);
+        let recorded = sys_event_store.as_vec::<ProcessingEngineTriggerStats>();
+        assert_eq!(1, recorded.len());
+        assert_eq!("other", recorded[0].data.trigger_name.as_ref());
+
+        // a trigger created again with the same name doesn't inherit the old statistics:
+        metrics
+            .recorder(&db, &trigger, TriggerId::new(2))
+            .record_rows_received(5);
+        let recorded = sys_event_store.as_vec::<ProcessingEngineTriggerStats>();
+        assert_eq!(2, recorded.len());
+        let stats = recorded
+            .iter()
+            .find(|event| event.data.trigger_name.as_ref() == "trigger")
+            .unwrap()
+            .data
+            .stats();
+        assert_eq!(0, stats.invocations);
+        assert_eq!(5, stats.rows_received);
+    }
+}
@@ -0,0 +1,66 @@
+//! Reads of the last and distinct value caches by plugins, through `influxdb3_local.last_cache()`
+//! and `influxdb3_local.distinct_cache()`
+//!
+//! These go straight to the cache providers, so a plugin that checks the latest values for a
+//! few series doesn't pay for planning and running a query.
+use std::sync::Arc;
+
+use anyhow::Context;
+use arrow::array::RecordBatch;
+use influxdb3_id::DbId;
+use influxdb3_py_api::system_py::ValueCacheReader;
+use influxdb3_write::WriteBuffer;
+
+/// Reads the value caches of a database, for the plugins of its triggers
+#[derive(Debug)]
+pub(crate) struct DatabaseValueCaches {
+    write_buffer: Arc<dyn WriteBuffer>,
+    db_name: String,
+}
+
+impl DatabaseValueCaches {
+    pub(crate) fn new(write_buffer: Arc<dyn WriteBuffer>, db_name: impl Into<String>) -> Self {
+        Self {
+            write_buffer,
+            db_name: db_name.into(),
+        }
+    }
+
+    fn db_id(&self) -> Result<DbId, anyhow::Error> {
+        self.write_buffer
+            .catalog()
+            .db_name_to_id(&self.db_name)
+            .with_context(|| format!("database {} not found", self.db_name))
+    }
+}
+
+impl ValueCacheReader for DatabaseValueCaches {
+    fn last_cache(
+        &self,
+        table_name: &str,
+        cache_name: Option<&str>,
+        key_filters: &[(String, Vec<String>)],
+    ) -> Result<Vec<RecordBatch>, anyhow::Error> {
+        Ok(self.write_buffer.last_cache_provider().read_cache(
+            self.db_id()?,
+            table_name,
+            cache_name,
+            key_filters,
+        )?)
+    }
+
+    fn distinct_cache(
+        &self,
+        table_name: &str,
+        cache_name: Option<&str>,
+        key_filters: &[(String, Vec<String>)],
+    ) -> Result<Vec<RecordBatch>, anyhow::Error> {
+        let batch = self.write_buffer.distinct_cache_provider().read_cache(
+            self.db_id()?,
+            table_name,
+            cache_name,
+            key_filters,
+        )?;
+        Ok(vec![batch])
+    }
+}
@@ -0,0 +1,1013 @@
+//! Plugins compiled to WebAssembly, which run alongside python plugins
+//!
+//! A plugin whose file name ends in `.wasm` is a WASM module, rather than python code. It can be
+//! used by WAL, schedule and request triggers, and is run in a sandbox of its own: it has no
+//! access to the filesystem or network, and only reaches the server through the host functions
+//! below. A fresh instance of the module is created for each run, so anything the plugin keeps
+//! between runs goes in the cache. The compiled module is kept until the plugin's file changes.
+//! A run's memory can grow to at most [`MAX_MEMORY_BYTES`], and its tables to
+//! [`MAX_TABLE_ELEMENTS`] elements, past which `memory.grow` and `table.grow` fail.
+//!
+//! The module must export its memory as `memory`, and an `alloc(len: i32) -> i32` function that
+//! returns a pointer to `len` bytes of that memory, which the server writes the plugin's input,
+//! and the results of host functions, into. It exports the function for each trigger type it
+//! is used with:
+//!
+//! - `process_writes(input_ptr: i32, input_len: i32) -> i32`, where the input is the JSON
+//!   `{"table_batches": [{"table_name": str, "rows": [{column: value}]}], "args": {..} | null}`
+//! - `process_scheduled_call(input_ptr: i32, input_len: i32) -> i32`, where the input is the
+//!   JSON `{"call_time": rfc3339, "args": {..} | null}`
+//! - `process_request(input_ptr: i32, input_len: i32, body_ptr: i32, body_len: i32) -> i64`,
+//!   where the input is the JSON `{"method", "path", "query_parameters", "headers", "token_id",
+//!   "args"}`, the body is the raw request body, and the return value points to a JSON response
+//!   `{"status": int, "headers": {..}, "body": str}`
+//!
+//! `process_writes` and `process_scheduled_call` return 0 on success, and anything else to fail
+//! the run. Host functions that return data hand it back as an `i64` with the pointer in the high
+//! 32 bits and the length in the low 32 bits. The host functions, in the `influxdb3` import
+//! module, mirror `influxdb3_local` for python plugins:
+//!
+//! - `info`, `warn` and `error(ptr: i32, len: i32)` log a message
+//! - `write(ptr: i32, len: i32)` writes a line of line protocol to the trigger's database, and
+//!   `write_to_db(db_ptr: i32, db_len: i32, ptr: i32, len: i32)` to another database, once the
+//!   run finishes
+//! - `query(query_ptr: i32, query_len: i32, args_ptr: i32, args_len: i32) -> i64` runs a SQL
+//!   query, with a JSON object of parameters if `args_len` isn't 0, returning the JSON
+//!   `{"rows": [{column: value}]}` or `{"error": str}`
+//! - `cache_get(key_ptr: i32, key_len: i32, use_global: i32) -> i64` returns the cached value, or
+//!   -1 if there is none, `cache_put(key_ptr: i32, key_len: i32, value_ptr: i32, value_len: i32,
+//!   ttl_ms: i64, use_global: i32)` caches a value, expiring it after `ttl_ms` unless that is
+//!   negative, and `cache_delete(key_ptr: i32, key_len: i32, use_global: i32) -> i32` returns 1
+//!   if a value was deleted. The cache is the one python plugins use, where values put by a WASM
+//!   plugin are `bytes`, and of the values put by a python plugin, only `str`, `bytes` and
+//!   `bytearray` values are seen.
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, anyhow, bail};
+use arrow::array::RecordBatch;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use hashbrown::HashMap;
+use humantime::format_duration;
+use influxdb3_catalog::catalog::DatabaseSchema;
+use influxdb3_catalog::log::PluginType;
+use influxdb3_id::TableId;
+use influxdb3_internal_api::query_executor::QueryExecutor;
+use influxdb3_py_api::ExecutePluginError;
+use influxdb3_py_api::logging::LogLevel;
+use influxdb3_py_api::system_py::{
+    ExecutionLimits, LogLine, PluginRequest, PluginResponse, PluginReturnState,
+    ProcessingEngineLogger, PyCache, ResponseBody,
+};
+use influxdb3_wal::{FieldData, WriteBatch};
+use iox_query_params::StatementParams;
+use observability_deps::tracing::{error, info, warn};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use wasmtime::{
+    AsContext, AsContextMut, Caller, Config, Engine, Extern, Instance, InstancePre, Linker, Memory,
+    Module, Store, StoreLimits, StoreLimitsBuilder, Trap, TypedFunc, UpdateDeadline,
+};
+
+use crate::plugin_store::parse_plugin_filename;
+use crate::plugins::PluginError;
+
+/// The file extension of plugins that are WASM modules
+pub const WASM_PLUGIN_EXTENSION: &str = "wasm";
+
+/// The module that the host functions are imported from
+const HOST_MODULE: &str = "influxdb3";
+
+const MEMORY_EXPORT: &str = "memory";
+
+const ALLOC_EXPORT: &str = "alloc";
+
+const PROCESS_WRITES_EXPORT: &str = "process_writes";
+
+const PROCESS_SCHEDULED_CALL_EXPORT: &str = "process_scheduled_call";
+
+const PROCESS_REQUEST_EXPORT: &str = "process_request";
+
+/// How often the epoch of the [`engine`] is advanced, which is the granularity of timeouts
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// The most memory that a run of a WASM plugin can grow to
+pub const MAX_MEMORY_BYTES: usize = 256 * 1024 * 1024;
+
+/// The most elements that a table of a run of a WASM plugin can grow to
+pub const MAX_TABLE_ELEMENTS: usize = 10_000;
+
+/// Whether the plugin `filename`, which may be pinned to a version, is a WASM module
+pub fn is_wasm_plugin(filename: &str) -> bool {
+    let (name, _version) = parse_plugin_filename(filename);
+    Path::new(name)
+        .extension()
+        .is_some_and(|extension| extension == WASM_PLUGIN_EXTENSION)
+}
+
+/// Check that the plugin `filename` can be run by a trigger of `plugin_type`, as WASM plugins
+/// can only be used by WAL, schedule and request triggers
+pub fn validate_trigger_type(filename: &str, plugin_type: PluginType) -> Result<(), PluginError> {
+    match plugin_type {
+        PluginType::WalRows | PluginType::Schedule | PluginType::Request => Ok(()),
+        PluginType::Persisted | PluginType::Catalog if is_wasm_plugin(filename) => {
+            Err(PluginError::UnsupportedWasmTrigger(plugin_type.to_string()))
+        }
+        PluginType::Persisted | PluginType::Catalog => Ok(()),
+    }
+}
+
+/// The engine that every WASM plugin is compiled and run with. Its epoch is advanced every
+/// [`EPOCH_TICK`], so that runs can be interrupted when they reach their deadline.
+fn engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut config = Config::new();
+        config.epoch_interruption(true);
+        let engine = Engine::new(&config).expect("WASM engine config should be valid");
+        let ticker = engine.clone();
+        std::thread::Builder::new()
+            .name("wasm-plugin-epoch".to_string())
+            .spawn(move || {
+                loop {
+                    std::thread::sleep(EPOCH_TICK);
+                    ticker.increment_epoch();
+                }
+            })
+            .expect("failed to start the WASM plugin epoch thread");
+        engine
+    })
+}
+
+/// A WASM plugin's compiled module, which is kept between runs, along with the limits that
+/// each run is held to
+#[derive(Debug, Default)]
+pub struct WasmPluginModule {
+    compiled: Mutex<Option<CompiledWasm>>,
+    limits: ExecutionLimits,
+}
+
+struct CompiledWasm {
+    code: Bytes,
+    instance_pre: InstancePre<WasmCallApi>,
+}
+
+impl std::fmt::Debug for CompiledWasm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompiledWasm")
+            .field("code_len", &self.code.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl WasmPluginModule {
+    /// Limit each run of the plugin
+    pub fn with_limits(mut self, limits: ExecutionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Get the module for `code`, compiling and linking it if the code is not the code that the
+    /// current module was compiled from
+    fn load(&self, code: &Bytes) -> Result<InstancePre<WasmCallApi>, ExecutePluginError> {
+        if let Some(compiled) = &*self.compiled.lock() {
+            let same_bytes =
+                compiled.code.as_ptr() == code.as_ptr() && compiled.code.len() == code.len();
+            if same_bytes || compiled.code == *code {
+                return Ok(compiled.instance_pre.clone());
+            }
+        }
+
+        // compiled without the lock held, as it can take a while for a large module
+        let engine = engine();
+        let module = Module::new(engine, code).context("failed to compile the WASM module")?;
+        let instance_pre = linker(engine)?
+            .instantiate_pre(&module)
+            .context("failed to link the WASM module")?;
+        *self.compiled.lock() = Some(CompiledWasm {
+            code: code.clone(),
+            instance_pre: instance_pre.clone(),
+        });
+        Ok(instance_pre)
+    }
+
+    /// Instantiate the module for `code` and run `call` against it, failing the run if the plugin
+    /// goes over its limits
+    fn run<T>(
+        &self,
+        code: &Bytes,
+        api: WasmCallApi,
+        call: impl FnOnce(&mut Store<WasmCallApi>, &Instance) -> wasmtime::Result<T>,
+    ) -> Result<(T, PluginReturnState), ExecutePluginError> {
+        let instance_pre = self.load(code)?;
+        let mut store = Store::new(engine(), api);
+        store.limiter(|api| &mut api.store_limits);
+        match self.limits.timeout {
+            Some(timeout) => {
+                store.set_epoch_deadline(epoch_ticks(timeout));
+                store.epoch_deadline_trap();
+            }
+            // let runs without a timeout carry on at every tick
+            None => {
+                store.set_epoch_deadline(1);
+                store.epoch_deadline_callback(|_| Ok(UpdateDeadline::Continue(1)));
+            }
+        }
+
+        let result = instance_pre
+            .instantiate(&mut store)
+            .context("failed to instantiate the WASM module")
+            .and_then(|instance| call(&mut store, &instance));
+        if let (Some(timeout), Err(e)) = (self.limits.timeout, &result) {
+            if matches!(e.downcast_ref::<Trap>(), Some(Trap::Interrupt)) {
+                return Err(ExecutePluginError::Timeout(timeout));
+            }
+        }
+        let return_state = store.into_data().return_state;
+        if let Some(max_rows_written) = self.limits.max_rows_written {
+            if return_state.rows_written > max_rows_written {
+                return Err(ExecutePluginError::RowLimitExceeded(max_rows_written));
+            }
+        }
+        Ok((result?, return_state))
+    }
+}
+
+/// The number of epoch ticks that a run is allowed before it times out
+fn epoch_ticks(timeout: Duration) -> u64 {
+    let ticks = timeout.as_nanos().div_ceil(EPOCH_TICK.as_nanos()).max(1);
+    u64::try_from(ticks).unwrap_or(u64::MAX)
+}
+
+/// What the host functions of a run of a WASM plugin work with, kept in its store
+struct WasmCallApi {
+    db_schema: Arc<DatabaseSchema>,
+    query_executor: Arc<dyn QueryExecutor>,
+    return_state: PluginReturnState,
+    logger: Option<ProcessingEngineLogger>,
+    cache: PyCache,
+    limits: ExecutionLimits,
+    store_limits: StoreLimits,
+}
+
+impl WasmCallApi {
+    fn new(
+        plugin: &WasmPluginModule,
+        db_schema: Arc<DatabaseSchema>,
+        query_executor: Arc<dyn QueryExecutor>,
+        logger: Option<ProcessingEngineLogger>,
+        cache: PyCache,
+    ) -> Self {
+        Self {
+            db_schema,
+            query_executor,
+            return_state: PluginReturnState::default(),
+            logger,
+            cache,
+            limits: plugin.limits,
+            store_limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY_BYTES)
+                .table_elements(MAX_TABLE_ELEMENTS)
+                .build(),
+        }
+    }
+
+    fn log(&mut self, level: LogLevel, line: String) {
+        match level {
+            LogLevel::Info => info!("processing engine: {}", line),
+            LogLevel::Warn => warn!("processing engine: {}", line),
+            LogLevel::Error => error!("processing engine: {}", line),
+        }
+        if let Some(logger) = &self.logger {
+            logger.log(level, line.clone());
+        }
+        self.return_state.log_lines.push(match level {
+            LogLevel::Info => LogLine::Info(line),
+            LogLevel::Warn => LogLine::Warn(line),
+            LogLevel::Error => LogLine::Error(line),
+        });
+    }
+
+    /// Keep a row written by the plugin, failing if it takes the plugin over its limit of rows
+    /// written per run
+    fn push_row(&mut self, db_name: Option<String>, line: String) -> wasmtime::Result<()> {
+        self.return_state.rows_written += 1;
+        if let Some(max_rows_written) = self.limits.max_rows_written {
+            if self.return_state.rows_written > max_rows_written {
+                return Err(ExecutePluginError::RowLimitExceeded(max_rows_written).into());
+            }
+        }
+        match db_name {
+            None => self.return_state.write_back_lines.push(line),
+            Some(db_name) => self
+                .return_state
+                .write_db_lines
+                .entry(db_name)
+                .or_default()
+                .push(line),
+        }
+        Ok(())
+    }
+
+    /// Run a query, returning its rows as a JSON array of objects
+    fn run_query(
+        &self,
+        query: String,
+        args: Option<std::collections::HashMap<String, String>>,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        let query_executor = Arc::clone(&self.query_executor);
+        let db_schema_name = Arc::clone(&self.db_schema.name);
+
+        let params = args.map(|args| {
+            let mut params = StatementParams::new();
+            for (key, value) in args {
+                params.insert(key, value);
+            }
+            params
+        });
+
+        let handle = tokio::spawn(async move {
+            let res = query_executor
+                .query_sql(db_schema_name.as_ref(), &query, params, None, None)
+                .await
+                .map_err(|e| anyhow!("error: {} executing query: {}", e, query))?;
+
+            res.try_collect::<Vec<RecordBatch>>()
+                .await
+                .map_err(|e| anyhow!("error: {} executing query: {}", e, query))
+        });
+
+        // the plugin runs on a blocking thread, so it waits here for the query to finish
+        let batches =
+            tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(handle))
+                .map_err(|e| anyhow!("join error: {}", e))??;
+
+        let mut writer = arrow::json::ArrayWriter::new(Vec::new());
+        writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+        writer.finish()?;
+        Ok(writer.into_inner())
+    }
+}
+
+/// The host functions that WASM plugins import
+fn linker(engine: &Engine) -> Result<Linker<WasmCallApi>, anyhow::Error> {
+    let mut linker = Linker::new(engine);
+    for (name, level) in [
+        ("info", LogLevel::Info),
+        ("warn", LogLevel::Warn),
+        ("error", LogLevel::Error),
+    ] {
+        linker.func_wrap(
+            HOST_MODULE,
+            name,
+            move |mut caller: Caller<'_, WasmCallApi>,
+                  ptr: i32,
+                  len: i32|
+                  -> wasmtime::Result<()> {
+                let line = read_string(&mut caller, ptr, len)?;
+                caller.data_mut().log(level, line);
+                Ok(())
+            },
+        )?;
+    }
+
+    linker.func_wrap(
+        HOST_MODULE,
+        "write",
+        |mut caller: Caller<'_, WasmCallApi>, ptr: i32, len: i32| {
+            let line = read_string(&mut caller, ptr, len)?;
+            caller.data_mut().push_row(None, line)
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "write_to_db",
+        |mut caller: Caller<'_, WasmCallApi>, db_ptr: i32, db_len: i32, ptr: i32, len: i32| {
+            let db_name = read_string(&mut caller, db_ptr, db_len)?;
+            let line = read_string(&mut caller, ptr, len)?;
+            caller.data_mut().push_row(Some(db_name), line)
+        },
+    )?;
+
+    linker.func_wrap(
+        HOST_MODULE,
+        "query",
+        |mut caller: Caller<'_, WasmCallApi>,
+         query_ptr: i32,
+         query_len: i32,
+         args_ptr: i32,
+         args_len: i32|
+         -> wasmtime::Result<i64> {
+            let query = read_string(&mut caller, query_ptr, query_len)?;
+            let args = if args_len == 0 {
+                None
+            } else {
+                let args = read_string(&mut caller, args_ptr, args_len)?;
+                Some(
+                    serde_json::from_str(&args)
+                        .context("query args must be a JSON object of string values")?,
+                )
+            };
+            let result = match caller.data().run_query(query, args) {
+                Ok(rows) => [b"{\"rows\":".as_slice(), &rows, b"}"].concat(),
+                Err(e) => json!({"error": e.to_string()}).to_string().into_bytes(),
+            };
+            write_result(&mut caller, &result)
+        },
+    )?;
+
+    linker.func_wrap(
+        HOST_MODULE,
+        "cache_get",
+        |mut caller: Caller<'_, WasmCallApi>,
+         key_ptr: i32,
+         key_len: i32,
+         use_global: i32|
+         -> wasmtime::Result<i64> {
+            let key = read_string(&mut caller, key_ptr, key_len)?;
+            match caller.data().cache.get_bytes(&key, use_global != 0) {
+                Some(value) => write_result(&mut caller, &value),
+                None => Ok(-1),
+            }
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "cache_put",
+        |mut caller: Caller<'_, WasmCallApi>,
+         key_ptr: i32,
+         key_len: i32,
+         value_ptr: i32,
+         value_len: i32,
+         ttl_ms: i64,
+         use_global: i32|
+         -> wasmtime::Result<()> {
+            let key = read_string(&mut caller, key_ptr, key_len)?;
+            let memory = guest_memory(&mut caller)?;
+            let value = read_from_guest(&caller, memory, value_ptr, value_len)?;
+            let ttl = u64::try_from(ttl_ms).ok().map(Duration::from_millis);
+            caller
+                .data()
+                .cache
+                .put_bytes(&key, &value, ttl, use_global != 0);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "cache_delete",
+        |mut caller: Caller<'_, WasmCallApi>,
+         key_ptr: i32,
+         key_len: i32,
+         use_global: i32|
+         -> wasmtime::Result<i32> {
+            let key = read_string(&mut caller, key_ptr, key_len)?;
+            Ok(i32::from(
+                caller.data().cache.delete_bytes(&key, use_global != 0),
+            ))
+        },
+    )?;
+
+    Ok(linker)
+}
+
+fn guest_memory(caller: &mut Caller<'_, WasmCallApi>) -> wasmtime::Result<Memory> {
+    caller
+        .get_export(MEMORY_EXPORT)
+        .and_then(Extern::into_memory)
+        .with_context(|| format!("the WASM module must export its memory as {MEMORY_EXPORT}"))
+}
+
+fn guest_alloc(caller: &mut Caller<'_, WasmCallApi>) -> wasmtime::Result<TypedFunc<i32, i32>> {
+    caller
+        .get_export(ALLOC_EXPORT)
+        .and_then(Extern::into_func)
+        .with_context(|| format!("the WASM module must export {ALLOC_EXPORT}(len: i32) -> i32"))?
+        .typed(&*caller)
+}
+
+fn read_from_guest(
+    ctx: impl AsContext,
+    memory: Memory,
+    ptr: i32,
+    len: i32,
+) -> wasmtime::Result<Vec<u8>> {
+    let start = ptr as u32 as usize;
+    let end = start.saturating_add(len as u32 as usize);
+    memory
+        .data(&ctx)
+        .get(start..end)
+        .map(<[u8]>::to_vec)
+        .context("the WASM module passed a pointer outside of its memory")
+}
+
+fn read_string(
+    caller: &mut Caller<'_, WasmCallApi>,
+    ptr: i32,
+    len: i32,
+) -> wasmtime::Result<String> {
+    let memory = guest_memory(caller)?;
+    let bytes = read_from_guest(&*caller, memory, ptr, len)?;
+    String::from_utf8(bytes).context("the WASM module passed a string that isn't valid UTF-8")
+}
+
+/// Copy `bytes` into memory allocated by the guest, returning the pointer and length
+fn write_to_guest(
+    mut ctx: impl AsContextMut<Data = WasmCallApi>,
+    memory: Memory,
+    alloc: &TypedFunc<i32, i32>,
+    bytes: &[u8],
+) -> wasmtime::Result<(i32, i32)> {
+    let len = i32::try_from(bytes.len()).context("too much data to pass to the WASM module")?;
+    let ptr = alloc.call(&mut ctx, len)?;
+    memory
+        .write(&mut ctx, ptr as u32 as usize, bytes)
+        .with_context(|| format!("{ALLOC_EXPORT} returned a pointer outside of the memory"))?;
+    Ok((ptr, len))
+}
+
+/// Hand the result of a host function back to the guest
+fn write_result(caller: &mut Caller<'_, WasmCallApi>, bytes: &[u8]) -> wasmtime::Result<i64> {
+    let memory = guest_memory(caller)?;
+    let alloc = guest_alloc(caller)?;
+    let (ptr, len) = write_to_guest(&mut *caller, memory, &alloc, bytes)?;
+    Ok(pack(ptr, len))
+}
+
+/// Copy the input of a run into the guest's memory
+fn write_input(
+    store: &mut Store<WasmCallApi>,
+    instance: &Instance,
+    bytes: &[u8],
+) -> wasmtime::Result<(i32, i32)> {
+    let memory = instance
+        .get_memory(&mut *store, MEMORY_EXPORT)
+        .with_context(|| format!("the WASM module must export its memory as {MEMORY_EXPORT}"))?;
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&mut *store, ALLOC_EXPORT)
+        .with_context(|| format!("the WASM module must export {ALLOC_EXPORT}(len: i32) -> i32"))?;
+    write_to_guest(store, memory, &alloc, bytes)
+}
+
+fn pack(ptr: i32, len: i32) -> i64 {
+    (((ptr as u32 as u64) << 32) | len as u32 as u64) as i64
+}
+
+fn unpack(packed: i64) -> (i32, i32) {
+    ((packed as u64 >> 32) as u32 as i32, packed as u32 as i32)
+}
+
+/// Call a `process_writes` or `process_scheduled_call` entry point, which returns 0 on success
+fn call_entry_point(
+    store: &mut Store<WasmCallApi>,
+    instance: &Instance,
+    name: &str,
+    input: &Value,
+) -> wasmtime::Result<()> {
+    let func = instance
+        .get_typed_func::<(i32, i32), i32>(&mut *store, name)
+        .with_context(|| {
+            format!(
+                "the {name} function is not exported by the WASM module. Should be exported as: {name}(input_ptr: i32, input_len: i32) -> i32"
+            )
+        })?;
+    let (ptr, len) = write_input(store, instance, input.to_string().as_bytes())?;
+    match func.call(&mut *store, (ptr, len))? {
+        0 => Ok(()),
+        code => bail!("{name} failed with error code {code}"),
+    }
+}
+
+/// Run a WASM plugin's `process_writes` against the rows of `write_batch`, only those of
+/// `table_filter` if it is given
+#[allow(clippy::too_many_arguments)]
+pub fn execute_wasm_with_batch(
+    plugin: &WasmPluginModule,
+    code: &Bytes,
+    write_batch: &WriteBatch,
+    schema: Arc<DatabaseSchema>,
+    query_executor: Arc<dyn QueryExecutor>,
+    logger: Option<ProcessingEngineLogger>,
+    table_filter: Option<TableId>,
+    args: &Option<HashMap<String, String>>,
+    cache: PyCache,
+) -> Result<PluginReturnState, ExecutePluginError> {
+    let start_time = Instant::now();
+    if let Some(logger) = &logger {
+        logger.log(LogLevel::Info, "starting execution of wal plugin.");
+    }
+
+    let mut table_batches = Vec::with_capacity(write_batch.table_chunks.len());
+    for (table_id, table_chunks) in &write_batch.table_chunks {
+        if table_filter.is_some_and(|table_filter| *table_id != table_filter) {
+            continue;
+        }
+        let table_def = schema
+            .tables
+            .get_by_id(table_id)
+            .context("table not found")?;
+
+        let mut rows = Vec::new();
+        for chunk in table_chunks.chunk_time_to_chunk.values() {
+            for row in &chunk.rows {
+                let mut json_row = serde_json::Map::with_capacity(row.fields.len());
+                for field in &row.fields {
+                    let field_name = table_def
+                        .column_id_to_name(&field.id)
+                        .context("field not found")?;
+                    let value = match &field.value {
+                        FieldData::String(s) | FieldData::Tag(s) | FieldData::Key(s) => json!(s),
+                        FieldData::Integer(i) | FieldData::Timestamp(i) => json!(i),
+                        FieldData::UInteger(u) => json!(u),
+                        FieldData::Float(f) => json!(f),
+                        FieldData::Boolean(b) => json!(b),
+                    };
+                    json_row.insert(field_name.to_string(), value);
+                }
+                rows.push(Value::Object(json_row));
+            }
+        }
+        table_batches.push(json!({
+            "table_name": table_def.table_name.as_ref(),
+            "rows": rows,
+        }));
+    }
+    let input = json!({"table_batches": table_batches, "args": args});
+
+    let api = WasmCallApi::new(plugin, schema, query_executor, logger.clone(), cache);
+    let ((), return_state) = plugin.run(code, api, |store, instance| {
+        call_entry_point(store, instance, PROCESS_WRITES_EXPORT, &input)
+    })?;
+
+    log_finished(&logger, start_time);
+    Ok(return_state)
+}
+
+/// Run a WASM plugin's `process_scheduled_call` for `schedule_time`
+#[allow(clippy::too_many_arguments)]
+pub fn execute_wasm_schedule_trigger(
+    plugin: &WasmPluginModule,
+    code: &Bytes,
+    schedule_time: DateTime<Utc>,
+    schema: Arc<DatabaseSchema>,
+    query_executor: Arc<dyn QueryExecutor>,
+    logger: Option<ProcessingEngineLogger>,
+    args: &Option<HashMap<String, String>>,
+    cache: PyCache,
+) -> Result<PluginReturnState, ExecutePluginError> {
+    let start_time = Instant::now();
+    if let Some(logger) = &logger {
+        logger.log(
+            LogLevel::Info,
+            format!("starting execution with scheduled time {}", schedule_time),
+        );
+    }
+    let input = json!({"call_time": schedule_time.to_rfc3339(), "args": args});
+
+    let api = WasmCallApi::new(plugin, schema, query_executor, logger.clone(), cache);
+    let ((), return_state) = plugin.run(code, api, |store, instance| {
+        call_entry_point(store, instance, PROCESS_SCHEDULED_CALL_EXPORT, &input)
+    })?;
+
+    log_finished(&logger, start_time);
+    Ok(return_state)
+}
+
+/// The response that a WASM request plugin returns
+#[derive(Debug, Deserialize)]
+struct WasmResponse {
+    #[serde(default = "default_response_status")]
+    status: u16,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: String,
+}
+
+fn default_response_status() -> u16 {
+    200
+}
+
+/// Run a WASM plugin's `process_request` for `request`
+#[allow(clippy::too_many_arguments)]
+pub fn execute_wasm_request_trigger(
+    plugin: &WasmPluginModule,
+    code: &Bytes,
+    db_schema: Arc<DatabaseSchema>,
+    query_executor: Arc<dyn QueryExecutor>,
+    logger: Option<ProcessingEngineLogger>,
+    args: &Option<HashMap<String, String>>,
+    request: &PluginRequest,
+    cache: PyCache,
+) -> Result<(PluginResponse, PluginReturnState), ExecutePluginError> {
+    let start_time = Instant::now();
+    if let Some(logger) = &logger {
+        logger.log(LogLevel::Info, "starting execution of request plugin.");
+    }
+    let input = json!({
+        "method": request.method,
+        "path": request.path,
+        "query_parameters": request.query_params,
+        "headers": request.headers,
+        "token_id": request.token_id,
+        "args": args,
+    });
+
+    let api = WasmCallApi::new(plugin, db_schema, query_executor, logger.clone(), cache);
+    let (response, return_state) = plugin.run(code, api, |store, instance| {
+        let func = instance
+            .get_typed_func::<(i32, i32, i32, i32), i64>(&mut *store, PROCESS_REQUEST_EXPORT)
+            .with_context(|| {
+                format!(
+                    "the {PROCESS_REQUEST_EXPORT} function is not exported by the WASM module. Should be exported as: {PROCESS_REQUEST_EXPORT}(input_ptr: i32, input_len: i32, body_ptr: i32, body_len: i32) -> i64"
+                )
+            })?;
+        let (input_ptr, input_len) = write_input(store, instance, input.to_string().as_bytes())?;
+        let (body_ptr, body_len) = write_input(store, instance, &request.body)?;
+        let (ptr, len) = unpack(func.call(&mut *store, (input_ptr, input_len, body_ptr, body_len))?);
+
+        let memory = instance
+            .get_memory(&mut *store, MEMORY_EXPORT)
+            .with_context(|| format!("the WASM module must export its memory as {MEMORY_EXPORT}"))?;
+        let response = read_from_guest(&*store, memory, ptr, len)?;
+        serde_json::from_slice::<WasmResponse>(&response).with_context(|| {
+            format!("{PROCESS_REQUEST_EXPORT} must return a JSON response of {{\"status\", \"headers\", \"body\"}}")
+        })
+    })?;
+
+    log_finished(&logger, start_time);
+    Ok((
+        PluginResponse {
+            status: response.status,
+            headers: response.headers,
+            body: ResponseBody::from(response.body),
+        },
+        return_state,
+    ))
+}
+
+fn log_finished(logger: &Option<ProcessingEngineLogger>, start_time: Instant) {
+    if let Some(logger) = logger {
+        logger.log(
+            LogLevel::Info,
+            format!(
+                "finished execution in {}",
+                format_duration(start_time.elapsed())
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::virtualenv::init_pyo3;
+    use influxdb3_id::DbId;
+    use influxdb3_internal_api::query_executor::UnimplementedQueryExecutor;
+    use influxdb3_py_api::system_py::CacheStore;
+    use iox_time::{MockProvider, Time, TimeProvider};
+    use pyo3::prelude::*;
+    use pyo3::types::PyBytes;
+
+    /// The imports, memory and bump allocator that every test module starts with, with a line of
+    /// line protocol at 16 and a request response at 64
+    const PRELUDE: &str = r#"
+        (import "influxdb3" "info" (func $info (param i32 i32)))
+        (import "influxdb3" "write" (func $write (param i32 i32)))
+        (import "influxdb3" "cache_get" (func $cache_get (param i32 i32 i32) (result i64)))
+        (import "influxdb3" "cache_put" (func $cache_put (param i32 i32 i32 i32 i64 i32)))
+        (memory (export "memory") 1)
+        (global $next (mut i32) (i32.const 1024))
+        (data (i32.const 0) "key")
+        (data (i32.const 8) "hello")
+        (data (i32.const 16) "cpu,host=a usage=1")
+        (data (i32.const 64) "{\"status\":201,\"body\":\"ok\"}")
+        (func (export "alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $next))
+            (global.set $next (i32.add (global.get $next) (local.get $len)))
+            (local.get $ptr))
+    "#;
+
+    fn module(funcs: &str) -> Bytes {
+        Bytes::from(format!("(module {PRELUDE} {funcs})"))
+    }
+
+    /// A test cache, which is the python plugins' cache. WASM plugins use it without python.
+    fn test_cache() -> PyCache {
+        let time_provider: Arc<dyn TimeProvider> =
+            Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        PyCache::new_test_cache(
+            Arc::new(Mutex::new(CacheStore::new(
+                time_provider,
+                Duration::from_secs(10),
+            ))),
+            "test".to_string(),
+        )
+    }
+
+    fn run_schedule(
+        plugin: &WasmPluginModule,
+        code: &Bytes,
+        cache: PyCache,
+    ) -> Result<PluginReturnState, ExecutePluginError> {
+        execute_wasm_schedule_trigger(
+            plugin,
+            code,
+            Utc::now(),
+            Arc::new(DatabaseSchema::new(DbId::new(0), "foo".into())),
+            Arc::new(UnimplementedQueryExecutor),
+            None,
+            &None,
+            cache,
+        )
+    }
+
+    #[test]
+    fn plugin_file_names() {
+        assert!(is_wasm_plugin("plugin.wasm"));
+        assert!(is_wasm_plugin("plugin.wasm@2"));
+        assert!(!is_wasm_plugin("plugin.py"));
+        assert!(!is_wasm_plugin("gh:examples/wasm.py"));
+
+        assert!(validate_trigger_type("plugin.wasm", PluginType::Schedule).is_ok());
+        assert!(validate_trigger_type("plugin.py", PluginType::Persisted).is_ok());
+        assert!(matches!(
+            validate_trigger_type("plugin.wasm", PluginType::Catalog),
+            Err(PluginError::UnsupportedWasmTrigger(_))
+        ));
+    }
+
+    #[test]
+    fn schedule_plugin_logs_and_writes() {
+        let code = module(
+            r#"(func (export "process_scheduled_call") (param i32 i32) (result i32)
+                (call $info (i32.const 8) (i32.const 5))
+                (call $write (i32.const 16) (i32.const 18))
+                (i32.const 0))"#,
+        );
+        let return_state = run_schedule(&WasmPluginModule::default(), &code, test_cache()).unwrap();
+
+        assert_eq!(return_state.log(), vec!["INFO: hello"]);
+        assert_eq!(return_state.write_back_lines, vec!["cpu,host=a usage=1"]);
+        assert_eq!(return_state.rows_written, 1);
+    }
+
+    #[test]
+    fn cache_is_kept_between_runs() {
+        // the first run caches a line, which the second writes
+        let code = module(
+            r#"(func (export "process_scheduled_call") (param i32 i32) (result i32)
+                (local $value i64)
+                (local.set $value (call $cache_get (i32.const 0) (i32.const 3) (i32.const 0)))
+                (if (i64.eq (local.get $value) (i64.const -1))
+                    (then
+                        (call $cache_put (i32.const 0) (i32.const 3) (i32.const 16) (i32.const 18)
+                            (i64.const -1) (i32.const 0)))
+                    (else
+                        (call $write
+                            (i32.wrap_i64 (i64.shr_u (local.get $value) (i64.const 32)))
+                            (i32.wrap_i64 (local.get $value)))))
+                (i32.const 0))"#,
+        );
+        let plugin = WasmPluginModule::default();
+        let cache = test_cache();
+
+        let first = run_schedule(&plugin, &code, cache.clone()).unwrap();
+        assert!(first.write_back_lines.is_empty());
+
+        let second = run_schedule(&plugin, &code, cache.clone()).unwrap();
+        assert_eq!(second.write_back_lines, vec!["cpu,host=a usage=1"]);
+
+        // the value is in the cache that python plugins use, and only in the trigger's own
+        assert_eq!(
+            cache.get_bytes("key", false),
+            Some(Bytes::from("cpu,host=a usage=1"))
+        );
+        assert!(cache.get_bytes("key", true).is_none());
+        init_pyo3();
+        Python::with_gil(|py| {
+            let py_cache = Py::new(py, cache.clone()).unwrap();
+            let value = py_cache.call_method1(py, "get", ("key",)).unwrap();
+            assert_eq!(
+                value.downcast_bound::<PyBytes>(py).unwrap().as_bytes(),
+                b"cpu,host=a usage=1"
+            );
+        });
+        assert!(cache.delete_bytes("key", false));
+        assert!(!cache.delete_bytes("key", false));
+    }
+
+    #[test]
+    fn failed_run() {
+        let code = module(
+            r#"(func (export "process_scheduled_call") (param i32 i32) (result i32)
+                (i32.const 3))"#,
+        );
+        let err = run_schedule(&WasmPluginModule::default(), &code, test_cache()).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("process_scheduled_call failed with error code 3"),
+            "{err}"
+        );
+
+        let err = execute_wasm_request_trigger(
+            &WasmPluginModule::default(),
+            &code,
+            Arc::new(DatabaseSchema::new(DbId::new(0), "foo".into())),
+            Arc::new(UnimplementedQueryExecutor),
+            None,
+            &None,
+            &PluginRequest {
+                method: "GET".to_string(),
+                path: String::new(),
+                query_params: HashMap::new(),
+                headers: HashMap::new(),
+                body: Bytes::new(),
+                token_id: None,
+            },
+            test_cache(),
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("the process_request function is not exported"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn request_plugin_response() {
+        let code = module(
+            r#"(func (export "process_request") (param i32 i32 i32 i32) (result i64)
+                (i64.or (i64.shl (i64.const 64) (i64.const 32)) (i64.const 26)))"#,
+        );
+        let (response, _) = execute_wasm_request_trigger(
+            &WasmPluginModule::default(),
+            &code,
+            Arc::new(DatabaseSchema::new(DbId::new(0), "foo".into())),
+            Arc::new(UnimplementedQueryExecutor),
+            None,
+            &None,
+            &PluginRequest {
+                method: "POST".to_string(),
+                path: String::new(),
+                query_params: HashMap::new(),
+                headers: HashMap::new(),
+                body: Bytes::from("{}"),
+                token_id: None,
+            },
+            test_cache(),
+        )
+        .unwrap();
+
+        assert_eq!(response.status, 201);
+        assert!(response.headers.is_empty());
+        assert!(matches!(response.body, ResponseBody::Full(body) if body == "ok"));
+    }
+
+    #[test]
+    fn limits() {
+        let code = module(
+            r#"(func (export "process_scheduled_call") (param i32 i32) (result i32)
+                (loop $forever (br $forever))
+                (i32.const 0))"#,
+        );
+        let plugin = WasmPluginModule::default().with_limits(ExecutionLimits {
+            timeout: Some(Duration::from_millis(50)),
+            max_rows_written: None,
+        });
+        assert!(matches!(
+            run_schedule(&plugin, &code, test_cache()),
+            Err(ExecutePluginError::Timeout(_))
+        ));
+
+        let code = module(
+            r#"(func (export "process_scheduled_call") (param i32 i32) (result i32)
+                (call $write (i32.const 16) (i32.const 18))
+                (call $write (i32.const 16) (i32.const 18))
+                (call $write (i32.const 16) (i32.const 18))
+                (i32.const 0))"#,
+        );
+        let plugin = WasmPluginModule::default().with_limits(ExecutionLimits {
+            timeout: None,
+            max_rows_written: Some(2),
+        });
+        assert!(matches!(
+            run_schedule(&plugin, &code, test_cache()),
+            Err(ExecutePluginError::RowLimitExceeded(2))
+        ));
+
+        // growing the memory past the limit fails, so memory.grow returns -1 and the run succeeds
+        let pages = MAX_MEMORY_BYTES / (64 * 1024);
+        let code = module(&format!(
+            r#"(func (export "process_scheduled_call") (param i32 i32) (result i32)
+                (i32.add (memory.grow (i32.const {pages})) (i32.const 1)))"#
+        ));
+        run_schedule(&WasmPluginModule::default(), &code, test_cache()).unwrap();
+    }
+}
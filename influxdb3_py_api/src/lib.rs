@@ -6,7 +6,7 @@ pub enum ExecutePluginError {
     MissingProcessWritesFunction,
 
     #[error(
-        "the process_request function is not present in the plugin. Should be defined as: process_request(influxdb3_local, query_parameters, request_headers, request_body, args=None) -> Tuple[str, Optional[Dict[str, str]]]"
+        "the process_request function is not present in the plugin. Should be defined as: process_request(influxdb3_local, query_parameters, request_headers, request_body, args=None, *, method=None, path=None, token_id=None) -> Tuple[str, Optional[Dict[str, str]]]"
     )]
     MissingProcessRequestFunction,
 
@@ -15,8 +15,27 @@ pub enum ExecutePluginError {
     )]
     MissingProcessScheduledCallFunction,
 
+    #[error(
+        "the process_persisted_files function is not present in the plugin. Should be defined as: process_persisted_files(influxdb3_local, persisted_files, args=None)"
+    )]
+    MissingProcessPersistedFilesFunction,
+
+    #[error(
+        "the process_catalog_event function is not present in the plugin. Should be defined as: process_catalog_event(influxdb3_local, event, args=None)"
+    )]
+    MissingProcessCatalogEventFunction,
+
     #[error("{0}")]
     PluginError(#[from] anyhow::Error),
+
+    #[error(
+        "the plugin did not finish within its execution timeout of {}",
+        humantime::format_duration(*.0)
+    )]
+    Timeout(std::time::Duration),
+
+    #[error("the plugin exceeded its limit of {0} rows written per run")]
+    RowLimitExceeded(usize),
 }
 
 pub mod logging;
@@ -1,10 +1,20 @@
-use arrow_array::builder::{StringBuilder, TimestampNanosecondBuilder};
+use crate::system_py::{CatalogEvent, PersistedParquetFile, PluginRequest};
+use arrow_array::builder::{
+    BooleanBuilder, Float64Builder, StringBuilder, TimestampNanosecondBuilder, UInt32Builder,
+    UInt64Builder,
+};
 use arrow_array::{ArrayRef, RecordBatch};
 use arrow_schema::{ArrowError, DataType, Field, Schema, TimeUnit};
+use chrono::{DateTime, Utc};
 use influxdb3_sys_events::{Event, RingBuffer, ToRecordBatch};
+use influxdb3_wal::WriteBatch;
 use iox_time::Time;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
 use std::fmt::Display;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct ProcessingEngineLog {
@@ -88,3 +98,637 @@ impl ToRecordBatch<ProcessingEngineLog> for ProcessingEngineLog {
         Some(RecordBatch::try_new(Arc::new(Self::schema()), columns))
     }
 }
+
+/// An event that a trigger failed to process after exhausting its retries
+///
+/// Dead letters are shown from the [`SysEventStore`][influxdb3_sys_events::SysEventStore], and can
+/// be replayed through the trigger that failed on them. The processing engine also keeps them in
+/// object store, so that they outlive the store.
+#[derive(Debug, Clone)]
+pub struct ProcessingEngineDeadLetter {
+    pub id: u64,
+    pub event_time: Time,
+    pub database_name: Arc<str>,
+    pub trigger_name: Arc<str>,
+    pub attempts: u32,
+    pub error: String,
+    pub event: DeadLetterEvent,
+    /// Shared between clones, so that replaying a dead letter taken from the store marks the
+    /// one in the store
+    replayed: Arc<AtomicBool>,
+}
+
+/// The event that a trigger failed to process
+#[derive(Debug, Clone)]
+pub enum DeadLetterEvent {
+    /// A batch of writes from the WAL, for a WAL trigger
+    WriteBatch(Arc<WriteBatch>),
+    /// The time of a scheduled run, for a schedule trigger
+    Schedule(DateTime<Utc>),
+    /// A request, for a request trigger
+    Request(Arc<PluginRequest>),
+    /// The Parquet files persisted in a snapshot, for a persisted trigger
+    PersistedFiles(Arc<[PersistedParquetFile]>),
+    /// A change to the catalog, for a catalog trigger
+    Catalog(Arc<CatalogEvent>),
+}
+
+impl DeadLetterEvent {
+    fn event_type(&self) -> &'static str {
+        match self {
+            Self::WriteBatch(_) => "wal",
+            Self::Schedule(_) => "schedule",
+            Self::Request(_) => "request",
+            Self::PersistedFiles(_) => "persisted",
+            Self::Catalog(_) => "catalog",
+        }
+    }
+
+    fn details(&self) -> String {
+        match self {
+            Self::WriteBatch(batch) => format!(
+                "{} table(s), time range {}..{}",
+                batch.table_chunks.len(),
+                batch.min_time_ns,
+                batch.max_time_ns
+            ),
+            Self::Schedule(time) => time.to_rfc3339(),
+            Self::Request(request) => format!(
+                "{} /{}, {} byte body",
+                request.method,
+                request.path,
+                request.body.len()
+            ),
+            Self::PersistedFiles(files) => format!(
+                "{} file(s), {} row(s)",
+                files.len(),
+                files.iter().map(|file| file.row_count).sum::<u64>()
+            ),
+            Self::Catalog(event) => event.event_type.to_string(),
+        }
+    }
+}
+
+impl ProcessingEngineDeadLetter {
+    pub fn new(
+        id: u64,
+        event_time: Time,
+        database_name: Arc<str>,
+        trigger_name: Arc<str>,
+        attempts: u32,
+        error: String,
+        event: DeadLetterEvent,
+    ) -> Self {
+        Self {
+            id,
+            event_time,
+            database_name,
+            trigger_name,
+            attempts,
+            error,
+            event,
+            replayed: Default::default(),
+        }
+    }
+
+    pub fn is_replayed(&self) -> bool {
+        self.replayed.load(Ordering::Acquire)
+    }
+
+    /// Mark the dead letter as replayed, returning `false` if it already was
+    pub fn mark_replayed(&self) -> bool {
+        !self.replayed.swap(true, Ordering::AcqRel)
+    }
+}
+
+impl ToRecordBatch<ProcessingEngineDeadLetter> for ProcessingEngineDeadLetter {
+    fn schema() -> Schema {
+        let fields = vec![
+            Field::new(
+                "event_time",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new("dead_letter_id", DataType::UInt64, false),
+            Field::new("database_name", DataType::Utf8, false),
+            Field::new("trigger_name", DataType::Utf8, false),
+            Field::new("event_type", DataType::Utf8, false),
+            Field::new("event_details", DataType::Utf8, false),
+            Field::new("attempts", DataType::UInt32, false),
+            Field::new("error", DataType::Utf8, false),
+            Field::new("replayed", DataType::Boolean, false),
+        ];
+        Schema::new(fields)
+    }
+
+    fn to_record_batch(
+        items: Option<&RingBuffer<Event<ProcessingEngineDeadLetter>>>,
+    ) -> Option<Result<RecordBatch, ArrowError>> {
+        let items = items?;
+        let capacity = items.len();
+        let mut event_time_builder = TimestampNanosecondBuilder::with_capacity(capacity);
+        let mut id_builder = UInt64Builder::with_capacity(capacity);
+        let mut database_name_builder = StringBuilder::new();
+        let mut trigger_name_builder = StringBuilder::new();
+        let mut event_type_builder = StringBuilder::new();
+        let mut event_details_builder = StringBuilder::new();
+        let mut attempts_builder = UInt32Builder::with_capacity(capacity);
+        let mut error_builder = StringBuilder::new();
+        let mut replayed_builder = BooleanBuilder::with_capacity(capacity);
+        for item in items.in_order() {
+            let dead_letter = &item.data;
+            event_time_builder.append_value(dead_letter.event_time.timestamp_nanos());
+            id_builder.append_value(dead_letter.id);
+            database_name_builder.append_value(&dead_letter.database_name);
+            trigger_name_builder.append_value(&dead_letter.trigger_name);
+            event_type_builder.append_value(dead_letter.event.event_type());
+            event_details_builder.append_value(dead_letter.event.details());
+            attempts_builder.append_value(dead_letter.attempts);
+            error_builder.append_value(&dead_letter.error);
+            replayed_builder.append_value(dead_letter.is_replayed());
+        }
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(event_time_builder.finish()),
+            Arc::new(id_builder.finish()),
+            Arc::new(database_name_builder.finish()),
+            Arc::new(trigger_name_builder.finish()),
+            Arc::new(event_type_builder.finish()),
+            Arc::new(event_details_builder.finish()),
+            Arc::new(attempts_builder.finish()),
+            Arc::new(error_builder.finish()),
+            Arc::new(replayed_builder.finish()),
+        ];
+
+        Some(RecordBatch::try_new(Arc::new(Self::schema()), columns))
+    }
+}
+
+/// A backfill of a WAL trigger over data that was written before it was run
+///
+/// Backfills are kept in the [`SysEventStore`][influxdb3_sys_events::SysEventStore] so that their
+/// progress can be followed in the `system.processing_engine_backfills` table.
+#[derive(Debug, Clone)]
+pub struct ProcessingEngineBackfill {
+    pub id: u64,
+    pub event_time: Time,
+    pub database_name: Arc<str>,
+    pub trigger_name: Arc<str>,
+    pub start_time: Time,
+    pub end_time: Time,
+    /// Shared between clones, so that the task running the backfill updates the one in the store
+    progress: Arc<Mutex<BackfillProgress>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackfillStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+impl Display for BackfillStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackfillStatus::Running => write!(f, "running"),
+            BackfillStatus::Completed => write!(f, "completed"),
+            BackfillStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BackfillProgress {
+    pub status: BackfillStatus,
+    /// The number of rows that the trigger's plugin has been run against
+    pub rows_processed: u64,
+    /// Everything before this time has been run through the plugin
+    pub processed_through: Option<Time>,
+    pub error: Option<String>,
+}
+
+impl ProcessingEngineBackfill {
+    pub fn new(
+        id: u64,
+        event_time: Time,
+        database_name: Arc<str>,
+        trigger_name: Arc<str>,
+        start_time: Time,
+        end_time: Time,
+    ) -> Self {
+        Self {
+            id,
+            event_time,
+            database_name,
+            trigger_name,
+            start_time,
+            end_time,
+            progress: Arc::new(Mutex::new(BackfillProgress {
+                status: BackfillStatus::Running,
+                rows_processed: 0,
+                processed_through: None,
+                error: None,
+            })),
+        }
+    }
+
+    pub fn progress(&self) -> BackfillProgress {
+        self.progress.lock().clone()
+    }
+
+    /// Record that everything before `processed_through` has been run through the plugin
+    pub fn record_progress(&self, rows: u64, processed_through: Time) {
+        let mut progress = self.progress.lock();
+        progress.rows_processed += rows;
+        progress.processed_through = Some(processed_through);
+    }
+
+    pub fn complete(&self) {
+        self.progress.lock().status = BackfillStatus::Completed;
+    }
+
+    pub fn fail(&self, error: impl Display) {
+        let mut progress = self.progress.lock();
+        progress.status = BackfillStatus::Failed;
+        progress.error = Some(error.to_string());
+    }
+}
+
+impl ToRecordBatch<ProcessingEngineBackfill> for ProcessingEngineBackfill {
+    fn schema() -> Schema {
+        let fields = vec![
+            Field::new(
+                "event_time",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new("backfill_id", DataType::UInt64, false),
+            Field::new("database_name", DataType::Utf8, false),
+            Field::new("trigger_name", DataType::Utf8, false),
+            Field::new(
+                "start_time",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new(
+                "end_time",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new("status", DataType::Utf8, false),
+            Field::new("rows_processed", DataType::UInt64, false),
+            Field::new(
+                "processed_through",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                true,
+            ),
+            Field::new("error", DataType::Utf8, true),
+        ];
+        Schema::new(fields)
+    }
+
+    fn to_record_batch(
+        items: Option<&RingBuffer<Event<ProcessingEngineBackfill>>>,
+    ) -> Option<Result<RecordBatch, ArrowError>> {
+        let items = items?;
+        let capacity = items.len();
+        let mut event_time_builder = TimestampNanosecondBuilder::with_capacity(capacity);
+        let mut id_builder = UInt64Builder::with_capacity(capacity);
+        let mut database_name_builder = StringBuilder::new();
+        let mut trigger_name_builder = StringBuilder::new();
+        let mut start_time_builder = TimestampNanosecondBuilder::with_capacity(capacity);
+        let mut end_time_builder = TimestampNanosecondBuilder::with_capacity(capacity);
+        let mut status_builder = StringBuilder::new();
+        let mut rows_processed_builder = UInt64Builder::with_capacity(capacity);
+        let mut processed_through_builder = TimestampNanosecondBuilder::with_capacity(capacity);
+        let mut error_builder = StringBuilder::new();
+        for item in items.in_order() {
+            let backfill = &item.data;
+            let progress = backfill.progress();
+            event_time_builder.append_value(backfill.event_time.timestamp_nanos());
+            id_builder.append_value(backfill.id);
+            database_name_builder.append_value(&backfill.database_name);
+            trigger_name_builder.append_value(&backfill.trigger_name);
+            start_time_builder.append_value(backfill.start_time.timestamp_nanos());
+            end_time_builder.append_value(backfill.end_time.timestamp_nanos());
+            status_builder.append_value(progress.status.to_string());
+            rows_processed_builder.append_value(progress.rows_processed);
+            processed_through_builder.append_option(
+                progress
+                    .processed_through
+                    .map(|time| time.timestamp_nanos()),
+            );
+            error_builder.append_option(progress.error);
+        }
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(event_time_builder.finish()),
+            Arc::new(id_builder.finish()),
+            Arc::new(database_name_builder.finish()),
+            Arc::new(trigger_name_builder.finish()),
+            Arc::new(start_time_builder.finish()),
+            Arc::new(end_time_builder.finish()),
+            Arc::new(status_builder.finish()),
+            Arc::new(rows_processed_builder.finish()),
+            Arc::new(processed_through_builder.finish()),
+            Arc::new(error_builder.finish()),
+        ];
+
+        Some(RecordBatch::try_new(Arc::new(Self::schema()), columns))
+    }
+}
+
+/// A virtual environment built for a plugin package that declares its own requirements
+///
+/// Environments are kept in the [`SysEventStore`][influxdb3_sys_events::SysEventStore] so that
+/// their builds can be followed in the `system.processing_engine_environments` table.
+#[derive(Debug, Clone)]
+pub struct ProcessingEnginePluginEnvironment {
+    pub event_time: Time,
+    pub plugin_name: Arc<str>,
+    pub path: Arc<str>,
+    pub requirements: Arc<[String]>,
+    /// Shared between clones, so that the task building the environment updates the one in the
+    /// store
+    build: Arc<Mutex<EnvironmentBuild>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvironmentStatus {
+    Building,
+    Ready,
+    Failed,
+}
+
+impl Display for EnvironmentStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvironmentStatus::Building => write!(f, "building"),
+            EnvironmentStatus::Ready => write!(f, "ready"),
+            EnvironmentStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EnvironmentBuild {
+    pub status: EnvironmentStatus,
+    pub finished_time: Option<Time>,
+    pub error: Option<String>,
+}
+
+impl ProcessingEnginePluginEnvironment {
+    pub fn new(
+        event_time: Time,
+        plugin_name: Arc<str>,
+        path: Arc<str>,
+        requirements: Arc<[String]>,
+    ) -> Self {
+        Self {
+            event_time,
+            plugin_name,
+            path,
+            requirements,
+            build: Arc::new(Mutex::new(EnvironmentBuild {
+                status: EnvironmentStatus::Building,
+                finished_time: None,
+                error: None,
+            })),
+        }
+    }
+
+    pub fn build(&self) -> EnvironmentBuild {
+        self.build.lock().clone()
+    }
+
+    pub fn ready(&self, finished_time: Time) {
+        let mut build = self.build.lock();
+        build.status = EnvironmentStatus::Ready;
+        build.finished_time = Some(finished_time);
+    }
+
+    pub fn fail(&self, finished_time: Time, error: impl Display) {
+        let mut build = self.build.lock();
+        build.status = EnvironmentStatus::Failed;
+        build.finished_time = Some(finished_time);
+        build.error = Some(error.to_string());
+    }
+}
+
+impl ToRecordBatch<ProcessingEnginePluginEnvironment> for ProcessingEnginePluginEnvironment {
+    fn schema() -> Schema {
+        let fields = vec![
+            Field::new(
+                "event_time",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new("plugin_name", DataType::Utf8, false),
+            Field::new("path", DataType::Utf8, false),
+            Field::new("requirements", DataType::Utf8, false),
+            Field::new("status", DataType::Utf8, false),
+            Field::new(
+                "finished_time",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                true,
+            ),
+            Field::new("error", DataType::Utf8, true),
+        ];
+        Schema::new(fields)
+    }
+
+    fn to_record_batch(
+        items: Option<&RingBuffer<Event<ProcessingEnginePluginEnvironment>>>,
+    ) -> Option<Result<RecordBatch, ArrowError>> {
+        let items = items?;
+        let capacity = items.len();
+        let mut event_time_builder = TimestampNanosecondBuilder::with_capacity(capacity);
+        let mut plugin_name_builder = StringBuilder::new();
+        let mut path_builder = StringBuilder::new();
+        let mut requirements_builder = StringBuilder::new();
+        let mut status_builder = StringBuilder::new();
+        let mut finished_time_builder = TimestampNanosecondBuilder::with_capacity(capacity);
+        let mut error_builder = StringBuilder::new();
+        for item in items.in_order() {
+            let environment = &item.data;
+            let build = environment.build();
+            event_time_builder.append_value(environment.event_time.timestamp_nanos());
+            plugin_name_builder.append_value(&environment.plugin_name);
+            path_builder.append_value(&environment.path);
+            requirements_builder.append_value(environment.requirements.join(", "));
+            status_builder.append_value(build.status.to_string());
+            finished_time_builder
+                .append_option(build.finished_time.map(|time| time.timestamp_nanos()));
+            error_builder.append_option(build.error);
+        }
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(event_time_builder.finish()),
+            Arc::new(plugin_name_builder.finish()),
+            Arc::new(path_builder.finish()),
+            Arc::new(requirements_builder.finish()),
+            Arc::new(status_builder.finish()),
+            Arc::new(finished_time_builder.finish()),
+            Arc::new(error_builder.finish()),
+        ];
+
+        Some(RecordBatch::try_new(Arc::new(Self::schema()), columns))
+    }
+}
+
+/// How many of a trigger's most recent execution times its percentiles are computed over
+const EXECUTION_TIME_SAMPLES: usize = 1024;
+
+/// Execution statistics for a trigger, recorded once per trigger and updated as it runs
+#[derive(Debug, Clone)]
+pub struct ProcessingEngineTriggerStats {
+    pub database_name: Arc<str>,
+    pub trigger_name: Arc<str>,
+    /// Shared between clones, so that the trigger's runner updates the one in the store
+    stats: Arc<Mutex<TriggerStats>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TriggerStats {
+    /// The number of times the plugin was run, not counting retries
+    pub invocations: u64,
+    /// The number of invocations that failed on all of their attempts
+    pub errors: u64,
+    pub retries: u64,
+    pub rows_received: u64,
+    pub lines_written: u64,
+    pub last_success_time: Option<Time>,
+    pub last_failure_time: Option<Time>,
+    /// The most recent execution times, of each attempt
+    execution_times: VecDeque<Duration>,
+}
+
+impl TriggerStats {
+    /// The given percentile, between 0 and 1, of the recent execution times
+    pub fn execution_time_percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.execution_times.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<_> = self.execution_times.iter().copied().collect();
+        sorted.sort_unstable();
+        let index = ((sorted.len() as f64 * percentile).ceil() as usize).clamp(1, sorted.len());
+        Some(sorted[index - 1])
+    }
+}
+
+impl ProcessingEngineTriggerStats {
+    pub fn new(database_name: Arc<str>, trigger_name: Arc<str>) -> Self {
+        Self {
+            database_name,
+            trigger_name,
+            stats: Default::default(),
+        }
+    }
+
+    pub fn stats(&self) -> TriggerStats {
+        self.stats.lock().clone()
+    }
+
+    /// Record a run of the plugin, which took `attempts` attempts that ran for
+    /// `execution_times`
+    pub fn record_invocation(&self, execution_times: &[Duration], succeeded: bool, now: Time) {
+        let mut stats = self.stats.lock();
+        stats.invocations += 1;
+        stats.retries += execution_times.len().saturating_sub(1) as u64;
+        if succeeded {
+            stats.last_success_time = Some(now);
+        } else {
+            stats.errors += 1;
+            stats.last_failure_time = Some(now);
+        }
+        for execution_time in execution_times {
+            if stats.execution_times.len() == EXECUTION_TIME_SAMPLES {
+                stats.execution_times.pop_front();
+            }
+            stats.execution_times.push_back(*execution_time);
+        }
+    }
+
+    pub fn record_rows_received(&self, rows: u64) {
+        self.stats.lock().rows_received += rows;
+    }
+
+    pub fn record_lines_written(&self, lines: u64) {
+        self.stats.lock().lines_written += lines;
+    }
+}
+
+impl ToRecordBatch<ProcessingEngineTriggerStats> for ProcessingEngineTriggerStats {
+    fn schema() -> Schema {
+        let fields = vec![
+            Field::new("database_name", DataType::Utf8, false),
+            Field::new("trigger_name", DataType::Utf8, false),
+            Field::new("invocations", DataType::UInt64, false),
+            Field::new("errors", DataType::UInt64, false),
+            Field::new("retries", DataType::UInt64, false),
+            Field::new("p50_execution_time_ms", DataType::Float64, true),
+            Field::new("p99_execution_time_ms", DataType::Float64, true),
+            Field::new("rows_received", DataType::UInt64, false),
+            Field::new("lines_written", DataType::UInt64, false),
+            Field::new(
+                "last_success_time",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                true,
+            ),
+            Field::new(
+                "last_failure_time",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                true,
+            ),
+        ];
+        Schema::new(fields)
+    }
+
+    fn to_record_batch(
+        items: Option<&RingBuffer<Event<ProcessingEngineTriggerStats>>>,
+    ) -> Option<Result<RecordBatch, ArrowError>> {
+        let items = items?;
+        let capacity = items.len();
+        let mut database_name_builder = StringBuilder::new();
+        let mut trigger_name_builder = StringBuilder::new();
+        let mut invocations_builder = UInt64Builder::with_capacity(capacity);
+        let mut errors_builder = UInt64Builder::with_capacity(capacity);
+        let mut retries_builder = UInt64Builder::with_capacity(capacity);
+        let mut p50_builder = Float64Builder::with_capacity(capacity);
+        let mut p99_builder = Float64Builder::with_capacity(capacity);
+        let mut rows_received_builder = UInt64Builder::with_capacity(capacity);
+        let mut lines_written_builder = UInt64Builder::with_capacity(capacity);
+        let mut last_success_builder = TimestampNanosecondBuilder::with_capacity(capacity);
+        let mut last_failure_builder = TimestampNanosecondBuilder::with_capacity(capacity);
+        let as_millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        for item in items.in_order() {
+            let trigger = &item.data;
+            let stats = trigger.stats();
+            database_name_builder.append_value(&trigger.database_name);
+            trigger_name_builder.append_value(&trigger.trigger_name);
+            invocations_builder.append_value(stats.invocations);
+            errors_builder.append_value(stats.errors);
+            retries_builder.append_value(stats.retries);
+            p50_builder.append_option(stats.execution_time_percentile(0.5).map(as_millis));
+            p99_builder.append_option(stats.execution_time_percentile(0.99).map(as_millis));
+            rows_received_builder.append_value(stats.rows_received);
+            lines_written_builder.append_value(stats.lines_written);
+            last_success_builder
+                .append_option(stats.last_success_time.map(|time| time.timestamp_nanos()));
+            last_failure_builder
+                .append_option(stats.last_failure_time.map(|time| time.timestamp_nanos()));
+        }
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(database_name_builder.finish()),
+            Arc::new(trigger_name_builder.finish()),
+            Arc::new(invocations_builder.finish()),
+            Arc::new(errors_builder.finish()),
+            Arc::new(retries_builder.finish()),
+            Arc::new(p50_builder.finish()),
+            Arc::new(p99_builder.finish()),
+            Arc::new(rows_received_builder.finish()),
+            Arc::new(lines_written_builder.finish()),
+            Arc::new(last_success_builder.finish()),
+            Arc::new(last_failure_builder.finish()),
+        ];
+
+        Some(RecordBatch::try_new(Arc::new(Self::schema()), columns))
+    }
+}
@@ -6,39 +6,53 @@ use crate::ExecutePluginError;
 use crate::logging::{LogLevel, ProcessingEngineLog};
 use crate::system_py::CacheId::{Global, GlobalTest, Trigger, TriggerTest};
 use anyhow::{Context, bail};
+use arrow_array::ffi_stream::FFI_ArrowArrayStream;
 use arrow_array::types::Int32Type;
 use arrow_array::{
     Array, BooleanArray, DictionaryArray, Float64Array, Int32Array, Int64Array, RecordBatch,
-    StringArray, TimestampNanosecondArray, UInt64Array,
+    RecordBatchIterator, StringArray, TimestampNanosecondArray, UInt64Array,
 };
-use arrow_schema::DataType;
+use arrow_schema::{DataType, SchemaRef};
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
+use futures::future::BoxFuture;
 use hashbrown::{HashMap, HashSet};
 use humantime::format_duration;
 use influxdb3_catalog::catalog::DatabaseSchema;
-use influxdb3_id::TableId;
+use influxdb3_catalog::log::{CatalogEventType, DatabaseCatalogOp, FieldDataType, FieldDefinition};
+use influxdb3_id::{ColumnId, DbId, TableId};
 use influxdb3_internal_api::query_executor::QueryExecutor;
 use influxdb3_sys_events::SysEventStore;
+use influxdb3_types::write::Precision;
 use influxdb3_wal::{FieldData, WriteBatch};
 use iox_query_params::StatementParams;
 use iox_time::{Time, TimeProvider};
 use observability_deps::tracing::{error, info, warn};
-use parking_lot::Mutex;
-use pyo3::exceptions::{PyException, PyValueError};
-use pyo3::prelude::{PyAnyMethods, PyModule};
-use pyo3::types::{PyBytes, PyDateTime, PyDict, PyList, PyTuple};
+use parking_lot::{Condvar, Mutex, MutexGuard};
+use pyo3::exceptions::{PyBaseException, PyException, PyValueError};
+use pyo3::prelude::{PyAnyMethods, PyModule, PyModuleMethods};
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{
+    PyBool, PyBoolMethods, PyByteArray, PyByteArrayMethods, PyBytes, PyBytesMethods, PyDateTime,
+    PyDict, PyDictMethods, PyInt, PyIterator, PyList, PyListMethods, PySet, PyString, PyTuple,
+    PyTypeMethods,
+};
 use pyo3::{
-    Bound, IntoPyObject, Py, PyAny, PyObject, PyResult, Python, create_exception, pyclass,
-    pymethods, pymodule,
+    Bound, IntoPyObject, IntoPyObjectExt, Py, PyAny, PyErr, PyObject, PyResult, PyTypeInfo, Python,
+    create_exception, pyclass, pymethods, pymodule,
 };
 use std::collections::BTreeMap;
 use std::ffi::CString;
-use std::sync::Arc;
-use std::time::Duration;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
 
 create_exception!(influxdb3_py_api, QueryError, PyException);
+create_exception!(influxdb3_py_api, RowLimitExceededError, PyException);
+create_exception!(influxdb3_py_api, WriteError, PyException);
+// a BaseException, like KeyboardInterrupt, so that it isn't caught by `except Exception`
+create_exception!(influxdb3_py_api, PluginTimeoutError, PyBaseException);
 
 #[pyclass]
 #[derive(Debug)]
@@ -48,6 +62,10 @@ struct PyPluginCallApi {
     return_state: Arc<Mutex<PluginReturnState>>,
     logger: Option<ProcessingEngineLogger>,
     py_cache: PyCache,
+    secrets: PluginSecrets,
+    limits: ExecutionLimits,
+    value_caches: Option<Arc<dyn ValueCacheReader>>,
+    line_writer: Option<Arc<dyn LineWriter>>,
 }
 
 #[derive(Debug)]
@@ -88,6 +106,9 @@ pub struct PluginReturnState {
     pub log_lines: Vec<LogLine>,
     pub write_back_lines: Vec<String>,
     pub write_db_lines: HashMap<String, Vec<String>>,
+    /// The rows that the plugin has written, including any that were over its limit and so
+    /// were not kept
+    pub rows_written: usize,
 }
 
 impl PluginReturnState {
@@ -118,6 +139,48 @@ impl std::fmt::Debug for LogLine {
     }
 }
 
+impl PyPluginCallApi {
+    fn value_cache_reader(&self) -> PyResult<&Arc<dyn ValueCacheReader>> {
+        self.value_caches
+            .as_ref()
+            .ok_or_else(|| QueryError::new_err("the value caches are not available to this plugin"))
+    }
+
+    /// Keep a row written by the plugin, failing if it takes the plugin over its limit of rows
+    /// written per run. Rows over the limit are counted but not kept, so that the run fails even
+    /// if the plugin catches the error.
+    fn push_row(&self, db_name: Option<&str>, line: String) -> PyResult<()> {
+        let mut return_state = self.return_state.lock();
+        self.count_rows_written(&mut return_state, 1)?;
+        match db_name {
+            None => return_state.write_back_lines.push(line),
+            Some(db_name) => return_state
+                .write_db_lines
+                .entry(db_name.to_string())
+                .or_default()
+                .push(line),
+        }
+        Ok(())
+    }
+
+    /// Count rows written by the plugin towards its limit of rows written per run
+    fn count_rows_written(
+        &self,
+        return_state: &mut PluginReturnState,
+        rows: usize,
+    ) -> PyResult<()> {
+        return_state.rows_written += rows;
+        if let Some(max_rows_written) = self.limits.max_rows_written {
+            if return_state.rows_written > max_rows_written {
+                return Err(RowLimitExceededError::new_err(format!(
+                    "the plugin exceeded its limit of {max_rows_written} rows written per run"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[pymethods]
 impl PyPluginCallApi {
     #[pyo3(signature = (*args))]
@@ -162,7 +225,7 @@ impl PyPluginCallApi {
             .map(|arg| arg?.str()?.extract::<String>())
             .collect::<Result<Vec<String>, _>>()?
             .join(" ");
-        Ok(line)
+        Ok(self.secrets.redact(line))
     }
 
     fn write(&self, line_builder: &Bound<'_, PyAny>) -> PyResult<()> {
@@ -170,32 +233,190 @@ impl PyPluginCallApi {
         let line = line_builder.getattr("build")?.call0()?;
         let line_str = line.extract::<String>()?;
 
-        // Add to write_back_lines
-        self.return_state.lock().write_back_lines.push(line_str);
-
-        Ok(())
+        self.push_row(None, line_str)
     }
 
     fn write_to_db(&self, db_name: &str, line_builder: &Bound<'_, PyAny>) -> PyResult<()> {
         let line = line_builder.getattr("build")?.call0()?;
         let line_str = line.extract::<String>()?;
 
-        self.return_state
-            .lock()
-            .write_db_lines
-            .entry(db_name.to_string())
-            .or_default()
-            .push(line_str);
+        self.push_row(Some(db_name), line_str)
+    }
 
-        Ok(())
+    /// Write lines now, rather than after the plugin returns, and return what was accepted and
+    /// rejected: `{"accepted": int, "rejected": int, "errors": [{"line_number", "line", "error"}]}`.
+    ///
+    /// `lines` is a `LineBuilder` or a list of them, written to the trigger's database unless
+    /// `db_name` is given. `precision` ("s", "ms", "us" or "ns") and `no_sync` work as they do
+    /// for the HTTP write API: with `no_sync` the call returns before the lines are in the WAL,
+    /// so they may not be queryable straight away. With `accept_partial=False` no lines are
+    /// written if any are invalid.
+    #[pyo3(signature = (lines, db_name=None, precision=None, no_sync=false, accept_partial=true))]
+    fn write_sync(
+        &self,
+        py: Python<'_>,
+        lines: &Bound<'_, PyAny>,
+        db_name: Option<String>,
+        precision: Option<&str>,
+        no_sync: bool,
+        accept_partial: bool,
+    ) -> PyResult<Py<PyDict>> {
+        let line_writer = self.line_writer.as_ref().ok_or_else(|| {
+            WriteError::new_err("synchronous writes are not available to this plugin")
+        })?;
+        let precision = match precision {
+            Some(precision) => precision.parse().map_err(PyValueError::new_err)?,
+            None => Precision::Auto,
+        };
+        let lines = if lines.downcast::<PyList>().is_ok() || lines.downcast::<PyTuple>().is_ok() {
+            lines
+                .try_iter()?
+                .map(|line_builder| line_builder?.call_method0("build")?.extract::<String>())
+                .collect::<PyResult<Vec<_>>>()?
+        } else {
+            vec![lines.call_method0("build")?.extract::<String>()?]
+        };
+        if lines.is_empty() {
+            return Err(PyValueError::new_err("no lines to write"));
+        }
+        self.count_rows_written(&mut self.return_state.lock(), lines.len())?;
+
+        let write = line_writer.write_lp(
+            db_name,
+            lines.join("\n"),
+            SyncWriteOptions {
+                precision,
+                accept_partial,
+                no_sync,
+            },
+        );
+        let handle = tokio::spawn(write);
+        // the write waits on the WAL flush, so don't hold up other plugins while waiting
+        let res = block_on_host(py, handle)
+            .map_err(|e| WriteError::new_err(format!("join error: {e}")))?;
+        let written = res.map_err(|e| WriteError::new_err(format!("error writing lines: {e}")))?;
+
+        let errors = PyList::empty(py);
+        for rejected in &written.rejected {
+            let error = PyDict::new(py);
+            error.set_item("line_number", rejected.line_number)?;
+            error.set_item("line", &rejected.line)?;
+            error.set_item("error", &rejected.error)?;
+            errors.append(error)?;
+        }
+        let result = PyDict::new(py);
+        result.set_item("accepted", written.accepted)?;
+        result.set_item("rejected", written.rejected.len())?;
+        result.set_item("errors", errors)?;
+        Ok(result.unbind())
     }
 
     #[pyo3(signature = (query, args=None))]
     fn query(
         &self,
+        py: Python<'_>,
+        query: String,
+        args: Option<std::collections::HashMap<String, String>>,
+    ) -> PyResult<Py<PyList>> {
+        let (_, batches) = self.run_query(py, query, args)?;
+
+        record_batches_to_rows(py, batches)
+    }
+
+    /// Run a query and return the result as a `pyarrow.Table`, rather than as a list of rows.
+    ///
+    /// The record batches are handed to pyarrow through the Arrow C stream interface, so no
+    /// per-row conversion is done, and every Arrow type the query can produce is supported.
+    #[pyo3(signature = (query, args=None))]
+    fn query_arrow(
+        &self,
+        py: Python<'_>,
         query: String,
         args: Option<std::collections::HashMap<String, String>>,
+    ) -> PyResult<PyObject> {
+        // the schema comes from the query rather than the batches, so that a query that returns
+        // no rows still gives a table with the right columns:
+        let (schema, batches) = self.run_query(py, query, args)?;
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema);
+        let mut stream = FFI_ArrowArrayStream::new(Box::new(reader));
+
+        let pyarrow = py.import("pyarrow").map_err(|e| {
+            QueryError::new_err(format!(
+                "query_arrow requires pyarrow to be installed in the plugin environment: {}",
+                e
+            ))
+        })?;
+        // pyarrow takes ownership of the stream by moving it out of `stream`, and leaves behind
+        // a released stream, which does nothing when it is dropped:
+        let stream_ptr = std::ptr::addr_of_mut!(stream) as usize;
+        let reader = pyarrow
+            .getattr("RecordBatchReader")?
+            .call_method1("_import_from_c", (stream_ptr,))?;
+        let table = reader.call_method0("read_all")?;
+
+        Ok(table.unbind())
+    }
+
+    /// Read the latest values from a last value cache on `table`, without running a query.
+    ///
+    /// `key_filters` maps key columns of the cache to a value, or a list of values, to keep. The
+    /// cache name can be left out if the table has only one last value cache.
+    #[pyo3(signature = (table, name=None, key_filters=None))]
+    fn last_cache(
+        &self,
+        py: Python<'_>,
+        table: &str,
+        name: Option<&str>,
+        key_filters: Option<Bound<'_, PyDict>>,
+    ) -> PyResult<Py<PyList>> {
+        let key_filters = key_filters_from_py(key_filters.as_ref())?;
+        let batches = self
+            .value_cache_reader()?
+            .last_cache(table, name, &key_filters)
+            .map_err(|e| QueryError::new_err(format!("error reading last cache: {e}")))?;
+        record_batches_to_rows(py, batches)
+    }
+
+    /// Read the distinct values from a distinct value cache on `table`, without running a
+    /// query.
+    ///
+    /// `key_filters` maps columns of the cache to a value, or a list of values, to keep. The
+    /// cache name can be left out if the table has only one distinct value cache.
+    #[pyo3(signature = (table, name=None, key_filters=None))]
+    fn distinct_cache(
+        &self,
+        py: Python<'_>,
+        table: &str,
+        name: Option<&str>,
+        key_filters: Option<Bound<'_, PyDict>>,
     ) -> PyResult<Py<PyList>> {
+        let key_filters = key_filters_from_py(key_filters.as_ref())?;
+        let batches = self
+            .value_cache_reader()?
+            .distinct_cache(table, name, &key_filters)
+            .map_err(|e| QueryError::new_err(format!("error reading distinct cache: {e}")))?;
+        record_batches_to_rows(py, batches)
+    }
+
+    #[getter]
+    fn cache(&self) -> PyResult<PyCache> {
+        self.py_cache.cache_store.lock().cleanup();
+        Ok(self.py_cache.clone())
+    }
+
+    #[getter]
+    fn secrets(&self) -> PyResult<PluginSecrets> {
+        Ok(self.secrets.clone())
+    }
+}
+
+impl PyPluginCallApi {
+    fn run_query(
+        &self,
+        py: Python<'_>,
+        query: String,
+        args: Option<std::collections::HashMap<String, String>>,
+    ) -> PyResult<(SchemaRef, Vec<RecordBatch>)> {
         let query_executor = Arc::clone(&self.query_executor);
         let db_schema_name = Arc::clone(&self.db_schema.name);
 
@@ -216,108 +437,19 @@ impl PyPluginCallApi {
                     QueryError::new_err(format!("error: {} executing query: {}", e, query))
                 })?;
 
-            res.try_collect().await.map_err(|e| {
+            let schema = res.schema();
+            let batches = res.try_collect().await.map_err(|e| {
                 QueryError::new_err(format!("error: {} executing query: {}", e, query))
-            })
+            })?;
+            Ok((schema, batches))
         });
 
         // Block the current thread until the async task completes
-        let res =
-            tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(handle));
+        let res = block_on_host(py, handle);
 
-        let res = res.map_err(|e| QueryError::new_err(format!("join error: {}", e)))?;
-
-        let batches: Vec<RecordBatch> = res?;
-
-        Python::with_gil(|py| {
-            let mut rows: Vec<PyObject> = Vec::new();
-
-            for batch in batches {
-                let num_rows = batch.num_rows();
-                let schema = batch.schema();
-
-                for row_idx in 0..num_rows {
-                    let row = PyDict::new(py);
-                    for col_idx in 0..schema.fields().len() {
-                        let field = schema.field(col_idx);
-                        let field_name = field.name().as_str();
-
-                        let array = batch.column(col_idx);
-
-                        match array.data_type() {
-                            DataType::Int64 => {
-                                let array = array.as_any().downcast_ref::<Int64Array>().unwrap();
-                                row.set_item(field_name, array.value(row_idx))?;
-                            }
-                            DataType::UInt64 => {
-                                let array = array.as_any().downcast_ref::<UInt64Array>().unwrap();
-                                row.set_item(field_name, array.value(row_idx))?;
-                            }
-                            DataType::Float64 => {
-                                let array = array.as_any().downcast_ref::<Float64Array>().unwrap();
-                                row.set_item(field_name, array.value(row_idx))?;
-                            }
-                            DataType::Utf8 => {
-                                let array = array.as_any().downcast_ref::<StringArray>().unwrap();
-                                row.set_item(field_name, array.value(row_idx))?;
-                            }
-                            DataType::Boolean => {
-                                let array = array.as_any().downcast_ref::<BooleanArray>().unwrap();
-                                row.set_item(field_name, array.value(row_idx))?;
-                            }
-                            DataType::Timestamp(_, _) => {
-                                let array = array
-                                    .as_any()
-                                    .downcast_ref::<TimestampNanosecondArray>()
-                                    .unwrap();
-                                row.set_item(field_name, array.value(row_idx))?;
-                            }
-                            DataType::Dictionary(_, _) => {
-                                let col = array
-                                    .as_any()
-                                    .downcast_ref::<DictionaryArray<Int32Type>>()
-                                    .expect("unexpected datatype");
-
-                                let keys = col
-                                    .keys()
-                                    .as_any()
-                                    .downcast_ref::<Int32Array>()
-                                    .expect("unexpected datatype");
-
-                                let values = col.values();
-                                let values = values
-                                    .as_any()
-                                    .downcast_ref::<StringArray>()
-                                    .expect("unexpected datatype");
-
-                                let val = values.value(keys.value(row_idx) as usize).to_string();
-                                row.set_item(field_name, val)?;
-                            }
-                            _ => {
-                                return Err(PyValueError::new_err(format!(
-                                    "Unsupported data type: {:?}",
-                                    array.data_type()
-                                )));
-                            }
-                        }
-                    }
-                    rows.push(row.into());
-                }
-            }
-
-            let list = PyList::new(py, rows)?.unbind();
-            Ok(list)
-        })
-    }
-
-    #[getter]
-    fn cache(&self) -> PyResult<PyCache> {
-        self.py_cache.cache_store.lock().cleanup();
-        Ok(self.py_cache.clone())
+        res.map_err(|e| QueryError::new_err(format!("join error: {}", e)))?
     }
-}
 
-impl PyPluginCallApi {
     fn write_to_logger(&self, level: LogLevel, log_line: String) {
         if let Some(logger) = &self.logger {
             logger.log(level, log_line);
@@ -332,6 +464,10 @@ const PROCESS_SCHEDULED_CALL_SITE: &str = "process_scheduled_call";
 
 const PROCESS_REQUEST_CALL_SITE: &str = "process_request";
 
+const PROCESS_PERSISTED_FILES_CALL_SITE: &str = "process_persisted_files";
+
+const PROCESS_CATALOG_EVENT_CALL_SITE: &str = "process_catalog_event";
+
 const LINE_BUILDER_CODE: &str = r#"
 from typing import Optional
 from collections import OrderedDict
@@ -474,9 +610,213 @@ fn map_to_py_object<'py>(py: Python<'py>, map: &HashMap<String, String>) -> Boun
     dict
 }
 
+const INIT_HOOK: &str = "init";
+
+const SHUTDOWN_HOOK: &str = "shutdown";
+
+/// A plugin's code compiled into a python module of its own.
+///
+/// The module is kept between runs of the plugin, so that its imports and module level setup
+/// only run again when its code changes. A plugin can define two lifecycle hooks in its module:
+/// `init(influxdb3_local, args)`, which is run once before the plugin is first called, and
+/// `shutdown(influxdb3_local)`, which is run when the module is replaced because the plugin's
+/// code changed, or when the trigger is stopped.
+#[derive(Debug)]
+pub struct PluginModule {
+    name: String,
+    compiled: Mutex<Option<CompiledPlugin>>,
+    secrets: PluginSecrets,
+    limits: ExecutionLimits,
+    value_caches: Option<Arc<dyn ValueCacheReader>>,
+    line_writer: Option<Arc<dyn LineWriter>>,
+    environment: Option<Arc<dyn PluginEnvironment>>,
+}
+
+#[derive(Debug)]
+struct CompiledPlugin {
+    code: Arc<str>,
+    module: Py<PyModule>,
+}
+
+impl PluginModule {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            compiled: Mutex::new(None),
+            secrets: PluginSecrets::default(),
+            limits: ExecutionLimits::default(),
+            value_caches: None,
+            line_writer: None,
+            environment: None,
+        }
+    }
+
+    /// Give the plugin access to secrets through `influxdb3_local.secrets`
+    pub fn with_secrets(mut self, reader: Arc<dyn SecretReader>) -> Self {
+        self.secrets = PluginSecrets::new(reader);
+        self
+    }
+
+    /// Give the plugin direct access to the last and distinct value caches of its database
+    pub fn with_value_caches(mut self, reader: Arc<dyn ValueCacheReader>) -> Self {
+        self.value_caches = Some(reader);
+        self
+    }
+
+    /// Let the plugin write lines synchronously through `influxdb3_local.write_sync()`
+    pub fn with_line_writer(mut self, writer: Arc<dyn LineWriter>) -> Self {
+        self.line_writer = Some(writer);
+        self
+    }
+
+    /// Run the plugin in a virtual environment of its own, rather than the server's shared one
+    pub fn with_environment(mut self, environment: Arc<dyn PluginEnvironment>) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    /// Limit each run of the plugin, including its `init` and `shutdown` hooks
+    pub fn with_limits(mut self, limits: ExecutionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Run `f`, which calls into the plugin, failing it if the plugin goes over its limits
+    fn run_limited<T>(
+        &self,
+        py: Python<'_>,
+        return_state: &Mutex<PluginReturnState>,
+        f: impl FnOnce() -> Result<T, ExecutePluginError>,
+    ) -> Result<T, ExecutePluginError> {
+        let result = run_watched(py, self.environment.as_deref(), self.limits.timeout, f)
+            .map_err(|e| self.secrets.redact_error(e));
+        if let Some(max_rows_written) = self.limits.max_rows_written {
+            if return_state.lock().rows_written > max_rows_written {
+                return Err(ExecutePluginError::RowLimitExceeded(max_rows_written));
+            }
+        }
+        result
+    }
+
+    /// Hand a streamed response body the same limits, environment and secrets as the run that
+    /// returned it
+    fn watch_stream(&self, response: &mut PluginResponse) {
+        if let ResponseBody::Stream(stream) = &mut response.body {
+            stream.timeout = self.limits.timeout;
+            stream.environment = self.environment.clone();
+            stream.secrets = self.secrets.clone();
+        }
+    }
+
+    /// Get the module for `code`, compiling it and running its `init` hook if the code is not
+    /// the code that the current module was compiled from.
+    ///
+    /// The lock is never held while python runs, as the plugin may release the GIL to another
+    /// thread that is waiting on it.
+    fn load<'py>(
+        &self,
+        py: Python<'py>,
+        code: &Arc<str>,
+        local_api: &Bound<'py, PyAny>,
+        args: &Option<Bound<'py, PyDict>>,
+    ) -> Result<Bound<'py, PyModule>, ExecutePluginError> {
+        if let Some(compiled) = &*self.compiled.lock() {
+            if Arc::ptr_eq(&compiled.code, code) || compiled.code == *code {
+                return Ok(compiled.module.bind(py).clone());
+            }
+        }
+
+        let module = PyModule::new(py, &self.name).map_err(anyhow::Error::from)?;
+        let globals = module.dict();
+        // import the LineBuilder for use in the python code
+        py.run(
+            &CString::new(LINE_BUILDER_CODE).unwrap(),
+            Some(&globals),
+            None,
+        )
+        .map_err(|e| anyhow::Error::new(e).context("failed to eval the LineBuilder API code"))?;
+        py.run(&CString::new(code.as_ref()).unwrap(), Some(&globals), None)
+            .map_err(anyhow::Error::from)?;
+
+        let replaced = self.compiled.lock().replace(CompiledPlugin {
+            code: Arc::clone(code),
+            module: module.clone().unbind(),
+        });
+        if let Some(replaced) = replaced {
+            call_shutdown_hook(replaced.module.bind(py), local_api)?;
+        }
+
+        if let Ok(init) = module.getattr(INIT_HOOK) {
+            if let Err(e) = init.call1((local_api, args)) {
+                // compile the module again on the next run, so that its init hook is retried
+                let mut compiled = self.compiled.lock();
+                if compiled
+                    .as_ref()
+                    .is_some_and(|c| Arc::ptr_eq(&c.code, code))
+                {
+                    *compiled = None;
+                }
+                return Err(anyhow::Error::new(e)
+                    .context("plugin init hook failed")
+                    .into());
+            }
+        }
+
+        Ok(module)
+    }
+
+    /// Drop the module, running its `shutdown` hook if it has been compiled
+    pub fn shutdown(
+        &self,
+        db_schema: Arc<DatabaseSchema>,
+        query_executor: Arc<dyn QueryExecutor>,
+        logger: Option<ProcessingEngineLogger>,
+        py_cache: PyCache,
+    ) -> Result<PluginReturnState, ExecutePluginError> {
+        let Some(compiled) = self.compiled.lock().take() else {
+            return Ok(PluginReturnState::default());
+        };
+        Python::with_gil(|py| {
+            let api = PyPluginCallApi {
+                db_schema,
+                query_executor,
+                logger,
+                return_state: Default::default(),
+                py_cache,
+                secrets: self.secrets.clone(),
+                limits: self.limits,
+                value_caches: self.value_caches.clone(),
+                line_writer: self.line_writer.clone(),
+            };
+            let return_state = Arc::clone(&api.return_state);
+            let local_api = api.into_pyobject(py).map_err(anyhow::Error::from)?;
+
+            self.run_limited(py, &return_state, || {
+                call_shutdown_hook(compiled.module.bind(py), local_api.as_any())
+            })?;
+
+            let ret = std::mem::take(&mut *return_state.lock());
+            Ok(ret)
+        })
+    }
+}
+
+fn call_shutdown_hook(
+    module: &Bound<'_, PyModule>,
+    local_api: &Bound<'_, PyAny>,
+) -> Result<(), ExecutePluginError> {
+    if let Ok(shutdown) = module.getattr(SHUTDOWN_HOOK) {
+        shutdown
+            .call1((local_api,))
+            .map_err(|e| anyhow::Error::new(e).context("plugin shutdown hook failed"))?;
+    }
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn execute_python_with_batch(
-    code: &str,
+    plugin: &PluginModule,
+    code: &Arc<str>,
     write_batch: &WriteBatch,
     schema: Arc<DatabaseSchema>,
     query_executor: Arc<dyn QueryExecutor>,
@@ -495,13 +835,6 @@ pub fn execute_python_with_batch(
         None
     };
     Python::with_gil(|py| {
-        // import the LineBuilder for use in the python code
-
-        py.run(&CString::new(LINE_BUILDER_CODE).unwrap(), None, None)
-            .map_err(|e| {
-                anyhow::Error::new(e).context("failed to eval the LineBuilder API code")
-            })?;
-
         // convert the write batch into a python object
         let mut table_batches = Vec::with_capacity(write_batch.table_chunks.len());
 
@@ -593,6 +926,10 @@ pub fn execute_python_with_batch(
             logger: logger.clone(),
             return_state: Default::default(),
             py_cache,
+            secrets: plugin.secrets.clone(),
+            limits: plugin.limits,
+            value_caches: plugin.value_caches.clone(),
+            line_writer: plugin.line_writer.clone(),
         };
         let return_state = Arc::clone(&api.return_state);
         let local_api = api.into_pyobject(py).map_err(anyhow::Error::from)?;
@@ -600,16 +937,18 @@ pub fn execute_python_with_batch(
         // turn args into an optional dict to pass into python
         let args = args_to_py_object(py, args);
 
-        // run the code and get the python function to call
-        py.run(&CString::new(code).unwrap(), None, None)
-            .map_err(anyhow::Error::from)?;
-        let py_func = py
-            .eval(&CString::new(PROCESS_WRITES_CALL_SITE).unwrap(), None, None)
-            .map_err(|_| ExecutePluginError::MissingProcessWritesFunction)?;
+        plugin.run_limited(py, &return_state, || {
+            // load the plugin's module and get the python function to call
+            let module = plugin.load(py, code, local_api.as_any(), &args)?;
+            let py_func = module
+                .getattr(PROCESS_WRITES_CALL_SITE)
+                .map_err(|_| ExecutePluginError::MissingProcessWritesFunction)?;
 
-        py_func
-            .call1((local_api, py_batches.unbind(), args))
-            .map_err(anyhow::Error::from)?;
+            py_func
+                .call1((local_api, py_batches.unbind(), args))
+                .map_err(anyhow::Error::from)?;
+            Ok(())
+        })?;
 
         // swap with an empty return state to avoid cloning
         let empty_return_state = PluginReturnState::default();
@@ -634,8 +973,10 @@ pub fn execute_python_with_batch(
     })
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_schedule_trigger(
-    code: &str,
+    plugin: &PluginModule,
+    code: &Arc<str>,
     schedule_time: DateTime<Utc>,
     schema: Arc<DatabaseSchema>,
     query_executor: Arc<dyn QueryExecutor>,
@@ -653,24 +994,21 @@ pub fn execute_schedule_trigger(
         None
     };
     Python::with_gil(|py| {
-        // import the LineBuilder for use in the python code
-
         let py_datetime = PyDateTime::from_timestamp(py, schedule_time.timestamp() as f64, None)
             .map_err(|e| {
                 anyhow::Error::new(e).context("error converting the schedule time to Python time")
             })?;
 
-        py.run(&CString::new(LINE_BUILDER_CODE).unwrap(), None, None)
-            .map_err(|e| {
-                anyhow::Error::new(e).context("failed to eval the LineBuilder API code")
-            })?;
-
         let api = PyPluginCallApi {
             db_schema: schema,
             query_executor,
             logger: logger.clone(),
             return_state: Default::default(),
             py_cache,
+            secrets: plugin.secrets.clone(),
+            limits: plugin.limits,
+            value_caches: plugin.value_caches.clone(),
+            line_writer: plugin.line_writer.clone(),
         };
         let return_state = Arc::clone(&api.return_state);
         let local_api = api.into_pyobject(py).map_err(anyhow::Error::from)?;
@@ -678,21 +1016,18 @@ pub fn execute_schedule_trigger(
         // turn args into an optional dict to pass into python
         let args = args_to_py_object(py, args);
 
-        // run the code and get the python function to call
-        py.run(&CString::new(code).unwrap(), None, None)
-            .map_err(anyhow::Error::from)?;
-
-        let py_func = py
-            .eval(
-                &CString::new(PROCESS_SCHEDULED_CALL_SITE).unwrap(),
-                None,
-                None,
-            )
-            .map_err(|_| ExecutePluginError::MissingProcessScheduledCallFunction)?;
+        plugin.run_limited(py, &return_state, || {
+            // load the plugin's module and get the python function to call
+            let module = plugin.load(py, code, local_api.as_any(), &args)?;
+            let py_func = module
+                .getattr(PROCESS_SCHEDULED_CALL_SITE)
+                .map_err(|_| ExecutePluginError::MissingProcessScheduledCallFunction)?;
 
-        py_func
-            .call1((local_api, py_datetime, args))
-            .map_err(anyhow::Error::from)?;
+            py_func
+                .call1((local_api, py_datetime, args))
+                .map_err(anyhow::Error::from)?;
+            Ok(())
+        })?;
 
         // swap with an empty return state to avoid cloning
         let empty_return_state = PluginReturnState::default();
@@ -715,40 +1050,73 @@ pub fn execute_schedule_trigger(
     })
 }
 
+/// A Parquet file that was persisted in a snapshot, as it is handed to a plugin
+#[derive(Debug, Clone)]
+pub struct PersistedParquetFile {
+    pub table_name: String,
+    pub path: String,
+    pub size_bytes: u64,
+    pub row_count: u64,
+    /// min time nanos
+    pub min_time: i64,
+    /// max time nanos
+    pub max_time: i64,
+}
+
 #[allow(clippy::too_many_arguments)]
-pub fn execute_request_trigger(
-    code: &str,
-    db_schema: Arc<DatabaseSchema>,
+pub fn execute_persisted_files_trigger(
+    plugin: &PluginModule,
+    code: &Arc<str>,
+    persisted_files: &[PersistedParquetFile],
+    schema: Arc<DatabaseSchema>,
     query_executor: Arc<dyn QueryExecutor>,
     logger: Option<ProcessingEngineLogger>,
     args: &Option<HashMap<String, String>>,
-    query_params: HashMap<String, String>,
-    request_headers: HashMap<String, String>,
-    request_body: Bytes,
     py_cache: PyCache,
-) -> Result<(u16, HashMap<String, String>, String, PluginReturnState), ExecutePluginError> {
+) -> Result<PluginReturnState, ExecutePluginError> {
     let start_time = if let Some(logger) = &logger {
         logger.log(
             LogLevel::Info,
-            "starting execution of request plugin.".to_string(),
+            format!(
+                "starting execution with {} persisted file(s)",
+                persisted_files.len()
+            ),
         );
         Some(logger.sys_event_store.time_provider().now())
     } else {
         None
     };
     Python::with_gil(|py| {
-        // import the LineBuilder for use in the python code
-        py.run(&CString::new(LINE_BUILDER_CODE).unwrap(), None, None)
-            .map_err(|e| {
-                anyhow::Error::new(e).context("failed to eval the LineBuilder API code")
-            })?;
+        let mut files = Vec::with_capacity(persisted_files.len());
+        for file in persisted_files {
+            let dict = PyDict::new(py);
+            dict.set_item("table_name", file.table_name.as_str())
+                .context("failed to set table_name")?;
+            dict.set_item("path", file.path.as_str())
+                .context("failed to set path")?;
+            dict.set_item("size_bytes", file.size_bytes)
+                .context("failed to set size_bytes")?;
+            dict.set_item("row_count", file.row_count)
+                .context("failed to set row_count")?;
+            dict.set_item("min_time", file.min_time)
+                .context("failed to set min_time")?;
+            dict.set_item("max_time", file.max_time)
+                .context("failed to set max_time")?;
+            files.push(dict);
+        }
+
+        let py_files = PyList::new(py, files).context("failed to create persisted_files list")?;
 
         let api = PyPluginCallApi {
-            db_schema,
+            db_schema: schema,
             query_executor,
             logger: logger.clone(),
             return_state: Default::default(),
             py_cache,
+            secrets: plugin.secrets.clone(),
+            limits: plugin.limits,
+            value_caches: plugin.value_caches.clone(),
+            line_writer: plugin.line_writer.clone(),
         };
         let return_state = Arc::clone(&api.return_state);
         let local_api = api.into_pyobject(py).map_err(anyhow::Error::from)?;
@@ -756,36 +1124,22 @@ pub fn execute_request_trigger(
         // turn args into an optional dict to pass into python
         let args = args_to_py_object(py, args);
 
-        let query_params = map_to_py_object(py, &query_params);
-        let request_params = map_to_py_object(py, &request_headers);
-
-        // run the code and get the python function to call
-        py.run(&CString::new(code).unwrap(), None, None)
-            .map_err(anyhow::Error::from)?;
-
-        let py_func = py
-            .eval(
-                &CString::new(PROCESS_REQUEST_CALL_SITE).unwrap(),
-                None,
-                None,
-            )
-            .map_err(|_| ExecutePluginError::MissingProcessRequestFunction)?;
-
-        // convert the body bytes into python bytes blob
-        let request_body = PyBytes::new(py, &request_body[..]);
-
-        // get the result from calling the python function
-        let result = py_func
-            .call1((local_api, query_params, request_params, request_body, args))
-            .map_err(|e| anyhow::anyhow!("Python function call failed: {}", e))?;
+        plugin.run_limited(py, &return_state, || {
+            // load the plugin's module and get the python function to call
+            let module = plugin.load(py, code, local_api.as_any(), &args)?;
+            let py_func = module
+                .getattr(PROCESS_PERSISTED_FILES_CALL_SITE)
+                .map_err(|_| ExecutePluginError::MissingProcessPersistedFilesFunction)?;
 
-        // Process the result according to Flask conventions
-        let (response_code, response_headers, response_body) = process_flask_response(py, result)?;
+            py_func
+                .call1((local_api, py_files, args))
+                .map_err(anyhow::Error::from)?;
+            Ok(())
+        })?;
 
         // swap with an empty return state to avoid cloning
         let empty_return_state = PluginReturnState::default();
         let ret = std::mem::replace(&mut *return_state.lock(), empty_return_state);
-
         if let Some(logger) = &logger {
             let runtime = logger
                 .sys_event_store
@@ -798,50 +1152,448 @@ pub fn execute_request_trigger(
                     "finished execution in {}",
                     format_duration(runtime.unwrap_or_default())
                 ),
-            )
+            );
         }
-
-        Ok((response_code, response_headers, response_body, ret))
+        Ok(ret)
     })
 }
 
-fn process_flask_response(
-    py: Python<'_>,
-    result: Bound<'_, PyAny>,
-) -> Result<(u16, HashMap<String, String>, String), anyhow::Error> {
-    let default_status: u16 = 200;
-    let default_headers: HashMap<String, String> = HashMap::new();
-    let default_mimetype = "text/html";
+/// A change to a database's catalog, as it is handed to a plugin
+#[derive(Debug, Clone)]
+pub struct CatalogEvent {
+    pub event_type: CatalogEventType,
+    /// The time of the catalog batch that made the change, in nanoseconds
+    pub time_ns: i64,
+    pub database_id: DbId,
+    pub database_name: Arc<str>,
+    pub op: DatabaseCatalogOp,
+}
 
-    // Check if it's a Flask Response object
-    if let Ok(true) = result
-        .call_method0("__flask_response__")
-        .and_then(|r| r.extract::<bool>())
-    {
-        // It's a Flask Response object, extract status, headers, and body
-        let status: u16 = result.getattr("status_code")?.extract()?;
-        let body: String = result.call_method0("get_data")?.extract()?;
+impl CatalogEvent {
+    /// Convert the event into the dict that is passed to `process_catalog_event`
+    fn to_py_dict<'py>(
+        &self,
+        py: Python<'py>,
+        schema: &DatabaseSchema,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("event_type", self.event_type.as_str())?;
+        dict.set_item("database_name", self.database_name.as_ref())?;
+        dict.set_item("time", self.time_ns)?;
+
+        let column_names = |table_id: &TableId, column_ids: &[ColumnId]| -> Vec<String> {
+            let Some(table_def) = schema.table_definition_by_id(table_id) else {
+                return vec![];
+            };
+            column_ids
+                .iter()
+                .filter_map(|id| table_def.column_id_to_name(id))
+                .map(|name| name.to_string())
+                .collect()
+        };
 
-        // Extract headers
-        let headers_dict = result.getattr("headers")?;
-        let headers: std::collections::HashMap<String, String> = headers_dict.extract()?;
+        match &self.op {
+            DatabaseCatalogOp::CreateTable(create_table) => {
+                dict.set_item("table_name", create_table.table_name.as_ref())?;
+                dict.set_item(
+                    "columns",
+                    columns_to_py_list(py, &create_table.field_definitions)?,
+                )?;
+            }
+            DatabaseCatalogOp::AddFields(add_fields) => {
+                dict.set_item("table_name", add_fields.table_name.as_ref())?;
+                dict.set_item(
+                    "columns",
+                    columns_to_py_list(py, &add_fields.field_definitions)?,
+                )?;
+            }
+            DatabaseCatalogOp::SoftDeleteTable(delete_table) => {
+                dict.set_item("table_name", delete_table.table_name.as_ref())?;
+            }
+            DatabaseCatalogOp::CreateLastCache(cache) => {
+                dict.set_item("table_name", cache.table.as_ref())?;
+                dict.set_item("cache_type", "last")?;
+                dict.set_item("cache_name", cache.name.as_ref())?;
+                dict.set_item("columns", column_names(&cache.table_id, &cache.key_columns))?;
+            }
+            DatabaseCatalogOp::CreateDistinctCache(cache) => {
+                dict.set_item("table_name", cache.table_name.as_ref())?;
+                dict.set_item("cache_type", "distinct")?;
+                dict.set_item("cache_name", cache.cache_name.as_ref())?;
+                dict.set_item("columns", column_names(&cache.table_id, &cache.column_ids))?;
+            }
+            DatabaseCatalogOp::DeleteLastCache(cache) => {
+                dict.set_item("table_name", cache.table_name.as_ref())?;
+                dict.set_item("cache_type", "last")?;
+                dict.set_item("cache_name", cache.name.as_ref())?;
+            }
+            DatabaseCatalogOp::DeleteDistinctCache(cache) => {
+                dict.set_item("table_name", cache.table_name.as_ref())?;
+                dict.set_item("cache_type", "distinct")?;
+                dict.set_item("cache_name", cache.cache_name.as_ref())?;
+            }
+            _ => {}
+        }
 
-        return Ok((status, headers.into_iter().collect(), body));
+        Ok(dict)
     }
+}
 
-    // Check if it's a tuple
-    if let Ok(tuple) = result.downcast::<PyTuple>() {
-        let tuple_len = tuple.len()?;
-        if tuple_len > 0 && tuple_len <= 3 {
-            // Extract response part (first element)
-            let response = tuple.get_item(0)?;
-            let (response_body, mut headers) = process_response_part(py, response)?;
+fn columns_to_py_list<'py>(
+    py: Python<'py>,
+    field_definitions: &[FieldDefinition],
+) -> PyResult<Bound<'py, PyList>> {
+    let mut columns = Vec::with_capacity(field_definitions.len());
+    for field_definition in field_definitions {
+        let column = PyDict::new(py);
+        column.set_item("name", field_definition.name.as_ref())?;
+        let column_type = match field_definition.data_type {
+            FieldDataType::String => "string",
+            FieldDataType::Integer => "integer",
+            FieldDataType::UInteger => "uinteger",
+            FieldDataType::Float => "float",
+            FieldDataType::Boolean => "boolean",
+            FieldDataType::Timestamp => "timestamp",
+            FieldDataType::Tag => "tag",
+        };
+        column.set_item("type", column_type)?;
+        columns.push(column);
+    }
+    PyList::new(py, columns)
+}
 
-            let mut status = default_status;
+#[allow(clippy::too_many_arguments)]
+pub fn execute_catalog_trigger(
+    plugin: &PluginModule,
+    code: &Arc<str>,
+    event: &CatalogEvent,
+    schema: Arc<DatabaseSchema>,
+    query_executor: Arc<dyn QueryExecutor>,
+    logger: Option<ProcessingEngineLogger>,
+    args: &Option<HashMap<String, String>>,
+    py_cache: PyCache,
+) -> Result<PluginReturnState, ExecutePluginError> {
+    let start_time = if let Some(logger) = &logger {
+        logger.log(
+            LogLevel::Info,
+            format!("starting execution with catalog event {}", event.event_type),
+        );
+        Some(logger.sys_event_store.time_provider().now())
+    } else {
+        None
+    };
+    Python::with_gil(|py| {
+        let py_event = event
+            .to_py_dict(py, &schema)
+            .context("failed to convert the catalog event")?;
 
-            // Handle status and/or headers if provided
-            if tuple_len >= 2 {
-                let second = tuple.get_item(1)?;
+        let api = PyPluginCallApi {
+            db_schema: schema,
+            query_executor,
+            logger: logger.clone(),
+            return_state: Default::default(),
+            py_cache,
+            secrets: plugin.secrets.clone(),
+            limits: plugin.limits,
+            value_caches: plugin.value_caches.clone(),
+            line_writer: plugin.line_writer.clone(),
+        };
+        let return_state = Arc::clone(&api.return_state);
+        let local_api = api.into_pyobject(py).map_err(anyhow::Error::from)?;
+
+        // turn args into an optional dict to pass into python
+        let args = args_to_py_object(py, args);
+
+        plugin.run_limited(py, &return_state, || {
+            // load the plugin's module and get the python function to call
+            let module = plugin.load(py, code, local_api.as_any(), &args)?;
+            let py_func = module
+                .getattr(PROCESS_CATALOG_EVENT_CALL_SITE)
+                .map_err(|_| ExecutePluginError::MissingProcessCatalogEventFunction)?;
+
+            py_func
+                .call1((local_api, py_event, args))
+                .map_err(anyhow::Error::from)?;
+            Ok(())
+        })?;
+
+        // swap with an empty return state to avoid cloning
+        let empty_return_state = PluginReturnState::default();
+        let ret = std::mem::replace(&mut *return_state.lock(), empty_return_state);
+        if let Some(logger) = &logger {
+            let runtime = logger
+                .sys_event_store
+                .time_provider()
+                .now()
+                .checked_duration_since(start_time.unwrap());
+            logger.log(
+                LogLevel::Info,
+                format!(
+                    "finished execution in {}",
+                    format_duration(runtime.unwrap_or_default())
+                ),
+            );
+        }
+        Ok(ret)
+    })
+}
+
+/// A request to a request trigger, as it is handed to the plugin
+#[derive(Debug, Clone)]
+pub struct PluginRequest {
+    pub method: String,
+    /// The part of the request's path below the trigger's path
+    pub path: String,
+    pub query_params: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
+    pub body: Bytes,
+    /// The id of the token that the request was authenticated with, if the server checks tokens
+    pub token_id: Option<u64>,
+}
+
+/// The body of a response from a request plugin
+#[derive(Debug)]
+pub enum ResponseBody {
+    Full(Bytes),
+    /// A body that the plugin produces a chunk at a time, from a python iterator
+    Stream(ResponseStream),
+}
+
+impl From<String> for ResponseBody {
+    fn from(body: String) -> Self {
+        Self::Full(Bytes::from(body))
+    }
+}
+
+/// The python iterator, such as a generator, that a streamed response body is read from
+#[derive(Debug)]
+pub struct ResponseStream {
+    iterator: Py<PyIterator>,
+    /// How long the plugin has to produce each chunk
+    timeout: Option<Duration>,
+    environment: Option<Arc<dyn PluginEnvironment>>,
+    /// Redacted from the errors that the plugin raises while streaming
+    secrets: PluginSecrets,
+}
+
+impl ResponseStream {
+    fn new(iterator: Py<PyIterator>) -> Self {
+        Self {
+            iterator,
+            timeout: None,
+            environment: None,
+            secrets: PluginSecrets::default(),
+        }
+    }
+
+    /// The next chunk of the body, or `None` once the iterator is exhausted. This runs python
+    /// code, so it should be called from a blocking thread.
+    pub fn next_chunk(&self) -> Option<Result<Bytes, anyhow::Error>> {
+        Python::with_gil(|py| {
+            run_watched(py, self.environment.as_deref(), self.timeout, || {
+                let mut iterator = self.iterator.bind(py).clone();
+                let Some(item) = iterator.next() else {
+                    return Ok(None);
+                };
+                let item = item.map_err(anyhow::Error::new)?;
+                let bytes = bytes_from_py(&item)?.ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "streamed response chunks must be str or bytes, got {}",
+                        item.get_type()
+                    )
+                })?;
+                Ok(Some(bytes))
+            })
+            .map_err(|e| anyhow::Error::from(self.secrets.redact_error(e)))
+            .transpose()
+        })
+    }
+}
+
+/// The response returned by a request plugin
+#[derive(Debug)]
+pub struct PluginResponse {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    pub body: ResponseBody,
+}
+
+/// Run a request plugin. A streamed response body is read after this returns, so anything the
+/// plugin writes or logs while producing it is not part of the returned [`PluginReturnState`].
+#[allow(clippy::too_many_arguments)]
+pub fn execute_request_trigger(
+    plugin: &PluginModule,
+    code: &Arc<str>,
+    db_schema: Arc<DatabaseSchema>,
+    query_executor: Arc<dyn QueryExecutor>,
+    logger: Option<ProcessingEngineLogger>,
+    args: &Option<HashMap<String, String>>,
+    request: &PluginRequest,
+    py_cache: PyCache,
+) -> Result<(PluginResponse, PluginReturnState), ExecutePluginError> {
+    let start_time = if let Some(logger) = &logger {
+        logger.log(
+            LogLevel::Info,
+            format!(
+                "starting execution of request plugin for {} /{}",
+                request.method, request.path
+            ),
+        );
+        Some(logger.sys_event_store.time_provider().now())
+    } else {
+        None
+    };
+    Python::with_gil(|py| {
+        let api = PyPluginCallApi {
+            db_schema,
+            query_executor,
+            logger: logger.clone(),
+            return_state: Default::default(),
+            py_cache,
+            secrets: plugin.secrets.clone(),
+            limits: plugin.limits,
+            value_caches: plugin.value_caches.clone(),
+            line_writer: plugin.line_writer.clone(),
+        };
+        let return_state = Arc::clone(&api.return_state);
+        let local_api = api.into_pyobject(py).map_err(anyhow::Error::from)?;
+
+        // turn args into an optional dict to pass into python
+        let args = args_to_py_object(py, args);
+
+        let query_params = map_to_py_object(py, &request.query_params);
+        let request_params = map_to_py_object(py, &request.headers);
+
+        let mut response = plugin.run_limited(py, &return_state, || {
+            // load the plugin's module and get the python function to call
+            let module = plugin.load(py, code, local_api.as_any(), &args)?;
+            let py_func = module
+                .getattr(PROCESS_REQUEST_CALL_SITE)
+                .map_err(|_| ExecutePluginError::MissingProcessRequestFunction)?;
+
+            // convert the body bytes into python bytes blob
+            let request_body = PyBytes::new(py, &request.body[..]);
+
+            // the method, path and token id were added to the request contract after plugins were
+            // first written against it, so they are only passed to plugins that accept them
+            let kwargs = (|| -> PyResult<_> {
+                accepted_kwargs(
+                    py,
+                    &py_func,
+                    [
+                        ("method", request.method.as_str().into_bound_py_any(py)?),
+                        ("path", request.path.as_str().into_bound_py_any(py)?),
+                        ("token_id", request.token_id.into_bound_py_any(py)?),
+                    ],
+                )
+            })()
+            .context("failed to inspect the process_request function")?;
+
+            // get the result from calling the python function
+            let result = py_func
+                .call(
+                    (local_api, query_params, request_params, request_body, args),
+                    Some(&kwargs),
+                )
+                .map_err(|e| anyhow::anyhow!("Python function call failed: {}", e))?;
+
+            // Process the result according to Flask conventions
+            Ok(process_flask_response(py, result)?)
+        })?;
+        plugin.watch_stream(&mut response);
+
+        // swap with an empty return state to avoid cloning
+        let empty_return_state = PluginReturnState::default();
+        let ret = std::mem::replace(&mut *return_state.lock(), empty_return_state);
+
+        if let Some(logger) = &logger {
+            let runtime = logger
+                .sys_event_store
+                .time_provider()
+                .now()
+                .checked_duration_since(start_time.unwrap());
+            logger.log(
+                LogLevel::Info,
+                format!(
+                    "finished execution in {}",
+                    format_duration(runtime.unwrap_or_default())
+                ),
+            )
+        }
+
+        Ok((response, ret))
+    })
+}
+
+/// The keyword arguments out of `kwargs` that `func` accepts, either by name or through `**kwargs`
+fn accepted_kwargs<'py, const N: usize>(
+    py: Python<'py>,
+    func: &Bound<'py, PyAny>,
+    kwargs: [(&str, Bound<'py, PyAny>); N],
+) -> PyResult<Bound<'py, PyDict>> {
+    let inspect = py.import("inspect")?;
+    let parameters = inspect
+        .call_method1("signature", (func,))?
+        .getattr("parameters")?;
+    let var_keyword = inspect.getattr("Parameter")?.getattr("VAR_KEYWORD")?;
+    let mut accepts_any = false;
+    for parameter in parameters.call_method0("values")?.try_iter()? {
+        if parameter?.getattr("kind")?.eq(&var_keyword)? {
+            accepts_any = true;
+        }
+    }
+
+    let accepted = PyDict::new(py);
+    for (name, value) in kwargs {
+        if accepts_any || parameters.contains(name)? {
+            accepted.set_item(name, value)?;
+        }
+    }
+    Ok(accepted)
+}
+
+fn process_flask_response(
+    py: Python<'_>,
+    result: Bound<'_, PyAny>,
+) -> Result<PluginResponse, anyhow::Error> {
+    let default_status: u16 = 200;
+
+    // Check if it's a Flask Response object
+    if let Ok(true) = result
+        .call_method0("__flask_response__")
+        .and_then(|r| r.extract::<bool>())
+    {
+        // It's a Flask Response object, extract status, headers, and body
+        let status: u16 = result.getattr("status_code")?.extract()?;
+        let data = result.call_method0("get_data")?;
+        let Some(body) = bytes_from_py(&data)? else {
+            bail!("expected str or bytes from the response's get_data()");
+        };
+
+        // Extract headers
+        let headers_dict = result.getattr("headers")?;
+        let headers: std::collections::HashMap<String, String> = headers_dict.extract()?;
+
+        return Ok(PluginResponse {
+            status,
+            headers: headers.into_iter().collect(),
+            body: ResponseBody::Full(body),
+        });
+    }
+
+    // Check if it's a tuple
+    if let Ok(tuple) = result.downcast::<PyTuple>() {
+        let tuple_len = tuple.len()?;
+        if tuple_len > 0 && tuple_len <= 3 {
+            // Extract response part (first element)
+            let response = tuple.get_item(0)?;
+            let (body, mut headers) = process_response_part(py, response)?;
+
+            let mut status = default_status;
+
+            // Handle status and/or headers if provided
+            if tuple_len >= 2 {
+                let second = tuple.get_item(1)?;
 
                 // Check if the second item is a status code or headers
                 if let Ok(status_code) = second.extract::<i64>() {
@@ -865,53 +1617,22 @@ fn process_flask_response(
                 }
             }
 
-            return Ok((status, headers, response_body));
+            return Ok(PluginResponse {
+                status,
+                headers,
+                body,
+            });
         }
     }
 
-    // Check if it's a string
-    if let Ok(string_val) = result.extract::<String>() {
-        let mut headers = default_headers.clone();
-        headers.insert("Content-Type".to_string(), default_mimetype.to_string());
-        return Ok((default_status, headers, string_val));
-    }
-
-    // Check if it's a dict or list for JSON response
-    if result.is_instance_of::<PyDict>() || result.is_instance_of::<PyList>() {
-        // We need to jsonify this
-        let json_module = py.import("json")?;
-        let json_string = json_module.call_method1("dumps", (result,))?;
-        let response_body: String = json_string.extract()?;
-
-        let mut headers = default_headers.clone();
-        headers.insert("Content-Type".to_string(), "application/json".to_string());
-
-        return Ok((default_status, headers, response_body));
-    }
-
-    // Check if it's an iterator or generator returning strings or bytes
-    if let Ok(true) = result.hasattr("__iter__") {
-        // Handling streaming response would require adapting the return type
-        // For now, we'll convert it to a concatenated string
-        let mut stream_content = String::new();
-
-        let iter = result.try_iter()?;
-        for item in iter {
-            let item = item?;
-            if let Ok(s) = item.extract::<String>() {
-                stream_content.push_str(&s);
-            } else if let Ok(b) = item.extract::<&[u8]>() {
-                // Convert bytes to string - might need better UTF-8 handling
-                if let Ok(s) = std::str::from_utf8(b) {
-                    stream_content.push_str(s);
-                }
-            }
-        }
-
-        let mut headers = default_headers.clone();
-        headers.insert("Content-Type".to_string(), default_mimetype.to_string());
-
-        return Ok((default_status, headers, stream_content));
+    // Check if it's a string, bytes, JSON or a stream
+    if let Some((body, content_type)) = response_body_from_py(py, &result)? {
+        let headers = HashMap::from([("Content-Type".to_string(), content_type.to_string())]);
+        return Ok(PluginResponse {
+            status: default_status,
+            headers,
+            body,
+        });
     }
 
     // If we can't identify the response type, return an error
@@ -923,41 +1644,102 @@ fn process_flask_response(
 fn process_response_part(
     py: Python<'_>,
     response: Bound<'_, PyAny>,
-) -> Result<(String, HashMap<String, String>), anyhow::Error> {
+) -> Result<(ResponseBody, HashMap<String, String>), anyhow::Error> {
     let mut headers = HashMap::new();
 
-    // If it's a string, return it directly
-    if let Ok(string_val) = response.extract::<String>() {
-        headers.insert("Content-Type".to_string(), "text/html".to_string());
-        return Ok((string_val, headers));
+    if let Some((body, content_type)) = response_body_from_py(py, &response)? {
+        headers.insert("Content-Type".to_string(), content_type.to_string());
+        return Ok((body, headers));
+    }
+
+    // Default fallback
+    let response_str = response.str()?.extract::<String>()?;
+    headers.insert("Content-Type".to_string(), "text/plain".to_string());
+
+    Ok((response_str.into(), headers))
+}
+
+/// Convert a value returned by a request plugin into a response body and its default content
+/// type, if it is of a type that can be used as a body:
+///
+/// * `str`, sent as html
+/// * `bytes` or `bytearray`, sent as is
+/// * `dict` or `list`, sent as JSON
+/// * any other iterable, such as a generator, of `str` or `bytes`, which is streamed
+fn response_body_from_py(
+    py: Python<'_>,
+    value: &Bound<'_, PyAny>,
+) -> Result<Option<(ResponseBody, &'static str)>, anyhow::Error> {
+    if let Ok(string_val) = value.extract::<String>() {
+        return Ok(Some((string_val.into(), "text/html")));
     }
 
-    // If it's a dict or list, convert to JSON
-    if response.is_instance_of::<PyDict>() || response.is_instance_of::<PyList>() {
+    if let Some(bytes) = bytes_from_py(value)? {
+        return Ok(Some((
+            ResponseBody::Full(bytes),
+            "application/octet-stream",
+        )));
+    }
+
+    if value.is_instance_of::<PyDict>() || value.is_instance_of::<PyList>() {
+        // We need to jsonify this
         let json_module = py.import("json")?;
-        let json_string = json_module.call_method1("dumps", (response,))?;
+        let json_string = json_module.call_method1("dumps", (value,))?;
         let response_body: String = json_string.extract()?;
+        return Ok(Some((response_body.into(), "application/json")));
+    }
 
-        headers.insert("Content-Type".to_string(), "application/json".to_string());
-        return Ok((response_body, headers));
+    if let Ok(true) = value.hasattr("__iter__") {
+        let iterator = value.try_iter()?.unbind();
+        return Ok(Some((
+            ResponseBody::Stream(ResponseStream::new(iterator)),
+            "text/html",
+        )));
     }
 
-    // Default fallback
-    let response_str = response.str()?.extract::<String>()?;
-    headers.insert("Content-Type".to_string(), "text/plain".to_string());
+    Ok(None)
+}
+
+/// The bytes of a `str`, `bytes` or `bytearray`, or `None` for any other type
+fn bytes_from_py(value: &Bound<'_, PyAny>) -> Result<Option<Bytes>, anyhow::Error> {
+    if let Ok(bytes) = value.downcast::<PyBytes>() {
+        return Ok(Some(Bytes::copy_from_slice(bytes.as_bytes())));
+    }
+    if let Ok(bytes) = value.downcast::<PyByteArray>() {
+        return Ok(Some(Bytes::from(bytes.to_vec())));
+    }
+    if let Ok(string_val) = value.extract::<String>() {
+        return Ok(Some(Bytes::from(string_val)));
+    }
+    Ok(None)
+}
+
+/// A cached value. Values cached by plugins that can't hold python objects, such as WASM
+/// plugins, are kept as bytes, so that caching them doesn't need python to be initialized.
+#[derive(Debug)]
+enum CacheValue {
+    Python(Py<PyAny>),
+    Bytes(Bytes),
+}
 
-    Ok((response_str, headers))
+impl CacheValue {
+    fn to_py(&self, py: Python<'_>) -> Py<PyAny> {
+        match self {
+            Self::Python(value) => value.clone_ref(py),
+            Self::Bytes(bytes) => PyBytes::new(py, bytes).into_any().unbind(),
+        }
+    }
 }
 
 // Cache entry with optional expiration
 #[derive(Debug)]
 pub struct CacheEntry {
-    value: Py<PyAny>,         // Python object reference with proper reference counting
+    value: CacheValue,
     expires_at: Option<Time>, // Expiration time if any
 }
 
 impl CacheEntry {
-    fn new(value: Py<PyAny>, ttl: Option<f64>, time_provider: Arc<dyn TimeProvider>) -> Self {
+    fn new(value: CacheValue, ttl: Option<f64>, time_provider: Arc<dyn TimeProvider>) -> Self {
         let expires_at = ttl.map(|seconds| time_provider.now() + Duration::from_secs_f64(seconds));
         Self { value, expires_at }
     }
@@ -1012,15 +1794,14 @@ impl ExpiringCache {
         }
     }
 
-    fn get(&mut self, key: &str, py: Python<'_>) -> Option<Py<PyAny>> {
-        let entry = self.entries.get(key)?;
-        if let Some(expiration) = entry.expires_at {
+    fn get(&mut self, key: &str) -> Option<&CacheValue> {
+        if let Some(expiration) = self.entries.get(key)?.expires_at {
             if expiration <= self.time_provider.now() {
                 self.remove(key);
                 return None;
             }
         }
-        Some(entry.value.clone_ref(py))
+        self.entries.get(key).map(|entry| &entry.value)
     }
 
     fn remove(&mut self, key: &str) -> bool {
@@ -1072,6 +1853,34 @@ impl CacheId {
             GlobalTest(_) | TriggerTest(_) => Some(Duration::from_secs(30 * 60)),
         }
     }
+
+    /// How the cache is named in a checkpoint, or `None` for the test caches, which aren't
+    /// checkpointed.
+    fn checkpoint_namespace(&self) -> Option<Vec<String>> {
+        match self {
+            Global() => Some(vec!["global".to_string()]),
+            Trigger {
+                database,
+                trigger_name,
+            } => Some(vec![
+                "trigger".to_string(),
+                database.clone(),
+                trigger_name.clone(),
+            ]),
+            GlobalTest(_) | TriggerTest(_) => None,
+        }
+    }
+
+    fn from_checkpoint_namespace(namespace: &[String]) -> Option<Self> {
+        match namespace {
+            [kind] if kind == "global" => Some(Global()),
+            [kind, database, trigger_name] if kind == "trigger" => Some(Trigger {
+                database: database.clone(),
+                trigger_name: trigger_name.clone(),
+            }),
+            _ => None,
+        }
+    }
 }
 
 impl CacheStore {
@@ -1104,7 +1913,7 @@ impl CacheStore {
         self.namespaces.retain(|_, cache| !cache.is_empty());
     }
 
-    fn put(&mut self, cache_id: &CacheId, key: &str, value: Py<PyAny>, ttl: Option<f64>) {
+    fn put(&mut self, cache_id: &CacheId, key: &str, value: CacheValue, ttl: Option<f64>) {
         let entry = CacheEntry::new(value, ttl, Arc::clone(&self.time_provider));
         if let Some(cache) = self.namespaces.get_mut(cache_id) {
             cache.insert(key.to_string(), entry);
@@ -1121,10 +1930,10 @@ impl CacheStore {
             .insert(key.to_string(), entry);
     }
 
-    fn get(&mut self, cache_id: &CacheId, py: Python<'_>, key: &str) -> Option<Py<PyAny>> {
+    fn get(&mut self, cache_id: &CacheId, key: &str) -> Option<&CacheValue> {
         let cache = self.namespaces.get_mut(cache_id)?;
 
-        cache.get(key, py)
+        cache.get(key)
     }
 
     fn delete(&mut self, cache_id: &CacheId, key: &str) -> bool {
@@ -1142,6 +1951,205 @@ impl CacheStore {
             })
             .is_some()
     }
+
+    /// Serialize the global and trigger caches, but not the test caches, into a checkpoint that
+    /// can be passed to [`CacheStore::restore`]. Values are pickled, and any that can't be are
+    /// left out of the checkpoint. Only values built from python's builtin types and a few from
+    /// its standard library, such as `datetime` and `Decimal`, can be restored.
+    ///
+    /// This takes the store's mutex rather than `&self` so that the lock is only held while the
+    /// entries are collected, and not while python code runs to pickle them.
+    pub fn checkpoint(cache_store: &Mutex<Self>) -> Result<Vec<u8>, anyhow::Error> {
+        Python::with_gil(|py| {
+            let namespaces = {
+                let store = cache_store.lock();
+                let now = store.time_provider.now();
+                store
+                    .namespaces
+                    .iter()
+                    .filter_map(|(cache_id, cache)| {
+                        let namespace = cache_id.checkpoint_namespace()?;
+                        let entries = cache
+                            .entries
+                            .iter()
+                            .filter(|(_, entry)| entry.expires_at.is_none_or(|t| t > now))
+                            .map(|(key, entry)| {
+                                (key.clone(), entry.value.to_py(py), entry.expires_at)
+                            })
+                            .collect::<Vec<_>>();
+                        Some((namespace, entries))
+                    })
+                    .collect::<Vec<_>>()
+            };
+
+            let pickle = py.import("pickle")?;
+            let mut checkpoint = Vec::with_capacity(namespaces.len());
+            for (namespace, entries) in namespaces {
+                let mut pickled_entries = Vec::with_capacity(entries.len());
+                for (key, value, expires_at) in entries {
+                    match pickle
+                        .call_method1("dumps", (value,))
+                        .and_then(|v| Ok(v.downcast_into::<PyBytes>()?))
+                    {
+                        Ok(pickled) => pickled_entries.push((
+                            key,
+                            pickled,
+                            expires_at.map(|t| t.timestamp_nanos()),
+                        )),
+                        Err(e) => warn!(
+                            ?namespace,
+                            %key,
+                            "leaving cache entry out of checkpoint, unable to pickle: {e}"
+                        ),
+                    }
+                }
+                checkpoint.push((namespace, pickled_entries));
+            }
+
+            let checkpoint =
+                pickle.call_method1("dumps", ((CACHE_CHECKPOINT_VERSION, checkpoint),))?;
+            Ok(checkpoint
+                .downcast_into::<PyBytes>()
+                .map_err(PyErr::from)?
+                .as_bytes()
+                .to_vec())
+        })
+    }
+
+    /// Restore entries from a checkpoint made by [`CacheStore::checkpoint`], returning the
+    /// number of entries restored. Entries that have expired since the checkpoint are dropped,
+    /// as are the caches of any trigger that `keep_trigger` returns false for.
+    ///
+    /// The checkpoint is unpickled without allowing it to import anything outside of
+    /// [`CHECKPOINT_CODE`]'s allow list, as loading an arbitrary pickle can run arbitrary code.
+    pub fn restore(
+        cache_store: &Mutex<Self>,
+        checkpoint: &[u8],
+        keep_trigger: impl Fn(&str, &str) -> bool,
+    ) -> Result<usize, anyhow::Error> {
+        type Checkpoint<'py> = (
+            u32,
+            Vec<(Vec<String>, Vec<(String, Bound<'py, PyBytes>, Option<i64>)>)>,
+        );
+
+        Python::with_gil(|py| {
+            let unpickler = checkpoint_module(py)?;
+            let (version, namespaces): Checkpoint<'_> = unpickler
+                .call_method1("loads", (PyBytes::new(py, checkpoint),))?
+                .extract()?;
+            if version != CACHE_CHECKPOINT_VERSION {
+                bail!("unsupported plugin cache checkpoint version {version}");
+            }
+
+            let now = cache_store.lock().time_provider.now();
+            let mut restored = Vec::new();
+            for (namespace, entries) in namespaces {
+                let Some(cache_id) = CacheId::from_checkpoint_namespace(&namespace) else {
+                    warn!(?namespace, "skipping unknown cache in checkpoint");
+                    continue;
+                };
+                if let Trigger {
+                    database,
+                    trigger_name,
+                } = &cache_id
+                {
+                    if !keep_trigger(database, trigger_name) {
+                        continue;
+                    }
+                }
+                for (key, pickled, expires_at) in entries {
+                    let expires_at = expires_at.map(Time::from_timestamp_nanos);
+                    if expires_at.is_some_and(|t| t <= now) {
+                        continue;
+                    }
+                    match unpickler.call_method1("loads", (pickled,)) {
+                        Ok(value) => restored.push((
+                            cache_id.clone(),
+                            key,
+                            CacheEntry {
+                                value: CacheValue::Python(value.unbind()),
+                                expires_at,
+                            },
+                        )),
+                        Err(e) => warn!(
+                            ?namespace,
+                            %key,
+                            "skipping cache entry in checkpoint, unable to unpickle: {e}"
+                        ),
+                    }
+                }
+            }
+
+            let count = restored.len();
+            let mut store = cache_store.lock();
+            for (cache_id, key, entry) in restored {
+                store.cache_mut(&cache_id).insert(key, entry);
+            }
+            Ok(count)
+        })
+    }
+
+    fn cache_mut(&mut self, cache_id: &CacheId) -> &mut ExpiringCache {
+        self.namespaces.entry(cache_id.clone()).or_insert_with(|| {
+            ExpiringCache::new(
+                Arc::clone(&self.time_provider),
+                cache_id.default_expiration(),
+            )
+        })
+    }
+}
+
+/// Bumped whenever the layout of a [`CacheStore::checkpoint`] changes
+const CACHE_CHECKPOINT_VERSION: u32 = 1;
+
+/// Unpickles checkpoints, only letting them load the classes on the allow list
+const CHECKPOINT_CODE: &str = r#"
+import io, pickle
+
+_ALLOWED = {
+    ("builtins", "bytearray"),
+    ("builtins", "complex"),
+    ("builtins", "frozenset"),
+    ("builtins", "range"),
+    ("builtins", "set"),
+    ("builtins", "slice"),
+    ("collections", "Counter"),
+    ("collections", "OrderedDict"),
+    ("collections", "defaultdict"),
+    ("collections", "deque"),
+    ("datetime", "date"),
+    ("datetime", "datetime"),
+    ("datetime", "time"),
+    ("datetime", "timedelta"),
+    ("datetime", "timezone"),
+    ("decimal", "Decimal"),
+    ("uuid", "UUID"),
+}
+
+class _CheckpointUnpickler(pickle.Unpickler):
+    def find_class(self, module, name):
+        if (module, name) not in _ALLOWED:
+            raise pickle.UnpicklingError(f"{module}.{name} can't be restored from a checkpoint")
+        return super().find_class(module, name)
+
+def loads(data):
+    return _CheckpointUnpickler(io.BytesIO(data)).load()
+"#;
+
+static CHECKPOINT_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+
+fn checkpoint_module(py: Python<'_>) -> PyResult<&Bound<'_, PyModule>> {
+    CHECKPOINT_MODULE
+        .get_or_try_init(py, || {
+            PyModule::from_code(
+                py,
+                &CString::new(CHECKPOINT_CODE)?,
+                c"influxdb3_checkpoint.py",
+                c"influxdb3_checkpoint",
+            )
+            .map(Bound::unbind)
+        })
+        .map(|module| module.bind(py))
 }
 
 // Python class for Cache
@@ -1183,6 +2191,44 @@ impl PyCache {
             cache_store,
         }
     }
+
+    /// Get the value cached under `key` as bytes, for plugins that can't hold python objects,
+    /// such as WASM plugins. A value cached by a python plugin is only seen if it is a `str`,
+    /// `bytes` or `bytearray`.
+    ///
+    /// The GIL is only taken to read a value that a python plugin cached, so this works
+    /// without python being initialized.
+    pub fn get_bytes(&self, key: &str, use_global: bool) -> Option<Bytes> {
+        let cache_id = self.cache_id(Some(use_global));
+        match self.cache_store.lock().get(cache_id, key)? {
+            CacheValue::Bytes(bytes) => return Some(bytes.clone()),
+            CacheValue::Python(_) => {}
+        }
+        // the store's lock is released before taking the GIL, as python plugins take the GIL
+        // before the lock
+        Python::with_gil(|py| {
+            let value = self.cache_store.lock().get(cache_id, key)?.to_py(py);
+            bytes_from_py(value.bind(py)).ok().flatten()
+        })
+    }
+
+    /// Cache `value` under `key`. Python plugins see it as a `bytes` object.
+    pub fn put_bytes(&self, key: &str, value: &[u8], ttl: Option<Duration>, use_global: bool) {
+        self.cache_store.lock().put(
+            self.cache_id(Some(use_global)),
+            key,
+            CacheValue::Bytes(Bytes::copy_from_slice(value)),
+            ttl.map(|ttl| ttl.as_secs_f64()),
+        );
+    }
+
+    /// Delete the value cached under `key`, returning whether there was one. A python object
+    /// that is removed without the GIL held is released the next time the GIL is taken.
+    pub fn delete_bytes(&self, key: &str, use_global: bool) -> bool {
+        self.cache_store
+            .lock()
+            .delete(self.cache_id(Some(use_global)), key)
+    }
 }
 
 #[pymethods]
@@ -1196,7 +2242,9 @@ impl PyCache {
         use_global: Option<bool>,
     ) -> PyResult<()> {
         let cache_id = self.cache_id(use_global);
-        self.cache_store.lock().put(cache_id, &key, value, ttl);
+        self.cache_store
+            .lock()
+            .put(cache_id, &key, CacheValue::Python(value), ttl);
 
         Ok(())
     }
@@ -1210,7 +2258,11 @@ impl PyCache {
     ) -> PyResult<PyObject> {
         Python::with_gil(|py| {
             let cache_id = self.cache_id(use_global);
-            let result = self.cache_store.lock().get(cache_id, py, &key);
+            let result = self
+                .cache_store
+                .lock()
+                .get(cache_id, &key)
+                .map(|value| value.to_py(py));
 
             match result {
                 Some(value) => {
@@ -1240,6 +2292,636 @@ impl PyCache {
     }
 }
 
+/// Convert record batches into a list of rows, as dicts of column name to value
+fn record_batches_to_rows(py: Python<'_>, batches: Vec<RecordBatch>) -> PyResult<Py<PyList>> {
+    let mut rows: Vec<PyObject> = Vec::new();
+
+    for batch in batches {
+        let num_rows = batch.num_rows();
+        let schema = batch.schema();
+
+        for row_idx in 0..num_rows {
+            let row = PyDict::new(py);
+            for col_idx in 0..schema.fields().len() {
+                let field = schema.field(col_idx);
+                let field_name = field.name().as_str();
+
+                let array = batch.column(col_idx);
+                if array.is_null(row_idx) {
+                    row.set_item(field_name, py.None())?;
+                    continue;
+                }
+
+                match array.data_type() {
+                    DataType::Int64 => {
+                        let array = array.as_any().downcast_ref::<Int64Array>().unwrap();
+                        row.set_item(field_name, array.value(row_idx))?;
+                    }
+                    DataType::UInt64 => {
+                        let array = array.as_any().downcast_ref::<UInt64Array>().unwrap();
+                        row.set_item(field_name, array.value(row_idx))?;
+                    }
+                    DataType::Float64 => {
+                        let array = array.as_any().downcast_ref::<Float64Array>().unwrap();
+                        row.set_item(field_name, array.value(row_idx))?;
+                    }
+                    DataType::Utf8 => {
+                        let array = array.as_any().downcast_ref::<StringArray>().unwrap();
+                        row.set_item(field_name, array.value(row_idx))?;
+                    }
+                    DataType::Boolean => {
+                        let array = array.as_any().downcast_ref::<BooleanArray>().unwrap();
+                        row.set_item(field_name, array.value(row_idx))?;
+                    }
+                    DataType::Timestamp(_, _) => {
+                        let array = array
+                            .as_any()
+                            .downcast_ref::<TimestampNanosecondArray>()
+                            .unwrap();
+                        row.set_item(field_name, array.value(row_idx))?;
+                    }
+                    DataType::Dictionary(_, _) => {
+                        let col = array
+                            .as_any()
+                            .downcast_ref::<DictionaryArray<Int32Type>>()
+                            .expect("unexpected datatype");
+
+                        let keys = col
+                            .keys()
+                            .as_any()
+                            .downcast_ref::<Int32Array>()
+                            .expect("unexpected datatype");
+
+                        let values = col.values();
+                        let values = values
+                            .as_any()
+                            .downcast_ref::<StringArray>()
+                            .expect("unexpected datatype");
+
+                        let val = values.value(keys.value(row_idx) as usize).to_string();
+                        row.set_item(field_name, val)?;
+                    }
+                    _ => {
+                        return Err(PyValueError::new_err(format!(
+                            "Unsupported data type: {:?}",
+                            array.data_type()
+                        )));
+                    }
+                }
+            }
+            rows.push(row.into());
+        }
+    }
+
+    let list = PyList::new(py, rows)?.unbind();
+    Ok(list)
+}
+
+/// Convert the key filters passed to `last_cache` or `distinct_cache` into column names and the
+/// values to keep for them, as strings
+fn key_filters_from_py(
+    key_filters: Option<&Bound<'_, PyDict>>,
+) -> PyResult<Vec<(String, Vec<String>)>> {
+    let Some(key_filters) = key_filters else {
+        return Ok(vec![]);
+    };
+    key_filters
+        .iter()
+        .map(|(column, values)| {
+            let column = column.extract::<String>()?;
+            let values = if values.downcast::<PyList>().is_ok()
+                || values.downcast::<PyTuple>().is_ok()
+                || values.downcast::<PySet>().is_ok()
+            {
+                values
+                    .try_iter()?
+                    .map(|value| key_filter_value(&value?))
+                    .collect::<PyResult<Vec<_>>>()?
+            } else {
+                vec![key_filter_value(&values)?]
+            };
+            Ok((column, values))
+        })
+        .collect()
+}
+
+fn key_filter_value(value: &Bound<'_, PyAny>) -> PyResult<String> {
+    // a bool is an int in python, so it has to be checked for first
+    if let Ok(b) = value.downcast::<PyBool>() {
+        Ok(b.is_true().to_string())
+    } else if value.downcast::<PyInt>().is_ok() || value.downcast::<PyString>().is_ok() {
+        Ok(value.str()?.to_string())
+    } else {
+        Err(PyValueError::new_err(format!(
+            "key filter values must be str, int or bool, got {}",
+            value.get_type().name()?
+        )))
+    }
+}
+
+/// Reads the last and distinct value caches of the database that a plugin runs against, so that
+/// a plugin can read them without the cost of planning a query
+pub trait ValueCacheReader: std::fmt::Debug + Send + Sync {
+    /// The records in a last value cache, keeping only those whose key columns have one of the
+    /// values given for them in `key_filters`
+    fn last_cache(
+        &self,
+        table_name: &str,
+        cache_name: Option<&str>,
+        key_filters: &[(String, Vec<String>)],
+    ) -> Result<Vec<RecordBatch>, anyhow::Error>;
+
+    /// The values in a distinct value cache, keeping only those where the columns have one of
+    /// the values given for them in `key_filters`
+    fn distinct_cache(
+        &self,
+        table_name: &str,
+        cache_name: Option<&str>,
+        key_filters: &[(String, Vec<String>)],
+    ) -> Result<Vec<RecordBatch>, anyhow::Error>;
+}
+
+/// Writes lines for a plugin as it runs, so that it learns which lines were accepted
+pub trait LineWriter: std::fmt::Debug + Send + Sync {
+    /// Write `lines` to `db_name`, or to the database of the plugin's trigger if it is `None`
+    fn write_lp(
+        &self,
+        db_name: Option<String>,
+        lines: String,
+        options: SyncWriteOptions,
+    ) -> BoxFuture<'static, Result<LinesWritten, anyhow::Error>>;
+}
+
+/// The options of a write through `influxdb3_local.write_sync()`
+#[derive(Debug, Clone, Copy)]
+pub struct SyncWriteOptions {
+    pub precision: Precision,
+    pub accept_partial: bool,
+    pub no_sync: bool,
+}
+
+/// The outcome of a write through `influxdb3_local.write_sync()`
+#[derive(Debug, Default)]
+pub struct LinesWritten {
+    /// The number of lines that were written
+    pub accepted: usize,
+    /// The lines that were not written, and why
+    pub rejected: Vec<RejectedLine>,
+}
+
+#[derive(Debug)]
+pub struct RejectedLine {
+    pub line_number: usize,
+    pub line: String,
+    pub error: String,
+}
+
+/// A virtual environment of a plugin's own, with the packages that it needs
+pub trait PluginEnvironment: std::fmt::Debug + Send + Sync {
+    /// The site-packages directory of the environment, or an error if the environment is not
+    /// ready to be used
+    fn site_packages(&self) -> Result<PathBuf, anyhow::Error>;
+}
+
+/// Swaps a plugin's environment in and out of the interpreter, which is shared by all plugins.
+///
+/// Activating an environment puts its site-packages first on `sys.path`, and swaps the third
+/// party modules that are imported, from any site-packages directory, for those that were
+/// imported in the environment when it was last active. Runs of plugins with an environment of
+/// their own hold a lock while their environment is active, so that only one such environment is
+/// swapped in at a time. The lock is not held while the plugin is blocked in a call into the
+/// server, such as a query or `write_sync`, as the environment is suspended for the call.
+///
+/// Runs in the shared environment don't take the lock, so they run alongside each other and
+/// alongside a run in an environment of its own. That run's modules are visible to them, and any
+/// third party module they import while it is active is kept with that environment. Packages
+/// with extension modules that can only be loaded once in a process, such as numpy, can't be
+/// isolated this way.
+const ENVIRONMENTS_CODE: &str = r#"
+import importlib, os, sys, threading
+
+_lock = threading.Lock()
+# the third party modules that were imported in each environment, while it is not active
+_environment_modules = {}
+# the environment that is active on each thread, as [site_packages, shared_modules, depth]
+_active = threading.local()
+
+def _take_third_party_modules():
+    modules = {}
+    for name, module in list(sys.modules.items()):
+        module_file = getattr(module, "__file__", None) or ""
+        if any(os.sep + d + os.sep in module_file for d in ("site-packages", "dist-packages")):
+            modules[name] = module
+            del sys.modules[name]
+    return modules
+
+def _swap_in(site_packages, depth):
+    _lock.acquire()
+    try:
+        shared_modules = _take_third_party_modules()
+        sys.modules.update(_environment_modules.pop(site_packages, {}))
+        sys.path.insert(0, site_packages)
+        importlib.invalidate_caches()
+        _active.env = [site_packages, shared_modules, depth]
+    except BaseException:
+        _lock.release()
+        raise
+
+def _swap_out():
+    site_packages, shared_modules, depth = _active.env
+    _active.env = None
+    try:
+        if site_packages in sys.path:
+            sys.path.remove(site_packages)
+        _environment_modules[site_packages] = _take_third_party_modules()
+        sys.modules.update(shared_modules)
+        importlib.invalidate_caches()
+    finally:
+        _lock.release()
+    return site_packages, depth
+
+def activate(site_packages):
+    if site_packages is None:
+        # the shared environment, whose modules are the ones in sys.modules
+        return
+    env = getattr(_active, "env", None)
+    if env is None:
+        _swap_in(site_packages, 1)
+    elif env[0] == site_packages:
+        env[2] += 1
+    else:
+        raise RuntimeError(f"environment {env[0]} is already active")
+
+def deactivate(site_packages):
+    if site_packages is None:
+        return
+    env = _active.env
+    env[2] -= 1
+    if env[2] == 0:
+        _swap_out()
+
+def suspend():
+    if getattr(_active, "env", None) is None:
+        return None
+    return _swap_out()
+
+def resume(suspended):
+    if suspended is not None:
+        _swap_in(*suspended)
+"#;
+
+static ENVIRONMENTS_MODULE: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+
+fn environments_module(py: Python<'_>) -> PyResult<&Bound<'_, PyModule>> {
+    ENVIRONMENTS_MODULE
+        .get_or_try_init(py, || {
+            PyModule::from_code(
+                py,
+                &CString::new(ENVIRONMENTS_CODE)?,
+                c"influxdb3_environments.py",
+                c"influxdb3_environments",
+            )
+            .map(Bound::unbind)
+        })
+        .map(|module| module.bind(py))
+}
+
+/// A plugin's environment, or the shared environment if the plugin doesn't have one of its own,
+/// which is active until this is dropped
+struct ActiveEnvironment {
+    site_packages: Option<String>,
+}
+
+impl ActiveEnvironment {
+    fn activate(
+        py: Python<'_>,
+        environment: Option<&dyn PluginEnvironment>,
+    ) -> Result<Self, ExecutePluginError> {
+        let site_packages = environment
+            .map(|environment| {
+                environment
+                    .site_packages()
+                    .map(|path| path.to_string_lossy().into_owned())
+            })
+            .transpose()?;
+        environments_module(py)
+            .and_then(|module| module.call_method1("activate", (site_packages.as_deref(),)))
+            .context("failed to activate the plugin's environment")?;
+        Ok(Self { site_packages })
+    }
+}
+
+impl Drop for ActiveEnvironment {
+    fn drop(&mut self) {
+        Python::with_gil(|py| {
+            let result = environments_module(py).and_then(|module| {
+                module.call_method1("deactivate", (self.site_packages.as_deref(),))
+            });
+            if let Err(e) = result {
+                error!(
+                    "failed to deactivate plugin environment {:?}: {e}",
+                    self.site_packages
+                );
+            }
+        });
+    }
+}
+
+/// Wait on `handle` for a plugin that called into the server, without holding the GIL or the
+/// plugin's environment, so that other plugins can run while it waits
+fn block_on_host<T: Send>(
+    py: Python<'_>,
+    handle: tokio::task::JoinHandle<T>,
+) -> Result<T, tokio::task::JoinError> {
+    let environments = environments_module(py).ok();
+    let suspended = environments.and_then(|module| match module.call_method0("suspend") {
+        Ok(suspended) => Some(suspended),
+        Err(e) => {
+            error!("failed to suspend plugin environment: {e}");
+            None
+        }
+    });
+    let result = py.allow_threads(|| {
+        tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(handle))
+    });
+    if let (Some(module), Some(suspended)) = (environments, suspended) {
+        if let Err(e) = module.call_method1("resume", (suspended,)) {
+            error!("failed to resume plugin environment: {e}");
+        }
+    }
+    result
+}
+
+/// Limits on each run of a plugin
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExecutionLimits {
+    /// Runs that take longer than this are interrupted
+    pub timeout: Option<Duration>,
+    /// Runs that write more than this many rows fail
+    pub max_rows_written: Option<usize>,
+}
+
+/// Run `f`, which calls into plugin code, in the plugin's `environment`, failing it if it runs
+/// for longer than `timeout`
+fn run_watched<T>(
+    py: Python<'_>,
+    environment: Option<&dyn PluginEnvironment>,
+    timeout: Option<Duration>,
+    f: impl FnOnce() -> Result<T, ExecutePluginError>,
+) -> Result<T, ExecutePluginError> {
+    // deactivated when dropped, after the watchdog has finished, so that a timeout can't
+    // interrupt it
+    let _environment = ActiveEnvironment::activate(py, environment)?;
+    let watchdog = timeout
+        .map(|timeout| Watchdog::start(py, timeout))
+        .transpose()?;
+    let result = f();
+    if let Some(watchdog) = watchdog {
+        if watchdog.finish(py) {
+            return Err(ExecutePluginError::Timeout(watchdog.timeout));
+        }
+    }
+    result
+}
+
+/// Interrupts a plugin that runs for longer than its timeout, by raising a [`PluginTimeoutError`]
+/// in the thread that is running it.
+///
+/// The exception is raised when the thread next runs python bytecode, so a plugin that is blocked
+/// in a call into C, such as a read from a socket without a timeout, is only interrupted once
+/// the call returns.
+///
+/// All runs are watched by a single thread, which waits on the earliest deadline in
+/// [`WATCHDOG_QUEUE`].
+struct Watchdog {
+    timeout: Duration,
+    thread_id: u64,
+    deadline: WatchdogDeadline,
+    state: Arc<Mutex<WatchdogState>>,
+}
+
+#[derive(Debug, Default)]
+struct WatchdogState {
+    finished: bool,
+    timed_out: bool,
+}
+
+/// When a run times out, along with a sequence number to tell apart runs with the same deadline
+type WatchdogDeadline = (Instant, u64);
+
+/// The runs that the watchdog thread is watching
+#[derive(Debug, Default)]
+struct WatchdogQueue {
+    runs: Mutex<WatchedRuns>,
+    /// Notified when a run is added with a deadline earlier than those already queued
+    changed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct WatchedRuns {
+    by_deadline: BTreeMap<WatchdogDeadline, WatchedRun>,
+    next_seq: u64,
+    thread_started: bool,
+}
+
+#[derive(Debug)]
+struct WatchedRun {
+    thread_id: u64,
+    state: Arc<Mutex<WatchdogState>>,
+}
+
+static WATCHDOG_QUEUE: LazyLock<WatchdogQueue> = LazyLock::new(WatchdogQueue::default);
+
+impl Watchdog {
+    fn start(py: Python<'_>, timeout: Duration) -> Result<Self, ExecutePluginError> {
+        let thread_id: u64 = py
+            .import("threading")
+            .and_then(|threading| threading.call_method0("get_ident"))
+            .and_then(|ident| ident.extract())
+            .context("failed to get the python thread id")?;
+        let state = Arc::new(Mutex::new(WatchdogState::default()));
+
+        let queue: &'static WatchdogQueue = &WATCHDOG_QUEUE;
+        let mut runs = queue.runs.lock();
+        if !runs.thread_started {
+            std::thread::Builder::new()
+                .name("plugin-watchdog".to_string())
+                .spawn(move || watch(queue))
+                .context("failed to start the plugin watchdog")?;
+            runs.thread_started = true;
+        }
+        // a timeout too long to represent is as good as none
+        let deadline = (
+            Instant::now()
+                .checked_add(timeout)
+                .unwrap_or_else(|| Instant::now() + Duration::from_secs(u32::MAX.into())),
+            runs.next_seq,
+        );
+        runs.next_seq += 1;
+        if runs
+            .by_deadline
+            .first_key_value()
+            .is_none_or(|(earliest, _)| deadline < *earliest)
+        {
+            queue.changed.notify_one();
+        }
+        runs.by_deadline.insert(
+            deadline,
+            WatchedRun {
+                thread_id,
+                state: Arc::clone(&state),
+            },
+        );
+
+        Ok(Self {
+            timeout,
+            thread_id,
+            deadline,
+            state,
+        })
+    }
+
+    /// Stop watching the run, returning whether it timed out
+    fn finish(&self, _py: Python<'_>) -> bool {
+        WATCHDOG_QUEUE
+            .runs
+            .lock()
+            .by_deadline
+            .remove(&self.deadline);
+        let mut state = self.state.lock();
+        state.finished = true;
+        if state.timed_out {
+            // the run may have finished before the exception was raised, so clear it, rather
+            // than have it raised in whatever runs on this thread next
+            // SAFETY: the GIL is held
+            unsafe {
+                pyo3::ffi::PyThreadState_SetAsyncExc(self.thread_id as _, std::ptr::null_mut());
+            }
+        }
+        state.timed_out
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        // covers a run that panicked before it could finish
+        WATCHDOG_QUEUE
+            .runs
+            .lock()
+            .by_deadline
+            .remove(&self.deadline);
+        self.state.lock().finished = true;
+    }
+}
+
+/// The watchdog thread, which interrupts each run that is still queued at its deadline
+fn watch(queue: &WatchdogQueue) {
+    let mut runs = queue.runs.lock();
+    loop {
+        let Some((&(deadline, _), _)) = runs.by_deadline.first_key_value() else {
+            queue.changed.wait(&mut runs);
+            continue;
+        };
+        if Instant::now() < deadline {
+            queue.changed.wait_until(&mut runs, deadline);
+            continue;
+        }
+        let (_, run) = runs.by_deadline.pop_first().expect("queue is not empty");
+        // the GIL is taken without the queue locked, as runs lock the queue to finish while
+        // holding the GIL
+        MutexGuard::unlocked(&mut runs, || {
+            // the state is only changed with the GIL held, so the run can't finish between
+            // checking it and raising the exception
+            Python::with_gil(|py| {
+                let mut state = run.state.lock();
+                if state.finished {
+                    return;
+                }
+                // SAFETY: the GIL is held, and the exception is a type object that lives for
+                // the life of the interpreter
+                unsafe {
+                    pyo3::ffi::PyThreadState_SetAsyncExc(
+                        run.thread_id as _,
+                        PluginTimeoutError::type_object_raw(py).cast(),
+                    );
+                }
+                state.timed_out = true;
+            })
+        });
+    }
+}
+
+/// Reads the secrets that a plugin can use, by name
+pub trait SecretReader: std::fmt::Debug + Send + Sync {
+    /// The value of the secret, or `None` if there is no secret with the name
+    fn read(&self, name: &str) -> Result<Option<String>, anyhow::Error>;
+}
+
+const REDACTED: &str = "******";
+
+/// The secrets available to a plugin through `influxdb3_local.secrets`. Any value that the
+/// plugin has read is redacted from the lines that it logs.
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub struct PluginSecrets {
+    reader: Option<Arc<dyn SecretReader>>,
+    revealed: Arc<Mutex<HashSet<String>>>,
+}
+
+impl PluginSecrets {
+    fn new(reader: Arc<dyn SecretReader>) -> Self {
+        Self {
+            reader: Some(reader),
+            revealed: Default::default(),
+        }
+    }
+
+    fn redact(&self, line: String) -> String {
+        self.revealed
+            .lock()
+            .iter()
+            .filter(|value| !value.is_empty())
+            .fold(line, |line, value| line.replace(value.as_str(), REDACTED))
+    }
+
+    /// Redact the secrets that the plugin has read from an error that it raised, as its message
+    /// ends up in the trigger's logs and dead letters
+    fn redact_error(&self, error: ExecutePluginError) -> ExecutePluginError {
+        let ExecutePluginError::PluginError(e) = &error else {
+            return error;
+        };
+        let message = format!("{e:#}");
+        let redacted = self.redact(message.clone());
+        if redacted == message {
+            error
+        } else {
+            ExecutePluginError::PluginError(anyhow::Error::msg(redacted))
+        }
+    }
+}
+
+#[pymethods]
+impl PluginSecrets {
+    #[pyo3(signature = (name, default=None))]
+    fn get(&self, py: Python<'_>, name: &str, default: Option<PyObject>) -> PyResult<PyObject> {
+        let Some(reader) = &self.reader else {
+            return Err(PyException::new_err(
+                "secrets are not available to this plugin",
+            ));
+        };
+        match reader.read(name) {
+            Ok(Some(value)) => {
+                self.revealed.lock().insert(value.clone());
+                value.into_py_any(py)
+            }
+            Ok(None) => Ok(default.unwrap_or_else(|| py.None())),
+            Err(e) => Err(PyException::new_err(format!(
+                "failed to read secret {name}: {e}"
+            ))),
+        }
+    }
+}
+
 // Module initialization
 #[pymodule]
 fn influxdb3_py_api(_m: &Bound<'_, PyModule>) -> PyResult<()> {
@@ -1,6 +1,9 @@
 use std::{path::PathBuf, sync::Arc};
 
-use crate::{CommonServerState, Server, http::HttpApi};
+use crate::{
+    CommonServerState, Server, continuous_query::ContinuousQueryScheduler, http::HttpApi,
+    query_cache::QueryResultCache,
+};
 use influxdb3_authz::{AuthProvider, NoAuthAuthenticator};
 use influxdb3_internal_api::query_executor::QueryExecutor;
 use influxdb3_processing_engine::ProcessingEngineManagerImpl;
@@ -20,6 +23,7 @@ pub struct ServerBuilder<W, Q, P, T, L, E> {
     listener: L,
     processing_engine: E,
     authorizer: Arc<dyn AuthProvider>,
+    query_result_cache: Option<Arc<QueryResultCache>>,
 }
 
 impl
@@ -42,6 +46,7 @@ impl
             persister: NoPersister,
             listener: NoListener,
             authorizer: Arc::new(NoAuthAuthenticator),
+            query_result_cache: None,
             processing_engine: NoProcessingEngine,
         }
     }
@@ -57,6 +62,11 @@ impl<W, Q, P, T, L, E> ServerBuilder<W, Q, P, T, L, E> {
         self.authorizer = a;
         self
     }
+
+    pub fn query_result_cache(mut self, cache: Arc<QueryResultCache>) -> Self {
+        self.query_result_cache = Some(cache);
+        self
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -99,6 +109,7 @@ impl<Q, P, T, L, E> ServerBuilder<NoWriteBuf, Q, P, T, L, E> {
             persister: self.persister,
             listener: self.listener,
             authorizer: self.authorizer,
+            query_result_cache: self.query_result_cache,
             processing_engine: self.processing_engine,
         }
     }
@@ -118,6 +129,7 @@ impl<W, P, T, L, E> ServerBuilder<W, NoQueryExec, P, T, L, E> {
             persister: self.persister,
             listener: self.listener,
             authorizer: self.authorizer,
+            query_result_cache: self.query_result_cache,
             processing_engine: self.processing_engine,
         }
     }
@@ -134,6 +146,7 @@ impl<W, Q, T, L, E> ServerBuilder<W, Q, NoPersister, T, L, E> {
             persister: WithPersister(p),
             listener: self.listener,
             authorizer: self.authorizer,
+            query_result_cache: self.query_result_cache,
             processing_engine: self.processing_engine,
         }
     }
@@ -153,6 +166,7 @@ impl<W, Q, P, L, E> ServerBuilder<W, Q, P, NoTimeProvider, L, E> {
             persister: self.persister,
             listener: self.listener,
             authorizer: self.authorizer,
+            query_result_cache: self.query_result_cache,
             processing_engine: self.processing_engine,
         }
     }
@@ -169,6 +183,7 @@ impl<W, Q, P, T, E> ServerBuilder<W, Q, P, T, NoListener, E> {
             persister: self.persister,
             listener: WithListener(listener),
             authorizer: self.authorizer,
+            query_result_cache: self.query_result_cache,
             processing_engine: self.processing_engine,
         }
     }
@@ -188,6 +203,7 @@ impl<W, Q, P, T, L> ServerBuilder<W, Q, P, T, L, NoProcessingEngine> {
             persister: self.persister,
             listener: self.listener,
             authorizer: self.authorizer,
+            query_result_cache: self.query_result_cache,
             processing_engine: WithProcessingEngine(processing_engine),
         }
     }
@@ -222,6 +238,20 @@ impl
             .0
             .wal()
             .add_file_notifier(Arc::clone(&processing_engine) as _);
+        if let Some(cache) = &self.query_result_cache {
+            self.write_buffer
+                .0
+                .wal()
+                .add_file_notifier(Arc::clone(cache) as _);
+            cache.watch_persisted_snapshots(self.write_buffer.0.subscribe_persisted_snapshots());
+        }
+        ContinuousQueryScheduler::start(
+            Arc::clone(&self.write_buffer.0),
+            Arc::clone(&self.query_executor.0),
+            Arc::clone(&self.time_provider.0),
+            Arc::clone(&self.common_state.telemetry_store),
+        )
+        .await;
         let http = Arc::new(HttpApi::new(
             self.common_state.clone(),
             Arc::clone(&self.time_provider.0),
@@ -230,6 +260,7 @@ impl
             processing_engine,
             self.max_request_size,
             Arc::clone(&authorizer),
+            self.query_result_cache,
         ));
         Server {
             common_state: self.common_state,
@@ -0,0 +1,470 @@
+//! Scheduling and execution of InfluxQL continuous queries
+//!
+//! Continuous queries are stored in the catalog, and each one is run by a task that wakes up on
+//! the boundaries of its `RESAMPLE EVERY` interval, runs its `SELECT ... INTO` statement for the
+//! time range that has just elapsed, and then records the end of that time range.
+//!
+//! The end of the most recently completed time range is kept in a file of its own in object store,
+//! rather than in the catalog, so that runs do not add to the catalog log. A server that is
+//! restarted will pick up where it left off: any time ranges that were missed while it was down
+//! are covered by the first run after it starts, and ranges that were already completed are not
+//! run again.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use bytes::Bytes;
+use influxdb_influxql_parser::statement::Statement;
+use influxdb3_catalog::{
+    catalog::Catalog,
+    channel::CatalogUpdateReceiver,
+    log::{CatalogBatch, ContinuousQueryDefinition, DatabaseCatalogOp, DeleteContinuousQueryLog},
+};
+use influxdb3_id::{ContinuousQueryId, DbId};
+use influxdb3_internal_api::query_executor::QueryExecutor;
+use influxdb3_telemetry::store::TelemetryStore;
+use influxdb3_write::WriteBuffer;
+use iox_query_influxql_rewrite as rewrite;
+use iox_time::{Time, TimeProvider};
+use object_store::{ObjectStore, path::Path as ObjPath};
+use observability_deps::tracing::{debug, error, info, warn};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use crate::http::select_into::run_select_into;
+
+/// Runs each of the continuous queries in the catalog on its schedule
+#[derive(Debug)]
+pub(crate) struct ContinuousQueryScheduler {
+    catalog: Arc<Catalog>,
+    write_buffer: Arc<dyn WriteBuffer>,
+    query_executor: Arc<dyn QueryExecutor>,
+    time_provider: Arc<dyn TimeProvider>,
+    telemetry_store: Arc<TelemetryStore>,
+    runners: Mutex<HashMap<(DbId, ContinuousQueryId), JoinHandle<()>>>,
+}
+
+impl ContinuousQueryScheduler {
+    /// Start a runner for every continuous query in the catalog, and keep the set of runners in
+    /// sync with the catalog as continuous queries are created and dropped
+    pub(crate) async fn start(
+        write_buffer: Arc<dyn WriteBuffer>,
+        query_executor: Arc<dyn QueryExecutor>,
+        time_provider: Arc<dyn TimeProvider>,
+        telemetry_store: Arc<TelemetryStore>,
+    ) -> Arc<Self> {
+        let catalog = write_buffer.catalog();
+        // subscribe before reading the catalog so that no continuous query created in between is
+        // missed; starting a runner is idempotent, so seeing one twice is not a problem:
+        let subscription = catalog.subscribe_to_updates("continuous_queries").await;
+        let scheduler = Arc::new(Self {
+            catalog,
+            write_buffer,
+            query_executor,
+            time_provider,
+            telemetry_store,
+            runners: Default::default(),
+        });
+
+        for db in scheduler.catalog.list_db_schema() {
+            if db.deleted {
+                continue;
+            }
+            for continuous_query in db.continuous_queries.resource_iter() {
+                scheduler.start_runner(db.id, continuous_query.id);
+            }
+        }
+
+        background_catalog_update(Arc::clone(&scheduler), subscription);
+
+        scheduler
+    }
+
+    fn start_runner(self: &Arc<Self>, db_id: DbId, id: ContinuousQueryId) {
+        let mut runners = self.runners.lock();
+        if runners.get(&(db_id, id)).is_some_and(|r| !r.is_finished()) {
+            return;
+        }
+        let runner = tokio::spawn(Arc::clone(self).run(db_id, id));
+        runners.insert((db_id, id), runner);
+    }
+
+    fn stop_runner(&self, db_id: DbId, id: ContinuousQueryId) {
+        if let Some(runner) = self.runners.lock().remove(&(db_id, id)) {
+            runner.abort();
+        }
+    }
+
+    fn stop_runners_for_db(&self, db_id: DbId) {
+        self.runners.lock().retain(|(runner_db_id, _), runner| {
+            if *runner_db_id == db_id {
+                runner.abort();
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Run a continuous query until it, or its database, is removed from the catalog
+    async fn run(self: Arc<Self>, db_id: DbId, id: ContinuousQueryId) {
+        let progress_store = ProgressStore::new(&self.catalog, db_id, id);
+        let mut last_run_end_ns = match progress_store.load().await {
+            Ok(progress) => progress.map(|progress| progress.last_run_end_ns),
+            Err(error) => {
+                // without the end of the last run, the continuous query carries on from now
+                warn!(%error, "failed to load continuous query progress");
+                None
+            }
+        };
+        loop {
+            // the definition is read on each iteration in case it has been removed:
+            let Some(db) = self
+                .catalog
+                .db_schema_by_id(&db_id)
+                .filter(|db| !db.deleted)
+            else {
+                return;
+            };
+            let Some(definition) = db.continuous_queries.get_by_id(&id) else {
+                return;
+            };
+            let run = next_run(
+                &definition,
+                last_run_end_ns,
+                self.time_provider.now().timestamp_nanos(),
+            );
+            self.time_provider
+                .sleep_until(Time::from_timestamp_nanos(run.at))
+                .await;
+
+            match self.run_once(&definition, &run).await {
+                Ok(()) => {
+                    last_run_end_ns = Some(run.end);
+                    if let Err(error) = progress_store
+                        .save(&Progress {
+                            last_run_end_ns: run.end,
+                        })
+                        .await
+                    {
+                        // the run is still known to this runner, but a restart will run it again
+                        warn!(
+                            %error,
+                            database = %definition.database_name,
+                            continuous_query = %definition.name,
+                            "failed to save continuous query progress"
+                        );
+                    }
+                }
+                Err(error) => {
+                    // the end of the run was not recorded, so the next run will cover this range
+                    // again; wait until then, rather than retrying straight away:
+                    error!(
+                        %error,
+                        database = %definition.database_name,
+                        continuous_query = %definition.name,
+                        "continuous query failed"
+                    );
+                    self.time_provider
+                        .sleep_until(Time::from_timestamp_nanos(
+                            run.at + nanos(definition.every()),
+                        ))
+                        .await;
+                }
+            }
+        }
+    }
+
+    async fn run_once(
+        &self,
+        definition: &ContinuousQueryDefinition,
+        run: &Run,
+    ) -> Result<(), anyhow::Error> {
+        let query_str =
+            rewrite::continuous_query::with_time_range(&definition.select, run.start, run.end);
+        let statement = rewrite::parse_statements(&query_str)?
+            .pop()
+            .context("continuous query has no statement")?;
+        let into = statement
+            .into_clause()
+            .cloned()
+            .context("continuous query has no INTO clause")?;
+        let statement = statement.to_statement();
+        let group_by = match &statement {
+            Statement::Select(select_statement) => select_statement.group_by.clone(),
+            _ => None,
+        };
+
+        let written = run_select_into(
+            &self.query_executor,
+            &self.write_buffer,
+            &self.telemetry_store,
+            self.time_provider.now(),
+            &definition.database_name,
+            &query_str,
+            statement,
+            group_by,
+            &into,
+            None,
+            None,
+        )
+        .await?;
+        debug!(
+            database = %definition.database_name,
+            continuous_query = %definition.name,
+            start = run.start,
+            end = run.end,
+            written,
+            "continuous query run"
+        );
+        Ok(())
+    }
+}
+
+/// How far a continuous query has got, which is kept in object store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Progress {
+    /// The end of the most recent time range, in nanoseconds since the epoch, that the continuous
+    /// query has completed a run for
+    last_run_end_ns: i64,
+}
+
+/// Where the [`Progress`] of a continuous query is kept in object store
+#[derive(Debug, Clone)]
+struct ProgressStore {
+    object_store: Arc<dyn ObjectStore>,
+    path: ObjPath,
+}
+
+impl ProgressStore {
+    fn new(catalog: &Catalog, db_id: DbId, id: ContinuousQueryId) -> Self {
+        let prefix = catalog.object_store_prefix();
+        Self {
+            object_store: catalog.object_store(),
+            path: ObjPath::from(format!("{prefix}/continuous_queries/{db_id}/{id}.json")),
+        }
+    }
+
+    async fn load(&self) -> Result<Option<Progress>, anyhow::Error> {
+        let bytes = match self.object_store.get(&self.path).await {
+            Ok(result) => result.bytes().await?,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(serde_json::from_slice(&bytes)?))
+    }
+
+    async fn save(&self, progress: &Progress) -> Result<(), anyhow::Error> {
+        let bytes = Bytes::from(serde_json::to_vec(progress)?);
+        self.object_store.put(&self.path, bytes.into()).await?;
+        Ok(())
+    }
+
+    /// Remove the progress of a continuous query that has been dropped
+    async fn delete(&self) -> Result<(), anyhow::Error> {
+        match self.object_store.delete(&self.path).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn background_catalog_update(
+    scheduler: Arc<ContinuousQueryScheduler>,
+    mut subscription: CatalogUpdateReceiver,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(catalog_update) = subscription.recv().await {
+            for batch in catalog_update
+                .batches()
+                .filter_map(CatalogBatch::as_database)
+            {
+                for op in batch.ops.iter() {
+                    match op {
+                        DatabaseCatalogOp::SoftDeleteDatabase(_) => {
+                            scheduler.stop_runners_for_db(batch.database_id);
+                        }
+                        DatabaseCatalogOp::CreateContinuousQuery(definition) => {
+                            info!(
+                                database = %definition.database_name,
+                                continuous_query = %definition.name,
+                                "starting continuous query"
+                            );
+                            scheduler.start_runner(batch.database_id, definition.id);
+                        }
+                        DatabaseCatalogOp::DeleteContinuousQuery(DeleteContinuousQueryLog {
+                            id,
+                            ..
+                        }) => {
+                            scheduler.stop_runner(batch.database_id, *id);
+                            if let Err(error) =
+                                ProgressStore::new(&scheduler.catalog, batch.database_id, *id)
+                                    .delete()
+                                    .await
+                            {
+                                warn!(%error, "failed to delete continuous query progress");
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+        warn!("catalog subscription for continuous queries closed");
+    })
+}
+
+/// A single run of a continuous query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Run {
+    /// When the run happens, in nanoseconds since the epoch
+    at: i64,
+    /// The inclusive start of the time range that is queried
+    start: i64,
+    /// The exclusive end of the time range that is queried
+    end: i64,
+}
+
+/// Determine the next run of a continuous query at or after `now_ns`, given the end of its last
+/// completed run
+///
+/// Runs happen on the boundaries of the `RESAMPLE EVERY` interval, and query the `RESAMPLE FOR`
+/// time range ending at the most recent `GROUP BY time(...)` boundary. If a range that should
+/// have been queried by now was missed, the next run happens straight away, and its range is
+/// extended back to the end of the last completed run.
+fn next_run(
+    definition: &ContinuousQueryDefinition,
+    last_run_end_ns: Option<i64>,
+    now_ns: i64,
+) -> Run {
+    let interval = nanos(definition.interval);
+    let offset = nanos(definition.offset);
+    let every = nanos(definition.every());
+    let run_for = nanos(definition.run_for());
+
+    let latest = align(now_ns, every, offset);
+    let at = match last_run_end_ns {
+        Some(last_run_end) if last_run_end < align(latest, interval, offset) => latest,
+        _ => latest + every,
+    };
+    let end = align(at, interval, offset);
+    let start = align(end - run_for, interval, offset);
+    let start = last_run_end_ns.map_or(start, |last_run_end| start.min(last_run_end));
+
+    Run { at, start, end }
+}
+
+/// Round `t` down to the nearest boundary of `interval`, shifted by `offset`
+fn align(t: i64, interval: i64, offset: i64) -> i64 {
+    (t - offset).div_euclid(interval) * interval + offset
+}
+
+fn nanos(duration: Duration) -> i64 {
+    duration.as_nanos().try_into().unwrap_or(i64::MAX).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use influxdb3_catalog::{catalog::Catalog, log::ContinuousQueryDefinition};
+    use influxdb3_id::{ContinuousQueryId, DbId};
+    use iox_time::{MockProvider, Time};
+    use object_store::memory::InMemory;
+
+    use super::{Progress, ProgressStore, Run, next_run};
+
+    const MINUTE: i64 = 60_000_000_000;
+    const HOUR: i64 = 60 * MINUTE;
+
+    fn definition(
+        resample_every: Option<Duration>,
+        resample_for: Option<Duration>,
+    ) -> ContinuousQueryDefinition {
+        ContinuousQueryDefinition {
+            id: ContinuousQueryId::new(0),
+            name: "cq".into(),
+            database_name: "db".into(),
+            query: String::new(),
+            select: String::new(),
+            interval: Duration::from_secs(3600),
+            offset: Duration::ZERO,
+            resample_every,
+            resample_for,
+        }
+    }
+
+    #[test]
+    fn runs_on_interval_boundaries() {
+        let now = 10 * HOUR + 20 * MINUTE;
+        assert_eq!(
+            Run {
+                at: 11 * HOUR,
+                start: 10 * HOUR,
+                end: 11 * HOUR
+            },
+            next_run(&definition(None, None), None, now)
+        );
+        // the last run already covered the range ending at the most recent boundary:
+        assert_eq!(
+            Run {
+                at: 11 * HOUR,
+                start: 10 * HOUR,
+                end: 11 * HOUR
+            },
+            next_run(&definition(None, None), Some(10 * HOUR), now)
+        );
+    }
+
+    #[test]
+    fn resample() {
+        let now = 10 * HOUR + 20 * MINUTE;
+        let every = Some(Duration::from_secs(30 * 60));
+        let run_for = Some(Duration::from_secs(2 * 3600));
+        assert_eq!(
+            Run {
+                at: 10 * HOUR + 30 * MINUTE,
+                start: 8 * HOUR,
+                end: 10 * HOUR
+            },
+            next_run(&definition(every, run_for), Some(10 * HOUR), now)
+        );
+    }
+
+    #[test]
+    fn missed_runs_are_caught_up() {
+        let now = 10 * HOUR + 20 * MINUTE;
+        assert_eq!(
+            Run {
+                at: 10 * HOUR,
+                start: 7 * HOUR,
+                end: 10 * HOUR
+            },
+            next_run(&definition(None, None), Some(7 * HOUR), now)
+        );
+    }
+
+    #[tokio::test]
+    async fn progress_is_kept_in_object_store() {
+        let catalog = Catalog::new(
+            "test-node",
+            Arc::new(InMemory::new()),
+            Arc::new(MockProvider::new(Time::from_timestamp_nanos(0))),
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        let store = ProgressStore::new(&catalog, DbId::new(0), ContinuousQueryId::new(0));
+        assert!(store.load().await.unwrap().is_none());
+
+        let progress = Progress {
+            last_run_end_ns: 7 * HOUR,
+        };
+        store.save(&progress).await.unwrap();
+        assert_eq!(Some(progress), store.load().await.unwrap());
+
+        store.delete().await.unwrap();
+        assert!(store.load().await.unwrap().is_none());
+    }
+}
@@ -1,5 +1,6 @@
 //! HTTP API service implementations for `server`
 
+use crate::query_cache::{CacheLookup, QueryResultCache};
 use crate::{CommonServerState, all_paths};
 use arrow::record_batch::RecordBatch;
 use arrow::util::pretty;
@@ -12,11 +13,13 @@ use datafusion::error::DataFusionError;
 use datafusion::execution::RecordBatchStream;
 use datafusion::execution::memory_pool::UnboundedMemoryPool;
 use datafusion::physical_plan::SendableRecordBatchStream;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use futures::FutureExt;
 use futures::{StreamExt, TryStreamExt};
 use http::header::ACCESS_CONTROL_ALLOW_ORIGIN;
 use hyper::HeaderMap;
 use hyper::header::AUTHORIZATION;
+use hyper::header::CACHE_CONTROL;
 use hyper::header::CONTENT_ENCODING;
 use hyper::header::CONTENT_TYPE;
 use hyper::http::HeaderValue;
@@ -27,11 +30,16 @@ use influxdb3_authz::{AuthProvider, NoAuthAuthenticator};
 use influxdb3_cache::distinct_cache;
 use influxdb3_cache::last_cache;
 use influxdb3_catalog::CatalogError;
-use influxdb3_catalog::log::FieldDataType;
+use influxdb3_catalog::log::{FieldDataType, TriggerSpecificationDefinition};
+use influxdb3_id::TokenId;
 use influxdb3_internal_api::query_executor::{QueryExecutor, QueryExecutorError};
 use influxdb3_process::{INFLUXDB3_GIT_HASH_SHORT, INFLUXDB3_VERSION, PROCESS_UUID};
 use influxdb3_processing_engine::ProcessingEngineManagerImpl;
 use influxdb3_processing_engine::manager::ProcessingEngineError;
+use influxdb3_processing_engine::plugin_store::PluginStoreError;
+use influxdb3_processing_engine::plugins::PluginError;
+use influxdb3_processing_engine::secrets::SecretsError;
+use influxdb3_processing_engine::wasm_plugin::validate_trigger_type;
 use influxdb3_types::http::*;
 use influxdb3_write::BufferedWriteRequest;
 use influxdb3_write::Precision;
@@ -43,7 +51,7 @@ use iox_http::write::v1::V1_NAMESPACE_RP_SEPARATOR;
 use iox_http::write::{WriteParseError, WriteRequestUnifier};
 use iox_query_influxql_rewrite as rewrite;
 use iox_query_params::StatementParams;
-use iox_time::TimeProvider;
+use iox_time::{Time, TimeProvider};
 use observability_deps::tracing::{debug, error, info, trace};
 use serde::Deserialize;
 use serde::Serialize;
@@ -56,10 +64,13 @@ use std::str::Utf8Error;
 use std::string::FromUtf8Error;
 use std::sync::Arc;
 use std::task::Poll;
+use std::time::Duration;
 use thiserror::Error;
 use trace::ctx::SpanContext;
 use unicode_segmentation::UnicodeSegmentation;
 
+mod continuous_query;
+pub(crate) mod select_into;
 mod v1;
 
 #[derive(Debug, Error)]
@@ -140,6 +151,10 @@ pub enum Error {
     #[error("missing query parameters 'db' and 'q'")]
     MissingQueryParams,
 
+    /// The `chunk_size` parameter for a query must be greater than zero
+    #[error("invalid chunk_size parameter, must be greater than zero")]
+    InvalidChunkSize,
+
     /// Missing the `q` parameter in the v1 /query API
     #[error("missing query parameter 'q'")]
     MissingQueryV1Params,
@@ -215,6 +230,9 @@ pub enum Error {
     )]
     InfluxqlDatabaseMismatch { param_db: String, query_db: String },
 
+    #[error("error in SELECT ... INTO statement: {0}")]
+    SelectInto(anyhow::Error),
+
     #[error("v1 query API error: {0}")]
     V1Query(#[from] v1::QueryError),
 
@@ -380,6 +398,7 @@ impl IntoResponse for Error {
                 | last_cache::Error::KeyColumnDoesNotExist { .. }
                 | last_cache::Error::KeyColumnDoesNotExistByName { .. }
                 | last_cache::Error::InvalidKeyColumn { .. }
+                | last_cache::Error::InvalidKeyValue { .. }
                 | last_cache::Error::ValueColumnDoesNotExist { .. } => Response::builder()
                     .status(StatusCode::BAD_REQUEST)
                     .body(Body::from(lc_err.to_string()))
@@ -388,6 +407,10 @@ impl IntoResponse for Error {
                     .status(StatusCode::NOT_FOUND)
                     .body(Body::from(self.to_string()))
                     .unwrap(),
+                last_cache::Error::Read(_) => Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::from(lc_err.to_string()))
+                    .unwrap(),
             },
             Self::WriteBuffer(WriteBufferError::DistinctCacheError(ref mc_err)) => match mc_err {
                 distinct_cache::ProviderError::Cache(cache_err) => match cache_err {
@@ -408,6 +431,10 @@ impl IntoResponse for Error {
                     .status(StatusCode::NOT_FOUND)
                     .body(Body::from(mc_err.to_string()))
                     .unwrap(),
+                distinct_cache::ProviderError::ColumnNotInCache(_) => Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(Body::from(mc_err.to_string()))
+                    .unwrap(),
                 distinct_cache::ProviderError::Unexpected(_) => Response::builder()
                     .status(StatusCode::INTERNAL_SERVER_ERROR)
                     .body(Body::from(mc_err.to_string()))
@@ -460,7 +487,10 @@ impl IntoResponse for Error {
                     .body(body)
                     .unwrap()
             }
-            Self::Query(QueryExecutorError::DatabaseNotFound { .. }) => {
+            Self::Query(
+                QueryExecutorError::DatabaseNotFound { .. }
+                | QueryExecutorError::PreparedStatementNotFound { .. },
+            ) => {
                 let err: ErrorMessage<()> = ErrorMessage {
                     error: self.to_string(),
                     data: None,
@@ -489,12 +519,26 @@ impl IntoResponse for Error {
                 .body(Body::from(self.to_string()))
                 .unwrap(),
             Self::MissingQueryParams
+            | Self::InvalidChunkSize
             | Self::MissingQueryV1Params
             | Self::MissingWriteParams
             | Self::MissingDeleteDatabaseParams => Response::builder()
                 .status(StatusCode::BAD_REQUEST)
                 .body(Body::from(self.to_string()))
                 .unwrap(),
+            Self::Plugin(
+                PluginError::PluginStore(
+                    PluginStoreError::InvalidName(_) | PluginStoreError::InvalidPackage(_),
+                )
+                | PluginError::UnsupportedWasmTrigger(_),
+            )
+            | Self::ProcessingEngine(
+                ProcessingEngineError::InvalidSecretName(_)
+                | ProcessingEngineError::Secrets(SecretsError::NoKey),
+            ) => Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::from(self.to_string()))
+                .unwrap(),
             _ => {
                 let body = Body::from(self.to_string());
                 Response::builder()
@@ -518,6 +562,7 @@ pub(crate) struct HttpApi {
     max_request_bytes: usize,
     authorizer: Arc<dyn AuthProvider>,
     legacy_write_param_unifier: SingleTenantRequestUnifier,
+    query_result_cache: Option<Arc<QueryResultCache>>,
 }
 
 impl HttpApi {
@@ -529,6 +574,7 @@ impl HttpApi {
         processing_engine: Arc<ProcessingEngineManagerImpl>,
         max_request_bytes: usize,
         authorizer: Arc<dyn AuthProvider>,
+        query_result_cache: Option<Arc<QueryResultCache>>,
     ) -> Self {
         // there is a global authentication setup, passing in auth provider just does the same
         // check twice. So, instead we pass in a NoAuthAuthenticator to avoid authenticating twice.
@@ -543,6 +589,7 @@ impl HttpApi {
             authorizer,
             legacy_write_param_unifier,
             processing_engine,
+            query_result_cache,
         }
     }
 }
@@ -633,23 +680,51 @@ impl HttpApi {
     }
 
     async fn query_sql(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let bypass_cache = bypass_query_result_cache(&req);
         let QueryRequest {
             database,
             query_str,
             format,
             params,
+            chunk_size,
         } = self.extract_query_request::<String>(req).await?;
 
-        info!(%database, %query_str, ?format, "handling query_sql");
+        info!(%database, %query_str, ?format, ?chunk_size, "handling query_sql");
 
         let span_ctx = Some(SpanContext::new_with_optional_collector(
             self.common_state.trace_collector(),
         ));
 
-        let stream = self
-            .query_executor
-            .query_sql(&database, &query_str, params, span_ctx, None)
-            .await?;
+        let lookup = self.query_result_cache_lookup(&database, bypass_cache, |cache| {
+            cache.lookup_sql(&database, &query_str, params.as_ref())
+        });
+        let stream = match lookup {
+            CacheLookup::Hit(stream) => {
+                self.query_executor
+                    .record_cached_query(
+                        &database,
+                        "sql",
+                        &query_str,
+                        params,
+                        stream.schema(),
+                        span_ctx,
+                        None,
+                    )
+                    .await?;
+                stream
+            }
+            CacheLookup::Miss(fill) => fill.wrap(
+                self.query_executor
+                    .query_sql(&database, &query_str, params, span_ctx, None)
+                    .await?,
+            ),
+            CacheLookup::Uncacheable => {
+                self.query_executor
+                    .query_sql(&database, &query_str, params, span_ctx, None)
+                    .await?
+            }
+        };
+        let stream = chunk_record_batch_stream(stream, chunk_size);
 
         Response::builder()
             .status(StatusCode::OK)
@@ -659,17 +734,20 @@ impl HttpApi {
     }
 
     async fn query_influxql(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let bypass_cache = bypass_query_result_cache(&req);
         let QueryRequest {
             database,
             query_str,
             format,
             params,
+            chunk_size,
         } = self.extract_query_request::<Option<String>>(req).await?;
 
-        info!(?database, %query_str, ?format, "handling query_influxql");
+        info!(?database, %query_str, ?format, ?chunk_size, "handling query_influxql");
         let (stream, _) = self
-            .query_influxql_inner(database, &query_str, params)
+            .query_influxql_inner(database, &query_str, params, bypass_cache)
             .await?;
+        let stream = chunk_record_batch_stream(stream, chunk_size);
 
         Response::builder()
             .status(StatusCode::OK)
@@ -678,6 +756,87 @@ impl HttpApi {
             .map_err(Into::into)
     }
 
+    /// Prepare a SQL query, so that it can be executed many times with different parameters using
+    /// the `/api/v3/query_prepared` API, without being planned again each time
+    async fn prepare_sql(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let PrepareSqlRequest {
+            database,
+            query_str,
+        } = self.read_body_json(req).await?;
+
+        info!(%database, %query_str, "handling prepare_sql");
+
+        let span_ctx = Some(SpanContext::new_with_optional_collector(
+            self.common_state.trace_collector(),
+        ));
+
+        let prepared = self
+            .query_executor
+            .prepare_sql(&database, &query_str, span_ctx)
+            .await?;
+        let body = serde_json::to_vec(&PrepareSqlResponse {
+            statement_id: prepared.id,
+            parameters: prepared.parameters,
+        })?;
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .map_err(Into::into)
+    }
+
+    async fn query_prepared(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let header_format = QueryFormat::try_from_headers(req.headers())?;
+        let QueryPreparedRequest {
+            statement_id,
+            format,
+            params,
+            chunk_size,
+        } = self.read_body_json(req).await?;
+        if chunk_size == Some(0) {
+            return Err(Error::InvalidChunkSize);
+        }
+        let format = format.unwrap_or(header_format);
+
+        info!(%statement_id, ?format, ?chunk_size, "handling query_prepared");
+
+        let span_ctx = Some(SpanContext::new_with_optional_collector(
+            self.common_state.trace_collector(),
+        ));
+
+        let stream = self
+            .query_executor
+            .query_prepared(statement_id, params, span_ctx, None)
+            .await?;
+        let stream = chunk_record_batch_stream(stream, chunk_size);
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, format.as_content_type())
+            .body(record_batch_stream_to_body(stream, format).await?)
+            .map_err(Into::into)
+    }
+
+    /// Close a statement that was prepared with the `/api/v3/prepared_statement` API
+    ///
+    /// The parameters must be passed in either the query string or the body of the request as JSON.
+    async fn close_prepared_statement(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let PreparedStatementDeleteRequest { statement_id } = if let Some(query) = req.uri().query()
+        {
+            serde_urlencoded::from_str(query)?
+        } else {
+            self.read_body_json(req).await?
+        };
+
+        self.query_executor.close_prepared(statement_id)?;
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::empty())
+            .map_err(Into::into)
+    }
+
     fn health(&self) -> Result<Response<Body>> {
         let response_body = "OK";
         Ok(Response::new(Body::from(response_body.to_string())))
@@ -812,6 +971,7 @@ impl HttpApi {
                     query_str: r.query_str,
                     format: r.format,
                     params: r.params.map(|s| serde_json::from_str(&s)).transpose()?,
+                    chunk_size: r.chunk_size,
                 }
             }
             Method::POST => {
@@ -821,24 +981,40 @@ impl HttpApi {
             _ => return Err(Error::UnsupportedMethod),
         };
 
+        if request.chunk_size == Some(0) {
+            return Err(Error::InvalidChunkSize);
+        }
+
         Ok(QueryRequest {
             database: request.database,
             query_str: request.query_str,
             format: request.format.unwrap_or(header_format),
             params: request.params,
+            chunk_size: request.chunk_size,
         })
     }
 
     /// Inner function for performing InfluxQL queries
     ///
     /// This is used by both the `/api/v3/query_influxql` and `/api/v1/query`
-    /// APIs.
+    /// APIs. Results are served from, and inserted into, the query result cache if it is
+    /// enabled, unless `bypass_cache` is set.
     async fn query_influxql_inner(
         &self,
         database: Option<String>,
         query_str: &str,
         params: Option<StatementParams>,
+        bypass_cache: bool,
     ) -> Result<(SendableRecordBatchStream, Option<GroupByClause>)> {
+        // continuous query statements name their own database, and are not understood by the
+        // InfluxQL parser, so they are handled separately:
+        if let Some(statement) = rewrite::continuous_query::parse_continuous_query(query_str) {
+            let stream = self
+                .query_influxql_continuous_query(query_str, statement?)
+                .await?;
+            return Ok((stream, None));
+        }
+
         let mut statements = rewrite::parse_statements(query_str)?;
 
         if statements.len() != 1 {
@@ -861,6 +1037,7 @@ impl HttpApi {
             }
         };
 
+        let into = statement.into_clause().cloned();
         let statement = statement.to_statement();
         let group_by = match &statement {
             Statement::Select(select_statement) => select_statement.group_by.clone(),
@@ -882,14 +1059,68 @@ impl HttpApi {
                 return Err(Error::InfluxqlNoDatabase);
             };
 
-            self.query_executor
-                .query_influxql(&database, query_str, statement, params, span_ctx, None)
-                .await?
+            // results of a SELECT ... INTO are written back to the database, so they are never
+            // served from the query result cache:
+            if let Some(into) = into {
+                let stream = self
+                    .query_influxql_select_into(
+                        &database, query_str, statement, group_by, &into, params, span_ctx,
+                    )
+                    .await?;
+                return Ok((stream, None));
+            }
+
+            let lookup = self.query_result_cache_lookup(&database, bypass_cache, |cache| {
+                cache.lookup_influxql(&database, query_str, &statement, params.as_ref())
+            });
+            match lookup {
+                CacheLookup::Hit(stream) => {
+                    self.query_executor
+                        .record_cached_query(
+                            &database,
+                            "influxql",
+                            query_str,
+                            params,
+                            stream.schema(),
+                            span_ctx,
+                            None,
+                        )
+                        .await?;
+                    stream
+                }
+                CacheLookup::Miss(fill) => fill.wrap(
+                    self.query_executor
+                        .query_influxql(&database, query_str, statement, params, span_ctx, None)
+                        .await?,
+                ),
+                CacheLookup::Uncacheable => {
+                    self.query_executor
+                        .query_influxql(&database, query_str, statement, params, span_ctx, None)
+                        .await?
+                }
+            }
         };
 
         Ok((stream, group_by))
     }
 
+    /// Check the query result cache, if it is enabled, using the given `lookup`
+    fn query_result_cache_lookup(
+        &self,
+        database: &str,
+        bypass_cache: bool,
+        lookup: impl FnOnce(&Arc<QueryResultCache>) -> CacheLookup,
+    ) -> CacheLookup {
+        match &self.query_result_cache {
+            Some(cache) if bypass_cache => {
+                cache.record_bypass(database);
+                CacheLookup::Uncacheable
+            }
+            Some(cache) => lookup(cache),
+            None => CacheLookup::Uncacheable,
+        }
+    }
+
     /// Create a new distinct value cache given the [`DistinctCacheCreateRequest`] arguments in the request
     /// body.
     ///
@@ -1026,6 +1257,12 @@ impl HttpApi {
             .processing_engine
             .validate_plugin_filename(&plugin_filename)
             .await?;
+        // an invalid trigger specification is reported by the catalog
+        if let Ok(specification) =
+            TriggerSpecificationDefinition::from_string_rep(&trigger_specification)
+        {
+            validate_trigger_type(&plugin_filename, specification.plugin_type())?;
+        }
         self.write_buffer
             .catalog()
             .create_processing_engine_trigger(
@@ -1063,6 +1300,35 @@ impl HttpApi {
             .body(Body::empty())?)
     }
 
+    async fn create_secret(&self, req: Request<Body>) -> Result<Response<Body>> {
+        // the value is only accepted in the body, so that it isn't logged with the request URI
+        let SecretCreateRequest { db, name, value } = self.read_body_json(req).await?;
+        debug!(%db, %name, "create_secret");
+        let encrypted_value = self.processing_engine.encrypt_secret(&db, &name, &value)?;
+        self.write_buffer
+            .catalog()
+            .create_secret(&db, &name, encrypted_value)
+            .await?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::empty())?)
+    }
+
+    async fn delete_secret(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let SecretDeleteRequest { db, name } = if let Some(query) = req.uri().query() {
+            serde_urlencoded::from_str(query)?
+        } else {
+            self.read_body_json(req).await?
+        };
+        self.write_buffer
+            .catalog()
+            .delete_secret(&db, &name)
+            .await?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::empty())?)
+    }
+
     async fn disable_processing_engine_trigger(
         &self,
         req: Request<Body>,
@@ -1100,6 +1366,91 @@ impl HttpApi {
         }
     }
 
+    async fn replay_processing_engine_dead_letters(
+        &self,
+        req: Request<Body>,
+    ) -> Result<Response<Body>> {
+        let ProcessingEngineTriggerReplayDeadLettersRequest {
+            db,
+            trigger_name,
+            dead_letter_id,
+        } = if let Some(query) = req.uri().query() {
+            serde_urlencoded::from_str(query)?
+        } else {
+            self.read_body_json(req).await?
+        };
+        let replayed = self
+            .processing_engine
+            .replay_dead_letters(&db, &trigger_name, dead_letter_id)
+            .await?;
+        let body =
+            serde_json::to_vec(&ProcessingEngineTriggerReplayDeadLettersResponse { replayed })?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))?)
+    }
+
+    async fn backfill_processing_engine_trigger(
+        &self,
+        req: Request<Body>,
+    ) -> Result<Response<Body>> {
+        let ProcessingEngineTriggerBackfillRequest {
+            db,
+            trigger_name,
+            start_time,
+            end_time,
+            window_seconds,
+        } = if let Some(query) = req.uri().query() {
+            serde_urlencoded::from_str(query)?
+        } else {
+            self.read_body_json(req).await?
+        };
+        let end_time = end_time
+            .map(Time::from_datetime)
+            .unwrap_or_else(|| self.time_provider.now());
+        let backfill_id = Arc::clone(&self.processing_engine)
+            .backfill_trigger(
+                &db,
+                &trigger_name,
+                Time::from_datetime(start_time),
+                end_time,
+                Duration::from_secs(window_seconds.unwrap_or(60)),
+            )
+            .await?;
+        let body = serde_json::to_vec(&ProcessingEngineTriggerBackfillResponse { backfill_id })?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))?)
+    }
+
+    async fn upload_plugin_file(&self, name: &str, req: Request<Body>) -> Result<Response<Body>> {
+        let code = self.read_body(req).await?;
+        let version = self.processing_engine.upload_plugin(name, code).await?;
+        info!(name, version, "uploaded plugin");
+        let body = serde_json::to_vec(&PluginFileUploadResponse {
+            name: name.to_string(),
+            version,
+        })?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))?)
+    }
+
+    async fn list_plugin_file_versions(&self, name: &str) -> Result<Response<Body>> {
+        let versions = self.processing_engine.plugin_versions(name).await?;
+        let body = serde_json::to_vec(&PluginFileVersionsResponse {
+            name: name.to_string(),
+            versions,
+        })?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))?)
+    }
+
     async fn install_plugin_environment_packages(
         &self,
         req: Request<Body>,
@@ -1204,6 +1555,25 @@ impl HttpApi {
             .body(Body::from(body))?)
     }
 
+    /// Endpoint for testing a plugin that will be triggered by requests.
+    async fn test_processing_engine_request_plugin(
+        &self,
+        req: Request<Body>,
+    ) -> Result<Response<Body>> {
+        let request: influxdb3_types::http::RequestPluginTestRequest =
+            self.read_body_json(req).await?;
+
+        let output = self
+            .processing_engine
+            .test_request_plugin(request, Arc::clone(&self.query_executor))
+            .await?;
+        let body = serde_json::to_string(&output)?;
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::from(body))?)
+    }
+
     async fn processing_engine_request_plugin(
         &self,
         trigger_path: &str,
@@ -1228,12 +1598,22 @@ impl HttpApi {
             .map(|(k, v)| (k.to_string(), v.to_str().unwrap().to_string()))
             .collect();
 
+        let method = req.method().to_string();
+        // the token is only worth passing on to the plugin if the server checks tokens
+        let token_id = if self.authorizer.should_check_token() {
+            req.extensions()
+                .get::<TokenId>()
+                .map(|token_id| token_id.get())
+        } else {
+            None
+        };
+
         // pull out the request body
         let body = self.read_body(req).await?;
 
         match self
             .processing_engine
-            .request_trigger(trigger_path, params, headers, body)
+            .request_trigger(trigger_path, method, params, headers, body, token_id)
             .await
         {
             Ok(response) => Ok(response),
@@ -1508,6 +1888,51 @@ pub enum ValidateDbNameError {
     Empty,
 }
 
+/// Check whether the client has asked for the query result cache to be skipped, by sending a
+/// `Cache-Control` header with the `no-cache` or `no-store` directive
+fn bypass_query_result_cache(req: &Request<Body>) -> bool {
+    req.headers()
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|directive| {
+            let directive = directive.trim();
+            directive.eq_ignore_ascii_case("no-cache") || directive.eq_ignore_ascii_case("no-store")
+        })
+}
+
+/// Split the [`RecordBatch`]es in a query result stream so that none hold more than `chunk_size`
+/// rows
+///
+/// The streaming formats (JSON, JSON lines, and CSV) encode each batch into its own chunk of the
+/// response body, so this bounds the amount of memory used to encode any one chunk, regardless of
+/// how large the batches produced by the query plan are. Slicing a [`RecordBatch`] does not copy
+/// the underlying data.
+fn chunk_record_batch_stream(
+    stream: SendableRecordBatchStream,
+    chunk_size: Option<usize>,
+) -> SendableRecordBatchStream {
+    let Some(chunk_size) = chunk_size else {
+        return stream;
+    };
+    let schema = stream.schema();
+    let chunked = stream.flat_map(move |batch| {
+        let batches = match batch {
+            Ok(batch) => {
+                let num_rows = batch.num_rows();
+                (0..num_rows)
+                    .step_by(chunk_size)
+                    .map(|offset| Ok(batch.slice(offset, chunk_size.min(num_rows - offset))))
+                    .collect::<Vec<_>>()
+            }
+            Err(e) => vec![Err(e)],
+        };
+        futures::stream::iter(batches)
+    });
+    Box::pin(RecordBatchStreamAdapter::new(schema, chunked))
+}
+
 async fn record_batch_stream_to_body(
     mut stream: Pin<Box<dyn RecordBatchStream + Send>>,
     format: QueryFormat,
@@ -1792,11 +2217,16 @@ pub(crate) async fn route_request(
         (Method::GET | Method::POST, all_paths::API_V3_QUERY_INFLUXQL) => {
             http_server.query_influxql(req).await
         }
+        (Method::POST, all_paths::API_V3_QUERY_PREPARED) => http_server.query_prepared(req).await,
+        (Method::POST, all_paths::API_V3_PREPARED_STATEMENT) => http_server.prepare_sql(req).await,
+        (Method::DELETE, all_paths::API_V3_PREPARED_STATEMENT) => {
+            http_server.close_prepared_statement(req).await
+        }
         (Method::GET | Method::POST, all_paths::API_V1_QUERY) => http_server.v1_query(req).await,
         (Method::GET, all_paths::API_V3_HEALTH | all_paths::API_V1_HEALTH) => http_server.health(),
         (Method::GET | Method::POST, all_paths::API_PING) => http_server.ping(),
         (Method::GET, all_paths::API_METRICS) => http_server.handle_metrics(),
-        (Method::GET | Method::POST, path) if path.starts_with(all_paths::API_V3_ENGINE) => {
+        (_, path) if path.starts_with(all_paths::API_V3_ENGINE) => {
             let path = path.strip_prefix(all_paths::API_V3_ENGINE).unwrap();
             http_server
                 .processing_engine_request_plugin(path, req)
@@ -1820,12 +2250,30 @@ pub(crate) async fn route_request(
         (Method::POST, all_paths::API_V3_CONFIGURE_PROCESSING_ENGINE_ENABLE) => {
             http_server.enable_processing_engine_trigger(req).await
         }
+        (Method::POST, all_paths::API_V3_CONFIGURE_PROCESSING_ENGINE_REPLAY_DEAD_LETTERS) => {
+            http_server.replay_processing_engine_dead_letters(req).await
+        }
+        (Method::POST, all_paths::API_V3_CONFIGURE_PROCESSING_ENGINE_BACKFILL) => {
+            http_server.backfill_processing_engine_trigger(req).await
+        }
         (Method::POST, all_paths::API_V3_CONFIGURE_PROCESSING_ENGINE_TRIGGER) => {
             http_server.configure_processing_engine_trigger(req).await
         }
         (Method::DELETE, all_paths::API_V3_CONFIGURE_PROCESSING_ENGINE_TRIGGER) => {
             http_server.delete_processing_engine_trigger(req).await
         }
+        (Method::PUT, path) if path.starts_with(all_paths::API_V3_CONFIGURE_PLUGIN_FILES) => {
+            let name = path
+                .strip_prefix(all_paths::API_V3_CONFIGURE_PLUGIN_FILES)
+                .unwrap();
+            http_server.upload_plugin_file(name, req).await
+        }
+        (Method::GET, path) if path.starts_with(all_paths::API_V3_CONFIGURE_PLUGIN_FILES) => {
+            let name = path
+                .strip_prefix(all_paths::API_V3_CONFIGURE_PLUGIN_FILES)
+                .unwrap();
+            http_server.list_plugin_file_versions(name).await
+        }
         (Method::POST, all_paths::API_V3_CONFIGURE_PLUGIN_INSTALL_PACKAGES) => {
             http_server.install_plugin_environment_packages(req).await
         }
@@ -1834,6 +2282,10 @@ pub(crate) async fn route_request(
                 .install_plugin_environment_requirements(req)
                 .await
         }
+        (Method::POST, all_paths::API_V3_CONFIGURE_SECRET) => http_server.create_secret(req).await,
+        (Method::DELETE, all_paths::API_V3_CONFIGURE_SECRET) => {
+            http_server.delete_secret(req).await
+        }
         (Method::GET, all_paths::API_V3_CONFIGURE_DATABASE) => {
             http_server.show_databases(req).await
         }
@@ -1853,6 +2305,9 @@ pub(crate) async fn route_request(
                 .test_processing_engine_schedule_plugin(req)
                 .await
         }
+        (Method::POST, all_paths::API_V3_TEST_REQUEST_ROUTE) => {
+            http_server.test_processing_engine_request_plugin(req).await
+        }
         _ => {
             let body = Body::from("not found");
             Ok(Response::builder()
@@ -1938,12 +2393,14 @@ mod tests {
 
     use super::QueryFormat;
     use super::ValidateDbNameError;
+    use super::chunk_record_batch_stream;
     use super::record_batch_stream_to_body;
     use super::token_part_as_bytes;
     use super::validate_db_name;
     use arrow_array::{Int32Array, RecordBatch, record_batch};
     use datafusion::execution::SendableRecordBatchStream;
     use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use futures::StreamExt;
     use hyper::body::to_bytes;
     use pretty_assertions::assert_eq;
     use std::str;
@@ -2198,6 +2655,68 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_chunked_json_output() {
+        let stream =
+            chunk_record_batch_stream(make_record_stream_with_sizes(vec![5, 0, 2]), Some(2));
+        let mut body = record_batch_stream_to_body(stream, QueryFormat::Json)
+            .await
+            .unwrap();
+        let mut chunks = vec![];
+        while let Some(chunk) = body.next().await {
+            chunks.push(String::from_utf8(chunk.unwrap().to_vec()).unwrap());
+        }
+        assert_eq!(
+            chunks,
+            [
+                "[{\"a\":1},{\"a\":1}",
+                ",{\"a\":1},{\"a\":1}",
+                ",{\"a\":1}",
+                ",{\"a\":1},{\"a\":1}",
+                "]"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chunked_csv_output() {
+        let stream = chunk_record_batch_stream(make_record_stream_with_sizes(vec![3, 4]), Some(3));
+        let mut body = record_batch_stream_to_body(stream, QueryFormat::Csv)
+            .await
+            .unwrap();
+        let mut chunks = vec![];
+        while let Some(chunk) = body.next().await {
+            chunks.push(String::from_utf8(chunk.unwrap().to_vec()).unwrap());
+        }
+        assert_eq!(chunks, ["a\n1\n1\n1\n", "1\n1\n1\n", "1\n"]);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_jsonl_output() {
+        let stream = chunk_record_batch_stream(make_record_stream(Some(1)), None);
+        let bytes = to_bytes(
+            record_batch_stream_to_body(stream, QueryFormat::JsonLines)
+                .await
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(str::from_utf8(bytes.as_ref()).unwrap(), "{\"a\":1}\n");
+
+        let stream = chunk_record_batch_stream(make_record_stream_with_sizes(vec![4]), Some(1));
+        let bytes = to_bytes(
+            record_batch_stream_to_body(stream, QueryFormat::JsonLines)
+                .await
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            str::from_utf8(bytes.as_ref()).unwrap(),
+            "{\"a\":1}\n{\"a\":1}\n{\"a\":1}\n{\"a\":1}\n"
+        );
+    }
+
     #[test]
     fn test_basic_auth_token_valid() {
         let token_bytes =
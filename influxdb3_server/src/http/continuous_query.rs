@@ -0,0 +1,88 @@
+//! Support for the InfluxQL `CREATE`, `SHOW`, and `DROP CONTINUOUS QUERY` statements
+//!
+//! Continuous queries are stored in the catalog, and run by the
+//! [`ContinuousQueryScheduler`][crate::continuous_query::ContinuousQueryScheduler].
+
+use std::sync::Arc;
+
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow_array::{ArrayRef, RecordBatch, StringArray};
+use datafusion::physical_plan::{SendableRecordBatchStream, stream::RecordBatchStreamAdapter};
+use iox_query_influxql_rewrite::continuous_query::ContinuousQueryStatement;
+use schema::INFLUXQL_MEASUREMENT_COLUMN_NAME;
+
+use super::{HttpApi, Result, validate_db_name};
+
+impl HttpApi {
+    /// Run a continuous query statement
+    ///
+    /// `CREATE` and `DROP` produce no rows, while `SHOW` produces a row for each continuous query,
+    /// with the database it belongs to as the measurement, so that the queries are grouped by
+    /// database in the v1 response, as they were in InfluxDB 1.x.
+    pub(super) async fn query_influxql_continuous_query(
+        &self,
+        query_str: &str,
+        statement: ContinuousQueryStatement,
+    ) -> Result<SendableRecordBatchStream> {
+        let catalog = self.write_buffer.catalog();
+        match statement {
+            ContinuousQueryStatement::Create(create) => {
+                validate_db_name(&create.database, false)?;
+                catalog
+                    .create_continuous_query(
+                        &create.database,
+                        &create.name,
+                        query_str.trim().trim_end_matches(';'),
+                        &create.select,
+                        create.interval,
+                        create.offset,
+                        create.resample_every,
+                        create.resample_for,
+                    )
+                    .await?;
+                Ok(empty_stream())
+            }
+            ContinuousQueryStatement::Drop { name, database } => {
+                catalog.delete_continuous_query(&database, &name).await?;
+                Ok(empty_stream())
+            }
+            ContinuousQueryStatement::Show => {
+                let mut databases = vec![];
+                let mut names = vec![];
+                let mut queries = vec![];
+                for db in catalog.list_db_schema() {
+                    if db.deleted {
+                        continue;
+                    }
+                    for continuous_query in db.continuous_queries.resource_iter() {
+                        databases.push(db.name.to_string());
+                        names.push(continuous_query.name.to_string());
+                        queries.push(continuous_query.query.clone());
+                    }
+                }
+                let schema = Arc::new(Schema::new(vec![
+                    Field::new(INFLUXQL_MEASUREMENT_COLUMN_NAME, DataType::Utf8, false),
+                    Field::new("name", DataType::Utf8, false),
+                    Field::new("query", DataType::Utf8, false),
+                ]));
+                let columns: Vec<ArrayRef> = vec![
+                    Arc::new(StringArray::from(databases)),
+                    Arc::new(StringArray::from(names)),
+                    Arc::new(StringArray::from(queries)),
+                ];
+                let batch = RecordBatch::try_new(Arc::clone(&schema), columns)?;
+                Ok(Box::pin(RecordBatchStreamAdapter::new(
+                    schema,
+                    futures::stream::iter([Ok(batch)]),
+                )))
+            }
+        }
+    }
+}
+
+fn empty_stream() -> SendableRecordBatchStream {
+    Box::pin(RecordBatchStreamAdapter::new(
+        Arc::new(Schema::empty()),
+        futures::stream::empty(),
+    ))
+}
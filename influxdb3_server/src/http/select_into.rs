@@ -0,0 +1,388 @@
+//! Support for InfluxQL `SELECT ... INTO` statements
+//!
+//! The query engine has no notion of an `INTO` clause, so the `SELECT` is run as normal, and the
+//! rows it produces are converted to line protocol and written back through the write buffer.
+//! Columns are mapped to tags and fields the same way that InfluxDB 1.x did it:
+//!
+//! * tags that are in the `GROUP BY` clause are written as tags
+//! * all other columns are written as fields, including any tags that were selected but not
+//!   grouped by
+//! * `NULL` values are omitted, and rows that have no fields are not written
+
+use std::{fmt::Write as _, sync::Arc};
+
+use anyhow::{Context as AnyhowContext, bail};
+use arrow::{
+    array::{AsArray, RecordBatch},
+    compute::{CastOptions, cast_with_options},
+    datatypes::{DataType, Field, Int64Type, Schema, TimeUnit},
+};
+use arrow_array::{ArrayRef, Int64Array, StringArray, TimestampNanosecondArray};
+use data_types::NamespaceName;
+use datafusion::physical_plan::{SendableRecordBatchStream, stream::RecordBatchStreamAdapter};
+use futures::TryStreamExt;
+use influxdb_influxql_parser::{select::GroupByClause, statement::Statement};
+use influxdb3_internal_api::query_executor::QueryExecutor;
+use influxdb3_telemetry::store::TelemetryStore;
+use influxdb3_write::{Precision, WriteBuffer};
+use iox_query_influxql_rewrite::{IntoClause, IntoMeasurement};
+use iox_query_params::StatementParams;
+use iox_time::Time;
+use schema::{INFLUXQL_MEASUREMENT_COLUMN_NAME, TIME_COLUMN_NAME};
+use serde_json::Value;
+use trace::ctx::SpanContext;
+
+use super::{
+    Error, HttpApi, Result,
+    v1::{ColumnMap, cast_column_value},
+    validate_db_name,
+};
+
+/// The name of the column that reports the number of points written by a `SELECT ... INTO`
+const WRITTEN_COLUMN_NAME: &str = "written";
+
+/// The size, in bytes, of line protocol that a `SELECT ... INTO` builds up before it is written
+const WRITE_CHUNK_SIZE: usize = 1024 * 1024;
+
+impl HttpApi {
+    /// Run an InfluxQL `SELECT ... INTO` statement, and write its results to the database, and
+    /// measurement, named in the `INTO` clause
+    ///
+    /// Like InfluxDB 1.x, the response is a single row reporting how many points were written.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) async fn query_influxql_select_into(
+        &self,
+        database: &str,
+        query_str: &str,
+        statement: Statement,
+        group_by: Option<GroupByClause>,
+        into: &IntoClause,
+        params: Option<StatementParams>,
+        span_ctx: Option<SpanContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let written = run_select_into(
+            &self.query_executor,
+            &self.write_buffer,
+            &self.common_state.telemetry_store,
+            self.time_provider.now(),
+            database,
+            query_str,
+            statement,
+            group_by,
+            into,
+            params,
+            span_ctx,
+        )
+        .await?;
+
+        let batch = written_batch(written)?;
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            batch.schema(),
+            futures::stream::iter([Ok(batch)]),
+        )))
+    }
+}
+
+/// Run the `SELECT` part of a `SELECT ... INTO` statement against `database`, and write the rows
+/// it produces to the target of the `INTO` clause, returning the number of points written
+///
+/// The rows are written in chunks as the query produces them, so if a chunk fails to write, the
+/// chunks before it have already been written.
+///
+/// This is shared by the HTTP API and the continuous query scheduler.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn run_select_into(
+    query_executor: &Arc<dyn QueryExecutor>,
+    write_buffer: &Arc<dyn WriteBuffer>,
+    telemetry_store: &TelemetryStore,
+    default_time: Time,
+    database: &str,
+    query_str: &str,
+    statement: Statement,
+    group_by: Option<GroupByClause>,
+    into: &IntoClause,
+    params: Option<StatementParams>,
+    span_ctx: Option<SpanContext>,
+) -> Result<usize> {
+    let target_db = into.resolve_dbrp(database);
+    validate_db_name(&target_db, true)?;
+
+    let mut stream = query_executor
+        .query_influxql(database, query_str, statement, params, span_ctx, None)
+        .await?;
+    let column_map = ColumnMap::new(stream.schema(), group_by).map_err(Error::SelectInto)?;
+    let target_db = NamespaceName::new(target_db)?;
+
+    // the rows are written as the batches come in, a chunk at a time, so that a large result is
+    // never held in memory in full
+    let mut lp = String::new();
+    let mut written = 0;
+    while let Some(batch) = stream.try_next().await? {
+        written += write_line_protocol(&mut lp, &batch, &column_map, into.measurement())
+            .map_err(Error::SelectInto)?;
+        if lp.len() >= WRITE_CHUNK_SIZE {
+            write_chunk(write_buffer, telemetry_store, &target_db, &lp, default_time).await?;
+            lp.clear();
+        }
+    }
+    if !lp.is_empty() {
+        write_chunk(write_buffer, telemetry_store, &target_db, &lp, default_time).await?;
+    }
+
+    Ok(written)
+}
+
+/// Write a chunk of the line protocol produced by a `SELECT ... INTO`
+async fn write_chunk(
+    write_buffer: &Arc<dyn WriteBuffer>,
+    telemetry_store: &TelemetryStore,
+    target_db: &NamespaceName<'static>,
+    lp: &str,
+    default_time: Time,
+) -> Result<()> {
+    let result = write_buffer
+        .write_lp(
+            target_db.clone(),
+            lp,
+            default_time,
+            false,
+            Precision::Nanosecond,
+            false,
+        )
+        .await?;
+    telemetry_store.add_write_metrics(result.line_count, lp.len());
+    if !result.invalid_lines.is_empty() {
+        return Err(Error::PartialLpWrite(result));
+    }
+    Ok(())
+}
+
+/// Produce the single row response to a `SELECT ... INTO`, which reports the number of points
+/// that were written
+fn written_batch(written: usize) -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(INFLUXQL_MEASUREMENT_COLUMN_NAME, DataType::Utf8, false),
+        Field::new(
+            TIME_COLUMN_NAME,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        ),
+        Field::new(WRITTEN_COLUMN_NAME, DataType::Int64, false),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(vec!["result"])),
+        Arc::new(TimestampNanosecondArray::from(vec![0])),
+        Arc::new(Int64Array::from(vec![written as i64])),
+    ];
+    RecordBatch::try_new(schema, columns).map_err(Into::into)
+}
+
+/// Append a line for each row in the `batch` to `lp`, returning the number of lines written
+fn write_line_protocol(
+    lp: &mut String,
+    batch: &RecordBatch,
+    column_map: &ColumnMap,
+    measurement: &IntoMeasurement,
+) -> Result<usize, anyhow::Error> {
+    let schema = batch.schema();
+    let time = batch
+        .column_by_name(TIME_COLUMN_NAME)
+        .context("query results do not have a time column")?;
+    let time = cast_with_options(time, &DataType::Int64, &CastOptions::default())
+        .context("failed to cast the time column")?;
+    let time = time.as_primitive::<Int64Type>();
+
+    let mut written = 0;
+    for row_index in 0..batch.num_rows() {
+        let mut tags = String::new();
+        let mut fields = String::new();
+        let mut source_measurement = None;
+        for (col_index, column) in batch.columns().iter().enumerate() {
+            let column_name = schema.field(col_index).name();
+            if column_name == TIME_COLUMN_NAME
+                || !column.is_valid(row_index)
+                || column_map.is_orphan_group_by_tag(column_name)
+            {
+                continue;
+            }
+            let value = cast_column_value(column, row_index)?;
+            if column_name == INFLUXQL_MEASUREMENT_COLUMN_NAME {
+                source_measurement = value.as_str().map(ToString::to_string);
+            } else if column_map.is_group_by_tag(column_name) {
+                let Value::String(tag_value) = value else {
+                    bail!("tag column {column_name} expected as a string, got {value:?}");
+                };
+                if !tag_value.is_empty() {
+                    write!(
+                        tags,
+                        ",{}={}",
+                        escape_key(column_name),
+                        escape_key(&tag_value)
+                    )?;
+                }
+            } else {
+                let field_value = field_value(column.data_type(), value)
+                    .with_context(|| format!("unsupported field type for column {column_name}"))?;
+                let separator = if fields.is_empty() { ' ' } else { ',' };
+                write!(
+                    fields,
+                    "{separator}{}={field_value}",
+                    escape_key(column_name)
+                )?;
+            }
+        }
+        if fields.is_empty() {
+            continue;
+        }
+        let measurement = match measurement {
+            IntoMeasurement::Name(name) => name.as_str(),
+            IntoMeasurement::Source => source_measurement
+                .as_deref()
+                .context("query results do not have a measurement for :MEASUREMENT")?,
+        };
+        writeln!(
+            lp,
+            "{}{tags}{fields} {}",
+            escape_measurement(measurement),
+            time.value(row_index)
+        )?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Format a field value as line protocol, based on the type of the column it came from
+fn field_value(data_type: &DataType, value: Value) -> Option<String> {
+    match (data_type, value) {
+        (
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64,
+            Value::Number(n),
+        ) => Some(format!("{n}i")),
+        (
+            DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64,
+            Value::Number(n),
+        ) => Some(format!("{n}u")),
+        (_, Value::Number(n)) => Some(n.to_string()),
+        (_, Value::Bool(b)) => Some(b.to_string()),
+        (_, Value::String(s)) => Some(format!(
+            "\"{}\"",
+            s.replace('\\', "\\\\").replace('"', "\\\"")
+        )),
+        _ => None,
+    }
+}
+
+fn escape_measurement(s: &str) -> String {
+    s.replace(',', "\\,").replace(' ', "\\ ")
+}
+
+/// Escape a tag key, tag value, or field key
+fn escape_key(s: &str) -> String {
+    s.replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+    use arrow_array::{
+        ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray,
+        TimestampNanosecondArray, UInt64Array,
+    };
+    use influxdb_influxql_parser::{select::GroupByClause, statement::Statement};
+    use iox_query_influxql_rewrite::parse_statements;
+    use schema::{INFLUXQL_MEASUREMENT_COLUMN_NAME, TIME_COLUMN_NAME};
+
+    use super::{ColumnMap, IntoMeasurement, write_line_protocol};
+
+    fn group_by(query: &str) -> Option<GroupByClause> {
+        match parse_statements(query)
+            .unwrap()
+            .pop()
+            .unwrap()
+            .to_statement()
+        {
+            Statement::Select(select) => select.group_by,
+            _ => panic!("expected a select statement"),
+        }
+    }
+
+    fn tag_field(name: &str) -> Field {
+        Field::new(name, DataType::Utf8, true).with_metadata(HashMap::from([(
+            "iox::column::type".to_string(),
+            "iox::column_type::tag".to_string(),
+        )]))
+    }
+
+    fn batch() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(INFLUXQL_MEASUREMENT_COLUMN_NAME, DataType::Utf8, false),
+            Field::new(
+                TIME_COLUMN_NAME,
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            tag_field("host"),
+            tag_field("region"),
+            Field::new("usage", DataType::Float64, true),
+            Field::new("count", DataType::Int64, true),
+            Field::new("total", DataType::UInt64, true),
+            Field::new("up", DataType::Boolean, true),
+            Field::new("note", DataType::Utf8, true),
+        ]));
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from(vec!["cpu", "cpu", "mem"])),
+            Arc::new(TimestampNanosecondArray::from(vec![10, 20, 30])),
+            Arc::new(StringArray::from(vec![Some("a b"), Some("b"), None])),
+            Arc::new(StringArray::from(vec![Some("us"), Some("eu"), None])),
+            Arc::new(Float64Array::from(vec![Some(1.5), None, None])),
+            Arc::new(Int64Array::from(vec![Some(1), Some(2), None])),
+            Arc::new(UInt64Array::from(vec![Some(3), None, None])),
+            Arc::new(BooleanArray::from(vec![Some(true), None, None])),
+            Arc::new(StringArray::from(vec![Some("say \"hi\""), None, None])),
+        ];
+        RecordBatch::try_new(schema, columns).unwrap()
+    }
+
+    #[test]
+    fn group_by_tags_are_written_as_tags() {
+        let batch = batch();
+        let column_map =
+            ColumnMap::new(batch.schema(), group_by("SELECT * FROM cpu GROUP BY host")).unwrap();
+        let mut lp = String::new();
+        let written = write_line_protocol(
+            &mut lp,
+            &batch,
+            &column_map,
+            &IntoMeasurement::Name("cpu_copy".into()),
+        )
+        .unwrap();
+        assert_eq!(2, written);
+        assert_eq!(
+            "cpu_copy,host=a\\ b region=\"us\",usage=1.5,count=1i,total=3u,up=true,\
+            note=\"say \\\"hi\\\"\" 10\n\
+            cpu_copy,host=b region=\"eu\",count=2i 20\n",
+            lp
+        );
+    }
+
+    #[test]
+    fn source_measurement() {
+        let batch = batch();
+        let column_map =
+            ColumnMap::new(batch.schema(), group_by("SELECT * FROM cpu GROUP BY *")).unwrap();
+        let mut lp = String::new();
+        let written =
+            write_line_protocol(&mut lp, &batch, &column_map, &IntoMeasurement::Source).unwrap();
+        // the row from `mem` has no fields, so it is not written:
+        assert_eq!(2, written);
+        assert_eq!(
+            "cpu,host=a\\ b,region=us usage=1.5,count=1i,total=3u,up=true,note=\"say \\\"hi\\\"\" 10\n\
+            cpu,host=b,region=eu count=2i 20\n",
+            lp
+        );
+    }
+}
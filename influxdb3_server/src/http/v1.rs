@@ -50,6 +50,7 @@ impl HttpApi {
         // determine the format from the request headers now because we need to consume req to get
         // the body:
         let mut format = QueryFormat::from_request(&req)?;
+        let bypass_cache = super::bypass_query_result_cache(&req);
         // now get the parameters provided in the body:
         let body = self.read_body(req).await?;
         let body_params = serde_urlencoded::from_bytes::<QueryParams>(&body)?;
@@ -73,7 +74,9 @@ impl HttpApi {
 
         // TODO - Currently not supporting parameterized queries, see
         //        https://github.com/influxdata/influxdb/issues/24805
-        let (stream, group_by) = self.query_influxql_inner(database, &query, None).await?;
+        let (stream, group_by) = self
+            .query_influxql_inner(database, &query, None, bypass_cache)
+            .await?;
         let stream = QueryResponseStream::new(0, stream, chunk_size, format, epoch, group_by)
             .map_err(QueryError)?;
         let body = Body::wrap_stream(stream);
@@ -762,7 +765,10 @@ fn convert_ns_epoch(value: Value, precision: Precision) -> Result<Value, anyhow:
 ///
 /// This function handles various Arrow data types, converting them into their corresponding
 /// JSON representations. For unsupported data types, it returns an error using the `anyhow` crate.
-fn cast_column_value(column: &ArrayRef, row_index: usize) -> Result<Value, anyhow::Error> {
+pub(super) fn cast_column_value(
+    column: &ArrayRef,
+    row_index: usize,
+) -> Result<Value, anyhow::Error> {
     let value = match column.data_type() {
         DataType::Boolean => Value::Bool(column.as_boolean().value(row_index)),
         DataType::Null => Value::Null,
@@ -862,7 +868,7 @@ fn cast_column_value(column: &ArrayRef, row_index: usize) -> Result<Value, anyho
 }
 
 /// Map column names to their respective [`ColumnType`]
-struct ColumnMap {
+pub(super) struct ColumnMap {
     /// The map of column names to column types
     map: HashMap<String, ColumnType>,
     /// How many columns are in the `values` set, i.e., that are not `GROUP BY` tags
@@ -890,7 +896,7 @@ enum ColumnType {
 
 impl ColumnMap {
     /// Create a new `ColumnMap`
-    fn new(
+    pub(super) fn new(
         schema: SchemaRef,
         group_by_clause: Option<GroupByClause>,
     ) -> Result<Self, anyhow::Error> {
@@ -949,14 +955,14 @@ impl ColumnMap {
     }
 
     /// This column is a `GROUP BY` tag
-    fn is_group_by_tag(&self, column_name: &str) -> bool {
+    pub(super) fn is_group_by_tag(&self, column_name: &str) -> bool {
         self.map
             .get(column_name)
             .is_some_and(|col| matches!(col, ColumnType::GroupByTag))
     }
 
     /// This column is an orphan `GROUP BY` tag
-    fn is_orphan_group_by_tag(&self, column_name: &str) -> bool {
+    pub(super) fn is_orphan_group_by_tag(&self, column_name: &str) -> bool {
         self.map
             .get(column_name)
             .is_some_and(|col| matches!(col, ColumnType::OrphanGroupByTag))
@@ -13,8 +13,10 @@ clippy::future_not_send
 
 pub mod all_paths;
 pub mod builder;
+mod continuous_query;
 mod grpc;
 mod http;
+pub mod query_cache;
 pub mod query_executor;
 mod query_planner;
 mod service;
@@ -904,6 +906,7 @@ mod tests {
             telemetry_store: Arc::clone(&sample_telem_store),
             sys_events_store: Arc::clone(&sys_events_store),
             started_with_auth: false,
+            query_result_cache: None,
         }));
 
         // bind to port 0 will assign a random available port:
@@ -918,6 +921,8 @@ mod tests {
                 plugin_dir: None,
                 virtual_env_location: None,
                 package_manager: Arc::new(DisabledManager),
+                cache_checkpoint_interval: None,
+                secrets_key: None,
             },
             write_buffer.catalog(),
             node_identifier_prefix,
@@ -925,6 +930,8 @@ mod tests {
             Arc::clone(&query_executor) as _,
             Arc::clone(&time_provider) as _,
             sys_events_store,
+            Arc::clone(&metrics),
+            shutdown_manager.register(),
         )
         .await;
 
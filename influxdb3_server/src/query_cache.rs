@@ -0,0 +1,1013 @@
+//! An opt-in cache for the results of queries made through the HTTP query APIs
+//!
+//! Entries are keyed on the database, the query language, the normalized query text, and the
+//! query parameters. Each entry records the tables that the query reads from, so that it can be
+//! invalidated when new data for any of those tables arrives through the WAL, or is persisted in a
+//! snapshot. Entries are also invalidated when the catalog changes, or when they exceed the
+//! configured time-to-live (TTL).
+//!
+//! The TTL bounds how stale the result for a query that is relative to the current time, e.g., one
+//! that uses `now()`, can be when there are no writes to the tables it reads from.
+
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap, HashSet},
+    pin::Pin,
+    sync::{Arc, Weak},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use arrow::record_batch::RecordBatch;
+use arrow_schema::SchemaRef;
+use async_trait::async_trait;
+use datafusion::{
+    error::DataFusionError,
+    execution::RecordBatchStream,
+    physical_plan::{SendableRecordBatchStream, stream::RecordBatchStreamAdapter},
+    sql::{
+        parser::{DFParser, Statement as DFStatement},
+        resolve::resolve_table_references,
+        sqlparser::ast::Statement as SqlStatement,
+    },
+};
+use futures::{Stream, StreamExt, ready};
+use influxdb_influxql_parser::{
+    select::{MeasurementName, MeasurementSelection, SelectStatement},
+    statement::Statement as InfluxQlStatement,
+};
+use influxdb3_catalog::catalog::{Catalog, CatalogSequenceNumber, DatabaseSchema};
+use influxdb3_id::{DbId, TableId};
+use influxdb3_wal::{SnapshotDetails, WalContents, WalFileNotifier, WalOp};
+use influxdb3_write::PersistedSnapshotVersion;
+use iox_query_params::StatementParams;
+use iox_time::{Time, TimeProvider};
+use observability_deps::tracing::{debug, error, info};
+use parking_lot::Mutex;
+use tokio::sync::{broadcast, oneshot};
+
+/// Configuration for the [`QueryResultCache`]
+#[derive(Debug, Clone, Copy)]
+pub struct QueryResultCacheConfig {
+    /// The maximum total size, in bytes, of the query results held in the cache
+    pub max_size_bytes: usize,
+    /// The duration after which an entry will no longer be served from the cache
+    pub ttl: Duration,
+}
+
+impl QueryResultCacheConfig {
+    /// The maximum size of a single result that will be cached, as a fraction of the total size
+    const MAX_ENTRY_FRACTION: usize = 10;
+
+    fn max_entry_size_bytes(&self) -> usize {
+        self.max_size_bytes / Self::MAX_ENTRY_FRACTION
+    }
+}
+
+/// The query language of a cached query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum QueryKind {
+    Sql,
+    InfluxQl,
+}
+
+impl QueryKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sql => "sql",
+            Self::InfluxQl => "influxql",
+        }
+    }
+}
+
+/// The key used to look up entries in the [`QueryResultCache`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    database: Arc<str>,
+    kind: QueryKind,
+    query: String,
+    params: String,
+}
+
+impl CacheKey {
+    pub(crate) fn new(
+        database: &str,
+        kind: QueryKind,
+        query: &str,
+        params: Option<&StatementParams>,
+    ) -> Self {
+        Self {
+            database: database.into(),
+            kind,
+            query: normalize_query(query),
+            params: params.map(normalize_params).unwrap_or_default(),
+        }
+    }
+}
+
+/// Normalize a query string so that queries differing only by whitespace outside of quoted strings
+/// or identifiers, or by a trailing `;`, map to the same cache entry.
+///
+/// A `--` comment runs to the end of its line, so it is kept as it is, along with the newline that
+/// ends it, as collapsing that newline would comment out the rest of the query.
+fn normalize_query(query: &str) -> String {
+    let mut normalized = String::with_capacity(query.len());
+    let mut quote: Option<char> = None;
+    let mut in_comment = false;
+    let mut pending_space = false;
+    let mut chars = query
+        .trim()
+        .trim_end_matches(';')
+        .trim_end()
+        .chars()
+        .peekable();
+    while let Some(c) = chars.next() {
+        if in_comment {
+            normalized.push(c);
+            in_comment = c != '\n';
+            continue;
+        }
+        match quote {
+            Some(q) => {
+                normalized.push(c);
+                if c == q {
+                    quote = None;
+                }
+            }
+            // the newline that ends a comment already separates it from what follows
+            None if c.is_whitespace() => pending_space = !normalized.ends_with('\n'),
+            None => {
+                if pending_space {
+                    normalized.push(' ');
+                    pending_space = false;
+                }
+                if matches!(c, '\'' | '"') {
+                    quote = Some(c);
+                } else if c == '-' && chars.peek() == Some(&'-') {
+                    in_comment = true;
+                }
+                normalized.push(c);
+            }
+        }
+    }
+    normalized
+}
+
+/// Serialize the query parameters with their names in sorted order
+fn normalize_params(params: &StatementParams) -> String {
+    match serde_json::to_value(params) {
+        Ok(serde_json::Value::Object(map)) => {
+            let sorted = map.into_iter().collect::<BTreeMap<_, _>>();
+            serde_json::to_string(&sorted).unwrap_or_default()
+        }
+        Ok(other) => other.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+/// The set of tables that a cached query reads from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CacheScope {
+    /// The query reads only from the given tables in the database
+    Tables {
+        db_id: DbId,
+        table_ids: HashSet<TableId>,
+    },
+    /// The query may read from any table in the database, e.g., an InfluxQL query that selects
+    /// measurements using a regex
+    Database { db_id: DbId },
+}
+
+impl CacheScope {
+    fn db_id(&self) -> DbId {
+        match self {
+            Self::Tables { db_id, .. } | Self::Database { db_id } => *db_id,
+        }
+    }
+
+    fn is_touched_by(&self, db_id: DbId, table_ids: &HashSet<TableId>) -> bool {
+        match self {
+            Self::Tables {
+                db_id: scope_db_id,
+                table_ids: scope_table_ids,
+            } => *scope_db_id == db_id && !scope_table_ids.is_disjoint(table_ids),
+            Self::Database { db_id: scope_db_id } => *scope_db_id == db_id,
+        }
+    }
+
+    fn table_names(&self, db_schema: &DatabaseSchema) -> Vec<Arc<str>> {
+        match self {
+            Self::Tables { table_ids, .. } => {
+                let mut names = table_ids
+                    .iter()
+                    .filter_map(|id| db_schema.table_id_to_name(id))
+                    .collect::<Vec<_>>();
+                names.sort();
+                names
+            }
+            Self::Database { .. } => vec![],
+        }
+    }
+}
+
+/// A cache of query results for queries made through the HTTP query APIs
+///
+/// See the module-level documentation for how entries are keyed and invalidated.
+#[derive(Debug)]
+pub struct QueryResultCache {
+    catalog: Arc<Catalog>,
+    time_provider: Arc<dyn TimeProvider>,
+    config: QueryResultCacheConfig,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, CacheEntry>,
+    size_bytes: usize,
+    /// A logical clock that is incremented for every invalidation
+    clock: u64,
+    /// The value of `clock` at the last invalidation of each database
+    db_invalidated_at: HashMap<DbId, u64>,
+    /// The value of `clock` at the last invalidation of each table
+    table_invalidated_at: HashMap<(DbId, TableId), u64>,
+    /// The value of `clock` at the last invalidation of every entry
+    all_invalidated_at: u64,
+    /// Counters, by database name
+    stats: HashMap<Arc<str>, CacheStats>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    schema: SchemaRef,
+    batches: Vec<RecordBatch>,
+    size_bytes: usize,
+    scope: CacheScope,
+    catalog_sequence: CatalogSequenceNumber,
+    created_at: Time,
+    last_accessed: Time,
+    hits: u64,
+}
+
+/// Counters for cache activity on a single database
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct CacheStats {
+    pub(crate) hits: u64,
+    pub(crate) misses: u64,
+    pub(crate) bypassed: u64,
+    pub(crate) inserts: u64,
+    pub(crate) invalidations: u64,
+    pub(crate) evictions: u64,
+}
+
+impl CacheStats {
+    pub(crate) fn hit_ratio(&self) -> Option<f64> {
+        let lookups = self.hits + self.misses;
+        (lookups > 0).then(|| self.hits as f64 / lookups as f64)
+    }
+}
+
+/// Summary of a single entry in the cache, used to populate the system table
+#[derive(Debug)]
+pub(crate) struct CacheEntrySummary {
+    pub(crate) query_type: &'static str,
+    pub(crate) query_text: String,
+    pub(crate) params: Option<String>,
+    pub(crate) tables: Vec<Arc<str>>,
+    pub(crate) size_bytes: usize,
+    pub(crate) hits: u64,
+    pub(crate) created_at: Time,
+    pub(crate) last_accessed: Time,
+}
+
+/// The outcome of checking the cache for a query, see [`QueryResultCache::lookup`]
+pub(crate) enum CacheLookup {
+    /// The query result was found in the cache
+    Hit(SendableRecordBatchStream),
+    /// The query result was not in the cache, but can be inserted once it has been produced
+    Miss(PendingFill),
+    /// The query result cannot be cached
+    Uncacheable,
+}
+
+impl std::fmt::Debug for CacheLookup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hit(_) => f.write_str("Hit"),
+            Self::Miss(fill) => f.debug_tuple("Miss").field(&fill.key).finish(),
+            Self::Uncacheable => f.write_str("Uncacheable"),
+        }
+    }
+}
+
+/// A query result that is to be inserted into the cache once the query completes
+///
+/// This captures the state of the cache before the query is executed, so that the result is not
+/// inserted if any of the tables it reads from are written to while the query is running.
+#[derive(Debug)]
+pub(crate) struct PendingFill {
+    cache: Arc<QueryResultCache>,
+    key: CacheKey,
+    scope: CacheScope,
+    catalog_sequence: CatalogSequenceNumber,
+    started_at: u64,
+}
+
+impl PendingFill {
+    /// Wrap the stream producing the query result, so that the result is inserted into the cache
+    /// once the stream completes successfully
+    pub(crate) fn wrap(self, stream: SendableRecordBatchStream) -> SendableRecordBatchStream {
+        Box::pin(CacheFillStream {
+            schema: stream.schema(),
+            inner: stream,
+            batches: vec![],
+            size_bytes: 0,
+            fill: Some(self),
+        })
+    }
+}
+
+impl QueryResultCache {
+    pub fn new(
+        catalog: Arc<Catalog>,
+        time_provider: Arc<dyn TimeProvider>,
+        config: QueryResultCacheConfig,
+    ) -> Arc<Self> {
+        info!(?config, "created query result cache");
+        Arc::new(Self {
+            catalog,
+            time_provider,
+            config,
+            state: Default::default(),
+        })
+    }
+
+    /// Spawn a background task that invalidates cache entries for tables that were persisted in
+    /// a snapshot
+    ///
+    /// The task will stop once the cache is dropped or the sender side of the channel is closed.
+    /// If it falls far enough behind to miss any snapshots, every entry is invalidated.
+    pub fn watch_persisted_snapshots(
+        self: &Arc<Self>,
+        mut persisted_snapshots: broadcast::Receiver<PersistedSnapshotVersion>,
+    ) {
+        let cache = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                let snapshot = persisted_snapshots.recv().await;
+                let Some(cache) = Weak::upgrade(&cache) else {
+                    break;
+                };
+                let snapshot = match snapshot {
+                    Ok(snapshot) => snapshot,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        error!(
+                            missed,
+                            "query result cache missed persisted snapshots, invalidating all entries"
+                        );
+                        cache.invalidate_all();
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let PersistedSnapshotVersion::V1(snapshot) = snapshot;
+                for (db_id, db_tables) in &snapshot.databases {
+                    cache.invalidate(*db_id, db_tables.tables.keys().copied().collect());
+                }
+            }
+        });
+    }
+
+    /// Record that a query on `database` skipped the cache at the request of the client
+    pub(crate) fn record_bypass(&self, database: &str) {
+        self.state.lock().stats_mut(database).bypassed += 1;
+    }
+
+    /// Check the cache for the result of a SQL query
+    pub(crate) fn lookup_sql(
+        self: &Arc<Self>,
+        database: &str,
+        query: &str,
+        params: Option<&StatementParams>,
+    ) -> CacheLookup {
+        let Some(db_schema) = self.catalog.db_schema(database) else {
+            return CacheLookup::Uncacheable;
+        };
+        let Some(scope) = sql_scope(&db_schema, query) else {
+            return CacheLookup::Uncacheable;
+        };
+        self.lookup(
+            CacheKey::new(database, QueryKind::Sql, query, params),
+            scope,
+        )
+    }
+
+    /// Check the cache for the result of an InfluxQL query
+    pub(crate) fn lookup_influxql(
+        self: &Arc<Self>,
+        database: &str,
+        query: &str,
+        statement: &InfluxQlStatement,
+        params: Option<&StatementParams>,
+    ) -> CacheLookup {
+        let Some(db_schema) = self.catalog.db_schema(database) else {
+            return CacheLookup::Uncacheable;
+        };
+        let Some(scope) = influxql_scope(&db_schema, statement) else {
+            return CacheLookup::Uncacheable;
+        };
+        self.lookup(
+            CacheKey::new(database, QueryKind::InfluxQl, query, params),
+            scope,
+        )
+    }
+
+    fn lookup(self: &Arc<Self>, key: CacheKey, scope: CacheScope) -> CacheLookup {
+        let now = self.time_provider.now();
+        let catalog_sequence = self.catalog.sequence_number();
+        let mut state = self.state.lock();
+        let fresh = state.entries.get(&key).map(|entry| {
+            entry.catalog_sequence == catalog_sequence
+                && now
+                    .checked_duration_since(entry.created_at)
+                    .is_some_and(|age| age < self.config.ttl)
+        });
+        match fresh {
+            Some(true) => {
+                let entry = state.entries.get_mut(&key).expect("entry exists");
+                entry.hits += 1;
+                entry.last_accessed = now;
+                let stream = futures::stream::iter(entry.batches.clone().into_iter().map(Ok));
+                let schema = Arc::clone(&entry.schema);
+                state.stats_mut(&key.database).hits += 1;
+                debug!(database = %key.database, query = %key.query, "query result cache hit");
+                CacheLookup::Hit(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+            }
+            Some(false) => {
+                state.remove(&key);
+                state.stats_mut(&key.database).misses += 1;
+                CacheLookup::Miss(self.pending_fill(&state, key, scope, catalog_sequence))
+            }
+            None => {
+                state.stats_mut(&key.database).misses += 1;
+                CacheLookup::Miss(self.pending_fill(&state, key, scope, catalog_sequence))
+            }
+        }
+    }
+
+    fn pending_fill(
+        self: &Arc<Self>,
+        state: &CacheState,
+        key: CacheKey,
+        scope: CacheScope,
+        catalog_sequence: CatalogSequenceNumber,
+    ) -> PendingFill {
+        PendingFill {
+            cache: Arc::clone(self),
+            key,
+            scope,
+            catalog_sequence,
+            started_at: state.clock,
+        }
+    }
+
+    fn insert(&self, fill: PendingFill, schema: SchemaRef, batches: Vec<RecordBatch>, size: usize) {
+        let PendingFill {
+            key,
+            scope,
+            catalog_sequence,
+            started_at,
+            ..
+        } = fill;
+        if size > self.config.max_entry_size_bytes()
+            || catalog_sequence != self.catalog.sequence_number()
+        {
+            return;
+        }
+        let now = self.time_provider.now();
+        let mut state = self.state.lock();
+        if state.invalidated_since(&scope, started_at) {
+            debug!(database = %key.database, query = %key.query, "query result invalidated before insert");
+            return;
+        }
+        state.remove(&key);
+        while !state.entries.is_empty() && state.size_bytes + size > self.config.max_size_bytes {
+            state.evict_least_recently_used();
+        }
+        state.size_bytes += size;
+        state.stats_mut(&key.database).inserts += 1;
+        state.entries.insert(
+            key,
+            CacheEntry {
+                schema,
+                batches,
+                size_bytes: size,
+                scope,
+                catalog_sequence,
+                created_at: now,
+                last_accessed: now,
+                hits: 0,
+            },
+        );
+    }
+
+    /// Invalidate all entries that read from any of the given tables in the database
+    fn invalidate(&self, db_id: DbId, table_ids: HashSet<TableId>) {
+        let mut state = self.state.lock();
+        state.clock += 1;
+        let clock = state.clock;
+        state.db_invalidated_at.insert(db_id, clock);
+        for table_id in &table_ids {
+            state.table_invalidated_at.insert((db_id, *table_id), clock);
+        }
+        let invalidated = state
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.scope.is_touched_by(db_id, &table_ids))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in invalidated {
+            state.remove(&key);
+            state.stats_mut(&key.database).invalidations += 1;
+        }
+    }
+
+    /// Invalidate every entry, for when the tables that changed are not known
+    fn invalidate_all(&self) {
+        let mut state = self.state.lock();
+        state.clock += 1;
+        state.all_invalidated_at = state.clock;
+        let invalidated = state.entries.keys().cloned().collect::<Vec<_>>();
+        for key in invalidated {
+            state.remove(&key);
+            state.stats_mut(&key.database).invalidations += 1;
+        }
+    }
+
+    /// Get the cache counters for the given database
+    pub(crate) fn stats(&self, database: &str) -> CacheStats {
+        self.state
+            .lock()
+            .stats
+            .get(database)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Get a summary of each entry in the cache for the given database
+    pub(crate) fn entries(&self, db_schema: &DatabaseSchema) -> Vec<CacheEntrySummary> {
+        let state = self.state.lock();
+        let mut entries = state
+            .entries
+            .iter()
+            .filter(|(key, entry)| {
+                key.database.as_ref() == db_schema.name.as_ref()
+                    && entry.scope.db_id() == db_schema.id
+            })
+            .map(|(key, entry)| CacheEntrySummary {
+                query_type: key.kind.as_str(),
+                query_text: key.query.clone(),
+                params: (!key.params.is_empty()).then(|| key.params.clone()),
+                tables: entry.scope.table_names(db_schema),
+                size_bytes: entry.size_bytes,
+                hits: entry.hits,
+                created_at: entry.created_at,
+                last_accessed: entry.last_accessed,
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        entries
+    }
+}
+
+impl CacheState {
+    fn stats_mut(&mut self, database: &str) -> &mut CacheStats {
+        if !self.stats.contains_key(database) {
+            self.stats.insert(database.into(), Default::default());
+        }
+        self.stats
+            .get_mut(database)
+            .expect("stats were just inserted")
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.size_bytes -= entry.size_bytes;
+        }
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        let Some(key) = self
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_accessed)
+            .map(|(key, _)| key.clone())
+        else {
+            return;
+        };
+        self.remove(&key);
+        self.stats_mut(&key.database).evictions += 1;
+    }
+
+    fn invalidated_since(&self, scope: &CacheScope, clock: u64) -> bool {
+        if self.all_invalidated_at > clock {
+            return true;
+        }
+        match scope {
+            CacheScope::Tables { db_id, table_ids } => table_ids.iter().any(|table_id| {
+                self.table_invalidated_at
+                    .get(&(*db_id, *table_id))
+                    .is_some_and(|at| *at > clock)
+            }),
+            CacheScope::Database { db_id } => self
+                .db_invalidated_at
+                .get(db_id)
+                .is_some_and(|at| *at > clock),
+        }
+    }
+}
+
+#[async_trait]
+impl WalFileNotifier for QueryResultCache {
+    async fn notify(&self, write: Arc<WalContents>) {
+        for op in &write.ops {
+            match op {
+                WalOp::Write(batch) => self.invalidate(
+                    batch.database_id,
+                    batch.table_chunks.keys().copied().collect(),
+                ),
+                WalOp::Noop(_) => (),
+            }
+        }
+    }
+
+    async fn notify_and_snapshot(
+        &self,
+        write: Arc<WalContents>,
+        snapshot_details: SnapshotDetails,
+    ) -> oneshot::Receiver<SnapshotDetails> {
+        self.notify(write).await;
+
+        // the cache does not take part in the snapshot, so respond immediately
+        let (tx, rx) = oneshot::channel();
+        tx.send(snapshot_details).ok();
+        rx
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Determine the tables that a SQL query reads from
+///
+/// Only single `SELECT` queries on tables in the database are cached; queries that read from the
+/// `system` or `information_schema` tables, or from table functions such as `last_cache`, are not.
+fn sql_scope(db_schema: &DatabaseSchema, query: &str) -> Option<CacheScope> {
+    let mut statements = DFParser::parse_sql(query).ok()?;
+    if statements.len() != 1 {
+        return None;
+    }
+    let statement = statements.pop_front()?;
+    match &statement {
+        DFStatement::Statement(s) if matches!(s.as_ref(), SqlStatement::Query(_)) => (),
+        _ => return None,
+    }
+    let (table_refs, _ctes) = resolve_table_references(&statement, true).ok()?;
+    let table_ids = table_refs
+        .iter()
+        .map(|table_ref| match table_ref.schema() {
+            None | Some("iox") => db_schema.table_name_to_id(table_ref.table()),
+            Some(_) => None,
+        })
+        .collect::<Option<HashSet<_>>>()?;
+    Some(CacheScope::Tables {
+        db_id: db_schema.id,
+        table_ids,
+    })
+}
+
+/// Determine the tables that an InfluxQL query reads from
+///
+/// Only `SELECT` statements are cached. Those that select measurements with a regex may read from
+/// any table in the database, and are scoped to the whole database.
+fn influxql_scope(db_schema: &DatabaseSchema, statement: &InfluxQlStatement) -> Option<CacheScope> {
+    fn collect_measurements(
+        db_schema: &DatabaseSchema,
+        select: &SelectStatement,
+        table_ids: &mut HashSet<TableId>,
+    ) -> Option<bool> {
+        let mut any_table = false;
+        for selection in select.from.iter() {
+            match selection {
+                MeasurementSelection::Name(qn) => match &qn.name {
+                    MeasurementName::Name(name) => {
+                        table_ids.insert(db_schema.table_name_to_id(&**name)?);
+                    }
+                    MeasurementName::Regex(_) => any_table = true,
+                },
+                MeasurementSelection::Subquery(subquery) => {
+                    any_table |= collect_measurements(db_schema, subquery, table_ids)?;
+                }
+            }
+        }
+        Some(any_table)
+    }
+
+    let InfluxQlStatement::Select(select) = statement else {
+        return None;
+    };
+    let mut table_ids = HashSet::new();
+    if collect_measurements(db_schema, select, &mut table_ids)? {
+        Some(CacheScope::Database {
+            db_id: db_schema.id,
+        })
+    } else {
+        Some(CacheScope::Tables {
+            db_id: db_schema.id,
+            table_ids,
+        })
+    }
+}
+
+/// A stream that collects the [`RecordBatch`]es that pass through it, and inserts them into the
+/// cache once the stream has completed
+///
+/// Collection is abandoned if the stream produces an error, or the result grows larger than the
+/// maximum size of a cache entry.
+struct CacheFillStream {
+    schema: SchemaRef,
+    inner: SendableRecordBatchStream,
+    batches: Vec<RecordBatch>,
+    size_bytes: usize,
+    fill: Option<PendingFill>,
+}
+
+impl Stream for CacheFillStream {
+    type Item = Result<RecordBatch, DataFusionError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        match ready!(this.inner.poll_next_unpin(cx)) {
+            Some(Ok(batch)) => {
+                if let Some(fill) = &this.fill {
+                    this.size_bytes += batch.get_array_memory_size();
+                    if this.size_bytes > fill.cache.config.max_entry_size_bytes() {
+                        this.fill = None;
+                        this.batches = vec![];
+                    } else {
+                        this.batches.push(batch.clone());
+                    }
+                }
+                Poll::Ready(Some(Ok(batch)))
+            }
+            Some(Err(e)) => {
+                this.fill = None;
+                this.batches = vec![];
+                Poll::Ready(Some(Err(e)))
+            }
+            None => {
+                if let Some(fill) = this.fill.take() {
+                    let cache = Arc::clone(&fill.cache);
+                    cache.insert(
+                        fill,
+                        Arc::clone(&this.schema),
+                        std::mem::take(&mut this.batches),
+                        this.size_bytes,
+                    );
+                }
+                Poll::Ready(None)
+            }
+        }
+    }
+}
+
+impl RecordBatchStream for CacheFillStream {
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Int64Array, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use influxdb3_catalog::log::FieldDataType;
+    use iox_time::MockProvider;
+
+    async fn setup(ttl: Duration) -> (Arc<QueryResultCache>, Arc<MockProvider>) {
+        let catalog = Arc::new(Catalog::new_in_memory("test-catalog").await.unwrap());
+        catalog.create_database("foo").await.unwrap();
+        for table in ["cpu", "mem"] {
+            catalog
+                .create_table(
+                    "foo",
+                    table,
+                    &["host"],
+                    &[("usage", FieldDataType::Integer)],
+                )
+                .await
+                .unwrap();
+        }
+        let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+        let cache = QueryResultCache::new(
+            catalog,
+            Arc::clone(&time_provider) as _,
+            QueryResultCacheConfig {
+                max_size_bytes: 1024 * 1024,
+                ttl,
+            },
+        );
+        (cache, time_provider)
+    }
+
+    fn batch() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("host", DataType::Utf8, false),
+            Field::new("usage", DataType::Int64, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(Int64Array::from(vec![1, 2])),
+            ],
+        )
+        .unwrap()
+    }
+
+    /// Run a cache miss through to completion so that its result gets inserted
+    async fn fill(lookup: CacheLookup) {
+        let CacheLookup::Miss(pending) = lookup else {
+            panic!("expected a cache miss, got: {lookup:?}");
+        };
+        let batch = batch();
+        let stream = Box::pin(RecordBatchStreamAdapter::new(
+            batch.schema(),
+            futures::stream::iter(vec![Ok(batch)]),
+        ));
+        let batches = pending
+            .wrap(stream)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(1, batches.len());
+    }
+
+    async fn assert_hit(lookup: CacheLookup) {
+        let CacheLookup::Hit(stream) = lookup else {
+            panic!("expected a cache hit, got: {lookup:?}");
+        };
+        let batches = stream
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(vec![batch()], batches);
+    }
+
+    fn table_id(cache: &QueryResultCache, table: &str) -> (DbId, TableId) {
+        let db_schema = cache.catalog.db_schema("foo").unwrap();
+        (db_schema.id, db_schema.table_name_to_id(table).unwrap())
+    }
+
+    #[test]
+    fn normalize_query_text() {
+        assert_eq!(
+            "SELECT * FROM cpu WHERE host = 'a  b'",
+            normalize_query("  SELECT *\n  FROM   cpu\tWHERE host = 'a  b' ;\n")
+        );
+        assert_eq!(
+            normalize_query("select \"my  col\" from cpu"),
+            normalize_query("select   \"my  col\"   from cpu;")
+        );
+        assert_ne!(
+            normalize_query("select \"my  col\" from cpu"),
+            normalize_query("select \"my col\" from cpu")
+        );
+        assert_eq!(
+            "SELECT 1 -- x\nFROM t",
+            normalize_query("SELECT 1  -- x\n  FROM   t")
+        );
+        assert_ne!(
+            normalize_query("SELECT 1 -- x\nFROM t"),
+            normalize_query("SELECT 1 -- x FROM t")
+        );
+    }
+
+    #[tokio::test]
+    async fn sql_scope_excludes_system_tables_and_non_queries() {
+        let (cache, _) = setup(Duration::from_secs(60)).await;
+        let db_schema = cache.catalog.db_schema("foo").unwrap();
+        let (db_id, cpu_id) = table_id(&cache, "cpu");
+        let (_, mem_id) = table_id(&cache, "mem");
+        assert_eq!(
+            Some(CacheScope::Tables {
+                db_id,
+                table_ids: [cpu_id, mem_id].into_iter().collect(),
+            }),
+            sql_scope(
+                &db_schema,
+                "SELECT * FROM cpu JOIN mem ON cpu.host = mem.host"
+            )
+        );
+        assert_eq!(None, sql_scope(&db_schema, "SELECT * FROM system.queries"));
+        assert_eq!(None, sql_scope(&db_schema, "SELECT * FROM not_a_table"));
+        assert_eq!(None, sql_scope(&db_schema, "SHOW TABLES"));
+        assert_eq!(None, sql_scope(&db_schema, "SELECT 1; SELECT 2"));
+    }
+
+    #[tokio::test]
+    async fn hit_after_fill_and_invalidate_on_write() {
+        let (cache, _) = setup(Duration::from_secs(60)).await;
+        let (db_id, cpu_id) = table_id(&cache, "cpu");
+        let (_, mem_id) = table_id(&cache, "mem");
+        fill(cache.lookup_sql("foo", "SELECT * FROM cpu", None)).await;
+        assert_hit(cache.lookup_sql("foo", "SELECT *  FROM cpu;", None)).await;
+
+        // a write to another table does not affect the entry:
+        cache.invalidate(db_id, [mem_id].into_iter().collect());
+        assert_hit(cache.lookup_sql("foo", "SELECT * FROM cpu", None)).await;
+
+        // a write to the table that was queried does:
+        cache.invalidate(db_id, [cpu_id].into_iter().collect());
+        assert!(matches!(
+            cache.lookup_sql("foo", "SELECT * FROM cpu", None),
+            CacheLookup::Miss(_)
+        ));
+
+        let stats = cache.stats("foo");
+        assert_eq!(2, stats.hits);
+        assert_eq!(2, stats.misses);
+        assert_eq!(1, stats.inserts);
+        assert_eq!(1, stats.invalidations);
+    }
+
+    #[tokio::test]
+    async fn entries_expire_after_ttl() {
+        let (cache, time_provider) = setup(Duration::from_secs(10)).await;
+        fill(cache.lookup_sql("foo", "SELECT * FROM cpu", None)).await;
+        time_provider.set(Time::from_timestamp_nanos(5_000_000_000));
+        assert_hit(cache.lookup_sql("foo", "SELECT * FROM cpu", None)).await;
+        time_provider.set(Time::from_timestamp_nanos(10_000_000_000));
+        assert!(matches!(
+            cache.lookup_sql("foo", "SELECT * FROM cpu", None),
+            CacheLookup::Miss(_)
+        ));
+        assert!(
+            cache
+                .entries(&cache.catalog.db_schema("foo").unwrap())
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn write_during_fill_prevents_insert() {
+        let (cache, _) = setup(Duration::from_secs(60)).await;
+        let (db_id, cpu_id) = table_id(&cache, "cpu");
+        let lookup = cache.lookup_sql("foo", "SELECT * FROM cpu", None);
+        cache.invalidate(db_id, [cpu_id].into_iter().collect());
+        fill(lookup).await;
+        assert!(matches!(
+            cache.lookup_sql("foo", "SELECT * FROM cpu", None),
+            CacheLookup::Miss(_)
+        ));
+        assert_eq!(0, cache.stats("foo").inserts);
+    }
+
+    #[tokio::test]
+    async fn missed_snapshots_invalidate_all_entries() {
+        let (cache, _) = setup(Duration::from_secs(60)).await;
+        fill(cache.lookup_sql("foo", "SELECT * FROM cpu", None)).await;
+
+        // neither snapshot persisted cpu, but the first is missed, so it could have:
+        let (tx, rx) = broadcast::channel(1);
+        for n in 0..2 {
+            tx.send(PersistedSnapshotVersion::V1(
+                influxdb3_write::PersistedSnapshot::new(
+                    "test".to_string(),
+                    influxdb3_wal::SnapshotSequenceNumber::new(n),
+                    influxdb3_wal::WalFileSequenceNumber::new(n),
+                    CatalogSequenceNumber::new(0),
+                ),
+            ))
+            .unwrap();
+        }
+        cache.watch_persisted_snapshots(rx);
+        for _ in 0..100 {
+            if cache.stats("foo").invalidations > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(1, cache.stats("foo").invalidations);
+        assert!(matches!(
+            cache.lookup_sql("foo", "SELECT * FROM cpu", None),
+            CacheLookup::Miss(_)
+        ));
+    }
+}
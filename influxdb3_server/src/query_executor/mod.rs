@@ -1,4 +1,6 @@
 //! module for query executor
+use crate::query_cache::QueryResultCache;
+use crate::query_executor::prepared::{PreparedSql, PreparedStatements};
 use crate::system_tables::{SYSTEM_SCHEMA_NAME, SystemSchemaProvider};
 use crate::{query_planner::Planner, system_tables::AllSystemSchemaTablesProvider};
 use arrow::array::{ArrayRef, Int64Builder, StringBuilder, StructArray};
@@ -13,8 +15,9 @@ use datafusion::common::arrow::datatypes::{DataType, Field, Schema as Datafusion
 use datafusion::datasource::{TableProvider, TableType};
 use datafusion::error::DataFusionError;
 use datafusion::execution::SendableRecordBatchStream;
-use datafusion::logical_expr::TableProviderFilterPushDown;
+use datafusion::logical_expr::{LogicalPlan, TableProviderFilterPushDown};
 use datafusion::physical_plan::ExecutionPlan;
+use datafusion::physical_plan::empty::EmptyExec;
 use datafusion::prelude::Expr;
 use datafusion_util::MemoryStream;
 use datafusion_util::config::DEFAULT_SCHEMA;
@@ -22,7 +25,9 @@ use influxdb_influxql_parser::statement::Statement;
 use influxdb3_cache::distinct_cache::{DISTINCT_CACHE_UDTF_NAME, DistinctCacheFunction};
 use influxdb3_cache::last_cache::{LAST_CACHE_UDTF_NAME, LastCacheFunction};
 use influxdb3_catalog::catalog::{Catalog, DatabaseSchema, TableDefinition};
-use influxdb3_internal_api::query_executor::{QueryExecutor, QueryExecutorError};
+use influxdb3_internal_api::query_executor::{
+    PreparedStatement, QueryExecutor, QueryExecutorError,
+};
 use influxdb3_sys_events::SysEventStore;
 use influxdb3_telemetry::store::TelemetryStore;
 use influxdb3_write::{ChunkFilter, WriteBuffer};
@@ -50,6 +55,8 @@ use tracker::{
     AsyncSemaphoreMetrics, InstrumentedAsyncOwnedSemaphorePermit, InstrumentedAsyncSemaphore,
 };
 
+mod prepared;
+
 #[derive(Debug, Clone)]
 pub struct QueryExecutorImpl {
     catalog: Arc<Catalog>,
@@ -61,6 +68,8 @@ pub struct QueryExecutorImpl {
     telemetry_store: Arc<TelemetryStore>,
     sys_events_store: Arc<SysEventStore>,
     started_with_auth: bool,
+    query_result_cache: Option<Arc<QueryResultCache>>,
+    prepared_statements: Arc<PreparedStatements>,
 }
 
 /// Arguments for [`QueryExecutorImpl::new`]
@@ -75,6 +84,7 @@ pub struct CreateQueryExecutorArgs {
     pub telemetry_store: Arc<TelemetryStore>,
     pub sys_events_store: Arc<SysEventStore>,
     pub started_with_auth: bool,
+    pub query_result_cache: Option<Arc<QueryResultCache>>,
 }
 
 impl QueryExecutorImpl {
@@ -89,6 +99,7 @@ impl QueryExecutorImpl {
             telemetry_store,
             sys_events_store,
             started_with_auth,
+            query_result_cache,
         }: CreateQueryExecutorArgs,
     ) -> Self {
         let semaphore_metrics = Arc::new(AsyncSemaphoreMetrics::new(
@@ -111,6 +122,8 @@ impl QueryExecutorImpl {
             telemetry_store,
             sys_events_store,
             started_with_auth,
+            query_result_cache,
+            prepared_statements: Default::default(),
         }
     }
 }
@@ -167,6 +180,115 @@ impl QueryExecutor for QueryExecutorImpl {
         .await
     }
 
+    async fn prepare_sql(
+        &self,
+        database: &str,
+        query: &str,
+        span_ctx: Option<SpanContext>,
+    ) -> Result<PreparedStatement, QueryExecutorError> {
+        info!(%database, %query, "preparing sql query");
+        let db_schema = self.catalog.db_schema(database).ok_or_else(|| {
+            QueryExecutorError::DatabaseNotFound {
+                db_name: database.to_string(),
+            }
+        })?;
+        let catalog_sequence = self.catalog.sequence_number();
+        let db = self.get_db_namespace(database, &span_ctx).await?;
+        let logical_plan = plan_sql_logical(db, query, span_ctx).await?;
+
+        let prepared =
+            PreparedSql::new(database, query, logical_plan, &db_schema, catalog_sequence);
+        let parameters = prepared
+            .parameters()
+            .map_err(QueryExecutorError::QueryPlanning)?;
+        let id = self.prepared_statements.insert(prepared);
+
+        Ok(PreparedStatement { id, parameters })
+    }
+
+    async fn query_prepared(
+        &self,
+        statement_id: u64,
+        params: Option<StatementParams>,
+        span_ctx: Option<SpanContext>,
+        external_span_ctx: Option<RequestLogContext>,
+    ) -> Result<SendableRecordBatchStream, QueryExecutorError> {
+        let prepared = self
+            .prepared_statements
+            .get(statement_id)
+            .ok_or(QueryExecutorError::PreparedStatementNotFound { statement_id })?;
+        let database = prepared.database.as_str();
+        info!(%database, query = %prepared.query, ?params, "executing prepared sql query");
+
+        let db_schema = self.catalog.db_schema(database).ok_or_else(|| {
+            QueryExecutorError::DatabaseNotFound {
+                db_name: database.to_string(),
+            }
+        })?;
+        let catalog_sequence = self.catalog.sequence_number();
+        let db = self.get_db_namespace(database, &span_ctx).await?;
+        let logical_plan = match prepared.cached_plan(&db_schema, catalog_sequence) {
+            Some(logical_plan) => logical_plan,
+            None => {
+                debug!(
+                    statement_id,
+                    "re-planning prepared statement after catalog change"
+                );
+                let logical_plan =
+                    plan_sql_logical(Arc::clone(&db), &prepared.query, span_ctx.clone()).await?;
+                prepared.replace_plan(logical_plan.clone(), &db_schema, catalog_sequence);
+                logical_plan
+            }
+        };
+
+        query_database_prepared(
+            db,
+            &prepared.query,
+            logical_plan,
+            params,
+            span_ctx,
+            external_span_ctx,
+            Arc::clone(&self.telemetry_store),
+        )
+        .await
+    }
+
+    fn close_prepared(&self, statement_id: u64) -> Result<(), QueryExecutorError> {
+        if self.prepared_statements.remove(statement_id) {
+            Ok(())
+        } else {
+            Err(QueryExecutorError::PreparedStatementNotFound { statement_id })
+        }
+    }
+
+    async fn record_cached_query(
+        &self,
+        database: &str,
+        query_type: &'static str,
+        query: &str,
+        params: Option<StatementParams>,
+        schema: SchemaRef,
+        span_ctx: Option<SpanContext>,
+        external_span_ctx: Option<RequestLogContext>,
+    ) -> Result<(), QueryExecutorError> {
+        info!(%database, %query, ?params, "served query from the query result cache");
+        let db = self.get_db_namespace(database, &span_ctx).await?;
+        let token = db.record_query(
+            external_span_ctx.as_ref().map(RequestLogContext::ctx),
+            query_type,
+            Box::new(query.to_string()),
+            params.unwrap_or_default(),
+        );
+
+        // nothing is planned or executed for a cached result, so it is logged with an empty plan
+        let ctx = db.new_query_context(span_ctx, Default::default());
+        let plan: Arc<dyn ExecutionPlan> = Arc::new(EmptyExec::new(schema));
+        token.planned(&ctx, plan).permit().success();
+
+        self.telemetry_store.update_num_queries();
+        Ok(())
+    }
+
     fn show_databases(
         &self,
         include_deleted: bool,
@@ -310,6 +432,71 @@ async fn query_database_sql(
     }
 }
 
+/// Produce the logical plan for a SQL query that is being prepared, see
+/// [`QueryExecutor::prepare_sql`]
+async fn plan_sql_logical(
+    db: Arc<dyn QueryNamespace>,
+    query: &str,
+    span_ctx: Option<SpanContext>,
+) -> Result<LogicalPlan, QueryExecutorError> {
+    let ctx = db.new_query_context(span_ctx, Default::default());
+    let planner = Planner::new(&ctx);
+    let query = query.to_string();
+
+    ctx.run(async move { planner.sql_logical(query).await })
+        .await
+        .map_err(QueryExecutorError::QueryPlanning)
+}
+
+/// Execute a prepared SQL query, using its cached `logical_plan`
+async fn query_database_prepared(
+    db: Arc<dyn QueryNamespace>,
+    query: &str,
+    logical_plan: LogicalPlan,
+    params: Option<StatementParams>,
+    span_ctx: Option<SpanContext>,
+    external_span_ctx: Option<RequestLogContext>,
+    telemetry_store: Arc<TelemetryStore>,
+) -> Result<SendableRecordBatchStream, QueryExecutorError> {
+    let params = params.unwrap_or_default();
+
+    let token = db.record_query(
+        external_span_ctx.as_ref().map(RequestLogContext::ctx),
+        "sql",
+        Box::new(query.to_string()),
+        params.clone(),
+    );
+
+    let ctx = db.new_query_context(span_ctx, Default::default());
+    let planner = Planner::new(&ctx);
+    let plan = ctx
+        .run(async move { planner.sql_prepared(logical_plan, params).await })
+        .await;
+
+    let plan = match plan.map_err(QueryExecutorError::QueryPlanning) {
+        Ok(plan) => plan,
+        Err(e) => {
+            token.fail();
+            return Err(e);
+        }
+    };
+    let token = token.planned(&ctx, Arc::clone(&plan));
+    let token = token.permit();
+
+    telemetry_store.update_num_queries();
+
+    match ctx.execute_stream(Arc::clone(&plan)).await {
+        Ok(query_results) => {
+            token.success();
+            Ok(query_results)
+        }
+        Err(err) => {
+            token.fail();
+            Err(QueryExecutorError::ExecuteStream(err))
+        }
+    }
+}
+
 async fn query_database_influxql(
     db: Arc<dyn QueryNamespace>,
     query_str: &str,
@@ -446,6 +633,7 @@ impl QueryDatabase for QueryExecutorImpl {
                 Arc::clone(&self.sys_events_store),
                 Arc::clone(&self.write_buffer.catalog()),
                 self.started_with_auth,
+                self.query_result_cache.clone(),
             ),
         ));
         Ok(Some(Arc::new(Database::new(CreateDatabaseArgs {
@@ -757,7 +945,7 @@ mod tests {
         parquet_cache::test_cached_obj_store_and_oracle,
     };
     use influxdb3_catalog::catalog::Catalog;
-    use influxdb3_internal_api::query_executor::QueryExecutor;
+    use influxdb3_internal_api::query_executor::{QueryExecutor, QueryExecutorError};
     use influxdb3_shutdown::ShutdownManager;
     use influxdb3_sys_events::SysEventStore;
     use influxdb3_telemetry::store::TelemetryStore;
@@ -886,6 +1074,7 @@ mod tests {
             telemetry_store,
             sys_events_store: Arc::clone(&sys_events_store),
             started_with_auth,
+            query_result_cache: None,
         });
 
         (
@@ -1363,4 +1552,109 @@ mod tests {
             );
         }
     }
+
+    #[test_log::test(tokio::test)]
+    async fn prepared_statement_lifecycle() {
+        let (write_buffer, query_executor, _, _) = setup(None, true).await;
+        let query_executor = Arc::new(query_executor);
+        let db_name = "test_db";
+        let write = |lp: &'static str| {
+            let write_buffer = Arc::clone(&write_buffer);
+            async move {
+                write_buffer
+                    .write_lp(
+                        NamespaceName::new(db_name).unwrap(),
+                        lp,
+                        Time::from_timestamp_nanos(0),
+                        false,
+                        influxdb3_write::Precision::Nanosecond,
+                        false,
+                    )
+                    .await
+                    .unwrap();
+            }
+        };
+        write("cpu,host=a usage=1 1\ncpu,host=b usage=2 2\nmem,host=a free=3 3").await;
+
+        let prepared = query_executor
+            .prepare_sql(
+                db_name,
+                "SELECT host, usage FROM cpu WHERE host = $host",
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(vec!["host".to_string()], prepared.parameters);
+        let statement_id = prepared.id;
+
+        let query = |host: &str| {
+            let params = serde_json::from_value(serde_json::json!({ "host": host })).unwrap();
+            let query_executor = Arc::clone(&query_executor);
+            async move {
+                let stream = query_executor
+                    .query_prepared(statement_id, Some(params), None, None)
+                    .await
+                    .unwrap();
+                stream.try_collect::<Vec<RecordBatch>>().await.unwrap()
+            }
+        };
+        assert_batches_sorted_eq!(
+            [
+                "+------+-------+",
+                "| host | usage |",
+                "+------+-------+",
+                "| a    | 1.0   |",
+                "+------+-------+",
+            ],
+            &query("a").await
+        );
+        assert_batches_sorted_eq!(
+            [
+                "+------+-------+",
+                "| host | usage |",
+                "+------+-------+",
+                "| b    | 2.0   |",
+                "+------+-------+",
+            ],
+            &query("b").await
+        );
+
+        // changing another table in the catalog does not invalidate the plan, but new rows in the
+        // referenced table are still returned:
+        write("mem,host=a free=3,used=4 4\ncpu,host=a usage=5 5").await;
+        assert_batches_sorted_eq!(
+            [
+                "+------+-------+",
+                "| host | usage |",
+                "+------+-------+",
+                "| a    | 1.0   |",
+                "| a    | 5.0   |",
+                "+------+-------+",
+            ],
+            &query("a").await
+        );
+
+        // adding a column to the referenced table causes the statement to be re-planned:
+        write("cpu,host=a,region=us usage=6 6").await;
+        assert_batches_sorted_eq!(
+            [
+                "+------+-------+",
+                "| host | usage |",
+                "+------+-------+",
+                "| a    | 1.0   |",
+                "| a    | 5.0   |",
+                "| a    | 6.0   |",
+                "+------+-------+",
+            ],
+            &query("a").await
+        );
+
+        query_executor.close_prepared(statement_id).unwrap();
+        assert!(matches!(
+            query_executor
+                .query_prepared(statement_id, None, None, None)
+                .await,
+            Err(QueryExecutorError::PreparedStatementNotFound { .. })
+        ));
+    }
 }
@@ -0,0 +1,174 @@
+//! Storage for SQL statements prepared with [`QueryExecutor::prepare_sql`]
+//!
+//! Each prepared statement holds on to the logical plan that was produced when it was prepared,
+//! so that executing it only requires the parameters to be substituted and a physical plan to be
+//! created. The logical plan is re-used for as long as the definitions of the tables that it
+//! reads from are unchanged in the catalog.
+//!
+//! [`QueryExecutor::prepare_sql`]: influxdb3_internal_api::query_executor::QueryExecutor::prepare_sql
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use datafusion::{
+    common::tree_node::TreeNodeRecursion, error::DataFusionError, logical_expr::LogicalPlan,
+};
+use influxdb3_catalog::catalog::{CatalogSequenceNumber, DatabaseSchema, TableDefinition};
+use influxdb3_id::DbId;
+use parking_lot::Mutex;
+
+/// The maximum number of prepared statements that are held at once. Once reached, the oldest
+/// statement is closed to make room for a new one.
+const MAX_PREPARED_STATEMENTS: usize = 1_000;
+
+#[derive(Debug, Default)]
+pub(super) struct PreparedStatements {
+    next_id: AtomicU64,
+    statements: Mutex<BTreeMap<u64, Arc<PreparedSql>>>,
+}
+
+impl PreparedStatements {
+    /// Store a prepared statement and return the id used to refer to it
+    pub(super) fn insert(&self, statement: PreparedSql) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut statements = self.statements.lock();
+        while statements.len() >= MAX_PREPARED_STATEMENTS {
+            statements.pop_first();
+        }
+        statements.insert(id, Arc::new(statement));
+        id
+    }
+
+    pub(super) fn get(&self, id: u64) -> Option<Arc<PreparedSql>> {
+        self.statements.lock().get(&id).cloned()
+    }
+
+    /// Remove a prepared statement, returning `false` if it did not exist
+    pub(super) fn remove(&self, id: u64) -> bool {
+        self.statements.lock().remove(&id).is_some()
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct PreparedSql {
+    pub(super) database: String,
+    pub(super) query: String,
+    plan: Mutex<CachedPlan>,
+}
+
+#[derive(Debug)]
+struct CachedPlan {
+    logical_plan: LogicalPlan,
+    db_id: DbId,
+    catalog_sequence: CatalogSequenceNumber,
+    tables: Vec<Arc<TableDefinition>>,
+}
+
+impl CachedPlan {
+    fn new(
+        logical_plan: LogicalPlan,
+        db_schema: &DatabaseSchema,
+        catalog_sequence: CatalogSequenceNumber,
+    ) -> Self {
+        let tables = referenced_tables(&logical_plan, db_schema);
+        Self {
+            logical_plan,
+            db_id: db_schema.id,
+            catalog_sequence,
+            tables,
+        }
+    }
+}
+
+impl PreparedSql {
+    pub(super) fn new(
+        database: impl Into<String>,
+        query: impl Into<String>,
+        logical_plan: LogicalPlan,
+        db_schema: &DatabaseSchema,
+        catalog_sequence: CatalogSequenceNumber,
+    ) -> Self {
+        Self {
+            database: database.into(),
+            query: query.into(),
+            plan: Mutex::new(CachedPlan::new(logical_plan, db_schema, catalog_sequence)),
+        }
+    }
+
+    /// The names of the parameters in the statement, without their `$` prefix
+    pub(super) fn parameters(&self) -> Result<Vec<String>, DataFusionError> {
+        let mut names = self
+            .plan
+            .lock()
+            .logical_plan
+            .get_parameter_types()?
+            .into_keys()
+            .map(|name| name.trim_start_matches('$').to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Get the cached logical plan, if it is still valid for the current state of the catalog
+    ///
+    /// The plan is valid if the catalog has not changed since it was produced, or if none of the
+    /// tables it reads from have changed.
+    pub(super) fn cached_plan(
+        &self,
+        db_schema: &DatabaseSchema,
+        catalog_sequence: CatalogSequenceNumber,
+    ) -> Option<LogicalPlan> {
+        let mut cached = self.plan.lock();
+        if cached.db_id != db_schema.id {
+            return None;
+        }
+        if cached.catalog_sequence != catalog_sequence {
+            let unchanged = cached.tables.iter().all(|table_def| {
+                db_schema
+                    .table_definition_by_id(&table_def.table_id)
+                    .is_some_and(|current| !current.deleted && current.schema == table_def.schema)
+            });
+            if !unchanged {
+                return None;
+            }
+            cached.catalog_sequence = catalog_sequence;
+        }
+        Some(cached.logical_plan.clone())
+    }
+
+    /// Replace the cached logical plan with one produced for the current state of the catalog
+    pub(super) fn replace_plan(
+        &self,
+        logical_plan: LogicalPlan,
+        db_schema: &DatabaseSchema,
+        catalog_sequence: CatalogSequenceNumber,
+    ) {
+        *self.plan.lock() = CachedPlan::new(logical_plan, db_schema, catalog_sequence);
+    }
+}
+
+/// Find the definitions of the tables in the database that a logical plan reads from
+fn referenced_tables(plan: &LogicalPlan, db_schema: &DatabaseSchema) -> Vec<Arc<TableDefinition>> {
+    let mut tables = Vec::<Arc<TableDefinition>>::new();
+    plan.apply_with_subqueries(|node| {
+        if let LogicalPlan::TableScan(scan) = node {
+            let table_def = match scan.table_name.schema() {
+                None | Some("iox") => db_schema.table_definition(scan.table_name.table()),
+                Some(_) => None,
+            };
+            if let Some(table_def) =
+                table_def.filter(|def| !tables.iter().any(|t| t.table_id == def.table_id))
+            {
+                tables.push(table_def);
+            }
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })
+    .expect("visiting the plan does not fail");
+    tables
+}
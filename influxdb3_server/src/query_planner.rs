@@ -3,7 +3,8 @@ use std::{any::Any, sync::Arc};
 use arrow_schema::SchemaRef;
 use datafusion::{
     error::DataFusionError,
-    execution::{SendableRecordBatchStream, TaskContext},
+    execution::{SendableRecordBatchStream, TaskContext, context::SQLOptions},
+    logical_expr::LogicalPlan,
     physical_expr::EquivalenceProperties,
     physical_plan::{
         DisplayAs, DisplayFormatType, ExecutionPlan, ExecutionPlanProperties, PlanProperties,
@@ -47,6 +48,37 @@ impl Planner {
         planner.query(query, params, &ctx).await
     }
 
+    /// Plan a SQL query and return a DataFusion logical plan, without substituting any of its
+    /// parameters, so that it can be re-used with [`Planner::sql_prepared`]
+    pub(crate) async fn sql_logical(&self, query: impl AsRef<str> + Send) -> Result<LogicalPlan> {
+        let ctx = self.ctx.child_ctx("rest_api_query_planner_sql_logical");
+        let plan = ctx
+            .inner()
+            .state()
+            .create_logical_plan(query.as_ref())
+            .await?;
+        SQLOptions::new()
+            .with_allow_ddl(false)
+            .with_allow_dml(false)
+            .with_allow_statements(false)
+            .verify_plan(&plan)?;
+
+        Ok(plan)
+    }
+
+    /// Substitute the parameters into a logical plan produced by [`Planner::sql_logical`] and
+    /// return a DataFusion physical plan
+    pub(crate) async fn sql_prepared(
+        &self,
+        logical_plan: LogicalPlan,
+        params: StatementParams,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let ctx = self.ctx.child_ctx("rest_api_query_planner_sql_prepared");
+        let logical_plan = logical_plan.with_param_values(params)?;
+
+        ctx.create_physical_plan(&logical_plan).await
+    }
+
     /// Plan an InfluxQL query and return a DataFusion physical plan
     pub(crate) async fn influxql(
         &self,
@@ -14,18 +14,25 @@ use influxdb3_write::WriteBuffer;
 use iox_query::query_log::QueryLog;
 use iox_system_tables::SystemTableProvider;
 use parquet_files::ParquetFilesTable;
+use query_result_cache::{QueryResultCacheStatsTable, QueryResultCacheTable};
 use tokens::TokenSystemTable;
 use tonic::async_trait;
 
 use self::{last_caches::LastCachesTable, queries::QueriesTable};
+use crate::query_cache::QueryResultCache;
 
 mod distinct_caches;
 mod last_caches;
 mod parquet_files;
-use crate::system_tables::python_call::{ProcessingEngineLogsTable, ProcessingEngineTriggerTable};
+use crate::system_tables::python_call::{
+    ProcessingEngineBackfillsTable, ProcessingEngineDeadLettersTable,
+    ProcessingEngineEnvironmentsTable, ProcessingEngineLogsTable,
+    ProcessingEngineTriggerStatsTable, ProcessingEngineTriggerTable,
+};
 
 mod python_call;
 mod queries;
+mod query_result_cache;
 mod tokens;
 
 pub(crate) const SYSTEM_SCHEMA_NAME: &str = "system";
@@ -36,11 +43,21 @@ pub(crate) const LAST_CACHES_TABLE_NAME: &str = "last_caches";
 pub(crate) const DISTINCT_CACHES_TABLE_NAME: &str = "distinct_caches";
 pub(crate) const PARQUET_FILES_TABLE_NAME: &str = "parquet_files";
 pub(crate) const TOKENS_TABLE_NAME: &str = "tokens";
+pub(crate) const QUERY_RESULT_CACHE_TABLE_NAME: &str = "query_result_cache";
+pub(crate) const QUERY_RESULT_CACHE_STATS_TABLE_NAME: &str = "query_result_cache_stats";
 
 const PROCESSING_ENGINE_TRIGGERS_TABLE_NAME: &str = "processing_engine_triggers";
 
 const PROCESSING_ENGINE_LOGS_TABLE_NAME: &str = "processing_engine_logs";
 
+const PROCESSING_ENGINE_DEAD_LETTERS_TABLE_NAME: &str = "processing_engine_dead_letters";
+
+const PROCESSING_ENGINE_BACKFILLS_TABLE_NAME: &str = "processing_engine_backfills";
+
+const PROCESSING_ENGINE_TRIGGER_STATS_TABLE_NAME: &str = "processing_engine_trigger_stats";
+
+const PROCESSING_ENGINE_ENVIRONMENTS_TABLE_NAME: &str = "processing_engine_environments";
+
 #[derive(Debug)]
 pub(crate) enum SystemSchemaProvider {
     AllSystemSchemaTables(AllSystemSchemaTablesProvider),
@@ -94,6 +111,7 @@ impl AllSystemSchemaTablesProvider {
         sys_events_store: Arc<SysEventStore>,
         catalog: Arc<Catalog>,
         started_with_auth: bool,
+        query_result_cache: Option<Arc<QueryResultCache>>,
     ) -> Self {
         let mut tables = HashMap::<&'static str, Arc<dyn TableProvider>>::new();
         let queries = Arc::new(SystemTableProvider::new(Arc::new(QueriesTable::new(
@@ -126,9 +144,48 @@ impl AllSystemSchemaTablesProvider {
         );
         tables.insert(PARQUET_FILES_TABLE_NAME, parquet_files);
         let logs_table = Arc::new(SystemTableProvider::new(Arc::new(
-            ProcessingEngineLogsTable::new(sys_events_store),
+            ProcessingEngineLogsTable::new(Arc::clone(&sys_events_store)),
         )));
         tables.insert(PROCESSING_ENGINE_LOGS_TABLE_NAME, logs_table);
+        let dead_letters_table = Arc::new(SystemTableProvider::new(Arc::new(
+            ProcessingEngineDeadLettersTable::new(Arc::clone(&sys_events_store)),
+        )));
+        tables.insert(
+            PROCESSING_ENGINE_DEAD_LETTERS_TABLE_NAME,
+            dead_letters_table,
+        );
+        let backfills_table = Arc::new(SystemTableProvider::new(Arc::new(
+            ProcessingEngineBackfillsTable::new(Arc::clone(&sys_events_store)),
+        )));
+        tables.insert(PROCESSING_ENGINE_BACKFILLS_TABLE_NAME, backfills_table);
+        let trigger_stats_table = Arc::new(SystemTableProvider::new(Arc::new(
+            ProcessingEngineTriggerStatsTable::new(Arc::clone(&sys_events_store)),
+        )));
+        tables.insert(
+            PROCESSING_ENGINE_TRIGGER_STATS_TABLE_NAME,
+            trigger_stats_table,
+        );
+        let environments_table = Arc::new(SystemTableProvider::new(Arc::new(
+            ProcessingEngineEnvironmentsTable::new(sys_events_store),
+        )));
+        tables.insert(
+            PROCESSING_ENGINE_ENVIRONMENTS_TABLE_NAME,
+            environments_table,
+        );
+        if let Some(cache) = query_result_cache {
+            tables.insert(
+                QUERY_RESULT_CACHE_TABLE_NAME,
+                Arc::new(SystemTableProvider::new(Arc::new(
+                    QueryResultCacheTable::new(Arc::clone(&db_schema), Arc::clone(&cache)),
+                ))),
+            );
+            tables.insert(
+                QUERY_RESULT_CACHE_STATS_TABLE_NAME,
+                Arc::new(SystemTableProvider::new(Arc::new(
+                    QueryResultCacheStatsTable::new(Arc::clone(&db_schema), cache),
+                ))),
+            );
+        }
         if db_schema.name.as_ref() == INTERNAL_DB_NAME {
             tables.insert(
                 TOKENS_TABLE_NAME,
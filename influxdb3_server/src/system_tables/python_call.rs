@@ -4,7 +4,10 @@ use async_trait::async_trait;
 use datafusion::common::Result;
 use datafusion::logical_expr::Expr;
 use influxdb3_catalog::log::TriggerDefinition;
-use influxdb3_py_api::logging::ProcessingEngineLog;
+use influxdb3_py_api::logging::{
+    ProcessingEngineBackfill, ProcessingEngineDeadLetter, ProcessingEngineLog,
+    ProcessingEnginePluginEnvironment, ProcessingEngineTriggerStats,
+};
 use influxdb3_sys_events::{SysEventStore, ToRecordBatch};
 use iox_system_tables::IoxSystemTable;
 use std::sync::Arc;
@@ -108,3 +111,139 @@ impl IoxSystemTable for ProcessingEngineLogsTable {
         Ok(result?)
     }
 }
+
+#[derive(Debug)]
+pub(super) struct ProcessingEngineDeadLettersTable {
+    sys_event_store: Arc<SysEventStore>,
+}
+
+impl ProcessingEngineDeadLettersTable {
+    pub(super) fn new(sys_event_store: Arc<SysEventStore>) -> Self {
+        Self { sys_event_store }
+    }
+}
+
+#[async_trait]
+impl IoxSystemTable for ProcessingEngineDeadLettersTable {
+    fn schema(&self) -> SchemaRef {
+        Arc::new(ProcessingEngineDeadLetter::schema())
+    }
+
+    async fn scan(
+        &self,
+        _filters: Option<Vec<Expr>>,
+        _limit: Option<usize>,
+    ) -> Result<RecordBatch> {
+        let Some(result) = self
+            .sys_event_store
+            .as_record_batch::<ProcessingEngineDeadLetter>()
+        else {
+            return Ok(RecordBatch::new_empty(Arc::new(
+                ProcessingEngineDeadLetter::schema(),
+            )));
+        };
+        Ok(result?)
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct ProcessingEngineBackfillsTable {
+    sys_event_store: Arc<SysEventStore>,
+}
+
+impl ProcessingEngineBackfillsTable {
+    pub(super) fn new(sys_event_store: Arc<SysEventStore>) -> Self {
+        Self { sys_event_store }
+    }
+}
+
+#[async_trait]
+impl IoxSystemTable for ProcessingEngineBackfillsTable {
+    fn schema(&self) -> SchemaRef {
+        Arc::new(ProcessingEngineBackfill::schema())
+    }
+
+    async fn scan(
+        &self,
+        _filters: Option<Vec<Expr>>,
+        _limit: Option<usize>,
+    ) -> Result<RecordBatch> {
+        let Some(result) = self
+            .sys_event_store
+            .as_record_batch::<ProcessingEngineBackfill>()
+        else {
+            return Ok(RecordBatch::new_empty(Arc::new(
+                ProcessingEngineBackfill::schema(),
+            )));
+        };
+        Ok(result?)
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct ProcessingEngineTriggerStatsTable {
+    sys_event_store: Arc<SysEventStore>,
+}
+
+impl ProcessingEngineTriggerStatsTable {
+    pub(super) fn new(sys_event_store: Arc<SysEventStore>) -> Self {
+        Self { sys_event_store }
+    }
+}
+
+#[async_trait]
+impl IoxSystemTable for ProcessingEngineTriggerStatsTable {
+    fn schema(&self) -> SchemaRef {
+        Arc::new(ProcessingEngineTriggerStats::schema())
+    }
+
+    async fn scan(
+        &self,
+        _filters: Option<Vec<Expr>>,
+        _limit: Option<usize>,
+    ) -> Result<RecordBatch> {
+        let Some(result) = self
+            .sys_event_store
+            .as_record_batch::<ProcessingEngineTriggerStats>()
+        else {
+            return Ok(RecordBatch::new_empty(Arc::new(
+                ProcessingEngineTriggerStats::schema(),
+            )));
+        };
+        Ok(result?)
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct ProcessingEngineEnvironmentsTable {
+    sys_event_store: Arc<SysEventStore>,
+}
+
+impl ProcessingEngineEnvironmentsTable {
+    pub(super) fn new(sys_event_store: Arc<SysEventStore>) -> Self {
+        Self { sys_event_store }
+    }
+}
+
+#[async_trait]
+impl IoxSystemTable for ProcessingEngineEnvironmentsTable {
+    fn schema(&self) -> SchemaRef {
+        Arc::new(ProcessingEnginePluginEnvironment::schema())
+    }
+
+    async fn scan(
+        &self,
+        _filters: Option<Vec<Expr>>,
+        _limit: Option<usize>,
+    ) -> Result<RecordBatch> {
+        let Some(result) = self
+            .sys_event_store
+            .as_record_batch::<ProcessingEnginePluginEnvironment>()
+        else {
+            return Ok(RecordBatch::new_empty(Arc::new(
+                ProcessingEnginePluginEnvironment::schema(),
+            )));
+        };
+        Ok(result?)
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use arrow::array::{
+    Float64Builder, GenericListBuilder, StringViewBuilder, TimestampNanosecondBuilder,
+    UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::{error::DataFusionError, logical_expr::Expr};
+use influxdb3_catalog::catalog::DatabaseSchema;
+use iox_system_tables::IoxSystemTable;
+
+use crate::query_cache::QueryResultCache;
+
+/// Lists the entries held in the query result cache for a database
+#[derive(Debug)]
+pub(super) struct QueryResultCacheTable {
+    db_schema: Arc<DatabaseSchema>,
+    schema: SchemaRef,
+    cache: Arc<QueryResultCache>,
+}
+
+impl QueryResultCacheTable {
+    pub(super) fn new(db_schema: Arc<DatabaseSchema>, cache: Arc<QueryResultCache>) -> Self {
+        Self {
+            db_schema,
+            schema: query_result_cache_schema(),
+            cache,
+        }
+    }
+}
+
+fn query_result_cache_schema() -> SchemaRef {
+    let columns = vec![
+        Field::new("query_type", DataType::Utf8View, false),
+        Field::new("query_text", DataType::Utf8View, false),
+        Field::new("params", DataType::Utf8View, true),
+        Field::new(
+            "tables",
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8View, true))),
+            false,
+        ),
+        Field::new("size_bytes", DataType::UInt64, false),
+        Field::new("hits", DataType::UInt64, false),
+        Field::new(
+            "created_at",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        ),
+        Field::new(
+            "last_accessed",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        ),
+    ];
+    Arc::new(Schema::new(columns))
+}
+
+#[async_trait::async_trait]
+impl IoxSystemTable for QueryResultCacheTable {
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    async fn scan(
+        &self,
+        _filters: Option<Vec<Expr>>,
+        _limit: Option<usize>,
+    ) -> Result<RecordBatch, DataFusionError> {
+        let entries = self.cache.entries(&self.db_schema);
+
+        let mut query_type_arr = StringViewBuilder::with_capacity(entries.len());
+        let mut query_text_arr = StringViewBuilder::with_capacity(entries.len());
+        let mut params_arr = StringViewBuilder::with_capacity(entries.len());
+        let mut tables_arr = GenericListBuilder::<i32, StringViewBuilder>::with_capacity(
+            StringViewBuilder::new(),
+            entries.len(),
+        );
+        let mut size_bytes_arr = UInt64Builder::with_capacity(entries.len());
+        let mut hits_arr = UInt64Builder::with_capacity(entries.len());
+        let mut created_at_arr = TimestampNanosecondBuilder::with_capacity(entries.len());
+        let mut last_accessed_arr = TimestampNanosecondBuilder::with_capacity(entries.len());
+
+        for entry in entries {
+            query_type_arr.append_value(entry.query_type);
+            query_text_arr.append_value(&entry.query_text);
+            params_arr.append_option(entry.params.as_deref());
+            for table in &entry.tables {
+                tables_arr.values().append_value(table);
+            }
+            tables_arr.append(true);
+            size_bytes_arr.append_value(entry.size_bytes as u64);
+            hits_arr.append_value(entry.hits);
+            created_at_arr.append_value(entry.created_at.timestamp_nanos());
+            last_accessed_arr.append_value(entry.last_accessed.timestamp_nanos());
+        }
+
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(query_type_arr.finish()),
+            Arc::new(query_text_arr.finish()),
+            Arc::new(params_arr.finish()),
+            Arc::new(tables_arr.finish()),
+            Arc::new(size_bytes_arr.finish()),
+            Arc::new(hits_arr.finish()),
+            Arc::new(created_at_arr.finish()),
+            Arc::new(last_accessed_arr.finish()),
+        ];
+        Ok(RecordBatch::try_new(self.schema(), columns)?)
+    }
+}
+
+/// Reports the activity of the query result cache for a database, including its hit ratio
+#[derive(Debug)]
+pub(super) struct QueryResultCacheStatsTable {
+    db_schema: Arc<DatabaseSchema>,
+    schema: SchemaRef,
+    cache: Arc<QueryResultCache>,
+}
+
+impl QueryResultCacheStatsTable {
+    pub(super) fn new(db_schema: Arc<DatabaseSchema>, cache: Arc<QueryResultCache>) -> Self {
+        Self {
+            db_schema,
+            schema: query_result_cache_stats_schema(),
+            cache,
+        }
+    }
+}
+
+fn query_result_cache_stats_schema() -> SchemaRef {
+    let columns = vec![
+        Field::new("entries", DataType::UInt64, false),
+        Field::new("size_bytes", DataType::UInt64, false),
+        Field::new("hits", DataType::UInt64, false),
+        Field::new("misses", DataType::UInt64, false),
+        Field::new("bypassed", DataType::UInt64, false),
+        Field::new("hit_ratio", DataType::Float64, true),
+        Field::new("inserts", DataType::UInt64, false),
+        Field::new("invalidations", DataType::UInt64, false),
+        Field::new("evictions", DataType::UInt64, false),
+    ];
+    Arc::new(Schema::new(columns))
+}
+
+#[async_trait::async_trait]
+impl IoxSystemTable for QueryResultCacheStatsTable {
+    fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    async fn scan(
+        &self,
+        _filters: Option<Vec<Expr>>,
+        _limit: Option<usize>,
+    ) -> Result<RecordBatch, DataFusionError> {
+        let stats = self.cache.stats(&self.db_schema.name);
+        let entries = self.cache.entries(&self.db_schema);
+        let size_bytes = entries.iter().map(|e| e.size_bytes as u64).sum::<u64>();
+
+        let mut hit_ratio_arr = Float64Builder::with_capacity(1);
+        hit_ratio_arr.append_option(stats.hit_ratio());
+
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from(vec![entries.len() as u64])),
+            Arc::new(UInt64Array::from(vec![size_bytes])),
+            Arc::new(UInt64Array::from(vec![stats.hits])),
+            Arc::new(UInt64Array::from(vec![stats.misses])),
+            Arc::new(UInt64Array::from(vec![stats.bypassed])),
+            Arc::new(hit_ratio_arr.finish()),
+            Arc::new(UInt64Array::from(vec![stats.inserts])),
+            Arc::new(UInt64Array::from(vec![stats.invalidations])),
+            Arc::new(UInt64Array::from(vec![stats.evictions])),
+        ];
+        Ok(RecordBatch::try_new(self.schema(), columns)?)
+    }
+}
@@ -73,6 +73,20 @@ impl SysEventStore {
             .push(wrapped);
     }
 
+    /// Removes the events of type `E` for which `f` returns false
+    pub fn retain<E>(&self, mut f: impl FnMut(&E) -> bool)
+    where
+        E: 'static + Debug + Sync + Send,
+    {
+        if let Some(mut buf) = self.events.get_mut(&TypeId::of::<RingBuffer<Event<E>>>()) {
+            // unwrap here is fine, we just used the same type above to
+            // get
+            buf.downcast_mut::<RingBuffer<Event<E>>>()
+                .unwrap()
+                .retain(|event| f(&event.data));
+        }
+    }
+
     /// Creates an intermediate `Vec` by cloning events. To
     /// create a record batch instead use [`Self::as_record_batch`]
     pub fn as_vec<E>(&self) -> Vec<Event<E>>
@@ -151,6 +165,13 @@ impl<T> RingBufferVec<T> {
         self.write_index = (self.write_index + 1) % self.max;
     }
 
+    fn retain(&mut self, f: impl FnMut(&T) -> bool) {
+        // put the oldest value first, so that it is still the next one to be overwritten
+        self.buf.rotate_left(self.write_index);
+        self.buf.retain(f);
+        self.write_index = self.buf.len() % self.max;
+    }
+
     fn is_at_max(&mut self) -> bool {
         self.buf.len() >= self.max
     }
@@ -296,6 +317,21 @@ mod tests {
         assert_eq!(&3, second);
     }
 
+    #[test]
+    fn test_ring_buffer_retain() {
+        let mut buf = RingBuffer::new(3);
+        for i in 1..=4 {
+            buf.push(i);
+        }
+        buf.retain(|i| *i != 3);
+        assert_eq!(vec![&2, &4], buf.in_order().collect::<Vec<_>>());
+
+        // the buffer fills back up, then overwrites the oldest value:
+        buf.push(5);
+        buf.push(6);
+        assert_eq!(vec![&4, &5, &6], buf.in_order().collect::<Vec<_>>());
+    }
+
     #[test_log::test(test)]
     fn test_event_store() {
         let event_data = SampleEvent1 {
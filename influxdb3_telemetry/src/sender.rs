@@ -92,6 +92,8 @@ pub(crate) struct TelemetryPayload {
     pub wal_all_triggers_count: u64,
     pub schedule_triggers_count: u64,
     pub request_triggers_count: u64,
+    pub persisted_triggers_count: u64,
+    pub catalog_triggers_count: u64,
 }
 
 /// This function runs in the background and if any call fails
@@ -206,6 +208,8 @@ mod tests {
             wal_all_triggers_count: 100,
             schedule_triggers_count: 150,
             request_triggers_count: 155,
+            persisted_triggers_count: 160,
+            catalog_triggers_count: 165,
         }
     }
 }
@@ -169,12 +169,20 @@ impl TelemetryStore {
             payload.parquet_file_size_mb = size_mb;
             payload.parquet_row_count = row_count;
         }
-        let (wal_count, all_wal_count, schedule_count, request_count) =
-            self.processing_engine_metrics.num_triggers();
+        let (
+            wal_count,
+            all_wal_count,
+            schedule_count,
+            request_count,
+            persisted_count,
+            catalog_count,
+        ) = self.processing_engine_metrics.num_triggers();
         payload.wal_single_triggers_count = wal_count;
         payload.wal_all_triggers_count = all_wal_count;
         payload.schedule_triggers_count = schedule_count;
         payload.request_triggers_count = request_count;
+        payload.persisted_triggers_count = persisted_count;
+        payload.catalog_triggers_count = catalog_count;
         payload
     }
 }
@@ -289,6 +297,8 @@ impl TelemetryStoreInner {
             wal_all_triggers_count: 0,
             schedule_triggers_count: 0,
             request_triggers_count: 0,
+            persisted_triggers_count: 0,
+            catalog_triggers_count: 0,
         }
     }
 
@@ -368,8 +378,8 @@ mod tests {
     }
 
     impl ProcessingEngineMetrics for SampleMetrics {
-        fn num_triggers(&self) -> (u64, u64, u64, u64) {
-            (150, 160, 200, 250)
+        fn num_triggers(&self) -> (u64, u64, u64, u64, u64, u64) {
+            (150, 160, 200, 250, 260, 270)
         }
     }
 
@@ -427,6 +437,8 @@ mod tests {
         assert_eq!(160, snapshot.wal_all_triggers_count);
         assert_eq!(200, snapshot.schedule_triggers_count);
         assert_eq!(250, snapshot.request_triggers_count);
+        assert_eq!(260, snapshot.persisted_triggers_count);
+        assert_eq!(270, snapshot.catalog_triggers_count);
 
         // add some writes
         store.add_write_metrics(100, 100);
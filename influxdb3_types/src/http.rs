@@ -163,6 +163,30 @@ pub struct ProcessingEngineTriggerDeleteRequest {
     pub force: bool,
 }
 
+/// Request definition for the `POST /api/v3/configure/secret` API
+#[derive(Deserialize, Serialize)]
+pub struct SecretCreateRequest {
+    pub db: String,
+    pub name: String,
+    pub value: String,
+}
+
+impl std::fmt::Debug for SecretCreateRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretCreateRequest")
+            .field("db", &self.db)
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Request definition for the `DELETE /api/v3/configure/secret` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SecretDeleteRequest {
+    pub db: String,
+    pub name: String,
+}
+
 /// Request definition for the `POST /api/v3/configure/plugin_environment/install_packages` API
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ProcessingEngineInstallPackagesRequest {
@@ -181,6 +205,55 @@ pub struct ProcessingEngineTriggerIdentifier {
     pub trigger_name: String,
 }
 
+/// Request definition for the `POST /api/v3/configure/processing_engine_trigger/replay_dead_letters` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProcessingEngineTriggerReplayDeadLettersRequest {
+    pub db: String,
+    pub trigger_name: String,
+    /// Replay only this dead letter, otherwise all of the trigger's dead letters that have not
+    /// yet been replayed are
+    pub dead_letter_id: Option<u64>,
+}
+
+/// Response definition for the `POST /api/v3/configure/processing_engine_trigger/replay_dead_letters` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProcessingEngineTriggerReplayDeadLettersResponse {
+    pub replayed: usize,
+}
+
+/// Request definition for the `POST /api/v3/configure/processing_engine_trigger/backfill` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProcessingEngineTriggerBackfillRequest {
+    pub db: String,
+    pub trigger_name: String,
+    pub start_time: DateTime<Utc>,
+    /// Defaults to the time the backfill is requested
+    pub end_time: Option<DateTime<Utc>>,
+    /// How much time the plugin is run over at once, which defaults to 1 minute
+    pub window_seconds: Option<u64>,
+}
+
+/// Response definition for the `POST /api/v3/configure/processing_engine_trigger/backfill` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProcessingEngineTriggerBackfillResponse {
+    pub backfill_id: u64,
+}
+
+/// Response definition for the `PUT /api/v3/configure/plugin_files/{name}` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PluginFileUploadResponse {
+    pub name: String,
+    /// The version given to the uploaded plugin, which triggers can pin with `<name>@<version>`
+    pub version: u64,
+}
+
+/// Response definition for the `GET /api/v3/configure/plugin_files/{name}` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PluginFileVersionsResponse {
+    pub name: String,
+    pub versions: Vec<u64>,
+}
+
 /// Request definition for the `POST /api/v3/plugin_test/wal` API
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WalPluginTestRequest {
@@ -218,6 +291,35 @@ pub struct SchedulePluginTestResponse {
     pub errors: Vec<String>,
 }
 
+/// Request definition for the `POST /api/v3/plugin_test/request` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RequestPluginTestRequest {
+    pub filename: String,
+    pub database: String,
+    /// The HTTP method to call the plugin with, `GET` if not given
+    pub method: Option<String>,
+    /// The path below the trigger's path to call the plugin with
+    pub path: Option<String>,
+    pub query_params: Option<HashMap<String, String>>,
+    pub headers: Option<HashMap<String, String>>,
+    pub body: Option<String>,
+    pub cache_name: Option<String>,
+    pub input_arguments: Option<HashMap<String, String>>,
+}
+
+/// Response definition for the `POST /api/v3/plugin_test/request` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RequestPluginTestResponse {
+    pub status: Option<u16>,
+    pub headers: HashMap<String, String>,
+    /// The response body, base64-encoded so that binary bodies come back intact; the
+    /// `Content-Type` header says how to interpret it
+    pub body: String,
+    pub log_lines: Vec<String>,
+    pub database_writes: HashMap<String, Vec<String>>,
+    pub errors: Vec<String>,
+}
+
 /// Request definition for the `GET /api/v3/configure/database` API
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 pub struct ShowDatabasesRequest {
@@ -293,6 +395,42 @@ pub struct QueryRequest<D, F, P> {
     pub query_str: String,
     pub format: F,
     pub params: Option<P>,
+    /// Split the streamed response so that no encoded chunk holds more than this many rows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_size: Option<usize>,
+}
+
+/// Request definition for the `POST /api/v3/prepared_statement` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PrepareSqlRequest {
+    #[serde(rename = "db")]
+    pub database: String,
+    #[serde(rename = "q")]
+    pub query_str: String,
+}
+
+/// Response definition for the `POST /api/v3/prepared_statement` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PrepareSqlResponse {
+    pub statement_id: u64,
+    pub parameters: Vec<String>,
+}
+
+/// Request definition for the `DELETE /api/v3/prepared_statement` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PreparedStatementDeleteRequest {
+    pub statement_id: u64,
+}
+
+/// Request definition for the `POST /api/v3/query_prepared` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct QueryPreparedRequest {
+    pub statement_id: u64,
+    pub format: Option<QueryFormat>,
+    pub params: Option<StatementParams>,
+    /// Split the streamed response so that no encoded chunk holds more than this many rows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_size: Option<usize>,
 }
 
 #[derive(Copy, Clone, Debug, Deserialize, Serialize)]
@@ -95,6 +95,15 @@ pub trait Bufferer: Debug + Send + Sync + 'static {
     fn watch_persisted_snapshots(
         &self,
     ) -> tokio::sync::watch::Receiver<Option<PersistedSnapshotVersion>>;
+
+    /// Subscribe to every persisted snapshot as it is created
+    ///
+    /// Unlike [`Bufferer::watch_persisted_snapshots`], which only holds the most recent
+    /// snapshot, no snapshot is missed unless the receiver falls far behind, in which case it
+    /// gets a [`tokio::sync::broadcast::error::RecvError::Lagged`] error.
+    fn subscribe_persisted_snapshots(
+        &self,
+    ) -> tokio::sync::broadcast::Receiver<PersistedSnapshotVersion>;
 }
 
 /// ChunkContainer is used by the query engine to get chunks for a given table. Chunks will generally be in the
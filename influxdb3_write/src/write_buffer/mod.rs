@@ -569,6 +569,12 @@ impl Bufferer for WriteBufferImpl {
     fn watch_persisted_snapshots(&self) -> Receiver<Option<PersistedSnapshotVersion>> {
         self.buffer.persisted_snapshot_notify_rx()
     }
+
+    fn subscribe_persisted_snapshots(
+        &self,
+    ) -> tokio::sync::broadcast::Receiver<PersistedSnapshotVersion> {
+        self.buffer.subscribe_persisted_snapshots()
+    }
 }
 
 impl ChunkContainer for WriteBufferImpl {
@@ -37,6 +37,10 @@ use std::time::Duration;
 use tokio::sync::oneshot::{self, Receiver};
 use tokio::task::JoinSet;
 
+/// The number of persisted snapshots that a subscriber to
+/// [`QueryableBuffer::subscribe_persisted_snapshots`] can fall behind by before it misses any
+const PERSISTED_SNAPSHOT_CHANNEL_CAPACITY: usize = 1_000;
+
 #[derive(Debug)]
 pub struct QueryableBuffer {
     pub(crate) executor: Arc<Executor>,
@@ -50,6 +54,9 @@ pub struct QueryableBuffer {
     /// Sends a notification to this watch channel whenever a snapshot info is persisted
     persisted_snapshot_notify_rx: tokio::sync::watch::Receiver<Option<PersistedSnapshotVersion>>,
     persisted_snapshot_notify_tx: tokio::sync::watch::Sender<Option<PersistedSnapshotVersion>>,
+    /// Sends every persisted snapshot to its subscribers, unlike the watch channel, which only
+    /// holds the most recent one
+    persisted_snapshot_broadcast_tx: tokio::sync::broadcast::Sender<PersistedSnapshotVersion>,
 }
 
 #[derive(Debug)]
@@ -78,6 +85,8 @@ impl QueryableBuffer {
         let buffer = Arc::new(RwLock::new(BufferState::new(Arc::clone(&catalog))));
         let (persisted_snapshot_notify_tx, persisted_snapshot_notify_rx) =
             tokio::sync::watch::channel(None);
+        let (persisted_snapshot_broadcast_tx, _) =
+            tokio::sync::broadcast::channel(PERSISTED_SNAPSHOT_CHANNEL_CAPACITY);
         Self {
             executor,
             catalog,
@@ -89,6 +98,7 @@ impl QueryableBuffer {
             parquet_cache,
             persisted_snapshot_notify_rx,
             persisted_snapshot_notify_tx,
+            persisted_snapshot_broadcast_tx,
         }
     }
 
@@ -226,6 +236,7 @@ impl QueryableBuffer {
         let buffer = Arc::clone(&self.buffer);
         let catalog = Arc::clone(&self.catalog);
         let notify_snapshot_tx = self.persisted_snapshot_notify_tx.clone();
+        let broadcast_snapshot_tx = self.persisted_snapshot_broadcast_tx.clone();
         let parquet_cache = self.parquet_cache.clone();
 
         tokio::spawn(async move {
@@ -355,6 +366,8 @@ impl QueryableBuffer {
                 loop {
                     match persister.persist_snapshot(&persisted_snapshot).await {
                         Ok(_) => {
+                            // this only fails if there are no subscribers:
+                            let _ = broadcast_snapshot_tx.send(persisted_snapshot.clone());
                             let persisted_snapshot = Some(persisted_snapshot.clone());
                             notify_snapshot_tx
                                 .send(persisted_snapshot)
@@ -391,6 +404,12 @@ impl QueryableBuffer {
         self.persisted_snapshot_notify_rx.clone()
     }
 
+    pub fn subscribe_persisted_snapshots(
+        &self,
+    ) -> tokio::sync::broadcast::Receiver<PersistedSnapshotVersion> {
+        self.persisted_snapshot_broadcast_tx.subscribe()
+    }
+
     pub fn clear_buffer_for_db(&self, db_id: &DbId) {
         let mut buffer = self.buffer.write();
         buffer.db_to_table.remove(db_id);
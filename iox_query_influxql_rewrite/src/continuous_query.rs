@@ -0,0 +1,471 @@
+//! Parsing of the InfluxQL continuous query statements, which are not supported by the InfluxQL
+//! parser:
+//!
+//! ```text
+//! CREATE CONTINUOUS QUERY <name> ON <database>
+//!     [RESAMPLE [EVERY <interval>] [FOR <interval>]]
+//!     BEGIN <SELECT ... INTO ... GROUP BY time(<interval>)> END
+//! SHOW CONTINUOUS QUERIES
+//! DROP CONTINUOUS QUERY <name> ON <database>
+//! ```
+
+use std::time::Duration;
+
+use influxdb_influxql_parser::{select::Dimension, statement::Statement};
+
+use crate::{Error, parse_identifier, parse_statements, top_level_words};
+
+/// A continuous query statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContinuousQueryStatement {
+    Create(CreateContinuousQuery),
+    Show,
+    Drop { name: String, database: String },
+}
+
+/// A parsed and validated `CREATE CONTINUOUS QUERY` statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateContinuousQuery {
+    pub name: String,
+    pub database: String,
+    pub resample_every: Option<Duration>,
+    pub resample_for: Option<Duration>,
+    /// The `SELECT ... INTO` statement that is run by the continuous query
+    pub select: String,
+    /// The interval of the `GROUP BY time(...)` clause of the `select`
+    pub interval: Duration,
+    /// The offset of the `GROUP BY time(...)` clause of the `select`, normalized to be within the
+    /// `interval`
+    pub offset: Duration,
+}
+
+/// Parse a continuous query statement
+///
+/// Returns `None` if the input is not a continuous query statement, so that it can be handled by
+/// [`parse_statements`] instead.
+pub fn parse_continuous_query(input: &str) -> Option<Result<ContinuousQueryStatement, Error>> {
+    let mut cursor = Cursor::new(input.trim().trim_end_matches(';'));
+    if cursor.keywords(&["SHOW", "CONTINUOUS", "QUERIES"]) {
+        return Some(if cursor.is_empty() {
+            Ok(ContinuousQueryStatement::Show)
+        } else {
+            Err(invalid("unexpected input after SHOW CONTINUOUS QUERIES"))
+        });
+    }
+    if cursor.keywords(&["DROP", "CONTINUOUS", "QUERY"]) {
+        return Some(parse_drop(cursor));
+    }
+    if cursor.keywords(&["CREATE", "CONTINUOUS", "QUERY"]) {
+        return Some(parse_create(cursor));
+    }
+    None
+}
+
+fn parse_drop(mut cursor: Cursor<'_>) -> Result<ContinuousQueryStatement, Error> {
+    let (name, database) = parse_name_on_database(&mut cursor)?;
+    if !cursor.is_empty() {
+        return Err(invalid("unexpected input after DROP CONTINUOUS QUERY"));
+    }
+    Ok(ContinuousQueryStatement::Drop { name, database })
+}
+
+fn parse_create(mut cursor: Cursor<'_>) -> Result<ContinuousQueryStatement, Error> {
+    let (name, database) = parse_name_on_database(&mut cursor)?;
+
+    let mut resample_every = None;
+    let mut resample_for = None;
+    if cursor.keywords(&["RESAMPLE"]) {
+        if cursor.keywords(&["EVERY"]) {
+            resample_every = Some(cursor.duration().ok_or_else(|| invalid("invalid EVERY"))?);
+        }
+        if cursor.keywords(&["FOR"]) {
+            resample_for = Some(cursor.duration().ok_or_else(|| invalid("invalid FOR"))?);
+        }
+        if resample_every.is_none() && resample_for.is_none() {
+            return Err(invalid("RESAMPLE requires EVERY or FOR"));
+        }
+    }
+
+    if !cursor.keywords(&["BEGIN"]) {
+        return Err(invalid("expected BEGIN"));
+    }
+    let body = cursor.rest.trim_end();
+    let select = body
+        .len()
+        .checked_sub(3)
+        .filter(|&i| {
+            body.is_char_boundary(i)
+                && body[i..].eq_ignore_ascii_case("end")
+                && body[..i].ends_with(char::is_whitespace)
+        })
+        .map(|i| body[..i].trim())
+        .ok_or_else(|| invalid("expected END"))?;
+
+    let (interval, offset) = validate_select(select)?;
+    if resample_every.is_some_and(|every| every.is_zero()) {
+        return Err(invalid("EVERY must be greater than zero"));
+    }
+    if resample_for.is_some_and(|resample_for| resample_for < interval) {
+        return Err(invalid(
+            "FOR must be greater than or equal to the GROUP BY time interval",
+        ));
+    }
+
+    Ok(ContinuousQueryStatement::Create(CreateContinuousQuery {
+        name,
+        database,
+        resample_every,
+        resample_for,
+        select: select.to_string(),
+        interval,
+        offset,
+    }))
+}
+
+fn parse_name_on_database(cursor: &mut Cursor<'_>) -> Result<(String, String), Error> {
+    let name = cursor
+        .identifier()
+        .ok_or_else(|| invalid("expected a continuous query name"))?;
+    if !cursor.keywords(&["ON"]) {
+        return Err(invalid("expected ON"));
+    }
+    let database = cursor
+        .identifier()
+        .ok_or_else(|| invalid("expected a database name"))?;
+    Ok((name, database))
+}
+
+/// Check that a continuous query's statement is a `SELECT ... INTO` with a `GROUP BY time(...)`
+/// and return the interval and offset of its `GROUP BY time(...)`
+fn validate_select(select: &str) -> Result<(Duration, Duration), Error> {
+    let mut statements = parse_statements(select)?;
+    if statements.len() != 1 {
+        return Err(invalid("must contain a single SELECT statement"));
+    }
+    let statement = statements.pop().expect("there is one statement");
+    if statement.into_clause().is_none() {
+        return Err(invalid("SELECT statement must have an INTO clause"));
+    }
+    let Statement::Select(select) = statement.to_statement() else {
+        return Err(invalid("must contain a SELECT statement"));
+    };
+    let time_dimension = select
+        .group_by
+        .as_ref()
+        .and_then(|group_by| {
+            group_by
+                .iter()
+                .find(|dim| matches!(dim, Dimension::Time(_)))
+                .map(ToString::to_string)
+        })
+        .ok_or_else(|| invalid("SELECT statement must have a GROUP BY time(...) clause"))?;
+
+    let args = time_dimension
+        .get(..5)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("time("))
+        .and_then(|_| time_dimension[5..].strip_suffix(')'))
+        .ok_or_else(|| invalid("invalid GROUP BY time(...) clause"))?;
+    let mut args = args.split(',').map(|arg| parse_duration_nanos(arg.trim()));
+    let interval = args
+        .next()
+        .flatten()
+        .filter(|&interval| interval > 0)
+        .ok_or_else(|| invalid("invalid GROUP BY time(...) interval"))?;
+    let offset = match args.next() {
+        Some(offset) => offset.ok_or_else(|| invalid("invalid GROUP BY time(...) offset"))?,
+        None => 0,
+    };
+    Ok((
+        Duration::from_nanos(interval as u64),
+        Duration::from_nanos(offset.rem_euclid(interval) as u64),
+    ))
+}
+
+/// Add a condition to a continuous query's `SELECT` statement that limits it to the time range
+/// from `start_ns`, inclusive, to `end_ns`, exclusive
+pub fn with_time_range(select: &str, start_ns: i64, end_ns: i64) -> String {
+    const CLAUSES_AFTER_WHERE: &[&str] = &[
+        "GROUP", "ORDER", "LIMIT", "OFFSET", "SLIMIT", "SOFFSET", "FILL", "TZ",
+    ];
+    let time_range = format!("time >= {start_ns} AND time < {end_ns}");
+    let words = top_level_words(select);
+    let position = |keywords: &[&str], after: usize| {
+        words
+            .iter()
+            .filter(|&&(start, _)| start >= after)
+            .find(|&&(start, end)| {
+                keywords
+                    .iter()
+                    .any(|kw| select[start..end].eq_ignore_ascii_case(kw))
+            })
+            .copied()
+    };
+    let from_end = position(&["FROM"], 0).map_or(0, |(_, end)| end);
+    match position(&["WHERE"], from_end) {
+        Some((_, where_end)) => {
+            let condition_end =
+                position(CLAUSES_AFTER_WHERE, where_end).map_or(select.len(), |(start, _)| start);
+            format!(
+                "{} ({}) AND {time_range} {}",
+                &select[..where_end],
+                select[where_end..condition_end].trim(),
+                &select[condition_end..]
+            )
+        }
+        None => {
+            let insert_at =
+                position(CLAUSES_AFTER_WHERE, from_end).map_or(select.len(), |(start, _)| start);
+            format!(
+                "{} WHERE {time_range} {}",
+                select[..insert_at].trim_end(),
+                &select[insert_at..]
+            )
+        }
+    }
+    .trim_end()
+    .to_string()
+}
+
+/// Parse an InfluxQL duration literal, e.g., `1h` or `1h30m`, to nanoseconds
+fn parse_duration_nanos(s: &str) -> Option<i64> {
+    const UNITS: &[(&str, i64)] = &[
+        ("ns", 1),
+        ("us", 1_000),
+        ("µs", 1_000),
+        ("u", 1_000),
+        ("µ", 1_000),
+        ("ms", 1_000_000),
+        ("s", 1_000_000_000),
+        ("m", 60 * 1_000_000_000),
+        ("h", 60 * 60 * 1_000_000_000),
+        ("d", 24 * 60 * 60 * 1_000_000_000),
+        ("w", 7 * 24 * 60 * 60 * 1_000_000_000),
+    ];
+    let mut rest = s;
+    let mut total = 0i64;
+    while !rest.is_empty() {
+        let (negative, unsigned) = match rest.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, rest),
+        };
+        let digits = unsigned
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(unsigned.len());
+        let value = unsigned[..digits].parse::<i64>().ok()?;
+        let after_digits = &unsigned[digits..];
+        let unit_len = after_digits
+            .find(|c: char| c.is_ascii_digit() || c == '-')
+            .unwrap_or(after_digits.len());
+        let (_, multiplier) = UNITS
+            .iter()
+            .find(|(unit, _)| *unit == &after_digits[..unit_len])?;
+        let nanos = value.checked_mul(*multiplier)?;
+        total = total.checked_add(if negative { -nanos } else { nanos })?;
+        rest = &after_digits[unit_len..];
+    }
+    (!s.is_empty()).then_some(total)
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::InvalidContinuousQuery(msg.to_string())
+}
+
+/// A minimal cursor over the keywords and identifiers of a continuous query statement
+struct Cursor<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            rest: input.trim_start(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.rest.trim().is_empty()
+    }
+
+    /// Take the next word, i.e., everything up to the next whitespace
+    fn peek_word(&self) -> &'a str {
+        let end = self
+            .rest
+            .find(char::is_whitespace)
+            .unwrap_or(self.rest.len());
+        &self.rest[..end]
+    }
+
+    fn advance(&mut self, len: usize) {
+        self.rest = self.rest[len..].trim_start();
+    }
+
+    /// Consume the given sequence of keywords, if the input starts with them
+    fn keywords(&mut self, keywords: &[&str]) -> bool {
+        let mut probe = Cursor { rest: self.rest };
+        for keyword in keywords {
+            let word = probe.peek_word();
+            if !word.eq_ignore_ascii_case(keyword) {
+                return false;
+            }
+            probe.advance(word.len());
+        }
+        *self = probe;
+        true
+    }
+
+    /// Consume an identifier, which may be double quoted and contain whitespace
+    fn identifier(&mut self) -> Option<String> {
+        let len = if self.rest.starts_with('"') {
+            let mut escaped = false;
+            self.rest
+                .char_indices()
+                .skip(1)
+                .find(|&(_, c)| match c {
+                    _ if escaped => {
+                        escaped = false;
+                        false
+                    }
+                    '\\' => {
+                        escaped = true;
+                        false
+                    }
+                    c => c == '"',
+                })
+                .map(|(i, _)| i + 1)?
+        } else {
+            self.peek_word().len()
+        };
+        let identifier = parse_identifier(&self.rest[..len])?;
+        self.advance(len);
+        Some(identifier.as_str().to_string())
+    }
+
+    fn duration(&mut self) -> Option<Duration> {
+        let word = self.peek_word();
+        let nanos = parse_duration_nanos(word).filter(|&nanos| nanos >= 0)?;
+        self.advance(word.len());
+        Some(Duration::from_nanos(nanos as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        ContinuousQueryStatement, CreateContinuousQuery, parse_continuous_query,
+        parse_duration_nanos, with_time_range,
+    };
+    use crate::Error;
+
+    fn parse(input: &str) -> ContinuousQueryStatement {
+        parse_continuous_query(input).unwrap().unwrap()
+    }
+
+    fn parse_failure(input: &str) -> Error {
+        parse_continuous_query(input).unwrap().unwrap_err()
+    }
+
+    #[test]
+    fn create() {
+        assert_eq!(
+            ContinuousQueryStatement::Create(CreateContinuousQuery {
+                name: "cq_1h".to_string(),
+                database: "foo".to_string(),
+                resample_every: None,
+                resample_for: None,
+                select: "SELECT mean(usage) INTO cpu_1h FROM cpu GROUP BY time(1h), host"
+                    .to_string(),
+                interval: Duration::from_secs(3600),
+                offset: Duration::ZERO,
+            }),
+            parse(
+                "CREATE CONTINUOUS QUERY cq_1h ON foo BEGIN \
+                SELECT mean(usage) INTO cpu_1h FROM cpu GROUP BY time(1h), host END"
+            )
+        );
+
+        let ContinuousQueryStatement::Create(create) = parse(
+            "create continuous query \"my cq\" on \"my db\" resample every 30m for 2h begin \
+            select max(usage) into :MEASUREMENT from cpu group by time(1h, 15m), * end;",
+        ) else {
+            panic!("expected a CREATE statement");
+        };
+        assert_eq!("my cq", create.name);
+        assert_eq!("my db", create.database);
+        assert_eq!(Some(Duration::from_secs(30 * 60)), create.resample_every);
+        assert_eq!(Some(Duration::from_secs(2 * 3600)), create.resample_for);
+        assert_eq!(Duration::from_secs(15 * 60), create.offset);
+    }
+
+    #[test]
+    fn create_failures() {
+        for input in [
+            // no INTO:
+            "CREATE CONTINUOUS QUERY cq ON foo BEGIN SELECT mean(usage) FROM cpu GROUP BY time(1h) END",
+            // no GROUP BY time(...):
+            "CREATE CONTINUOUS QUERY cq ON foo BEGIN SELECT mean(usage) INTO a FROM cpu GROUP BY host END",
+            // no END:
+            "CREATE CONTINUOUS QUERY cq ON foo BEGIN SELECT mean(usage) INTO a FROM cpu GROUP BY time(1h)",
+            // no ON:
+            "CREATE CONTINUOUS QUERY cq BEGIN SELECT mean(usage) INTO a FROM cpu GROUP BY time(1h) END",
+            // FOR is shorter than the interval:
+            "CREATE CONTINUOUS QUERY cq ON foo RESAMPLE FOR 30m BEGIN \
+            SELECT mean(usage) INTO a FROM cpu GROUP BY time(1h) END",
+        ] {
+            assert!(
+                matches!(parse_failure(input), Error::InvalidContinuousQuery(_)),
+                "input: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn show_and_drop() {
+        assert_eq!(
+            ContinuousQueryStatement::Show,
+            parse("SHOW CONTINUOUS QUERIES")
+        );
+        assert_eq!(
+            ContinuousQueryStatement::Drop {
+                name: "cq".to_string(),
+                database: "foo".to_string()
+            },
+            parse("drop continuous query cq on foo")
+        );
+        assert!(parse_continuous_query("SHOW DATABASES").is_none());
+        assert!(parse_continuous_query("SELECT * FROM cpu").is_none());
+    }
+
+    #[test]
+    fn time_range() {
+        assert_eq!(
+            "SELECT mean(usage) INTO a FROM cpu WHERE time >= 0 AND time < 10 GROUP BY time(1h)",
+            with_time_range(
+                "SELECT mean(usage) INTO a FROM cpu GROUP BY time(1h)",
+                0,
+                10
+            )
+        );
+        assert_eq!(
+            "SELECT mean(usage) INTO a FROM cpu WHERE (host = 'a' OR host = 'where') \
+            AND time >= 0 AND time < 10 GROUP BY time(1h) fill(none)",
+            with_time_range(
+                "SELECT mean(usage) INTO a FROM cpu WHERE host = 'a' OR host = 'where' \
+                GROUP BY time(1h) fill(none)",
+                0,
+                10
+            )
+        );
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(Some(3_600_000_000_000), parse_duration_nanos("1h"));
+        assert_eq!(Some(5_400_000_000_000), parse_duration_nanos("1h30m"));
+        assert_eq!(Some(-900_000_000_000), parse_duration_nanos("-15m"));
+        assert_eq!(Some(1_000), parse_duration_nanos("1u"));
+        assert_eq!(None, parse_duration_nanos("1y"));
+        assert_eq!(None, parse_duration_nanos(""));
+    }
+}
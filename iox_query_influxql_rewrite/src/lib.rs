@@ -10,10 +10,13 @@ use influxdb_influxql_parser::{
     statement::Statement,
 };
 
+pub mod continuous_query;
+
 #[derive(Debug)]
 pub struct Rewritten<S> {
     database: Option<Identifier>,
     retention_policy: Option<Identifier>,
+    into: Option<IntoClause>,
     statement: S,
 }
 
@@ -22,10 +25,16 @@ impl<S> Rewritten<S> {
         Self {
             database: None,
             retention_policy: None,
+            into: None,
             statement,
         }
     }
 
+    fn with_into(mut self, into: IntoClause) -> Self {
+        self.into = Some(into);
+        self
+    }
+
     fn with_database(mut self, db: Option<Identifier>) -> Self {
         self.database = db;
         self
@@ -44,6 +53,12 @@ impl<S> Rewritten<S> {
         self.retention_policy.as_ref()
     }
 
+    /// The `INTO` clause of a `SELECT ... INTO` statement, which has been removed from the
+    /// statement itself
+    pub fn into_clause(&self) -> Option<&IntoClause> {
+        self.into.as_ref()
+    }
+
     pub fn statement(&self) -> &S {
         &self.statement
     }
@@ -67,6 +82,177 @@ impl<S> Rewritten<S> {
     }
 }
 
+/// The target of a `SELECT ... INTO` statement
+///
+/// The InfluxQL parser does not support `INTO` clauses, so they are removed from the query text
+/// before it is parsed, see [`parse_statements`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntoClause {
+    database: Option<Identifier>,
+    retention_policy: Option<Identifier>,
+    measurement: IntoMeasurement,
+}
+
+/// The measurement that a `SELECT ... INTO` statement writes to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntoMeasurement {
+    /// Write all rows into the named measurement
+    Name(Identifier),
+    /// Write each row into the measurement that it was selected from, i.e., `:MEASUREMENT`
+    Source,
+}
+
+impl IntoClause {
+    pub fn database(&self) -> Option<&Identifier> {
+        self.database.as_ref()
+    }
+
+    pub fn retention_policy(&self) -> Option<&Identifier> {
+        self.retention_policy.as_ref()
+    }
+
+    pub fn measurement(&self) -> &IntoMeasurement {
+        &self.measurement
+    }
+
+    /// Resolve the name of the database to write into, using `default_db`, the database that the
+    /// query is run against, if the `INTO` clause does not name one
+    pub fn resolve_dbrp(&self, default_db: &str) -> String {
+        let db = self.database.as_ref().map_or(default_db, |db| db.as_str());
+        match &self.retention_policy {
+            Some(rp) if rp.as_str() != "autogen" && rp.as_str() != "default" => {
+                format!("{db}/{}", rp.as_str())
+            }
+            _ => db.to_string(),
+        }
+    }
+
+    /// Parse the target of an `INTO` clause, which takes the form `[[db.]rp.]measurement`, where
+    /// `measurement` can be the `:MEASUREMENT` back-reference
+    fn parse(target: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidInto(target.to_string());
+        let segments = split_unquoted(target, '.');
+        let (db, rp, measurement) = match segments.as_slice() {
+            [m] => (None, None, *m),
+            [rp, m] => (None, Some(*rp), *m),
+            [db, rp, m] => (Some(*db), Some(*rp), *m),
+            _ => return Err(invalid()),
+        };
+        let measurement = if measurement.trim().eq_ignore_ascii_case(":measurement") {
+            IntoMeasurement::Source
+        } else {
+            IntoMeasurement::Name(parse_identifier(measurement).ok_or_else(invalid)?)
+        };
+        let database = db
+            .map(|db| parse_identifier(db).ok_or_else(invalid))
+            .transpose()?;
+        // the retention policy can be left empty when a database is given, e.g., `db..measurement`
+        let retention_policy = match rp.map(str::trim) {
+            Some("") if database.is_some() => None,
+            Some(rp) => Some(parse_identifier(rp).ok_or_else(invalid)?),
+            None => None,
+        };
+        Ok(Self {
+            database,
+            retention_policy,
+            measurement,
+        })
+    }
+}
+
+/// Parse a single, optionally double-quoted, identifier
+fn parse_identifier(s: &str) -> Option<Identifier> {
+    let s = s.trim();
+    if let Some(quoted) = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        let unescaped = quoted.replace("\\\"", "\"").replace("\\\\", "\\");
+        return (!unescaped.is_empty()).then(|| unescaped.as_str().into());
+    }
+    let mut chars = s.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then(|| s.into())
+}
+
+/// Split `s` on `separator`, ignoring any that are inside of double quotes
+fn split_unquoted(s: &str, separator: char) -> Vec<&str> {
+    let mut segments = vec![];
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            c if c == separator && !in_quotes => {
+                segments.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => (),
+        }
+    }
+    segments.push(&s[start..]);
+    segments
+}
+
+/// Find the `INTO` clause in a `SELECT` statement, and return the statement with the clause removed
+/// along with the text of its target
+///
+/// Only an `INTO` that is outside of any quotes or parentheses, i.e., not within a sub-query, and
+/// that is followed by a `FROM` is considered.
+fn split_into_clause(input: &str) -> Option<(String, &str)> {
+    let words = top_level_words(input);
+    let into = words
+        .iter()
+        .position(|&(start, end)| input[start..end].eq_ignore_ascii_case("into"))?;
+    let (into_start, into_end) = words[into];
+    let &(from_start, _) = words[into + 1..]
+        .iter()
+        .find(|&&(start, end)| input[start..end].eq_ignore_ascii_case("from"))?;
+    let target = input[into_end..from_start].trim();
+    Some((
+        format!("{}{}", &input[..into_start], &input[from_start..]),
+        target,
+    ))
+}
+
+/// Get the byte ranges of the words in `input` that are not within quotes or parentheses
+fn top_level_words(input: &str) -> Vec<(usize, usize)> {
+    let mut words = vec![];
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut word_start = None;
+    for (i, c) in input.char_indices() {
+        let is_word_char = c.is_ascii_alphanumeric() || c == '_';
+        if let Some(start) = word_start.filter(|_| !is_word_char || quote.is_some()) {
+            if depth == 0 {
+                words.push((start, i));
+            }
+            word_start = None;
+        }
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None => match c {
+                '\'' | '"' => quote = Some(c),
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                _ if is_word_char && word_start.is_none() => word_start = Some(i),
+                _ => (),
+            },
+        }
+    }
+    if let Some(start) = word_start.filter(|_| depth == 0) {
+        words.push((start, input.len()));
+    }
+    words
+}
+
 impl From<Rewritten<Statement>> for Statement {
     fn from(r: Rewritten<Statement>) -> Self {
         r.to_statement()
@@ -193,21 +379,100 @@ pub enum Error {
     MultiDatabase,
     #[error("parsing error: {0}")]
     Parse(ParseError),
+    #[error("invalid INTO clause: {0}")]
+    InvalidInto(String),
+    #[error("invalid continuous query: {0}")]
+    InvalidContinuousQuery(String),
 }
 
 pub fn parse_statements(input: &str) -> Result<Vec<Rewritten<Statement>>, Error> {
-    parse_internal(input)
-        .map_err(Error::Parse)?
+    let statements = match parse_internal(input) {
+        Ok(statements) => statements,
+        Err(e) => return parse_with_select_into(input).unwrap_or(Err(Error::Parse(e))),
+    };
+    statements
         .into_iter()
         .map(Rewritten::<Statement>::try_from)
         .collect::<Result<Vec<Rewritten<Statement>>, Error>>()
 }
 
+/// Parse each of the `;` separated statements in `input` on its own, so that any that are
+/// `SELECT ... INTO` statements can be parsed by [`parse_select_into`]
+///
+/// Returns `None` if none of the statements is a `SELECT ... INTO` statement, otherwise the first
+/// error from any of the statements.
+fn parse_with_select_into(input: &str) -> Option<Result<Vec<Rewritten<Statement>>, Error>> {
+    let mut has_into = false;
+    let mut results = vec![];
+    for statement in split_statements(input) {
+        if statement.trim().is_empty() {
+            continue;
+        }
+        match parse_internal(statement) {
+            Ok(statements) => {
+                results.extend(statements.into_iter().map(Rewritten::<Statement>::try_from))
+            }
+            Err(e) => match parse_select_into(statement) {
+                Some(rewritten) => {
+                    has_into = true;
+                    results.push(rewritten.map(|mut r| r.pop().expect("there is one statement")));
+                }
+                None => results.push(Err(Error::Parse(e))),
+            },
+        }
+    }
+    has_into.then(|| results.into_iter().collect())
+}
+
+/// Split `input` into the text of each statement, on the `;`s that are not within quotes
+fn split_statements(input: &str) -> Vec<&str> {
+    let mut statements = vec![];
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None => match c {
+                '\'' | '"' => quote = Some(c),
+                ';' => {
+                    statements.push(&input[start..i]);
+                    start = i + 1;
+                }
+                _ => (),
+            },
+        }
+    }
+    statements.push(&input[start..]);
+    statements
+}
+
+/// Parse a single `SELECT ... INTO` statement, by removing the `INTO` clause, which the parser does
+/// not support, and attaching it to the rewritten statement
+///
+/// Returns `None` if the input is not a `SELECT ... INTO` statement.
+fn parse_select_into(input: &str) -> Option<Result<Vec<Rewritten<Statement>>, Error>> {
+    let (stripped, target) = split_into_clause(input)?;
+    let mut statements = parse_internal(&stripped).ok()?;
+    if statements.len() != 1 || !matches!(statements[0], Statement::Select(_)) {
+        return None;
+    }
+    let statement = statements.pop().expect("there is one statement");
+    Some(
+        IntoClause::parse(target)
+            .and_then(|into| Ok(Rewritten::<Statement>::try_from(statement)?.with_into(into)))
+            .map(|rewritten| vec![rewritten]),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use influxdb_influxql_parser::statement::Statement;
 
-    use crate::{Error, Rewritten, parse_statements};
+    use crate::{Error, IntoMeasurement, Rewritten, parse_statements};
 
     fn parse_single(input: &str) -> Rewritten<Statement> {
         parse_statements(input).unwrap().pop().unwrap()
@@ -552,4 +817,89 @@ mod tests {
         }
         .assert();
     }
+
+    #[test]
+    fn select_into() {
+        let s = parse_single("SELECT usage INTO cpu_1h FROM cpu");
+        assert_eq!("SELECT usage FROM cpu", s.to_statement().to_string());
+        let into = s.into_clause().unwrap();
+        assert!(into.database().is_none());
+        assert!(into.retention_policy().is_none());
+        assert_eq!(&IntoMeasurement::Name("cpu_1h".into()), into.measurement());
+        assert_eq!("foo", into.resolve_dbrp("foo"));
+
+        let s = parse_single("SELECT * INTO \"other db\".\"1w\".:MEASUREMENT FROM foo.bar.cpu");
+        assert_eq!(Some("foo"), s.database().map(|db| db.as_str()));
+        assert_eq!(Some("bar"), s.retention_policy().map(|rp| rp.as_str()));
+        let into = s.into_clause().unwrap();
+        assert_eq!(&IntoMeasurement::Source, into.measurement());
+        assert_eq!("other db/1w", into.resolve_dbrp("foo"));
+
+        let into = parse_single("select * into down..cpu from cpu")
+            .into_clause()
+            .cloned()
+            .unwrap();
+        assert_eq!("down", into.resolve_dbrp("foo"));
+
+        let into = parse_single("SELECT * INTO autogen.cpu_copy FROM cpu")
+            .into_clause()
+            .cloned()
+            .unwrap();
+        assert_eq!("foo", into.resolve_dbrp("foo"));
+
+        // INTO within a string literal is not treated as a clause:
+        assert!(
+            parse_single("SELECT * FROM cpu WHERE host = 'into'")
+                .into_clause()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn select_into_failures() {
+        assert!(matches!(
+            parse_single_failure("SELECT * INTO a.b.c.d FROM cpu"),
+            Error::InvalidInto(_)
+        ));
+        assert!(matches!(
+            parse_single_failure("SELECT * INTO 1cpu FROM cpu"),
+            Error::InvalidInto(_)
+        ));
+        assert!(matches!(
+            parse_single_failure("SELECT * INTO FROM cpu"),
+            Error::InvalidInto(_)
+        ));
+        // not a SELECT statement, so the original parse error is returned:
+        assert!(matches!(
+            parse_single_failure("SHOW INTO FROM cpu"),
+            Error::Parse(_)
+        ));
+        // an error in another statement is returned along with a SELECT ... INTO:
+        assert!(matches!(
+            parse_single_failure("SELECT * INTO cpu_copy FROM cpu; SELECT FROM"),
+            Error::Parse(_)
+        ));
+    }
+
+    #[test]
+    fn select_into_with_other_statements() {
+        let statements = parse_statements(
+            "SELECT * FROM mem WHERE host = 'a;b'; SELECT usage INTO cpu_1h FROM cpu;",
+        )
+        .unwrap();
+        assert_eq!(2, statements.len());
+        assert!(statements[0].into_clause().is_none());
+        assert_eq!(
+            "SELECT * FROM mem WHERE host = 'a;b'",
+            statements[0].statement().to_string()
+        );
+        assert_eq!(
+            &IntoMeasurement::Name("cpu_1h".into()),
+            statements[1].into_clause().unwrap().measurement()
+        );
+        assert_eq!(
+            "SELECT usage FROM cpu",
+            statements[1].statement().to_string()
+        );
+    }
 }